@@ -1,16 +1,19 @@
 #![allow(dead_code)]
 #![allow(unused_variables)]
 use crate::consts::*;
+use crate::frontend::AudioSink;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 mod channel1;
 mod channel2;
 mod channel3;
 mod channel4;
-mod audio;
+#[cfg(feature = "sdl")]
+pub mod audio;
 
 // TODO: Turning the APU off resets the duty counters
 
-pub trait Channel: ComponentWithMemory {
+pub trait Channel: ComponentWithMemory + Savestate {
     fn is_enabled(&self) -> bool;
     fn inc_length(&mut self);
     fn turn_off(&mut self);
@@ -28,12 +31,16 @@ pub struct APU {
     ch3 :channel3::Channel3,
     ch4 :channel4::Channel4,
 
-    audio: audio::Audio,
-    sample_counter: u16
+    audio: Box<dyn AudioSink>,
+    sample_counter: u16,
+
+    // Emulation speed. Samples are dropped or repeated so that the audio keeps up with it
+    speed: f64,
+    sample_acc: f64
 }
 
 impl APU {
-    pub fn new(subsystem :sdl2::AudioSubsystem) -> APU {
+    pub fn new(audio :Box<dyn AudioSink>) -> APU {
         return APU {
             nr50 :0x77, nr51 :0xF3, nr52 :0xF1,
             prev_div_bit: false,
@@ -44,8 +51,11 @@ impl APU {
             ch3: channel3::Channel3::new(),
             ch4: channel4::Channel4::new(),
 
-            audio: audio::Audio::new(subsystem),
-            sample_counter: 0
+            audio,
+            sample_counter: 0,
+
+            speed: 1.0,
+            sample_acc: 0.0
         }
     }
 
@@ -58,6 +68,26 @@ impl APU {
         self.audio.resume();
     }
 
+    /*
+     Speed multiplier of the emulation. Every batch of samples is queued 1/speed times, so
+     the batches are dropped when running faster and repeated when running slower.
+     f64::INFINITY drops every sample.
+     */
+    pub fn set_speed(&mut self, speed :f64) {
+        self.speed = speed;
+        self.sample_acc = 0.0;
+    }
+
+    pub fn pause_audio(&mut self) {
+        self.audio.pause();
+    }
+
+    pub fn resume_audio(&mut self) {
+        if self.is_apu_enabled() {
+            self.audio.resume();
+        }
+    }
+
     // Master control
     fn write_nr52(&mut self, val :u8) {
         let prev_enable = self.is_apu_enabled();
@@ -170,7 +200,14 @@ impl APU {
         
         // FREQ_CPU/FREQ_AUDIO = 87.38, accounting for both channels = 174.76
         if self.sample_counter == 88 {
-            self.add_samples(4);
+            self.sample_acc += 1.0 / self.speed;
+            let n_batches = self.sample_acc as u16;
+            self.sample_acc -= n_batches as f64;
+
+            if n_batches > 0 {
+                self.add_samples(4 * n_batches);
+            }
+
             self.sample_counter = 0;
         } else {
             self.sample_counter += 1;
@@ -232,3 +269,35 @@ impl ComponentWithMemory for APU {
         }
     }
 }
+
+impl Savestate for APU {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.nr50);
+        w.u8(self.nr51);
+        w.u8(self.nr52);
+        w.bool(self.prev_div_bit);
+        w.u8(self.div_apu);
+        w.u16(self.sample_counter);
+
+        self.ch1.save_state(w);
+        self.ch2.save_state(w);
+        self.ch3.save_state(w);
+        self.ch4.save_state(w);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.nr50 = r.u8()?;
+        self.nr51 = r.u8()?;
+        self.nr52 = r.u8()?;
+        self.prev_div_bit = r.bool()?;
+        self.div_apu = r.u8()?;
+        self.sample_counter = r.u16()?;
+
+        self.ch1.load_state(r)?;
+        self.ch2.load_state(r)?;
+        self.ch3.load_state(r)?;
+        self.ch4.load_state(r)?;
+
+        return Ok(());
+    }
+}
@@ -2,6 +2,8 @@ extern crate sdl2;
 use sdl2::AudioSubsystem;
 use sdl2::audio::{AudioQueue, AudioSpecDesired};
 
+use crate::frontend::AudioSink;
+
 const FREQ :i32 = 48_000;
 
 pub struct Audio {
@@ -23,8 +25,10 @@ impl Audio {
             device
         }
     }
+}
 
-    pub fn queue(&mut self, val_left: u8, val_right: u8, n_samples: u16) {
+impl AudioSink for Audio {
+    fn queue(&mut self, val_left: u8, val_right: u8, n_samples: u16) {
         if self.device.size() > 10000 {
             return;
         }
@@ -46,11 +50,11 @@ impl Audio {
         }
     }
 
-    pub fn pause(&mut self) {
+    fn pause(&mut self) {
         self.device.pause();
     }
 
-    pub fn resume(&mut self) {
+    fn resume(&mut self) {
         self.device.clear();
         self.device.resume();
     }
@@ -2,6 +2,7 @@
 #![allow(unused_variables)]
 use crate::consts::*;
 use crate::apu::Channel as Channel;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 pub struct Channel1 {
     nr10 :u8, nr11 :u8, nr12 :u8, nr13 :u8, nr14 :u8,
@@ -299,4 +300,33 @@ impl ComponentWithMemory for Channel1 {
             _ => panic!()
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for Channel1 {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.nr10); w.u8(self.nr11); w.u8(self.nr12); w.u8(self.nr13); w.u8(self.nr14);
+
+        w.bool(self.is_enabled);
+        w.u8(self.volume);
+        w.u8(self.env_counter);
+        w.u8(self.sweep_counter);
+        w.u8(self.length_timer);
+        w.u8(self.duty_counter);
+        w.u32(self.period_timer);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.nr10 = r.u8()?; self.nr11 = r.u8()?; self.nr12 = r.u8()?;
+        self.nr13 = r.u8()?; self.nr14 = r.u8()?;
+
+        self.is_enabled = r.bool()?;
+        self.volume = r.u8()?;
+        self.env_counter = r.u8()?;
+        self.sweep_counter = r.u8()?;
+        self.length_timer = r.u8()?;
+        self.duty_counter = r.u8()?;
+        self.period_timer = r.u32()?;
+
+        return Ok(());
+    }
+}
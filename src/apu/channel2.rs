@@ -3,6 +3,7 @@
 
 use crate::consts::*;
 use crate::apu::Channel as Channel;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 pub struct Channel2 {
     nr21 :u8, nr22 :u8, nr23 :u8, nr24 :u8,
@@ -243,4 +244,32 @@ impl ComponentWithMemory for Channel2 {
             _ => panic!("write(): Invalid address: {:04X}", addr)
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for Channel2 {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.nr21); w.u8(self.nr22); w.u8(self.nr23); w.u8(self.nr24);
+
+        w.bool(self.is_enabled);
+        w.u8(self.volume);
+        w.u8(self.env_counter);
+        w.u8(self.length_timer);
+        w.u8(self.duty_counter);
+        w.u8(self.counter);
+        w.u32(self.period_timer);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.nr21 = r.u8()?; self.nr22 = r.u8()?; self.nr23 = r.u8()?; self.nr24 = r.u8()?;
+
+        self.is_enabled = r.bool()?;
+        self.volume = r.u8()?;
+        self.env_counter = r.u8()?;
+        self.length_timer = r.u8()?;
+        self.duty_counter = r.u8()?;
+        self.counter = r.u8()?;
+        self.period_timer = r.u32()?;
+
+        return Ok(());
+    }
+}
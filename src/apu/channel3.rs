@@ -3,9 +3,9 @@
 #![allow(unused_imports)]
 use std::path::Component;
 
-use sdl2::audio::{AudioCallback, AudioSpecDesired};
 use crate::consts::*;
 use crate::apu::Channel as Channel;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 
 pub struct Channel3 {
@@ -180,4 +180,31 @@ impl ComponentWithMemory for Channel3 {
             _ => panic!()
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for Channel3 {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.nr30); w.u8(self.nr31); w.u8(self.nr32); w.u8(self.nr33); w.u8(self.nr34);
+        w.bytes(&self.wave_ram);
+
+        w.bool(self.is_enabled);
+        w.u8(self.length_timer);
+        w.u8(self.counter);
+        w.u32(self.period_timer);
+        w.u8(self.waveram_pos);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.nr30 = r.u8()?; self.nr31 = r.u8()?; self.nr32 = r.u8()?;
+        self.nr33 = r.u8()?; self.nr34 = r.u8()?;
+        r.bytes_into(&mut self.wave_ram)?;
+
+        self.is_enabled = r.bool()?;
+        self.length_timer = r.u8()?;
+        self.counter = r.u8()?;
+        self.period_timer = r.u32()?;
+        self.waveram_pos = r.u8()?;
+
+        return Ok(());
+    }
+}
@@ -2,6 +2,7 @@
 #![allow(unused_variables)]
 use crate::consts::*;
 use crate::apu::Channel as Channel;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 
 pub struct Channel4 {
@@ -191,4 +192,38 @@ impl ComponentWithMemory for Channel4 {
             _ => panic!()
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for Channel4 {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.nr41); w.u8(self.nr42); w.u8(self.nr43); w.u8(self.nr44);
+        w.bool(self.is_enabled);
+
+        w.u8(self.volume);
+        w.u32(self.period_timer);
+        w.u8(self.length_timer);
+        w.u8(self.env_counter);
+
+        // LFSR bits packed in a single word
+        let lsfr = self.lsfr.iter().enumerate()
+            .fold(0u16, |acc, (i, bit)| acc | ((*bit as u16) << i));
+        w.u16(lsfr);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.nr41 = r.u8()?; self.nr42 = r.u8()?; self.nr43 = r.u8()?; self.nr44 = r.u8()?;
+        self.is_enabled = r.bool()?;
+
+        self.volume = r.u8()?;
+        self.period_timer = r.u32()?;
+        self.length_timer = r.u8()?;
+        self.env_counter = r.u8()?;
+
+        let lsfr = r.u16()?;
+        for (i, bit) in self.lsfr.iter_mut().enumerate() {
+            *bit = (lsfr >> i) & 1 == 1;
+        }
+
+        return Ok(());
+    }
+}
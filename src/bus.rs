@@ -1,24 +1,40 @@
 use std::cell::RefCell;
+use std::path::PathBuf;
 use std::rc::Rc;
 
 use crate::ppu::PPU;
 use crate::apu::APU;
 use crate::interruptManager::InterruptManager;
 use crate::joypad::Joypad;
-use crate::cartridge::{Cartridge, load_cartridge};
+use crate::cartridge::Cartridge;
+use crate::cheats::Cheats;
+use crate::debugger::breakpoints::{Access, Watchpoints};
+use crate::link::LinkEndpoint;
 use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 mod ram;
 mod timer;
+mod serial;
+pub mod recorder;
+
+use recorder::{BusAccess, BusRecorder, Initiator};
 
 pub struct Bus {
     ram    : ram::RAM,
     timer  : timer::Timer,
+    serial : serial::Serial,
     ppu    : PPU,
     apu    : APU,
     int    : Rc<RefCell<InterruptManager>>,
     joypad : Rc<RefCell<Joypad>>,
     cart   : Box<dyn Cartridge>,
+    is_ext_ram_written :bool, // Since the last call to take_ext_ram_written
+    cheats :Cheats,
+    watchpoints :Watchpoints,
+    ly_stub :Option<u8>, // Read instead of LY
+    cycle :u64, // T-cycles run
+    recorder :Option<BusRecorder>,
 
     is_oam_dma: bool,
     wait_oam_dma :u8, // Wait for 4 cycles until the OAM DMA actually starts
@@ -33,11 +49,18 @@ impl Bus {
                apu      :APU,
                int      :Rc<RefCell<InterruptManager>>,
                joypad   :Rc<RefCell<Joypad>>,
-               path     :&str) -> Bus {
+               cart     :Box<dyn Cartridge>) -> Bus {
         return Bus {
             ram   : ram::RAM::new(),
             timer : timer::Timer::new(int.clone()),
-            cart  : load_cartridge(path),
+            serial: serial::Serial::new(int.clone()),
+            cart,
+            is_ext_ram_written: false,
+            cheats: Cheats::new(),
+            watchpoints: Watchpoints::new(),
+            ly_stub: None,
+            cycle: 0,
+            recorder: None,
             ppu,
             apu,
             int,
@@ -82,9 +105,15 @@ impl Bus {
     }
 
     pub fn tick(&mut self) {
+        self.cycle += 1;
         self.ppu.tick();    // TODO: Possible delay of 1 cycle on OAM DMA
+        if self.ppu.is_vblank_start() {
+            self.apply_game_shark();
+        }
         self.timer.tick();
-        self.apu.tick(self.read(ADDR_DIV));
+        self.serial.tick(self.timer.div_counter());
+        self.apu.tick(self.peek(ADDR_DIV));
+        self.cart.tick();
 
         // Wait for 5 cycles (actual cycle + 1 M-cycle to start OAM-DMA
         if self.schedule_oam_dma {
@@ -103,10 +132,12 @@ impl Bus {
                 //println!("dma move");
 
                 self.is_oam_dma = false; // Deactivate momentarily to be able to read
-                let val = self.read(self.dma_src_addr);
+                let val = self.peek(self.dma_src_addr); // Not seen by the watchpoints, as it's not the CPU
                 self.is_oam_dma = true; // Enable it again so that the cpu can only access HRAM
 
                 self.ppu.write_oam_dma(self.dma_dst_addr, val);
+                self.record(self.dma_src_addr, val, Access::Read, Initiator::Dma);
+                self.record(self.dma_dst_addr, val, Access::Write, Initiator::Dma);
                 self.dma_src_addr += 1;
                 self.dma_dst_addr += 1;
                 self.dma_until_next_m_cycle = 4;
@@ -121,24 +152,150 @@ impl Bus {
         }
     }
 
+    /*
+     * GameShark codes write their value once per frame, when VBlank starts. These aren't writes
+     * of the CPU: they don't trigger the watchpoints, aren't recorded, and don't mark the
+     * cartridge RAM to be saved, which a code on it would otherwise do every frame.
+     */
+    fn apply_game_shark(&mut self) {
+        let writes :Vec<_> = self.cheats.game_shark_writes().collect();
+
+        for (bank, addr, val) in writes {
+            match (bank, addr) {
+                (Some(bank), EXT_RAM_START..=EXT_RAM_END) => self.cart.write_ram_bank(bank, addr, val),
+                (None, EXT_RAM_START..=EXT_RAM_END) => self.cart.write_ram_bank(self.cart.ram_bank(), addr, val),
+                _ => self.poke(addr, val)
+            }
+        }
+    }
+
+    pub fn watchpoints(&self) -> &Watchpoints { return &self.watchpoints; }
+    pub fn watchpoints_mut(&mut self) -> &mut Watchpoints { return &mut self.watchpoints; }
+
+    /* ROM bank mapped at addr */
+    pub fn rom_bank(&self, addr :u16) -> u16 { return self.cart.rom_bank(addr); }
+    /* Cartridge RAM bank mapped at A000-BFFF */
+    pub fn ram_bank(&self) -> u8 { return self.cart.ram_bank(); }
+
+    /* Read a ROM bank, whichever is mapped. 0xFF past the end of the ROM. */
+    pub fn peek_rom_bank(&self, bank :u16, addr :u16) -> u8 {
+        let index = bank as usize * 0x4000 + (addr & 0x3FFF) as usize;
+        return self.cart.rom().get(index).copied().unwrap_or(0xFF);
+    }
+
+    pub fn peek_ram_bank(&self, bank :u8, addr :u16) -> Option<u8> { return self.cart.read_ram_bank(bank, addr); }
+    pub fn poke_ram_bank(&mut self, bank :u8, addr :u16, val :u8) { self.cart.write_ram_bank(bank, addr, val); }
+
+    /* T-cycles run since power-on */
+    pub fn cycle(&self) -> u64 { return self.cycle; }
+
+    /* Record the accesses to the bus, see recorder.rs */
+    pub fn set_recorder(&mut self, recorder :Option<BusRecorder>) { self.recorder = recorder; }
+    pub fn recorder(&self) -> Option<&BusRecorder> { return self.recorder.as_ref(); }
+
+    fn record(&self, addr :u16, val :u8, access :Access, initiator :Initiator) {
+        if let Some(recorder) = &self.recorder {
+            recorder.record(BusAccess { cycle: self.cycle, addr, val, access, initiator });
+        }
+    }
+
+    /* Make LY always read a value, e.g. 0x90 like the logs of Gameboy Doctor expect */
+    pub fn set_ly_stub(&mut self, val :Option<u8>) { self.ly_stub = val; }
+
+    pub fn vram(&self) -> &[u8] { return self.ppu.vram_data(); }
+    pub fn oam(&self) -> &[u8] { return self.ppu.oam_data(); }
+
+    /* Write to the VRAM or the OAM, even when the PPU blocks the CPU */
+    pub fn poke_video(&mut self, addr :u16, val :u8) {
+        match addr {
+            VRAM_START..=VRAM_END => self.ppu.vram_data_mut()[(addr - VRAM_START) as usize] = val,
+            OAM_START..=OAM_END => self.ppu.oam_data_mut()[(addr - OAM_START) as usize] = val,
+            _ => {}
+        }
+    }
+
+    pub fn cheats(&self) -> &Cheats { return &self.cheats; }
+    pub fn cheats_mut(&mut self) -> &mut Cheats { return &mut self.cheats; }
+
     pub fn save_ram(&self) {
         self.cart.save_ram();
     }
-}
 
-impl ComponentWithMemory for Bus {
-    fn read(&self, addr :u16) -> u8 {
+    pub fn set_save_path(&mut self, path :PathBuf) { self.cart.set_save_path(path); }
+
+    /* The cartridge RAM and RTC, as load_battery takes them back */
+    pub fn save_battery(&self) -> Vec<u8> {
+        let mut w = StateWriter::new();
+        self.cart.save_battery(&mut w);
+        return w.into_bytes();
+    }
+
+    pub fn load_battery(&mut self, data :&[u8]) -> Result<(), StateError> {
+        return self.cart.load_battery(&mut StateReader::new(data));
+    }
+
+    pub fn clear_battery(&mut self) { self.cart.clear_battery(); }
+
+    /* Whether the game wrote to the external RAM since the last call */
+    pub fn take_ext_ram_written(&mut self) -> bool {
+        return std::mem::replace(&mut self.is_ext_ram_written, false);
+    }
+
+    pub fn set_link(&mut self, link :Box<dyn LinkEndpoint>) { self.serial.set_link(link); }
+
+    pub fn set_audio_speed(&mut self, speed :f64) { self.apu.set_speed(speed); }
+    pub fn pause_audio(&mut self)  { self.apu.pause_audio(); }
+    pub fn resume_audio(&mut self) { self.apu.resume_audio(); }
+
+    pub fn take_frame_done(&mut self) -> bool { return self.ppu.take_frame_done(); }
+    pub fn is_vblank_start(&self) -> bool { return self.ppu.is_vblank_start(); }
+
+    /* Hash of the framebuffer and RAM, to check that two runs are in sync */
+    pub fn sync_hash(&self) -> [u8;16] {
+        let mut ctx = md5::Context::new();
+
+        ctx.consume(self.ppu.framebuffer());
+        ctx.consume(self.ram.wram());
+        ctx.consume(self.ram.hram());
+
+        return ctx.compute().0;
+    }
+
+    pub fn redraw_screen(&self) {
+        self.ppu.redraw();
+    }
+
+    fn read_rom(&self, addr :u16) -> u8 {
+        let val = self.cart.read(addr);
+
+        if self.cheats.has_game_genie() {
+            return self.cheats.read_rom(addr, val, || self.cart.rom_bank(addr));
+        }
+        return val;
+    }
+
+    pub fn rom(&self) -> &[u8] {
+        return self.cart.rom();
+    }
+
+    /* Read without triggering the watchpoints, for the debugger itself or the OAM DMA */
+    pub fn peek(&self, addr :u16) -> u8 {
         if self.cart.is_test_cart() {
             return self.cart.read(addr); // For tests. Remove.
         }
 
+        if addr == ADDR_LY {
+            if let Some(ly) = self.ly_stub {
+                return ly;
+            }
+        }
+
         if self.is_oam_dma {
             return self.read_oam_dma(addr);
         }
         else {
             return match addr {
-                BANK0_START..=BANK0_END       => self.cart.read(addr),
-                BANK1_START..=BANK1_END       => self.cart.read(addr),
+                BANK0_START..=BANK1_END       => self.read_rom(addr),
                 VRAM_START..=VRAM_END         => self.ppu.read(addr),
                 EXT_RAM_START..=EXT_RAM_END   => self.cart.read(addr),
                 WORK_RAM_START..=WORK_RAM_END => self.ram.read(addr),
@@ -162,6 +319,9 @@ impl ComponentWithMemory for Bus {
                 // Joypad
                 ADDR_P1
                     => self.joypad.borrow_mut().read(addr),
+                // Serial
+                ADDR_SB | ADDR_SC
+                    => self.serial.read(addr),
                 // Timer
                 ADDR_DIV | ADDR_TIMA | ADDR_TMA | ADDR_TAC
                     => self.timer.read(addr),
@@ -174,7 +334,12 @@ impl ComponentWithMemory for Bus {
         }
     }
 
-    fn write(&mut self, addr :u16, val :u8) {
+    /*
+     * Write like the CPU, side effects of the IO registers included, but without triggering the
+     * watchpoints, being recorded or marking the cartridge RAM to be saved. For the cheats and
+     * the debugger.
+     */
+    pub fn poke(&mut self, addr :u16, val :u8) {
         if self.cart.is_test_cart() {
             return self.cart.write(addr, val); // For tests. Remove.
         }
@@ -220,6 +385,9 @@ impl ComponentWithMemory for Bus {
                 // Joypad
                 ADDR_P1 =>
                     self.joypad.borrow_mut().write(addr, val),
+                // Serial
+                ADDR_SB | ADDR_SC
+                    => self.serial.write(addr, val),
                 // Timer
                 ADDR_DIV | ADDR_TIMA | ADDR_TMA | ADDR_TAC
                     => self.timer.write(addr, val),
@@ -233,4 +401,67 @@ impl ComponentWithMemory for Bus {
             }
         }
     }
-}
\ No newline at end of file
+
+}
+
+impl ComponentWithMemory for Bus {
+    fn read(&self, addr :u16) -> u8 {
+        let val = self.peek(addr);
+        self.watchpoints.on_read(addr, val);
+        self.record(addr, val, Access::Read, Initiator::Cpu);
+
+        return val;
+    }
+
+    fn write(&mut self, addr :u16, val :u8) {
+        self.watchpoints.on_write(addr, val);
+        self.record(addr, val, Access::Write, Initiator::Cpu);
+
+        if !self.is_oam_dma && (EXT_RAM_START..=EXT_RAM_END).contains(&addr) {
+            self.is_ext_ram_written = true;
+        }
+        self.poke(addr, val);
+    }
+}
+
+impl Savestate for Bus {
+    fn save_state(&self, w :&mut StateWriter) {
+        self.ram.save_state(w);
+        self.timer.save_state(w);
+        self.serial.save_state(w);
+        self.ppu.save_state(w);
+        self.apu.save_state(w);
+        self.int.borrow().save_state(w);
+        self.joypad.borrow().save_state(w);
+        self.cart.save_state(w);
+
+        w.bool(self.is_oam_dma);
+        w.u8(self.wait_oam_dma);
+        w.u16(self.dma_src_addr);
+        w.u16(self.dma_dst_addr);
+        w.bool(self.schedule_oam_dma);
+        w.u8(self.dma_until_next_m_cycle);
+        w.u64(self.cycle);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.ram.load_state(r)?;
+        self.timer.load_state(r)?;
+        self.serial.load_state(r)?;
+        self.ppu.load_state(r)?;
+        self.apu.load_state(r)?;
+        self.int.borrow_mut().load_state(r)?;
+        self.joypad.borrow_mut().load_state(r)?;
+        self.cart.load_state(r)?;
+
+        self.is_oam_dma = r.bool()?;
+        self.wait_oam_dma = r.u8()?;
+        self.dma_src_addr = r.u16()?;
+        self.dma_dst_addr = r.u16()?;
+        self.schedule_oam_dma = r.bool()?;
+        self.dma_until_next_m_cycle = r.u8()?;
+        self.cycle = r.u64()?;
+
+        return Ok(());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 pub struct RAM {
     wram : [RAMVAL;(WORK_RAM_END-WORK_RAM_START+1) as usize],
@@ -17,8 +18,6 @@ impl RAM {
     
     pub fn init(&mut self) {
         let hardware_regs = [
-            (0xFF01, 0x00), (0xFF02, 0x7E), // SB, SC
-
             (0xFF2A, 0xFF), (0xFF2B, 0xFF), (0xFF2C, 0xFF), (0xFF2D, 0xFF), // Other registers
             (0xFF2E, 0xFF), (0xFF2F, 0xFF),
 
@@ -37,6 +36,9 @@ impl RAM {
         }
     }
 
+    pub fn wram(&self) -> &[u8] { return &self.wram; }
+    pub fn hram(&self) -> &[u8] { return &self.hram; }
+
     pub fn read_io(&self, addr :RAMINDEX) -> RAMVAL {
         // Mask unused bits
         return self.io_reg[(addr-IO_REG_START) as usize] | match addr {    // OR mask
@@ -86,4 +88,20 @@ impl ComponentWithMemory for RAM {
             _ => panic!("write(): Invalid address: 0x{:04X}", addr)
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for RAM {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.wram);
+        w.bytes(&self.hram);
+        w.bytes(&self.io_reg);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.wram)?;
+        r.bytes_into(&mut self.hram)?;
+        r.bytes_into(&mut self.io_reg)?;
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,114 @@
+use std::cell::{Cell, RefCell};
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+
+use crate::debugger::breakpoints::Access;
+
+/*
+ * Recorder of the bus accesses, stamped with the T-cycle of the bus they happen at.
+ *
+ * The reads and writes of the CPU, and the bytes the OAM DMA copies, are kept in a ring buffer
+ * of the last accesses. What the debugger and the cheats peek or poke isn't recorded. Reads only
+ * borrow the bus, so the buffer is in a RefCell.
+ */
+
+// About a second of a game, in 16 MiB
+pub const DEFAULT_CAPACITY :usize = 1 << 20;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Initiator { Cpu, Dma }
+
+impl fmt::Display for Initiator {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Initiator::Cpu => write!(f, "CPU"),
+            Initiator::Dma => write!(f, "DMA"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BusAccess {
+    pub cycle     :u64, // T-cycles run by the bus before it
+    pub addr      :u16,
+    pub val       :u8,
+    pub access    :Access, // Read or Write
+    pub initiator :Initiator,
+}
+
+impl fmt::Display for BusAccess {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:>12}  {}  {:<5}  {:04X}  {:02X}", self.cycle, self.initiator, self.access.to_string(), self.addr, self.val)
+    }
+}
+
+pub struct BusRecorder {
+    accesses   :RefCell<VecDeque<BusAccess>>,
+    capacity   :usize,
+    n_recorded :Cell<u64>, // Since the start, dropped ones included
+}
+
+impl BusRecorder {
+    pub fn new(capacity :usize) -> BusRecorder {
+        return BusRecorder {
+            accesses: RefCell::new(VecDeque::new()),
+            capacity: capacity.max(1),
+            n_recorded: Cell::new(0),
+        };
+    }
+
+    pub fn capacity(&self) -> usize { return self.capacity; }
+    pub fn len(&self) -> usize { return self.accesses.borrow().len(); }
+    pub fn is_empty(&self) -> bool { return self.accesses.borrow().is_empty(); }
+    pub fn n_recorded(&self) -> u64 { return self.n_recorded.get(); }
+
+    pub fn record(&self, access :BusAccess) {
+        let mut accesses = self.accesses.borrow_mut();
+        if accesses.len() == self.capacity {
+            accesses.pop_front();
+        }
+        accesses.push_back(access);
+        self.n_recorded.set(self.n_recorded.get() + 1);
+    }
+
+    pub fn clear(&self) {
+        self.accesses.borrow_mut().clear();
+    }
+
+    /* Oldest first */
+    pub fn accesses(&self) -> Vec<BusAccess> {
+        return self.accesses.borrow().iter().copied().collect();
+    }
+
+    /* Accesses from the T-cycle on */
+    pub fn since(&self, cycle :u64) -> Vec<BusAccess> {
+        return self.accesses.borrow().iter().filter(|access| access.cycle >= cycle).copied().collect();
+    }
+
+    /* The last n accesses to start-end (included), oldest first */
+    pub fn last(&self, n :usize, start :u16, end :u16) -> Vec<BusAccess> {
+        let accesses = self.accesses.borrow();
+        let mut last :Vec<BusAccess> = accesses.iter().rev()
+            .filter(|access| access.addr >= start && access.addr <= end)
+            .take(n).copied().collect();
+        last.reverse();
+
+        return last;
+    }
+
+    /* Write the accesses to a text file, a line each. Returns how many. */
+    pub fn dump(&self, path :&str) -> io::Result<usize> {
+        let mut file = BufWriter::new(File::create(path)?);
+        let accesses = self.accesses.borrow();
+
+        writeln!(file, "{:>12}  {:<3}  {:<5}  {:<4}  val", "cycle", "by", "op", "addr")?;
+        for access in accesses.iter() {
+            writeln!(file, "{}", access)?;
+        }
+        file.flush()?;
+
+        return Ok(accesses.len());
+    }
+}
@@ -0,0 +1,154 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::interruptManager::InterruptManager;
+use crate::link::{LinkEndpoint, NoLink};
+use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+
+/*
+ * Serial port.
+ *
+ * With the internal clock (SC bit 0 set) the transfer is clocked at 8192 Hz by the falling
+ * edge of bit 8 of the DIV counter, one bit every 512 T-cycles. The byte of the other side is
+ * fetched from the link endpoint when the transfer starts and shifted into SB bit by bit. When
+ * the endpoint can't answer at once, the shifting only starts once its reply is there.
+ *
+ * With the external clock the port waits until the master on the other side of the link
+ * sends a byte, and then SB is exchanged at once.
+ */
+
+pub struct Serial {
+    sb :u8,
+    sc :u8,
+
+    link     :Box<dyn LinkEndpoint>,
+    int      :Rc<RefCell<InterruptManager>>,
+
+    bits_left      :u8,   // Bits left to shift in the current transfer (internal clock), 0 until the reply
+    incoming       :u8,   // Byte received from the other side
+    prev_clock_bit :bool,
+}
+
+impl Serial {
+    pub fn new(int :Rc<RefCell<InterruptManager>>) -> Serial {
+        return Serial {
+            sb: 0x00,
+            sc: 0x7E,
+
+            link: Box::new(NoLink),
+            int,
+
+            bits_left: 0,
+            incoming: 0xFF,
+            prev_clock_bit: false,
+        }
+    }
+
+    pub fn set_link(&mut self, link :Box<dyn LinkEndpoint>) {
+        self.link = link;
+    }
+
+    fn is_transfer_enabled(&self) -> bool { return self.sc & 0x80 != 0; }
+    fn is_internal_clock(&self)   -> bool { return self.sc & 0x01 != 0; }
+
+    /* Master waiting for the byte of the other side */
+    fn is_waiting_reply(&self) -> bool {
+        return self.is_transfer_enabled() && self.is_internal_clock() && self.bits_left == 0;
+    }
+
+    fn start_shifting(&mut self, incoming :u8) {
+        self.incoming = incoming;
+        self.bits_left = 8;
+    }
+
+    fn finish_transfer(&mut self) {
+        self.sc &= 0x7F;
+        self.int.borrow_mut().request_interrupt(Interrupt::Serial);
+    }
+
+    pub fn tick(&mut self, div_counter :u16) {
+        let clock_bit = (div_counter >> 8) & 1 == 1;
+        let is_falling_edge = self.prev_clock_bit && !clock_bit;
+        self.prev_clock_bit = clock_bit;
+
+        if !is_falling_edge {
+            return;
+        }
+
+        // Master: the reply is shifted in from the next bit on
+        if self.is_waiting_reply() {
+            if let Some(val) = self.link.reply() {
+                self.start_shifting(val);
+            }
+            return;
+        }
+
+        // Master: shift one bit
+        if self.is_transfer_enabled() && self.is_internal_clock() && self.bits_left > 0 {
+            self.sb = (self.sb << 1) | (self.incoming >> 7);
+            self.incoming <<= 1;
+            self.bits_left -= 1;
+
+            if self.bits_left == 0 {
+                self.finish_transfer();
+            }
+        }
+
+        // Slave: answer the master on the other side
+        let is_slave = self.is_transfer_enabled() && !self.is_internal_clock();
+
+        if let Some(val) = self.link.poll(if is_slave { Some(self.sb) } else { None }) {
+            self.sb = val;
+            self.finish_transfer();
+        }
+    }
+}
+
+impl ComponentWithMemory for Serial {
+    fn read(&self, addr :u16) -> u8 {
+        return match addr {
+            ADDR_SB => self.sb,
+            ADDR_SC => self.sc | 0b01111110,
+            _ => panic!("Invalid serial address: {:04X}", addr)
+        }
+    }
+
+    fn write(&mut self, addr :u16, val :u8) {
+        match addr {
+            ADDR_SB => self.sb = val,
+            ADDR_SC => {
+                self.sc = val;
+
+                // Start a transfer as master
+                if self.is_transfer_enabled() && self.is_internal_clock() {
+                    self.bits_left = 0;
+                    if let Some(val) = self.link.transfer(self.sb) {
+                        self.start_shifting(val);
+                    }
+                }
+            },
+            _ => panic!("Invalid serial address: {:04X}", addr)
+        }
+    }
+}
+
+impl Savestate for Serial {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.sb);
+        w.u8(self.sc);
+        w.u8(self.bits_left);
+        w.u8(self.incoming);
+        w.bool(self.prev_clock_bit);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.sb = r.u8()?;
+        self.sc = r.u8()?;
+        self.bits_left = r.u8()?;
+        self.incoming = r.u8()?;
+        self.prev_clock_bit = r.bool()?;
+
+        return Ok(());
+    }
+}
@@ -3,6 +3,7 @@ use std::rc::Rc;
 
 use crate::interruptManager::InterruptManager;
 use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 pub struct Timer {
     timer_counter: u16,
@@ -165,3 +166,27 @@ impl Timer {
         }
     }
 }
+
+impl Savestate for Timer {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u16(self.timer_counter);
+        w.u8(self.tma);
+        w.u8(self.tima);
+        w.u8(self.tac);
+        w.u8(self.old_tma);
+        w.bool(self.is_wait_set_tima);
+        w.u8(self.wait_tima);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.timer_counter = r.u16()?;
+        self.tma = r.u8()?;
+        self.tima = r.u8()?;
+        self.tac = r.u8()?;
+        self.old_tma = r.u8()?;
+        self.is_wait_set_tima = r.bool()?;
+        self.wait_tima = r.u8()?;
+
+        return Ok(());
+    }
+}
@@ -1,5 +1,9 @@
-use crate::consts::CART_HEADER_CART_TYPE;
-use crate::consts::ComponentWithMemory;
+use std::fmt;
+use std::io::prelude::Write;
+use std::path::{Path, PathBuf};
+
+use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 mod noMBC;
 mod mbc1;
@@ -7,9 +11,16 @@ mod mbc2;
 mod mbc3;
 mod mbc5;
 mod mbcTest;
+mod rtc;
+pub mod header;
+pub mod archive;
+pub mod patch;
+
+// Where the saves were kept before they were written next to the ROM
+const LEGACY_SAVE_PATH :&str = "roms/games/saves";
 
 #[allow(non_camel_case_types)]
-#[derive(Debug)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum CartridgeType {
     ROM                 = 0x00,
     ROM_RAM             = 0x08,
@@ -94,28 +105,242 @@ impl CartridgeType {
     }
 }
 
-pub trait Cartridge: ComponentWithMemory {
+pub trait Cartridge: ComponentWithMemory + Savestate {
     fn init(&mut self);
+    fn rom(&self) -> &[u8];
 
+    fn set_save_path(&mut self, path :PathBuf);
     fn load_ram(&mut self);
     fn save_ram(&self);
-    fn print_rom_data(&self);
+    // Called every T-cycle, for the hardware on the cartridge which runs on its own
+    fn tick(&mut self) {}
+
+    // ROM bank mapped at an address, for cheats made for a given bank
+    fn rom_bank(&self, addr :u16) -> u16 { return if addr < BANK1_START {0} else {1}; }
+    // Write to a bank of the cartridge RAM, whatever the mapped bank and the RAM gate
+    fn write_ram_bank(&mut self, _bank :u8, _addr :u16, _val :u8) {}
+    // Read a bank of the cartridge RAM the same way, None if there is no such bank
+    fn read_ram_bank(&self, _bank :u8, _addr :u16) -> Option<u8> { return None; }
+    // RAM bank mapped at A000-BFFF
+    fn ram_bank(&self) -> u8 { return 0; }
+    // The RAM and RTC kept by the battery, which a reset of the Game Boy leaves alone
+    fn save_battery(&self, _w :&mut StateWriter) {}
+    fn load_battery(&mut self, _r :&mut StateReader) -> Result<(), StateError> { return Ok(()); }
+    // Like a cartridge without a save: cleared RAM and the RTC at 0
+    fn clear_battery(&mut self) {}
 
     fn is_test_cart(&self) -> bool; // For tests. Remove.
 }
 
-pub fn load_cartridge(path :&str) -> Box<dyn Cartridge> {
-    let rom = std::fs::read(path).unwrap();
-    let file = path.split('/').last().unwrap();
-    let cartridge_type :CartridgeType = rom[CART_HEADER_CART_TYPE].into();
+/* Battery save of a ROM: <ROM name>.sav, next to the ROM unless a directory is given */
+pub fn save_path(rom_path :&str, save_dir :Option<&Path>) -> PathBuf {
+    let rom_path = Path::new(rom_path).with_extension("sav");
+
+    return match save_dir {
+        Some(dir) => dir.join(rom_path.file_name().unwrap_or_default()),
+        None => rom_path
+    };
+}
+
+/* Where older versions kept the save of a ROM: named like the ROM, in LEGACY_SAVE_PATH by default */
+pub fn legacy_save_path(rom_path :&str, legacy_dir :Option<&Path>) -> PathBuf {
+    let file = Path::new(rom_path).file_name().unwrap_or_default();
+
+    return legacy_dir.unwrap_or(Path::new(LEGACY_SAVE_PATH)).join(file);
+}
+
+/* Read the battery save, or the legacy one */
+fn read_save(path :&Path, legacy_path :&Path) -> Option<Vec<u8>> {
+    return std::fs::read(path).or_else(|_| std::fs::read(legacy_path)).ok();
+}
+
+/*
+ Write the save to a temporary file first and rename it, so that the previous save is kept
+ whole if the emulator or the system dies in the middle of the write.
+ */
+fn write_save(path :&Path, data :&[&[u8]]) {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+
+    let result = (|| {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        let mut file = std::fs::File::create(&tmp_path)?;
+        for part in data {
+            file.write_all(part)?;
+        }
+        file.sync_all()?;
+
+        return std::fs::rename(&tmp_path, path);
+    })();
+
+    if let Err(err) = result {
+        println!("Couldn't save the cartridge RAM to {}: {}", path.display(), err);
+    }
+}
+
+#[derive(Debug)]
+pub enum LoadError {
+    Io(std::io::Error),
+    TooShort(usize),                                   // Length of the file
+    RomSizeMismatch { expected :usize, actual :usize }, // In bytes, from the header and the file
+    HeaderChecksum { expected :u8, actual :u8 },
+    GlobalChecksum { expected :u16, actual :u16 },
+    Unsupported(u8),                                    // Cartridge type in the header
+    Archive(String),                                    // No ROM in a zip file, or a broken one
+    Patch(String, patch::PatchError)                    // Path of the patch
+}
+
+impl fmt::Display for LoadError {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            LoadError::Io(err) => write!(f, "I/O error: {}", err),
+            LoadError::TooShort(len) => write!(f, "the file is too short for a ROM header ({} bytes)", len),
+            LoadError::RomSizeMismatch { expected, actual } =>
+                write!(f, "the header declares {} bytes of ROM but the file has {}", expected, actual),
+            LoadError::HeaderChecksum { expected, actual } =>
+                write!(f, "bad header checksum: 0x{:02X} in the header, 0x{:02X} computed", expected, actual),
+            LoadError::GlobalChecksum { expected, actual } =>
+                write!(f, "bad global checksum: 0x{:04X} in the header, 0x{:04X} computed", expected, actual),
+            LoadError::Unsupported(code) =>
+                write!(f, "unsupported cartridge type 0x{:02X} ({:?})", code, CartridgeType::from(*code)),
+            LoadError::Archive(err) => write!(f, "archive: {}", err),
+            LoadError::Patch(path, err) => write!(f, "couldn't apply {}: {}", path, err)
+        }
+    }
+}
+
+impl From<std::io::Error> for LoadError {
+    fn from(err :std::io::Error) -> LoadError {
+        return LoadError::Io(err);
+    }
+}
+
+/* ROM size declared by the value at 0x148 */
+pub fn rom_size_from_header(code :u8) -> Option<usize> {
+    return if code <= 8 { Some(0x8000 << code) } else { None };
+}
+
+/* Checksum of 0x134-0x14C, checked by the boot ROM against 0x14D */
+pub fn header_checksum(rom :&[u8]) -> u8 {
+    return rom[CART_HEADER_TITLE_START..CART_HEADER_HEADER_CHECKSUM].iter()
+        .fold(0u8, |acc, byte| acc.wrapping_sub(*byte).wrapping_sub(1));
+}
+
+/* Sum of every byte of the ROM but the checksum itself, stored big endian at 0x14E */
+pub fn global_checksum(rom :&[u8]) -> u16 {
+    let checksum_bytes = rom[CART_HEADER_CHECKSUM_START] as u16 + rom[CART_HEADER_CHECKSUM_END] as u16;
+
+    return rom.iter()
+        .fold(0u16, |acc, byte| acc.wrapping_add(*byte as u16))
+        .wrapping_sub(checksum_bytes);
+}
+
+fn check_header(rom :&[u8]) -> Result<(), LoadError> {
+    if rom.len() <= CART_HEADER_CHECKSUM_END {
+        return Err(LoadError::TooShort(rom.len()));
+    }
+
+    let expected = rom[CART_HEADER_HEADER_CHECKSUM];
+    let actual = header_checksum(rom);
+    if expected != actual {
+        return Err(LoadError::HeaderChecksum { expected, actual });
+    }
+
+    let code = rom[CART_HEADER_CART_TYPE];
+    if matches!(CartridgeType::from(code), CartridgeType::OTHER | CartridgeType::MBC6 | CartridgeType::MBC7) {
+        return Err(LoadError::Unsupported(code));
+    }
+
+    let expected = rom_size_from_header(rom[CART_HEADER_ROM_SIZE]).unwrap_or(0);
+    if expected != rom.len() {
+        return Err(LoadError::RomSizeMismatch { expected, actual: rom.len() });
+    }
+
+    let expected = ((rom[CART_HEADER_CHECKSUM_START] as u16) << 8) | rom[CART_HEADER_CHECKSUM_END] as u16;
+    let actual = global_checksum(rom);
+    if expected != actual {
+        return Err(LoadError::GlobalChecksum { expected, actual });
+    }
+
+    return Ok(());
+}
+
+/*
+ How to load a ROM. Unless `is_lenient`, the header has to be valid. The lenient mode is for
+ headerless homebrew: the ROM is zero-padded up to the size in its header (at least 32 KiB),
+ the checksums aren't checked and unknown types run without a mapper.
+
+ Without any patch given, the <ROM name>.ips/.ups/.bps next to the ROM are applied.
+ */
+#[derive(Debug, Clone, Default)]
+pub struct LoadOptions {
+    pub is_lenient      :bool,
+    pub patches         :Vec<String>,
+    pub legacy_save_dir :Option<String>,   // See legacy_save_path
+}
+
+const PATCH_EXTENSIONS :[&str;3] = ["ips", "ups", "bps"];
+
+/* Load a ROM file, which can be compressed (see archive::read_rom) */
+pub fn load_cartridge(path :&str, options :&LoadOptions) -> Result<Box<dyn Cartridge>, LoadError> {
+    let (rom, rom_path) = load_rom(path, options)?;
+
+    return new_cartridge(rom, &rom_path, options);
+}
+
+/* Contents of the ROM, checked and patched, and the path of the ROM itself */
+pub fn load_rom(path :&str, options :&LoadOptions) -> Result<(Vec<u8>, String), LoadError> {
+    let (mut rom, rom_path) = archive::read_rom(path)?;
+
+    // The dump is checked as it is, as patches rarely fix the global checksum
+    if !options.is_lenient {
+        check_header(&rom)?;
+    }
+
+    let patches = if options.patches.is_empty() {
+        PATCH_EXTENSIONS.iter()
+            .map(|ext| Path::new(&rom_path).with_extension(ext))
+            .filter(|path| path.exists())
+            .map(|path| path.to_string_lossy().to_string())
+            .collect()
+    } else {
+        options.patches.clone()
+    };
+
+    for patch in patches {
+        rom = patch::apply_patch(&rom, &std::fs::read(&patch)?)
+            .map_err(|err| LoadError::Patch(patch, err))?;
+    }
+
+    return Ok((rom, rom_path));
+}
+
+/* Mapper of the cartridge type of a ROM from load_rom */
+pub fn new_cartridge(mut rom :Vec<u8>, path :&str, options :&LoadOptions) -> Result<Box<dyn Cartridge>, LoadError> {
+    if options.is_lenient {
+        let declared = rom.get(CART_HEADER_ROM_SIZE).and_then(|code| rom_size_from_header(*code));
+        let size = declared.unwrap_or(0).max(0x8000);
+
+        if rom.len() < size {
+            rom.resize(size, 0x00);
+        }
+    }
+
+    let code = rom[CART_HEADER_CART_TYPE];
+    let cartridge_type :CartridgeType = code.into();
+    let save_path = save_path(path, None);
+    let legacy_path = legacy_save_path(path, options.legacy_save_dir.as_deref().map(Path::new));
 
     return match cartridge_type.mbc_n() {
-        0 => Box::new(noMBC::NoMBC::new(path, rom)),
-        1 => Box::new(mbc1::MBC1::new(file, rom)),
-        2 => Box::new(mbc2::MBC2::new(file, rom)),
-        3 => Box::new(mbc3::MBC3::new(file, rom)),
-        5 => Box::new(mbc5::MBC5::new(file, rom)),
-        255 => Box::new(mbcTest::MBCTest::new(file, rom)),
-        _ => panic!("MBC type not supported: {:?}", cartridge_type)
+        0 => Ok(Box::new(noMBC::NoMBC::new(legacy_path, save_path, rom))),
+        1 => Ok(Box::new(mbc1::MBC1::new(legacy_path, save_path, rom))),
+        2 => Ok(Box::new(mbc2::MBC2::new(legacy_path, save_path, rom))),
+        3 => Ok(Box::new(mbc3::MBC3::new(legacy_path, save_path, rom))),
+        5 => Ok(Box::new(mbc5::MBC5::new(legacy_path, save_path, rom))),
+        255 => Ok(Box::new(mbcTest::MBCTest::new(rom))),
+        _ => Err(LoadError::Unsupported(code))
     }
 }
@@ -0,0 +1,80 @@
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
+
+use flate2::read::GzDecoder;
+use zip::ZipArchive;
+use zip::result::ZipError;
+
+use crate::cartridge::LoadError;
+
+/*
+ * ROMs stored compressed: `.gz` files, and `.zip` archives from which the first `.gb`/`.gbc`
+ * entry is loaded, or the one named after a '#' (`games.zip#Tetris.gb`).
+ *
+ * The ROM gets the path it would have if it were extracted next to the archive, so that its
+ * save and save states are named after it rather than after the archive.
+ */
+
+const ROM_EXTENSIONS :[&str;2] = ["gb", "gbc"];
+
+/* Contents of the ROM at `path`, and the path of the ROM itself */
+pub fn read_rom(path :&str) -> Result<(Vec<u8>, String), LoadError> {
+    if let Some((archive, entry)) = path.rsplit_once('#') {
+        if has_extension(archive, "zip") {
+            return read_zip(archive, Some(entry));
+        }
+    }
+
+    if has_extension(path, "zip") {
+        return read_zip(path, None);
+    }
+
+    if has_extension(path, "gz") {
+        let mut rom = vec![];
+        GzDecoder::new(File::open(path)?).read_to_end(&mut rom)?;
+
+        return Ok((rom, path[..path.len() - ".gz".len()].to_string()));
+    }
+
+    return Ok((std::fs::read(path)?, path.to_string()));
+}
+
+fn has_extension(path :&str, ext :&str) -> bool {
+    return Path::new(path).extension()
+        .map(|e| e.to_string_lossy().eq_ignore_ascii_case(ext))
+        .unwrap_or(false);
+}
+
+fn read_zip(path :&str, entry :Option<&str>) -> Result<(Vec<u8>, String), LoadError> {
+    let mut archive = ZipArchive::new(File::open(path)?).map_err(zip_error)?;
+
+    // In the order they are stored. Matched by their full name or just their file name.
+    let names = (0..archive.len())
+        .map(|i| archive.by_index_raw(i).map(|file| file.name().to_string()))
+        .collect::<Result<Vec<String>, ZipError>>()
+        .map_err(zip_error)?;
+    let name = match entry {
+        Some(entry) => names.iter().find(|name| *name == entry || file_name(name) == entry)
+            .ok_or_else(|| LoadError::Archive(format!("no entry named {} in {}", entry, path)))?,
+        None => names.iter().find(|name| ROM_EXTENSIONS.iter().any(|ext| has_extension(name, ext)))
+            .ok_or_else(|| LoadError::Archive(format!("no .gb or .gbc file in {}", path)))?
+    };
+
+    let mut rom = vec![];
+    archive.by_name(name).map_err(zip_error)?.read_to_end(&mut rom)?;
+
+    let rom_path = Path::new(path).with_file_name(file_name(name));
+    return Ok((rom, rom_path.to_string_lossy().to_string()));
+}
+
+fn file_name(name :&str) -> &str {
+    return name.rsplit('/').next().unwrap_or(name);
+}
+
+fn zip_error(err :ZipError) -> LoadError {
+    return match err {
+        ZipError::Io(err) => LoadError::Io(err),
+        err => LoadError::Archive(err.to_string())
+    }
+}
@@ -0,0 +1,388 @@
+use std::fmt;
+
+use serde_json::json;
+
+use crate::consts::*;
+use crate::cartridge::{CartridgeType, rom_size_from_header, header_checksum, global_checksum};
+
+/*
+ * Cartridge header, at 0x0100-0x014F of every ROM.
+ *
+ * Later cartridges shortened the title to make room for the CGB flag and a manufacturer code,
+ * and moved the licensee to a 2-character code at 0x144 (flagged by 0x33 in the old one).
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CgbSupport {
+    None,
+    Supported,  // 0x80, also runs on DMG
+    Only        // 0xC0
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Destination {
+    Japan,
+    Overseas
+}
+
+// Old licensee code meaning that the new one is used instead
+const USE_NEW_LICENSEE :u8 = 0x33;
+
+const RAM_SIZES :[usize;6] = [0, 0, 8, 32, 128, 64]; // In KiB
+
+#[derive(Debug, Clone)]
+pub struct CartridgeHeader {
+    pub title               :String,
+    pub manufacturer_code   :Option<String>,
+    pub old_licensee_code   :u8,
+    pub new_licensee_code   :Option<String>,
+    pub cgb                 :CgbSupport,
+    pub is_sgb              :bool,
+    pub cartridge_type      :CartridgeType,
+    pub rom_size            :usize,         // In bytes, 0 if the code is unknown
+    pub rom_bank_n          :u16,
+    pub ram_size            :usize,         // In KiB
+    pub ram_bank_n          :u16,
+    pub destination         :Destination,
+    pub version             :u8,
+    pub header_checksum     :u8,
+    pub global_checksum     :u16,
+}
+
+impl CartridgeHeader {
+    /* The ROM has to be at least 0x150 bytes long */
+    pub fn parse(rom :&[u8]) -> CartridgeHeader {
+        let cgb = match rom[CART_HEADER_CGB_FLAG] {
+            0xC0 => CgbSupport::Only,
+            0x80 => CgbSupport::Supported,
+            _ => CgbSupport::None
+        };
+
+        // Only CGB cartridges can have one, as 4 uppercase characters
+        let manuf_code = &rom[CART_HEADER_MANUF_CODE_START..=CART_HEADER_MANUF_CODE_END];
+        let manufacturer_code = if cgb != CgbSupport::None
+            && manuf_code.iter().all(|c| c.is_ascii_uppercase() || c.is_ascii_digit()) {
+            Some(String::from_utf8_lossy(manuf_code).to_string())
+        } else {
+            None
+        };
+
+        let title_end = if manufacturer_code.is_some() { CART_HEADER_MANUF_CODE_START }
+                        else if cgb != CgbSupport::None { CART_HEADER_CGB_FLAG }
+                        else { CART_HEADER_TITLE_END + 1 };
+        let title = rom[CART_HEADER_TITLE_START..title_end].iter()
+            .take_while(|c| **c != 0)
+            .filter(|c| c.is_ascii_graphic() || **c == b' ')
+            .map(|c| *c as char)
+            .collect::<String>();
+
+        let old_licensee_code = rom[CART_HEADER_OLD_LICENSE_CODE];
+        let new_licensee_code = if old_licensee_code == USE_NEW_LICENSEE {
+            let code = &rom[CART_HEADER_LICENSE_CODE_START..=CART_HEADER_LICENSE_CODE_END];
+            Some(code.iter().map(|c| *c as char).collect())
+        } else {
+            None
+        };
+
+        let rom_size = rom_size_from_header(rom[CART_HEADER_ROM_SIZE]).unwrap_or(0);
+        let ram_size = RAM_SIZES.get(rom[CART_HEADER_RAM_SIZE] as usize).copied().unwrap_or(0);
+
+        return CartridgeHeader {
+            title,
+            manufacturer_code,
+            old_licensee_code,
+            new_licensee_code,
+            cgb,
+            is_sgb              : rom[CART_HEADER_SGB_FLAG] == 0x03,
+            cartridge_type      : rom[CART_HEADER_CART_TYPE].into(),
+            rom_size,
+            rom_bank_n          : (rom_size / 0x4000).max(2) as u16,
+            ram_size,
+            ram_bank_n          : (ram_size / 8) as u16,
+            destination         : if rom[CART_HEADER_DESTINATION] == 0x00 { Destination::Japan } else { Destination::Overseas },
+            version             : rom[CART_HEADER_ROM_VERSION],
+            header_checksum     : rom[CART_HEADER_HEADER_CHECKSUM],
+            global_checksum     : ((rom[CART_HEADER_CHECKSUM_START] as u16) << 8) | rom[CART_HEADER_CHECKSUM_END] as u16,
+        }
+    }
+
+    /* Name of the publisher, from the new licensee code when there is one */
+    pub fn licensee(&self) -> Option<&'static str> {
+        return match &self.new_licensee_code {
+            Some(code) => new_licensee_name(code),
+            None => old_licensee_name(self.old_licensee_code)
+        }
+    }
+
+    /* Report of `gb info`, along with whether the checksums match the ROM */
+    pub fn to_json(&self, rom :&[u8]) -> serde_json::Value {
+        return json!({
+            "title": self.title,
+            "manufacturer_code": self.manufacturer_code,
+            "licensee": self.licensee(),
+            "old_licensee_code": format!("{:02X}", self.old_licensee_code),
+            "new_licensee_code": self.new_licensee_code,
+            "cgb": format!("{:?}", self.cgb),
+            "sgb": self.is_sgb,
+            "cartridge_type": format!("{:?}", self.cartridge_type),
+            "rom_size": self.rom_size,
+            "rom_banks": self.rom_bank_n,
+            "ram_size": self.ram_size * 1024,
+            "ram_banks": self.ram_bank_n,
+            "destination": format!("{:?}", self.destination),
+            "version": self.version,
+            "header_checksum": self.header_checksum,
+            "header_checksum_ok": self.header_checksum == header_checksum(rom),
+            "global_checksum": self.global_checksum,
+            "global_checksum_ok": self.global_checksum == global_checksum(rom),
+        });
+    }
+}
+
+impl fmt::Display for CartridgeHeader {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        let licensee_code = match &self.new_licensee_code {
+            Some(code) => code.clone(),
+            None => format!("{:02X}", self.old_licensee_code)
+        };
+
+        writeln!(f, "Title\t\t\t: {}", self.title)?;
+        if let Some(code) = &self.manufacturer_code {
+            writeln!(f, "Manufacturer code\t: {}", code)?;
+        }
+        writeln!(f, "Licensee\t\t: {} ({})", self.licensee().unwrap_or("Unknown"), licensee_code)?;
+        writeln!(f, "CGB\t\t\t: {:?}", self.cgb)?;
+        writeln!(f, "SGB\t\t\t: {}", self.is_sgb)?;
+        writeln!(f, "Cartridge type\t\t: {:?}", self.cartridge_type)?;
+        writeln!(f, "ROM size\t\t: {} KiB", self.rom_size / 1024)?;
+        writeln!(f, "ROM banks\t\t: {}", self.rom_bank_n)?;
+        writeln!(f, "RAM size\t\t: {} KiB", self.ram_size)?;
+        writeln!(f, "RAM banks\t\t: {}", self.ram_bank_n)?;
+        writeln!(f, "Destination\t\t: {:?}", self.destination)?;
+        writeln!(f, "Version\t\t\t: 0x{:02X}", self.version)?;
+        writeln!(f, "Header checksum\t\t: 0x{:02X}", self.header_checksum)?;
+        return write!(f, "Global checksum\t\t: 0x{:04X}", self.global_checksum);
+    }
+}
+
+fn new_licensee_name(code :&str) -> Option<&'static str> {
+    return Some(match code {
+        "00" => "None",
+        "01" => "Nintendo R&D1",
+        "08" => "Capcom",
+        "13" => "EA (Electronic Arts)",
+        "18" => "Hudson Soft",
+        "19" => "B-AI",
+        "20" => "KSS",
+        "22" => "Planning Office WADA",
+        "24" => "PCM Complete",
+        "25" => "San-X",
+        "28" => "Kemco",
+        "29" => "SETA Corporation",
+        "30" => "Viacom",
+        "31" => "Nintendo",
+        "32" => "Bandai",
+        "33" => "Ocean Software/Acclaim Entertainment",
+        "34" => "Konami",
+        "35" => "HectorSoft",
+        "37" => "Taito",
+        "38" => "Hudson Soft",
+        "39" => "Banpresto",
+        "41" => "Ubi Soft",
+        "42" => "Atlus",
+        "44" => "Malibu Interactive",
+        "46" => "Angel",
+        "47" => "Bullet-Proof Software",
+        "49" => "Irem",
+        "50" => "Absolute",
+        "51" => "Acclaim Entertainment",
+        "52" => "Activision",
+        "53" => "Sammy USA Corporation",
+        "54" => "Konami",
+        "55" => "Hi Tech Expressions",
+        "56" => "LJN",
+        "57" => "Matchbox",
+        "58" => "Mattel",
+        "59" => "Milton Bradley Company",
+        "60" => "Titus Interactive",
+        "61" => "Virgin Games Ltd.",
+        "64" => "Lucasfilm Games",
+        "67" => "Ocean Software",
+        "69" => "EA (Electronic Arts)",
+        "70" => "Infogrames",
+        "71" => "Interplay Entertainment",
+        "72" => "Broderbund",
+        "73" => "Sculptured Software",
+        "75" => "The Sales Curve Limited",
+        "78" => "THQ",
+        "79" => "Accolade",
+        "80" => "Misawa Entertainment",
+        "83" => "LOZC G.",
+        "86" => "Tokuma Shoten",
+        "87" => "Tsukuda Original",
+        "91" => "Chunsoft Co.",
+        "92" => "Video System",
+        "93" => "Ocean Software/Acclaim Entertainment",
+        "95" => "Varie",
+        "96" => "Yonezawa/S'Pal",
+        "97" => "Kaneko",
+        "99" => "Pack-In-Video",
+        "9H" => "Bottom Up",
+        "A4" => "Konami (Yu-Gi-Oh!)",
+        "BL" => "MTO",
+        "DK" => "Kodansha",
+        _ => return None
+    });
+}
+
+fn old_licensee_name(code :u8) -> Option<&'static str> {
+    return Some(match code {
+        0x00 => "None",
+        0x01 => "Nintendo",
+        0x08 => "Capcom",
+        0x09 => "HOT-B",
+        0x0A => "Jaleco",
+        0x0B => "Coconuts Japan",
+        0x0C => "Elite Systems",
+        0x13 => "EA (Electronic Arts)",
+        0x18 => "Hudson Soft",
+        0x19 => "ITC Entertainment",
+        0x1A => "Yanoman",
+        0x1D => "Japan Clary",
+        0x1F => "Virgin Games Ltd.",
+        0x24 => "PCM Complete",
+        0x25 => "San-X",
+        0x28 => "Kemco",
+        0x29 => "SETA Corporation",
+        0x30 => "Infogrames",
+        0x31 => "Nintendo",
+        0x32 => "Bandai",
+        0x34 => "Konami",
+        0x35 => "HectorSoft",
+        0x38 => "Capcom",
+        0x39 => "Banpresto",
+        0x3C => "Entertainment Interactive",
+        0x3E => "Gremlin",
+        0x41 => "Ubi Soft",
+        0x42 => "Atlus",
+        0x44 => "Malibu Interactive",
+        0x46 => "Angel",
+        0x47 => "Spectrum HoloByte",
+        0x49 => "Irem",
+        0x4A => "Virgin Games Ltd.",
+        0x4D => "Malibu Interactive",
+        0x4F => "U.S. Gold",
+        0x50 => "Absolute",
+        0x51 => "Acclaim Entertainment",
+        0x52 => "Activision",
+        0x53 => "Sammy USA Corporation",
+        0x54 => "GameTek",
+        0x55 => "Park Place",
+        0x56 => "LJN",
+        0x57 => "Matchbox",
+        0x59 => "Milton Bradley Company",
+        0x5A => "Mindscape",
+        0x5B => "Romstar",
+        0x5C => "Naxat Soft",
+        0x5D => "Tradewest",
+        0x60 => "Titus Interactive",
+        0x61 => "Virgin Games Ltd.",
+        0x67 => "Ocean Software",
+        0x69 => "EA (Electronic Arts)",
+        0x6E => "Elite Systems",
+        0x6F => "Electro Brain",
+        0x70 => "Infogrames",
+        0x71 => "Interplay Entertainment",
+        0x72 => "Broderbund",
+        0x73 => "Sculptured Software",
+        0x75 => "The Sales Curve Limited",
+        0x78 => "THQ",
+        0x79 => "Accolade",
+        0x7A => "Triffix Entertainment",
+        0x7C => "MicroProse",
+        0x7F => "Kemco",
+        0x80 => "Misawa Entertainment",
+        0x83 => "LOZC G.",
+        0x86 => "Tokuma Shoten",
+        0x8B => "Bullet-Proof Software",
+        0x8C => "Vic Tokai Corp.",
+        0x8E => "Ape Inc.",
+        0x8F => "I'Max",
+        0x91 => "Chunsoft Co.",
+        0x92 => "Video System",
+        0x93 => "Tsubaraya Productions",
+        0x95 => "Varie",
+        0x96 => "Yonezawa/S'Pal",
+        0x97 => "Kemco",
+        0x99 => "Arc",
+        0x9A => "Nihon Bussan",
+        0x9B => "Tecmo",
+        0x9C => "Imagineer",
+        0x9D => "Banpresto",
+        0x9F => "Nova",
+        0xA1 => "Hori Electric",
+        0xA2 => "Bandai",
+        0xA4 => "Konami",
+        0xA6 => "Kawada",
+        0xA7 => "Takara",
+        0xA9 => "Technos Japan",
+        0xAA => "Broderbund",
+        0xAC => "Toei Animation",
+        0xAD => "Toho",
+        0xAF => "Namco",
+        0xB0 => "Acclaim Entertainment",
+        0xB1 => "ASCII Corporation or Nexsoft",
+        0xB2 => "Bandai",
+        0xB4 => "Square Enix",
+        0xB6 => "HAL Laboratory",
+        0xB7 => "SNK",
+        0xB9 => "Pony Canyon",
+        0xBA => "Culture Brain",
+        0xBB => "Sunsoft",
+        0xBD => "Sony Imagesoft",
+        0xBF => "Sammy Corporation",
+        0xC0 => "Taito",
+        0xC2 => "Kemco",
+        0xC3 => "Square",
+        0xC4 => "Tokuma Shoten",
+        0xC5 => "Data East",
+        0xC6 => "Tonkin House",
+        0xC8 => "Koei",
+        0xC9 => "UFL",
+        0xCA => "Ultra Games",
+        0xCB => "VAP, Inc.",
+        0xCC => "Use Corporation",
+        0xCD => "Meldac",
+        0xCE => "Pony Canyon",
+        0xCF => "Angel",
+        0xD0 => "Taito",
+        0xD1 => "SOFEL",
+        0xD2 => "Quest",
+        0xD3 => "Sigma Enterprises",
+        0xD4 => "ASK Kodansha Co.",
+        0xD6 => "Naxat Soft",
+        0xD7 => "Copya System",
+        0xD9 => "Banpresto",
+        0xDA => "Tomy",
+        0xDB => "LJN",
+        0xDD => "Nippon Computer Systems",
+        0xDE => "Human Ent.",
+        0xDF => "Altron",
+        0xE0 => "Jaleco",
+        0xE1 => "Towa Chiki",
+        0xE2 => "Yutaka",
+        0xE3 => "Varie",
+        0xE5 => "Epoch",
+        0xE7 => "Athena",
+        0xE8 => "Asmik Ace Entertainment",
+        0xE9 => "Natsume",
+        0xEA => "King Records",
+        0xEB => "Atlus",
+        0xEC => "Epic/Sony Records",
+        0xEE => "IGS",
+        0xF0 => "A Wave",
+        0xF3 => "Extreme Entertainment",
+        0xFF => "LJN",
+        _ => return None
+    });
+}
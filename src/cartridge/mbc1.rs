@@ -1,25 +1,23 @@
 use crate::consts::*;
-use crate::cartridge::{CartridgeType, Cartridge};
-use std::io::prelude::Write;
+use crate::cartridge::{CartridgeType, Cartridge, read_save, write_save};
+use crate::cartridge::header::CartridgeHeader;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+use std::path::PathBuf;
 
 
-const SAVE_PATH :&str = "roms/games/saves";
 const ROM_BANK_SIZE :u16 = 0x4000;
 const RAM_BANK_SIZE :u16 = 0x2000;
 
 pub struct MBC1 {
-    file                : String,
+    legacy_save_path    : PathBuf,
+    save_path           : PathBuf,
     rom                 : Vec<u8>,
     // 32 KiBs, or 4 KiB banks
     ext_ram             : Vec<u8>,
     cartridge_type      : CartridgeType,
-    rom_size            : u16,
     rom_bank_n          : u16,
     ram_size            : u16,
     ram_bank_n          : u8,
-    mask_rom_version_n  : u8,
-    header_checksum     : u8,
-    global_checksum     : u16,
 
     // MBC registers
     ramg          : bool, // RAM gate register / RAM enable/disable
@@ -30,20 +28,18 @@ pub struct MBC1 {
 
 
 impl MBC1 {
-    pub fn new(file :&str, rom :Vec<u8>) -> MBC1 {
-        let cartridge_type :CartridgeType = rom[CART_HEADER_CART_TYPE].into();
-        let ram_size  = if cartridge_type.has_ram() { [0, 0, 8, 32, 128, 64][rom[CART_HEADER_RAM_SIZE] as usize] } else {0};
+    pub fn new(legacy_save_path :PathBuf, save_path :PathBuf, rom :Vec<u8>) -> MBC1 {
+        let header = CartridgeHeader::parse(&rom);
+        let cartridge_type = header.cartridge_type;
+        let ram_size  = if cartridge_type.has_ram() { header.ram_size } else {0};
 
         return MBC1 {
-            file: file.to_string(),
+            legacy_save_path,
+            save_path,
             cartridge_type,
-            rom_size            : 32 * (1 << rom[CART_HEADER_ROM_SIZE]), // In KiB
-            rom_bank_n          : 1 << (rom[CART_HEADER_ROM_SIZE]+1),
+            rom_bank_n          : header.rom_bank_n,
             ram_size            : ram_size as u16,
             ram_bank_n          : (ram_size as u8/8),
-            mask_rom_version_n  : rom[CART_HEADER_ROM_VERSION],
-            header_checksum     : rom[CART_HEADER_HEADER_CHECKSUM],
-            global_checksum     : ((rom[CART_HEADER_CHECKSUM_START] as u16) << 8) | rom[CART_HEADER_CHECKSUM_END] as u16,
             rom,
             ext_ram             : vec![0;ram_size*1024],
 
@@ -92,58 +88,68 @@ impl MBC1 {
         let bank_n = if self.selected_mode == 0 {0} else {self.romb1};
         return (addr - EXT_RAM_START + RAM_BANK_SIZE*(bank_n%self.ram_bank_n) as u16) as usize;
     }
+
+    fn has_save(&self) -> bool {
+        return self.cartridge_type.has_battery() && self.ram_size > 0;
+    }
 }
 
 impl Cartridge for MBC1 {
     fn is_test_cart(&self) -> bool { return false; }
+    fn rom(&self) -> &[u8] { return &self.rom; }
+    fn set_save_path(&mut self, path :PathBuf) { self.save_path = path; }
 
-    fn init(&mut self) {
-        if self.cartridge_type.has_ram() && self.ram_size > 0 {
-            self.load_ram();
+    fn rom_bank(&self, addr :u16) -> u16 {
+        return match addr {
+            BANK0_START..=BANK0_END => (self.map_bank0_addr(addr) / ROM_BANK_SIZE as usize) as u16,
+            _ => (self.map_bank1_addr(addr) / ROM_BANK_SIZE as usize) as u16
         }
-
-        self.print_rom_data(); // TODO: Disable on debug
     }
 
-    fn load_ram(&mut self) {
-        let path = format!("{}/{}", SAVE_PATH, self.file);
-        if std::path::Path::new(&path).exists() {
-            let ram = std::fs::read(path).unwrap();
-            self.ext_ram = ram;
+    fn write_ram_bank(&mut self, bank :u8, addr :u16, val :u8) {
+        let index = (addr - EXT_RAM_START) as usize + RAM_BANK_SIZE as usize * bank as usize;
+        if let Some(byte) = self.ext_ram.get_mut(index) {
+            *byte = val;
         }
     }
 
-    fn save_ram(&self) {
-        if self.cartridge_type.has_ram() && self.ram_size > 0 {
-            let path = format!("{}/{}", SAVE_PATH, self.file);
+    fn read_ram_bank(&self, bank :u8, addr :u16) -> Option<u8> {
+        return self.ext_ram.get((addr - EXT_RAM_START) as usize + RAM_BANK_SIZE as usize * bank as usize).copied();
+    }
+
+    fn ram_bank(&self) -> u8 {
+        return if self.selected_mode == 0 || self.ram_bank_n == 0 {0} else {self.romb1 % self.ram_bank_n};
+    }
+
+    fn save_battery(&self, w :&mut StateWriter) {
+        w.bytes(&self.ext_ram);
+    }
+
+    fn load_battery(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        return r.bytes_into(&mut self.ext_ram);
+    }
+
+    fn clear_battery(&mut self) {
+        self.ext_ram.fill(0);
+    }
 
-            let mut file = std::fs::File::create(path).unwrap();
-            file.write_all(&self.ext_ram).unwrap();
+    fn init(&mut self) {
+        if self.has_save() {
+            self.load_ram();
         }
     }
 
-    fn print_rom_data(&self) {
-        println!("\nFile:\n{}", self.file);
-        
-        println!("\nTitle:");
-        for n in self.rom[0x134..=0x143].iter() {
-            if *n >= 60 && *n <= 120 { // Pritable ascii
-                print!("{}", *n as char);
-            }
+    fn load_ram(&mut self) {
+        if let Some(ram) = read_save(&self.save_path, &self.legacy_save_path) {
+            let len = ram.len().min(self.ext_ram.len());
+            self.ext_ram[..len].copy_from_slice(&ram[..len]);
+        }
+    }
+
+    fn save_ram(&self) {
+        if self.has_save() {
+            write_save(&self.save_path, &[&self.ext_ram]);
         }
-        println!();
-
-        println!("Cartridge type\t\t: {:?}", self.cartridge_type);
-        println!("ROM size\t\t: {} KiB", self.rom_size);
-        println!("ROM Banks \t\t: {}", self.rom_bank_n);
-        println!("RAM size\t\t: {} KiB", self.ram_size);
-        println!("RAM Banks \t\t: {}", self.ram_bank_n);
-        println!("Mask ROM version number\t: 0x{:02X}", self.mask_rom_version_n);
-        println!("Header checksum\t\t: 0x{:02X}", self.header_checksum);
-        println!("Global checksum\t\t: 0x{:04X}", self.global_checksum);
-        println!();
-        println!("ROM loaded");
-        println!("--------------------------------------\n");
     }
 }
 
@@ -196,4 +202,24 @@ impl ComponentWithMemory for MBC1 {
             _ => panic!("write(): Invalid address: {:04X}", addr)
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for MBC1 {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.ext_ram);
+        w.bool(self.ramg);
+        w.u8(self.romb0);
+        w.u8(self.romb1);
+        w.u8(self.selected_mode);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ext_ram)?;
+        self.ramg = r.bool()?;
+        self.romb0 = r.u8()?;
+        self.romb1 = r.u8()?;
+        self.selected_mode = r.u8()?;
+
+        return Ok(());
+    }
+}
@@ -1,23 +1,21 @@
 use crate::consts::*;
-use crate::cartridge::{CartridgeType, Cartridge};
+use crate::cartridge::{CartridgeType, Cartridge, read_save, write_save};
+use crate::cartridge::header::CartridgeHeader;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+use std::path::PathBuf;
 
 
 const ROM_BANK_SIZE :u16 = 0x4000;
 
 
 pub struct MBC2 {
-    file                : String,
+    legacy_save_path    : PathBuf,
+    save_path           : PathBuf,
     rom                 : Vec<u8>,
     // 32 KiBs, or 4 KiB banks
     builtin_ram         : Vec<u8>,
     cartridge_type      : CartridgeType,
-    rom_size            : u16,
     rom_bank_n          : u8,
-    ram_size            : u16,
-    ram_bank_n          : u8,
-    mask_rom_version_n  : u8,
-    header_checksum     : u8,
-    global_checksum     : u16,
 
     // MBC registers
     ramg : bool,
@@ -25,20 +23,14 @@ pub struct MBC2 {
 }
 
 impl MBC2 {
-    pub fn new(file :&str, rom :Vec<u8>) -> MBC2 {
-        let cartridge_type :CartridgeType = rom[CART_HEADER_CART_TYPE].into();
-        let ram_size  = if cartridge_type.has_ram() { [0, 0, 8, 32, 128, 64][rom[CART_HEADER_RAM_SIZE] as usize] } else {0};
+    pub fn new(legacy_save_path :PathBuf, save_path :PathBuf, rom :Vec<u8>) -> MBC2 {
+        let header = CartridgeHeader::parse(&rom);
 
         return MBC2 {
-            file: file.to_string(),
-            cartridge_type,
-            rom_size            : 32 * (1 << rom[CART_HEADER_ROM_SIZE]), // In KiB
-            rom_bank_n          : 1 << (rom[CART_HEADER_ROM_SIZE]+1),
-            ram_size            : ram_size as u16,
-            ram_bank_n          : (ram_size as u8/8),
-            mask_rom_version_n  : rom[CART_HEADER_ROM_VERSION],
-            header_checksum     : rom[CART_HEADER_HEADER_CHECKSUM],
-            global_checksum     : ((rom[CART_HEADER_CHECKSUM_START] as u16) << 8) | rom[CART_HEADER_CHECKSUM_END] as u16,
+            legacy_save_path,
+            save_path,
+            cartridge_type      : header.cartridge_type,
+            rom_bank_n          : header.rom_bank_n.min(16) as u8, // 4-bit bank register
             rom,
             builtin_ram         : vec![0;512],
 
@@ -58,38 +50,55 @@ impl MBC2 {
 
 impl Cartridge for MBC2 {
     fn is_test_cart(&self) -> bool { return false; }
+    fn rom(&self) -> &[u8] { return &self.rom; }
+    fn set_save_path(&mut self, path :PathBuf) { self.save_path = path; }
 
-    fn init(&mut self) {
-        // TODO: load RAM
-        // TODO: Disable on debug
-        self.print_rom_data();
+    fn rom_bank(&self, addr :u16) -> u16 {
+        return if addr < BANK1_START {0} else {self.romb as u16};
+    }
+
+    // A single bank of 512 half-bytes, repeated in A000-BFFF
+    fn write_ram_bank(&mut self, bank :u8, addr :u16, val :u8) {
+        if bank == 0 {
+            self.builtin_ram[((addr - EXT_RAM_START) & 0x1FF) as usize] = val | 0xF0;
+        }
     }
 
-    fn load_ram(&mut self) {}
-    fn save_ram(&self) {}
+    fn read_ram_bank(&self, bank :u8, addr :u16) -> Option<u8> {
+        return if bank == 0 { Some(self.builtin_ram[((addr - EXT_RAM_START) & 0x1FF) as usize]) } else { None };
+    }
+
+    fn save_battery(&self, w :&mut StateWriter) {
+        w.bytes(&self.builtin_ram);
+    }
 
-    fn print_rom_data(&self) {
-        println!("\nFile:\n{}", self.file);
-        
-        println!("\nTitle:");
-        for n in self.rom[0x134..=0x143].iter() {
-            if *n >= 60 && *n <= 120 { // Pritable ascii
-                print!("{}", *n as char);
+    fn load_battery(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        return r.bytes_into(&mut self.builtin_ram);
+    }
+
+    fn clear_battery(&mut self) {
+        self.builtin_ram.fill(0);
+    }
+
+    fn init(&mut self) {
+        if self.cartridge_type.has_battery() {
+            self.load_ram();
+        }
+    }
+
+    /* The 512 half-bytes are saved a byte each, as other emulators do */
+    fn load_ram(&mut self) {
+        if let Some(ram) = read_save(&self.save_path, &self.legacy_save_path) {
+            for (dst, src) in self.builtin_ram.iter_mut().zip(ram.iter()) {
+                *dst = src | 0xF0;
             }
         }
-        println!();
-
-        println!("Cartridge type\t\t: {:?}", self.cartridge_type);
-        println!("ROM size\t\t: {} KiB", self.rom_size);
-        println!("ROM Banks \t\t: {}", self.rom_bank_n);
-        println!("RAM size\t\t: {} KiB", self.ram_size);
-        println!("RAM Banks \t\t: {}", self.ram_bank_n);
-        println!("Mask ROM version number\t: 0x{:02X}", self.mask_rom_version_n);
-        println!("Header checksum\t\t: 0x{:02X}", self.header_checksum);
-        println!("Global checksum\t\t: 0x{:04X}", self.global_checksum);
-        println!();
-        println!("ROM loaded");
-        println!("--------------------------------------\n");
+    }
+
+    fn save_ram(&self) {
+        if self.cartridge_type.has_battery() {
+            write_save(&self.save_path, &[&self.builtin_ram]);
+        }
     }
 }
 
@@ -132,7 +141,8 @@ impl ComponentWithMemory for MBC2 {
             BANK1_START..=BANK1_END => {},
             // Builtin RAM write
             EXT_RAM_START..=0xA1FF => if self.ramg {
-                self.builtin_ram[(addr-EXT_RAM_START) as usize] = val | 0xF0; // Why the MSBs and not LSBs?
+                // Only the lower 4 bits exist, the upper ones read as 1
+                self.builtin_ram[(addr-EXT_RAM_START) as usize] = val | 0xF0;
             },
             // ECHO of A000-A1FF 15 times. Sustract 512 (0x200) until it's on normal RAM range.
             0xA200..=EXT_RAM_END => {
@@ -144,4 +154,20 @@ impl ComponentWithMemory for MBC2 {
             _ => panic!("write(): Invalid address: {:04X}", addr)
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for MBC2 {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.builtin_ram);
+        w.bool(self.ramg);
+        w.u8(self.romb);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.builtin_ram)?;
+        self.ramg = r.bool()?;
+        self.romb = r.u8()?;
+
+        return Ok(());
+    }
+}
@@ -1,9 +1,11 @@
 use crate::consts::*;
-use crate::cartridge::{CartridgeType, Cartridge};
-use std::io::prelude::Write;
+use crate::cartridge::{CartridgeType, Cartridge, read_save, write_save};
+use crate::cartridge::header::CartridgeHeader;
+use crate::cartridge::rtc::Rtc;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+use std::path::PathBuf;
 
 
-const SAVE_PATH :&str = "roms/games/saves";
 
 #[derive(PartialEq)]
 enum SelectionExternal {
@@ -12,20 +14,14 @@ enum SelectionExternal {
 }
 
 pub struct MBC3 {
-    file                : String,
+    legacy_save_path    : PathBuf,
+    save_path           : PathBuf,
     rom                 : Vec<u8>,
     // 32 KiBs, or 4 KiB banks
     ext_ram             : Vec<u8>,
-    cgb_flag            : bool,
-    sgb_flag            : bool,
     cartridge_type      : CartridgeType,
-    rom_size            : u16,
     rom_bank_n          : u16,
     ram_size            : u16,
-    ram_bank_n          : u16,
-    mask_rom_version_n  : u8,
-    header_checksum     : u8,
-    global_checksum     : u16,
 
     // MBC registers
     enable_ext_ram      : bool,
@@ -35,31 +31,22 @@ pub struct MBC3 {
     reg_rtc             : u8,
     ext_selected        : SelectionExternal,
 
-    //rtc_secs : u8,
-    //rtc_mins : u8,
-    //rtc_hour : u8,
-    //rtc_day  : u8,
+    rtc                 : Rtc,
 }
 
 impl MBC3 {
-    pub fn new(file :&str, rom :Vec<u8>) -> MBC3 {
-        let cartridge_type :CartridgeType = rom[0x147].into();
-        let rom_bank_n = 1 << (rom[0x148]+1);
-        let ram_size = if cartridge_type.has_ram() { [0, 0, 8, 32, 128, 64][rom[0x149] as usize] } else {0};
+    pub fn new(legacy_save_path :PathBuf, save_path :PathBuf, rom :Vec<u8>) -> MBC3 {
+        let header = CartridgeHeader::parse(&rom);
+        let cartridge_type = header.cartridge_type;
+        let ram_size = if cartridge_type.has_ram() { header.ram_size } else {0};
         let ext_ram = vec![0;ram_size*1024];
 
         return MBC3 {
-            file: file.to_string(),
-            cgb_flag            : rom[0x143] == 0xC0,
-            sgb_flag            : rom[0x146] == 0x03,
+            legacy_save_path,
+            save_path,
             cartridge_type,
-            rom_size            : 32 * (1 << rom[0x148]), // In KiB
-            rom_bank_n,
+            rom_bank_n          : header.rom_bank_n,
             ram_size            : ram_size as u16,
-            ram_bank_n          : (ram_size as u16/8),
-            mask_rom_version_n  : rom[0x14c],
-            header_checksum     : rom[0x14d],
-            global_checksum     : ((rom[0x14e] as u16) << 8) | rom[0x14f] as u16,
             rom,
             ext_ram,
 
@@ -71,10 +58,7 @@ impl MBC3 {
             enable_ext_ram      : false,
             enable_rtc          : false,
 
-            //rtc_secs : 0,
-            //rtc_mins : 0,
-            //rtc_hour : 0,
-            //rtc_day  : 0,
+            rtc                 : Rtc::new(),
         }
     }
 
@@ -84,65 +68,86 @@ impl MBC3 {
     pub fn map_ext_ram_addr(&self, addr :u16) -> u16 {
         return addr - EXT_RAM_START + 0x2000*self.reg_ram_bank as u16;
     }
+
+    fn has_save(&self) -> bool {
+        return self.cartridge_type.has_battery() && (self.ram_size > 0 || self.cartridge_type.has_timer());
+    }
 }
 
 impl Cartridge for MBC3 {
     fn is_test_cart(&self) -> bool { return false; }
+    fn rom(&self) -> &[u8] { return &self.rom; }
+    fn set_save_path(&mut self, path :PathBuf) { self.save_path = path; }
+
+    fn rom_bank(&self, addr :u16) -> u16 {
+        return if addr < BANK1_START {0} else {self.reg_bank1};
+    }
+
+    fn write_ram_bank(&mut self, bank :u8, addr :u16, val :u8) {
+        let index = (addr - EXT_RAM_START) as usize + 0x2000 * bank as usize;
+        if let Some(byte) = self.ext_ram.get_mut(index) {
+            *byte = val;
+        }
+    }
+
+    fn read_ram_bank(&self, bank :u8, addr :u16) -> Option<u8> {
+        return self.ext_ram.get((addr - EXT_RAM_START) as usize + 0x2000 * bank as usize).copied();
+    }
+
+    // The RTC registers are mapped with 08-0C
+    fn ram_bank(&self) -> u8 { return self.reg_ram_bank; }
+
+    fn save_battery(&self, w :&mut StateWriter) {
+        w.bytes(&self.ext_ram);
+        self.rtc.save_state(w);
+    }
+
+    fn load_battery(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ext_ram)?;
+        return self.rtc.load_state(r);
+    }
+
+    fn clear_battery(&mut self) {
+        self.ext_ram.fill(0);
+        self.rtc = Rtc::new();
+    }
 
     fn init(&mut self) {
-        if self.cartridge_type.has_ram() && self.ram_size > 0 {
+        if self.has_save() {
             self.load_ram();
         }
-
-        // TODO: Disable on debug
-        self.print_rom_data();
     }
 
     fn load_ram(&mut self) {
-        let path = format!("{}/{}", SAVE_PATH, self.file);
-        if std::path::Path::new(&path).exists() {
-            let ram = std::fs::read(path).unwrap();
+        if let Some(ram) = read_save(&self.save_path, &self.legacy_save_path) {
+            let ram_len = ram.len().min(self.ext_ram.len());
+
+            self.ext_ram[..ram_len].copy_from_slice(&ram[..ram_len]);
 
-            for (i, byte) in ram.iter().enumerate() {
-                self.ext_ram[i] = *byte;
+            // The clock is saved after the RAM
+            if self.cartridge_type.has_timer() {
+                self.rtc.load_footer(&ram[ram_len..]);
             }
         }
 
     }
 
     fn save_ram(&self) {
-        if self.cartridge_type.has_ram() && self.ram_size > 0 {
-            let path = format!("{}/{}", SAVE_PATH, self.file);
+        if !self.has_save() {
+            return;
+        }
 
-            let mut file = std::fs::File::create(path).unwrap();
-            file.write_all(&self.ext_ram).unwrap();
+        if self.cartridge_type.has_timer() {
+            write_save(&self.save_path, &[&self.ext_ram, &self.rtc.footer()]);
+        } else {
+            write_save(&self.save_path, &[&self.ext_ram]);
         }
     }
 
-    fn print_rom_data(&self) {
-        println!("\nFile:\n{}", self.file);
-        
-        println!("\nTitle:");
-        for n in self.rom[0x134..=0x143].iter() {
-            if *n >= 60 && *n <= 120 { // Pritable ascii
-                print!("{}", *n as char);
-            }
+    fn tick(&mut self) {
+        if self.cartridge_type.has_timer() {
+            self.rtc.tick();
         }
-        println!();
-
-        println!("\nCGB Flag\t\t: {}", self.cgb_flag);
-        println!("SGB Flag\t\t: {}", self.sgb_flag);
-        println!("Cartridge type\t\t: {:?}", self.cartridge_type);
-        println!("ROM size\t\t: {} KiB", self.rom_size);
-        println!("ROM Banks \t\t: {}", self.rom_bank_n);
-        println!("RAM size\t\t: {} KiB", self.ram_size);
-        println!("RAM Banks \t\t: {}", self.ram_bank_n);
-        println!("Mask ROM version number\t: 0x{:02X}", self.mask_rom_version_n);
-        println!("Header checksum\t\t: 0x{:02X}", self.header_checksum);
-        println!("Global checksum\t\t: 0x{:04X}", self.global_checksum);
-        println!();
-        println!("ROM loaded");
-        println!("--------------------------------------\n");
     }
 }
 
@@ -158,8 +163,7 @@ impl ComponentWithMemory for MBC3 {
                                            && self.ext_selected == SelectionExternal::ExtRAM {
                 self.ext_ram[self.map_ext_ram_addr(addr) as usize]
             } else if self.enable_rtc && self.ext_selected == SelectionExternal::RTC {
-                // TODO: Read RTC
-                0xFF
+                self.rtc.read(self.reg_rtc)
             } else {
                 0xFF
             }
@@ -195,12 +199,7 @@ impl ComponentWithMemory for MBC3 {
                 }
             },
             // RTC register latching
-            0x6000..=0x7FFF => {
-                // TODO: 0x01 -> 0x00
-                if val == 0x01 {
-                    // TODO
-                }
-            },
+            0x6000..=0x7FFF => self.rtc.write_latch(val),
             // External RAM/RTC write
             0xA000..=0xBFFF => {
                 // TODO: WRite RAM
@@ -209,13 +208,37 @@ impl ComponentWithMemory for MBC3 {
                     //println!("addr {:04X} len {:04X}", _addr, self.ext_ram.len());
                     self.ext_ram[_addr as usize] = val;
                 }
-                // TODO: Write RTC
                 else if self.enable_rtc && self.ext_selected == SelectionExternal::RTC {
-                    
+                    self.rtc.write(self.reg_rtc, val);
                 }
             },
             _ => panic!("write(): Invalid address: {:04X}", addr)
         }
     }
+}
 
-}
\ No newline at end of file
+impl Savestate for MBC3 {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.ext_ram);
+        w.bool(self.enable_ext_ram);
+        w.bool(self.enable_rtc);
+        w.u16(self.reg_bank1);
+        w.u8(self.reg_ram_bank);
+        w.u8(self.reg_rtc);
+        w.bool(self.ext_selected == SelectionExternal::RTC);
+        self.rtc.save_state(w);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.ext_ram)?;
+        self.enable_ext_ram = r.bool()?;
+        self.enable_rtc = r.bool()?;
+        self.reg_bank1 = r.u16()?;
+        self.reg_ram_bank = r.u8()?;
+        self.reg_rtc = r.u8()?;
+        self.ext_selected = if r.bool()? { SelectionExternal::RTC } else { SelectionExternal::ExtRAM };
+        self.rtc.load_state(r)?;
+
+        return Ok(());
+    }
+}
@@ -1,41 +1,32 @@
 use crate::{cartridge::Cartridge, consts::ComponentWithMemory};
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+use std::path::PathBuf;
 
 /*
  Type of cartridge which is just an array for tests
  */
 
 pub struct MBCTest {
-    file                : String,
     rom                 : Vec<u8>
 }
 
 impl MBCTest {
-    pub fn new(file :&str, rom :Vec<u8>) -> MBCTest {
+    pub fn new(rom :Vec<u8>) -> MBCTest {
         return MBCTest {
-            file: file.to_string(),
             rom: rom
         }
     }
 }
 
 impl Cartridge for MBCTest {
-    fn init(&mut self) {
-        // TODO: Disable on debug
-        self.print_rom_data();
-    }
+    fn init(&mut self) {}
 
     fn is_test_cart(&self) -> bool { return true; }
+    fn rom(&self) -> &[u8] { return &self.rom; }
+    fn set_save_path(&mut self, _path :PathBuf) {}
 
     fn load_ram(&mut self) {}
     fn save_ram(&self) {}
-
-    fn print_rom_data(&self) {
-        println!("\nFile:\n{}", self.file);
-        println!();
-        println!("MBCTest");
-        println!();
-        println!("--------------------------------------\n");
-    }
 }
 
 impl ComponentWithMemory for MBCTest {
@@ -48,3 +39,14 @@ impl ComponentWithMemory for MBCTest {
     }
 
 }
+
+// The whole address space is writable, so the entire array is part of the state
+impl Savestate for MBCTest {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.rom);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        return r.bytes_into(&mut self.rom);
+    }
+}
@@ -1,44 +1,32 @@
 use crate::consts::*;
-use crate::cartridge::{CartridgeType, Cartridge};
-use std::io::prelude::Write;
+use crate::cartridge::{CartridgeType, Cartridge, read_save, write_save};
+use crate::cartridge::header::CartridgeHeader;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+use std::path::PathBuf;
 
 
-const SAVE_PATH :&str = "roms/games/saves";
 
 
 pub struct NoMBC {
-    file                : String,
+    legacy_save_path    : PathBuf,
+    save_path           : PathBuf,
     rom                 : Vec<u8>,
     ext_ram             : Vec<u8>,
-    cgb_flag            : bool,
-    sgb_flag            : bool,
     cartridge_type      : CartridgeType,
-    rom_size            : u16,
-    rom_bank_n          : u16,
     ram_size            : u16,
-    ram_bank_n          : u16,
-    mask_rom_version_n  : u8,
-    header_checksum     : u8,
-    global_checksum     : u16,
 }
 
 impl NoMBC {
-    pub fn new(file :&str, rom :Vec<u8>) -> NoMBC {
-        let cartridge_type :CartridgeType = rom[0x147].into();
-        let ram_size = if cartridge_type.has_ram() { [0, 0, 8, 32, 128, 64][rom[0x149] as usize] } else {0};
+    pub fn new(legacy_save_path :PathBuf, save_path :PathBuf, rom :Vec<u8>) -> NoMBC {
+        let header = CartridgeHeader::parse(&rom);
+        let cartridge_type = header.cartridge_type;
+        let ram_size = if cartridge_type.has_ram() { header.ram_size } else {0};
 
         return NoMBC {
-            file                : file.to_string(),
-            cgb_flag            : rom[0x143] == 0xC0,
-            sgb_flag            : rom[0x146] == 0x03,
+            legacy_save_path,
+            save_path,
             cartridge_type,
-            rom_size            : 32 * (1 << rom[0x148]), // In KiB
-            rom_bank_n          : 1 << (rom[0x148]+1),
             ram_size            : ram_size as u16,
-            ram_bank_n          : (ram_size as u16/8),
-            mask_rom_version_n  : rom[0x14c],
-            header_checksum     : rom[0x14d],
-            global_checksum     : ((rom[0x14e] as u16) << 8) | rom[0x14f] as u16,
             rom,
             ext_ram             : vec![0;ram_size*1024],
         }
@@ -48,61 +36,46 @@ impl NoMBC {
     fn map_ext_ram_addr(&self, addr :u16) -> usize {
         return (addr - 0xA000) as usize;
     }
+
+    fn has_save(&self) -> bool {
+        return self.cartridge_type.has_battery() && self.ram_size > 0;
+    }
 }
 
 impl Cartridge for NoMBC {
     fn is_test_cart(&self) -> bool { return false; }
+    fn rom(&self) -> &[u8] { return &self.rom; }
+    fn set_save_path(&mut self, path :PathBuf) { self.save_path = path; }
 
-    fn init(&mut self) {
-        // TODO: Disable on debug
-        self.print_rom_data();
+    fn save_battery(&self, w :&mut StateWriter) {
+        w.bytes(&self.ext_ram);
     }
 
-    fn load_ram(&mut self) {
-        if self.cartridge_type.has_ram() && self.ram_size > 0 {
-            let path = format!("{}/{}", SAVE_PATH, self.file);
-            if std::path::Path::new(&path).exists() {
-                let ram = std::fs::read(path).unwrap();
-                self.ext_ram = ram;
-            }
-        }
+    fn load_battery(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        return r.bytes_into(&mut self.ext_ram);
     }
 
-    fn save_ram(&self) {
-        if self.cartridge_type.has_ram() && self.ram_size > 0 {
-            if self.cartridge_type.has_ram() && self.ram_size > 0 {
-                let path = format!("{}/{}", SAVE_PATH, self.file);
+    fn clear_battery(&mut self) {
+        self.ext_ram.fill(0);
+    }
 
-                let mut file = std::fs::File::create(path).unwrap();
-                file.write_all(&self.ext_ram).unwrap();
-            }
+    fn init(&mut self) {
+        if self.has_save() {
+            self.load_ram();
         }
     }
 
-    fn print_rom_data(&self) {
-        println!("\nFile:\n{}", self.file);
-        
-        println!("\nTitle:");
-        for n in self.rom[0x134..=0x143].iter() {
-            if *n >= 60 && *n <= 120 { // Pritable ascii
-                print!("{}", *n as char);
-            }
+    fn load_ram(&mut self) {
+        if let Some(ram) = read_save(&self.save_path, &self.legacy_save_path) {
+            let len = ram.len().min(self.ext_ram.len());
+            self.ext_ram[..len].copy_from_slice(&ram[..len]);
+        }
+    }
+
+    fn save_ram(&self) {
+        if self.has_save() {
+            write_save(&self.save_path, &[&self.ext_ram]);
         }
-        println!();
-
-        println!("\nCGB Flag\t\t: {}", self.cgb_flag);
-        println!("SGB Flag\t\t: {}", self.sgb_flag);
-        println!("Cartridge type\t\t: {:?}", self.cartridge_type);
-        println!("ROM size\t\t: {} KiB", self.rom_size);
-        println!("ROM Banks \t\t: {}", self.rom_bank_n);
-        println!("RAM size\t\t: {} KiB", self.ram_size);
-        println!("RAM Banks \t\t: {}", self.ram_bank_n);
-        println!("Mask ROM version number\t: 0x{:02X}", self.mask_rom_version_n);
-        println!("Header checksum\t\t: 0x{:02X}", self.header_checksum);
-        println!("Global checksum\t\t: 0x{:04X}", self.global_checksum);
-        println!();
-        println!("ROM loaded");
-        println!("--------------------------------------\n");
     }
 }
 
@@ -132,4 +105,14 @@ impl ComponentWithMemory for NoMBC {
             _ => {}
         }
     }
-}
\ No newline at end of file
+}
+
+impl Savestate for NoMBC {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.ext_ram);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        return r.bytes_into(&mut self.ext_ram);
+    }
+}
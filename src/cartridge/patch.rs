@@ -0,0 +1,298 @@
+use std::fmt;
+
+/*
+ * Soft-patching of ROMs with IPS, UPS and BPS patches, recognised by their magic.
+ *
+ * IPS only lists the bytes to replace. UPS and BPS end with the CRC32 of the source, of the
+ * target and of the patch itself, which are all checked so that a patch made for another
+ * revision of the game is rejected instead of producing a broken ROM.
+ */
+
+const IPS_MAGIC :&[u8] = b"PATCH";
+const IPS_EOF   :usize = 0x454F46; // "EOF"
+const UPS_MAGIC :&[u8] = b"UPS1";
+const BPS_MAGIC :&[u8] = b"BPS1";
+
+// Source, target and patch CRC32
+const FOOTER_SIZE :usize = 12;
+
+// The largest ROM, of an MBC5 with 512 banks
+const MAX_TARGET_SIZE :usize = 0x800000;
+
+#[derive(Debug)]
+pub enum PatchError {
+    BadMagic,
+    Truncated,
+    OutOfBounds,                                    // Reads or writes past the ROM
+    SourceSize { expected :usize, actual :usize },
+    SourceCrc { expected :u32, actual :u32 },
+    TargetCrc { expected :u32, actual :u32 },
+    PatchCrc { expected :u32, actual :u32 }
+}
+
+impl fmt::Display for PatchError {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PatchError::BadMagic => write!(f, "not an IPS, UPS or BPS patch"),
+            PatchError::Truncated => write!(f, "the patch is truncated"),
+            PatchError::OutOfBounds => write!(f, "the patch goes past the end of the ROM"),
+            PatchError::SourceSize { expected, actual } =>
+                write!(f, "the patch is for a {} bytes ROM, this one has {}", expected, actual),
+            PatchError::SourceCrc { expected, actual } =>
+                write!(f, "the patch is for a ROM with CRC32 {:08X}, this one has {:08X}", expected, actual),
+            PatchError::TargetCrc { expected, actual } =>
+                write!(f, "the patched ROM should have CRC32 {:08X} but has {:08X}", expected, actual),
+            PatchError::PatchCrc { expected, actual } =>
+                write!(f, "the patch is corrupt, CRC32 {:08X} instead of {:08X}", actual, expected)
+        }
+    }
+}
+
+/* Patched copy of the ROM */
+pub fn apply_patch(rom :&[u8], patch :&[u8]) -> Result<Vec<u8>, PatchError> {
+    return if patch.starts_with(IPS_MAGIC) {
+        apply_ips(rom, patch)
+    } else if patch.starts_with(UPS_MAGIC) {
+        apply_ups(rom, patch)
+    } else if patch.starts_with(BPS_MAGIC) {
+        apply_bps(rom, patch)
+    } else {
+        Err(PatchError::BadMagic)
+    }
+}
+
+struct PatchReader<'a> {
+    buf :&'a [u8],
+    pos :usize,
+    end :usize,
+}
+
+impl<'a> PatchReader<'a> {
+    fn new(buf :&'a [u8], start :usize, end :usize) -> PatchReader<'a> {
+        return PatchReader { buf, pos: start, end };
+    }
+
+    fn is_done(&self) -> bool {
+        return self.pos >= self.end;
+    }
+
+    fn bytes(&mut self, n :usize) -> Result<&'a [u8], PatchError> {
+        if self.end - self.pos.min(self.end) < n {
+            return Err(PatchError::Truncated);
+        }
+
+        self.pos += n;
+        return Ok(&self.buf[self.pos-n..self.pos]);
+    }
+
+    fn u8(&mut self) -> Result<u8, PatchError> {
+        return Ok(self.bytes(1)?[0]);
+    }
+
+    fn u16_be(&mut self) -> Result<usize, PatchError> {
+        let b = self.bytes(2)?;
+        return Ok(((b[0] as usize) << 8) | b[1] as usize);
+    }
+
+    fn u24_be(&mut self) -> Result<usize, PatchError> {
+        let b = self.bytes(3)?;
+        return Ok(((b[0] as usize) << 16) | ((b[1] as usize) << 8) | b[2] as usize);
+    }
+
+    /* Variable-length integer of UPS and BPS: 7 bits per byte, the last one has bit 7 set */
+    fn varint(&mut self) -> Result<usize, PatchError> {
+        let mut val :usize = 0;
+        let mut shift :u32 = 0;
+
+        loop {
+            let byte = self.u8()?;
+            let bits = ((byte & 0x7F) as usize).checked_mul(1 << shift).ok_or(PatchError::OutOfBounds)?;
+            val = val.checked_add(bits).ok_or(PatchError::OutOfBounds)?;
+            if byte & 0x80 != 0 {
+                return Ok(val);
+            }
+            // Past the bits of a usize, the patch is corrupt
+            shift += 7;
+            if shift >= usize::BITS {
+                return Err(PatchError::OutOfBounds);
+            }
+            val = val.checked_add(1 << shift).ok_or(PatchError::OutOfBounds)?;
+        }
+    }
+}
+
+fn apply_ips(rom :&[u8], patch :&[u8]) -> Result<Vec<u8>, PatchError> {
+    let mut target = rom.to_vec();
+    let mut r = PatchReader::new(patch, IPS_MAGIC.len(), patch.len());
+
+    loop {
+        let offset = r.u24_be()?;
+        if offset == IPS_EOF {
+            break;
+        }
+
+        let size = r.u16_be()?;
+        // A size of 0 is a run of the same byte
+        let data = if size == 0 {
+            let size = r.u16_be()?;
+            vec![r.u8()?; size]
+        } else {
+            r.bytes(size)?.to_vec()
+        };
+
+        if target.len() < offset + data.len() {
+            target.resize(offset + data.len(), 0);
+        }
+        target[offset..offset + data.len()].copy_from_slice(&data);
+    }
+
+    // Optional size to truncate the ROM to
+    if let Ok(size) = r.u24_be() {
+        target.truncate(size);
+    }
+
+    return Ok(target);
+}
+
+/* Check the CRCs in the footer of a UPS or BPS patch, and the source ROM against them */
+fn check_footer(rom :&[u8], patch :&[u8]) -> Result<u32, PatchError> {
+    if patch.len() < FOOTER_SIZE + 4 {
+        return Err(PatchError::Truncated);
+    }
+
+    let footer = &patch[patch.len() - FOOTER_SIZE..];
+    let crc = |i :usize| u32::from_le_bytes(footer[i*4..i*4+4].try_into().unwrap());
+
+    let actual = crc32fast::hash(&patch[..patch.len() - 4]);
+    if crc(2) != actual {
+        return Err(PatchError::PatchCrc { expected: crc(2), actual });
+    }
+
+    let actual = crc32fast::hash(rom);
+    if crc(0) != actual {
+        return Err(PatchError::SourceCrc { expected: crc(0), actual });
+    }
+
+    return Ok(crc(1));
+}
+
+fn check_target(target :&[u8], expected :u32) -> Result<(), PatchError> {
+    let actual = crc32fast::hash(target);
+    if expected != actual {
+        return Err(PatchError::TargetCrc { expected, actual });
+    }
+
+    return Ok(());
+}
+
+fn apply_ups(rom :&[u8], patch :&[u8]) -> Result<Vec<u8>, PatchError> {
+    let target_crc = check_footer(rom, patch)?;
+    let mut r = PatchReader::new(patch, UPS_MAGIC.len(), patch.len() - FOOTER_SIZE);
+
+    let source_size = r.varint()?;
+    let target_size = r.varint()?;
+    if source_size != rom.len() {
+        return Err(PatchError::SourceSize { expected: source_size, actual: rom.len() });
+    }
+    // Before allocating the target
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::OutOfBounds);
+    }
+
+    let mut target = rom.to_vec();
+    target.resize(target_size, 0);
+
+    // Skip some bytes, then XOR the following ones with the source until a 0
+    let mut pos = 0;
+    while !r.is_done() {
+        pos += r.varint()?;
+
+        loop {
+            let val = r.u8()?;
+            if val == 0 {
+                pos += 1;
+                break;
+            }
+
+            if pos < target.len() {
+                target[pos] ^= val;
+            }
+            pos += 1;
+        }
+    }
+
+    check_target(&target, target_crc)?;
+    return Ok(target);
+}
+
+fn apply_bps(rom :&[u8], patch :&[u8]) -> Result<Vec<u8>, PatchError> {
+    let target_crc = check_footer(rom, patch)?;
+    let mut r = PatchReader::new(patch, BPS_MAGIC.len(), patch.len() - FOOTER_SIZE);
+
+    let source_size = r.varint()?;
+    let target_size = r.varint()?;
+    if source_size != rom.len() {
+        return Err(PatchError::SourceSize { expected: source_size, actual: rom.len() });
+    }
+    // Before allocating the target
+    if target_size > MAX_TARGET_SIZE {
+        return Err(PatchError::OutOfBounds);
+    }
+
+    let metadata_size = r.varint()?;
+    r.bytes(metadata_size)?;
+
+    let mut target = Vec::with_capacity(target_size);
+    let mut source_offset :isize = 0;
+    let mut target_offset :isize = 0;
+
+    // Offsets relative to the last copy, with the sign in bit 0
+    let relative = |r :&mut PatchReader| -> Result<isize, PatchError> {
+        let val = r.varint()?;
+        let offset = (val >> 1) as isize;
+        return Ok(if val & 1 != 0 { -offset } else { offset });
+    };
+
+    while !r.is_done() {
+        let action = r.varint()?;
+        let len = (action >> 2) + 1;
+
+        if target.len() + len > target_size {
+            return Err(PatchError::OutOfBounds);
+        }
+
+        match action & 3 {
+            // Source read, the bytes at the same offset in the source
+            0 => {
+                let pos = target.len();
+                target.extend_from_slice(rom.get(pos..pos + len).ok_or(PatchError::OutOfBounds)?);
+            },
+            // Target read, the bytes from the patch
+            1 => target.extend_from_slice(r.bytes(len)?),
+            // Source copy
+            2 => {
+                source_offset += relative(&mut r)?;
+                let pos = usize::try_from(source_offset).map_err(|_| PatchError::OutOfBounds)?;
+                target.extend_from_slice(rom.get(pos..pos + len).ok_or(PatchError::OutOfBounds)?);
+                source_offset += len as isize;
+            },
+            // Target copy, byte by byte as it may overlap what is being written
+            _ => {
+                target_offset += relative(&mut r)?;
+                for _ in 0..len {
+                    let pos = usize::try_from(target_offset).map_err(|_| PatchError::OutOfBounds)?;
+                    let val = *target.get(pos).ok_or(PatchError::OutOfBounds)?;
+                    target.push(val);
+                    target_offset += 1;
+                }
+            }
+        }
+    }
+
+    if target.len() != target_size {
+        return Err(PatchError::Truncated);
+    }
+
+    check_target(&target, target_crc)?;
+    return Ok(target);
+}
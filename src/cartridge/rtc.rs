@@ -0,0 +1,232 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+
+/*
+ * MBC3 real time clock.
+ *
+ * The clock counts emulated time (4194304 T-cycles per second), so it stays in sync with save
+ * states, rewind and the speed settings. Between sessions it is advanced with the host time
+ * stored in the footer of the save file.
+ *
+ * The registers are read through a latched copy, updated when 0x00 and then 0x01 are written
+ * to 0x6000-0x7FFF.
+ */
+
+const RTC_FOOTER_SIZE :usize = 48;
+
+const CYCLES_PER_SECOND :u32 = 4194304;
+
+// Registers selected by writing to 0x4000-0x5FFF: seconds, minutes, hours, day low and day high
+const RTC_S  :u8 = 0x08;
+const RTC_DH :u8 = 0x0C;
+
+// Bits which exist on each register, from RTC_S to RTC_DH
+const REG_MASKS :[u8;5] = [0x3F, 0x3F, 0x1F, 0xFF, 0xC1];
+
+pub struct Rtc {
+    secs         :u8,
+    mins         :u8,
+    hours        :u8,
+    days         :u16,  // 9 bits
+    is_halted    :bool,
+    is_day_carry :bool,
+
+    latched     :[u8;5],
+    last_latch  :u8,    // Last value written to 0x6000-0x7FFF
+    cycles      :u32,   // T-cycles into the current second
+}
+
+impl Rtc {
+    pub fn new() -> Rtc {
+        return Rtc {
+            secs: 0,
+            mins: 0,
+            hours: 0,
+            days: 0,
+            is_halted: false,
+            is_day_carry: false,
+
+            latched: [0;5],
+            last_latch: 0xFF,
+            cycles: 0,
+        }
+    }
+
+    fn regs(&self) -> [u8;5] {
+        let dh = ((self.days >> 8) as u8 & 0x01)
+            | if self.is_halted { 0x40 } else { 0 }
+            | if self.is_day_carry { 0x80 } else { 0 };
+
+        return [self.secs, self.mins, self.hours, self.days as u8, dh];
+    }
+
+    fn set_regs(&mut self, regs :&[u8;5]) {
+        self.secs = regs[0] & REG_MASKS[0];
+        self.mins = regs[1] & REG_MASKS[1];
+        self.hours = regs[2] & REG_MASKS[2];
+        self.days = regs[3] as u16 | ((regs[4] as u16 & 0x01) << 8);
+        self.is_halted = regs[4] & 0x40 != 0;
+        self.is_day_carry = regs[4] & 0x80 != 0;
+    }
+
+    pub fn read(&self, reg :u8) -> u8 {
+        return match reg {
+            RTC_S..=RTC_DH => self.latched[(reg - RTC_S) as usize],
+            _ => 0xFF
+        }
+    }
+
+    /* Writes go to the clock itself, and to the latched copy so that they can be read back */
+    pub fn write(&mut self, reg :u8, val :u8) {
+        if !(RTC_S..=RTC_DH).contains(&reg) {
+            return;
+        }
+
+        let index = (reg - RTC_S) as usize;
+        let mut regs = self.regs();
+        regs[index] = val;
+        self.set_regs(&regs);
+        self.latched[index] = val & REG_MASKS[index];
+
+        // Writing the seconds resets the divider
+        if reg == RTC_S {
+            self.cycles = 0;
+        }
+    }
+
+    pub fn write_latch(&mut self, val :u8) {
+        if self.last_latch == 0x00 && val == 0x01 {
+            self.latched = self.regs();
+        }
+        self.last_latch = val;
+    }
+
+    pub fn tick(&mut self) {
+        if self.is_halted {
+            return;
+        }
+
+        self.cycles += 1;
+        if self.cycles == CYCLES_PER_SECOND {
+            self.cycles = 0;
+            self.tick_second();
+        }
+    }
+
+    /*
+     Each counter only carries into the next one when it reaches its limit. A counter set to an
+     invalid value (e.g. 62 seconds) counts up until it overflows its bits, without carrying.
+     */
+    fn tick_second(&mut self) {
+        self.secs = (self.secs + 1) & REG_MASKS[0];
+        if self.secs != 60 { return; }
+        self.secs = 0;
+
+        self.mins = (self.mins + 1) & REG_MASKS[1];
+        if self.mins != 60 { return; }
+        self.mins = 0;
+
+        self.hours = (self.hours + 1) & REG_MASKS[2];
+        if self.hours != 24 { return; }
+        self.hours = 0;
+
+        self.days += 1;
+        if self.days == 512 {
+            self.days = 0;
+            self.is_day_carry = true;
+        }
+    }
+
+    /* Advance the clock by the time elapsed on the host, unless it is halted */
+    pub fn advance(&mut self, mut secs :u64) {
+        if self.is_halted {
+            return;
+        }
+
+        // Count second by second until every counter is back in its range
+        while secs > 0 && (self.secs >= 60 || self.mins >= 60 || self.hours >= 24) {
+            self.tick_second();
+            secs -= 1;
+        }
+
+        let total = secs
+            + self.secs as u64
+            + self.mins as u64 * 60
+            + self.hours as u64 * 3600
+            + self.days as u64 * 86400;
+        let days = total / 86400;
+
+        self.secs = (total % 60) as u8;
+        self.mins = (total / 60 % 60) as u8;
+        self.hours = (total / 3600 % 24) as u8;
+        self.days = (days % 512) as u16;
+        self.is_day_carry |= days >= 512;
+    }
+
+    /*
+     Footer appended to the save file, as written by VBA and BGB: the 5 registers and their
+     latched copy as 32-bit integers, followed by the UNIX time of the save as a 64-bit integer.
+     */
+    pub fn footer(&self) -> [u8;RTC_FOOTER_SIZE] {
+        let mut footer = [0;RTC_FOOTER_SIZE];
+
+        for (i, val) in self.regs().iter().chain(self.latched.iter()).enumerate() {
+            footer[i*4..i*4+4].copy_from_slice(&(*val as u32).to_le_bytes());
+        }
+        footer[40..48].copy_from_slice(&unix_time().to_le_bytes());
+
+        return footer;
+    }
+
+    /* Restore the clock from a footer. Older files use a 32-bit time, making it 44 bytes long. */
+    pub fn load_footer(&mut self, footer :&[u8]) {
+        let reg = |i :usize| footer[i*4];
+        let timestamp = match footer.len() {
+            48 => u64::from_le_bytes(footer[40..48].try_into().unwrap()),
+            44 => u32::from_le_bytes(footer[40..44].try_into().unwrap()) as u64,
+            _ => return
+        };
+
+        self.set_regs(&[reg(0), reg(1), reg(2), reg(3), reg(4)]);
+        for (i, (latched, mask)) in self.latched.iter_mut().zip(REG_MASKS).enumerate() {
+            *latched = reg(5 + i) & mask;
+        }
+
+        self.advance(unix_time().saturating_sub(timestamp));
+    }
+}
+
+fn unix_time() -> u64 {
+    return SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+}
+
+impl Savestate for Rtc {
+    fn save_state(&self, w :&mut StateWriter) {
+        for val in self.regs().iter().chain(self.latched.iter()) {
+            w.u8(*val);
+        }
+        w.u8(self.last_latch);
+        w.u32(self.cycles);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        let mut regs = [0;5];
+        for val in regs.iter_mut() {
+            *val = r.u8()?;
+        }
+        self.set_regs(&regs);
+
+        for val in self.latched.iter_mut() {
+            *val = r.u8()?;
+        }
+        self.last_latch = r.u8()?;
+        self.cycles = r.u32()?;
+
+        if self.cycles >= CYCLES_PER_SECOND {
+            return Err(StateError::Corrupt("RTC cycles".to_string()));
+        }
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,221 @@
+use std::fmt;
+use std::path::Path;
+
+use crate::consts::*;
+
+/*
+ * Game Genie and GameShark cheats.
+ *
+ * Game Genie codes (ABC-DEF or ABC-DEF-GHI) replace the value read from a ROM address, only
+ * when the original value matches the compare byte if there is one. As the same address is
+ * mapped to a different bank depending on the MBC, a code can also be limited to a ROM bank
+ * with an @<bank> suffix (ABC-DEF@3).
+ *
+ * GameShark codes (ttvvaaaa) write the value vv to the RAM address aaaa at every VBlank.
+ * tt is 01 to write to the address as mapped at that time, or 8n for the bank n of the
+ * cartridge RAM.
+ *
+ * Cheat files have a code per line, followed by an optional name. Lines starting with '#'
+ * are comments, and codes starting with '-' are loaded disabled.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Cheat {
+    GameGenie { addr :u16, val :u8, compare :Option<u8>, bank :Option<u16> },
+    GameShark { bank :Option<u8>, addr :u16, val :u8 }
+}
+
+#[derive(Debug)]
+pub enum CheatError {
+    Io(std::io::Error),
+    BadCode(String)
+}
+
+impl fmt::Display for CheatError {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CheatError::Io(err) => write!(f, "I/O error: {}", err),
+            CheatError::BadCode(code) => write!(f, "not a Game Genie or GameShark code: {}", code)
+        }
+    }
+}
+
+impl From<std::io::Error> for CheatError {
+    fn from(err :std::io::Error) -> CheatError {
+        return CheatError::Io(err);
+    }
+}
+
+impl Cheat {
+    pub fn parse(code :&str) -> Result<Cheat, CheatError> {
+        let bad_code = || CheatError::BadCode(code.to_string());
+
+        let (digits, bank) = match code.split_once('@') {
+            Some((digits, bank)) => (digits, Some(bank.parse::<u16>().map_err(|_| bad_code())?)),
+            None => (code, None)
+        };
+
+        let hex :Vec<u8> = digits.chars()
+            .filter(|c| *c != '-')
+            .map(|c| c.to_digit(16).map(|d| d as u8))
+            .collect::<Option<_>>()
+            .ok_or_else(bad_code)?;
+
+        return match hex.len() {
+            // ABC-DEF(-GHI): AB is the value and the address is (F^F)CDE. GI is the compare
+            // byte rotated left by 2 and XORed with 0xBA, H is unused.
+            6 | 9 => {
+                let addr = (((hex[5] ^ 0xF) as u16) << 12) | ((hex[2] as u16) << 8)
+                    | ((hex[3] as u16) << 4) | hex[4] as u16;
+                if addr >= VRAM_START {
+                    return Err(bad_code());
+                }
+
+                let compare = if hex.len() == 9 { Some((hex[6] << 4 | hex[8]).rotate_right(2) ^ 0xBA) } else { None };
+
+                Ok(Cheat::GameGenie { addr, val: hex[0] << 4 | hex[1], compare, bank })
+            },
+            // ttvvaaaa, with the address in little endian
+            8 if bank.is_none() => {
+                let byte = |i :usize| hex[i*2] << 4 | hex[i*2 + 1];
+                let addr = ((byte(3) as u16) << 8) | byte(2) as u16;
+                let bank = match byte(0) {
+                    0x01 => None,
+                    0x80..=0x8F => Some(byte(0) & 0x0F),
+                    _ => return Err(bad_code())
+                };
+                if addr < VRAM_START {
+                    return Err(bad_code());
+                }
+
+                Ok(Cheat::GameShark { bank, addr, val: byte(1) })
+            },
+            _ => Err(bad_code())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct CheatEntry {
+    pub code        :String,
+    pub name        :String,
+    pub cheat       :Cheat,
+    pub is_enabled  :bool,
+}
+
+pub struct Cheats {
+    entries     :Vec<CheatEntry>,
+    is_enabled  :bool, // All of them at once
+    // Enabled codes of each kind, so that reads from the ROM only look at what matters
+    game_genie  :Vec<Cheat>,
+    game_shark  :Vec<Cheat>,
+}
+
+// Not derived: the cheats start enabled
+impl Default for Cheats {
+    fn default() -> Cheats { return Cheats::new(); }
+}
+
+impl Cheats {
+    pub fn new() -> Cheats {
+        return Cheats {
+            entries: vec![],
+            is_enabled: true,
+            game_genie: vec![],
+            game_shark: vec![],
+        }
+    }
+
+    /* Add the cheats of a cheat file */
+    pub fn load(&mut self, path :impl AsRef<Path>) -> Result<(), CheatError> {
+        let file = std::fs::read_to_string(path)?;
+
+        for line in file.lines().map(|line| line.trim()) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            let (code, name) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+            let (code, is_enabled) = match code.strip_prefix('-') {
+                Some(code) => (code, false),
+                None => (code, true)
+            };
+
+            self.add(code, name.trim())?;
+            self.set_enabled(self.entries.len() - 1, is_enabled);
+        }
+
+        return Ok(());
+    }
+
+    pub fn add(&mut self, code :&str, name :&str) -> Result<(), CheatError> {
+        self.entries.push(CheatEntry {
+            code: code.to_string(),
+            name: name.to_string(),
+            cheat: Cheat::parse(code)?,
+            is_enabled: true,
+        });
+        self.update();
+
+        return Ok(());
+    }
+
+    pub fn remove(&mut self, index :usize) {
+        if index < self.entries.len() {
+            self.entries.remove(index);
+            self.update();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.update();
+    }
+
+    pub fn entries(&self) -> &[CheatEntry] { return &self.entries; }
+    pub fn is_enabled(&self) -> bool { return self.is_enabled; }
+
+    pub fn set_enabled(&mut self, index :usize, is_enabled :bool) {
+        if let Some(entry) = self.entries.get_mut(index) {
+            entry.is_enabled = is_enabled;
+            self.update();
+        }
+    }
+
+    pub fn set_all_enabled(&mut self, is_enabled :bool) {
+        self.is_enabled = is_enabled;
+        self.update();
+    }
+
+    fn update(&mut self) {
+        let enabled = self.entries.iter().filter(|e| e.is_enabled && self.is_enabled).map(|e| e.cheat);
+
+        self.game_genie = enabled.clone().filter(|c| matches!(c, Cheat::GameGenie { .. })).collect();
+        self.game_shark = enabled.filter(|c| matches!(c, Cheat::GameShark { .. })).collect();
+    }
+
+    pub fn has_game_genie(&self) -> bool { return !self.game_genie.is_empty(); }
+
+    /* Value read from the ROM once the Game Genie codes are applied */
+    pub fn read_rom(&self, addr :u16, val :u8, bank :impl Fn() -> u16) -> u8 {
+        for cheat in self.game_genie.iter() {
+            if let Cheat::GameGenie { addr: a, val: v, compare, bank: b } = *cheat {
+                if a == addr
+                    && compare.is_none_or(|c| c == val)
+                    && b.is_none_or(|b| b == bank()) {
+                    return v;
+                }
+            }
+        }
+
+        return val;
+    }
+
+    /* Writes of the GameShark codes, as (cartridge RAM bank, address, value) */
+    pub fn game_shark_writes(&self) -> impl Iterator<Item = (Option<u8>, u16, u8)> + '_ {
+        return self.game_shark.iter().filter_map(|cheat| match *cheat {
+            Cheat::GameShark { bank, addr, val } => Some((bank, addr, val)),
+            _ => None
+        });
+    }
+}
@@ -17,10 +17,28 @@ pub const REG_L :REGINDEX = 1;
 #[derive(Debug)]
 pub struct Config {
     pub is_debug :bool,
-    pub has_breakpoint :bool,
-    pub breakpoint_addr :u16,
+    pub breakpoints :Vec<String>,
     pub rom_path :String,
-    pub screen_mult: u8
+    pub screen_mult: u8,
+    pub rewind_budget: usize,
+    pub speed: f64,
+    pub is_uncapped: bool,
+    pub record_movie: Option<String>,
+    pub record_from_slot: Option<u8>,
+    pub play_movie: Option<String>,
+    pub link_listen: Option<String>,
+    pub link_connect: Option<String>,
+    pub printer_dir: Option<String>,
+    pub save_dir: Option<String>,
+    pub is_lenient: bool,
+    pub patches: Vec<String>,
+    pub cheats_file: Option<String>,
+    pub symbols_file: Option<String>,
+    pub trace_file: Option<String>,
+    pub trace_reference: Option<String>,
+    pub is_ly_stubbed: bool,
+    pub bus_log: Option<String>,
+    pub profile: Option<String>
 }
 
 pub enum JmpCond {
@@ -50,11 +68,15 @@ pub const CART_HEADER_TITLE_START         :usize = 0x134;
 pub const CART_HEADER_TITLE_END           :usize = 0x143;
 pub const CART_HEADER_MANUF_CODE_START    :usize = 0x13F;
 pub const CART_HEADER_MANUF_CODE_END      :usize = 0x142;
+pub const CART_HEADER_CGB_FLAG            :usize = 0x143;
 pub const CART_HEADER_LICENSE_CODE_START  :usize = 0x144;
 pub const CART_HEADER_LICENSE_CODE_END    :usize = 0x145;
+pub const CART_HEADER_SGB_FLAG            :usize = 0x146;
 pub const CART_HEADER_CART_TYPE           :usize = 0x147;
 pub const CART_HEADER_ROM_SIZE            :usize = 0x148;
 pub const CART_HEADER_RAM_SIZE            :usize = 0x149;
+pub const CART_HEADER_DESTINATION         :usize = 0x14A;
+pub const CART_HEADER_OLD_LICENSE_CODE    :usize = 0x14B;
 pub const CART_HEADER_ROM_VERSION         :usize = 0x14C;
 pub const CART_HEADER_HEADER_CHECKSUM     :usize = 0x14D;
 pub const CART_HEADER_CHECKSUM_START      :usize = 0x14E;
@@ -4,6 +4,7 @@ use std::rc::Rc;
 use crate::bus::Bus;
 use crate::interruptManager::InterruptManager;
 use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 mod opcodes;
 
@@ -97,7 +98,10 @@ impl CPU {
     pub fn get_opcode_cb(&self) -> u8     { return self.opcode_cb; }
     pub fn get_ime(&self)       -> bool   { return self.int.borrow().get_ime(); }
     pub fn get_pc(&self)        -> PCSIZE { return self.pc; }
+    pub fn instr_addr(&self)    -> PCSIZE { return self.pc.wrapping_sub(1); } // Of the prefetched opcode
     pub fn is_wait(&self)       -> bool   { return false; }
+    pub fn is_halted(&self)     -> bool   { return self.is_halt; }
+    pub fn is_interrupt_next(&self) -> bool { return self.is_transfer_control_interrupt; } // Instead of the prefetched opcode
     pub fn get_sp(&self)        -> SPSIZE { return self.sp; }
     pub fn set_ime(&mut self, val :bool)  { self.int.borrow_mut().set_ime(val); }
     pub fn set_pc(&mut self, val :PCSIZE) { self.pc = val; }
@@ -715,8 +719,12 @@ impl CPU {
             // 1 M-cycle
             // TODO: Check off-by-one errors
             if self.is_halt {
-                // Wait for ie and if
-                if ((self.read(ADDR_IE)&0x1F) & (self.read(ADDR_IF)&0x1F)) != 0 {
+                // Wait for ie and if. Peeked, as the halted CPU doesn't access the bus
+                let is_interrupt = {
+                    let bus = self.bus.borrow();
+                    ((bus.peek(ADDR_IE)&0x1F) & (bus.peek(ADDR_IF)&0x1F)) != 0
+                };
+                if is_interrupt {
                     self.is_halt = false;
                     self.handle_interrupts();
 
@@ -775,3 +783,47 @@ impl CPU {
         }
     }
 }
+
+impl Savestate for CPU {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.regs);
+        w.u16(self.sp);
+        w.u16(self.pc);
+        w.u8(self.opcode);
+        w.u8(self.opcode_cb);
+        w.bool(self.is_cb_opcode);
+        w.bool(self.is_instr_done);
+        w.u8(self.instr_m_cycle);
+        w.bytes(&self.cache);
+        for val in self.cache16.iter() { w.u16(*val); }
+        w.bool(self.is_transfer_control_interrupt);
+        w.u16(self.transfer_control_addr);
+        w.bool(self.schedule_ime);
+        w.bool(self.is_halt);
+        w.bool(self.enable_halt_bug);
+        w.bool(self.is_stop);
+        w.u64(self.t_cycle);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.regs)?;
+        self.sp = r.u16()?;
+        self.pc = r.u16()?;
+        self.opcode = r.u8()?;
+        self.opcode_cb = r.u8()?;
+        self.is_cb_opcode = r.bool()?;
+        self.is_instr_done = r.bool()?;
+        self.instr_m_cycle = r.u8()?;
+        r.bytes_into(&mut self.cache)?;
+        for val in self.cache16.iter_mut() { *val = r.u16()?; }
+        self.is_transfer_control_interrupt = r.bool()?;
+        self.transfer_control_addr = r.u16()?;
+        self.schedule_ime = r.bool()?;
+        self.is_halt = r.bool()?;
+        self.enable_halt_bug = r.bool()?;
+        self.is_stop = r.bool()?;
+        self.t_cycle = r.u64()?;
+
+        return Ok(());
+    }
+}
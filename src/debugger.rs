@@ -1,14 +1,29 @@
 use std::fmt;
-use std::collections::{HashMap, VecDeque};
+use std::collections::VecDeque;
 
+use crate::bus::Bus;
 use crate::consts::*;
 use crate::gbemulator::GBEmulator;
-use crate::cpu::CPU;
+use crate::trace::TraceError;
 
 mod instrs;
 mod tui;
-
-use self::tui::DebuggerTUI;
+pub mod expr;
+pub mod breakpoints;
+pub mod search;
+pub mod console;
+pub mod symbols;
+pub mod memview;
+pub mod video;
+
+use self::tui::{DebuggerTUI, Input, View};
+use self::search::RamSearch;
+use self::breakpoints::{Breakpoints, RunMode};
+use self::console::{Console, Outcome};
+use self::symbols::{Symbols, SymbolError};
+use self::memview::MemoryView;
+use self::video::{VideoView, VideoInput};
+use self::instrs::{instr_text, instr_cb_text};
 
 // TODO: After a jump it should not sustract from the PC
 
@@ -38,15 +53,17 @@ const IO_ADDR_TEXT :[(u16, &str);55] = [
 pub struct Instruction {
     pos    :u16,
     opcode :u16, // 2B to encode cb-prefixed instructions
-    text   :String
+    text   :String,
+    label  :Option<String>, // Of pos
 }
 
 impl Instruction {
     pub fn new(pos :u16, opcode :u16, text :&str) -> Instruction {
-        return Instruction { pos, opcode, text: text.to_string() };
+        return Instruction { pos, opcode, text: text.to_string(), label: None };
     }
 
     pub fn get_pos(&self) -> u16 { return self.pos; }
+    pub fn get_label(&self) -> Option<&str> { return self.label.as_deref(); }
 }
 
 impl fmt::Display for Instruction {
@@ -63,37 +80,69 @@ pub struct Debugger {
     tui                 :DebuggerTUI,       // TUI object
     instrs              :Vec<Instruction>,  // Instruction dissasembled in the last cycle
     last_instrs         :VecDeque<Instruction>,  // Last executed instructions
-    wait_instr_n        :u16,               // Wait for n instructions to update the TUI
-    has_breakpoint_addr :bool,              // Whether it has a breakpoint address to start
-    breakpoint_addr     :u16,               // The breakpoint address to start
-    io_table            :HashMap<u16, &'static str>,
+    breakpoints         :Breakpoints,       // And when to stop next
+    search              :RamSearch,
+    console             :Console,
+    symbols             :Symbols,
+    memory              :MemoryView,
+    video               :VideoView,
+    #[cfg(feature = "sdl")]
+    video_window        :Option<crate::screen::ImageWindow>,
 }
 
+// Instructions shown in the dissasembly
+const N_SHOWN_INSTRS :usize = 100;
+// Pixels of the SDL window of the video viewers per pixel shown
+#[cfg(feature = "sdl")]
+const VIDEO_WINDOW_SCALE :u32 = 3;
+
 impl Debugger {
-    pub fn new(gbemu :GBEmulator, has_breakpoint :bool, breakpoint_addr :u16) -> Debugger {
+    pub fn new(gbemu :GBEmulator) -> Debugger {
         return Debugger {
             gbemu,
             tui                 : DebuggerTUI::new(),
             instrs              : vec![],
             last_instrs         : VecDeque::with_capacity(100),
-            wait_instr_n        : 0,
-            has_breakpoint_addr : has_breakpoint,
-            breakpoint_addr     : breakpoint_addr,
-            io_table :HashMap::from(IO_ADDR_TEXT),
+            breakpoints         : Breakpoints::new(),
+            search              : RamSearch::new(),
+            console             : Console::new(),
+            symbols             : Symbols::new(),
+            memory              : MemoryView::new(),
+            video               : VideoView::Tiles8000,
+            #[cfg(feature = "sdl")]
+            video_window        : None,
         }
     }
 
+    pub fn breakpoints_mut(&mut self) -> &mut Breakpoints { return &mut self.breakpoints; }
+    pub fn symbols(&self) -> &Symbols { return &self.symbols; }
+
+    /* Load a symbol file, replacing the labels. Returns how many there are. */
+    pub fn load_symbols(&mut self, path :&str) -> Result<usize, SymbolError> {
+        self.symbols = Symbols::load(path)?;
+        return Ok(self.symbols.len());
+    }
+
     /* Initialize the TUI */
     pub fn init(&mut self) {
         self.gbemu.init();
         self.tui.init();
+        self.tui.set_quit_signal(self.gbemu.quit_signal());
     }
 
     pub fn run(&mut self) {
         while !self.tui.is_done() {
+            // Quit cleanly on SIGINT/SIGTERM, saving the cartridge RAM below
+            if self.gbemu.is_quit_signaled() {
+                self.tui.close();
+                break;
+            }
+
             // TODO: Remove. For tests.
             if self.gbemu.get_cpu().get_pc() > 0xFFF0 { self.tui.close(); println!("end"); return; }
 
+            self.trace();
+            self.gbemu.profile();
             if self.gbemu.get_cpu().is_new_instr() {
                 self.update();
             }
@@ -101,137 +150,203 @@ impl Debugger {
             self.gbemu.get_bus().borrow_mut().tick();
             self.gbemu.get_cpu_mut().tick();
         }
-    }
 
-    /* Convert two u8 to u16. Utility function. */
-    fn to_u16(&self, hi :u8, lo :u8) -> u16 {
-        return ((hi as u16) << 8) | (lo as u16);
+        self.gbemu.save_ram();
+        self.gbemu.finish_trace();
+        self.gbemu.finish_bus_log();
+        self.gbemu.finish_profile();
     }
 
-    /* Returns whether it has reached a breakpoint */
-    fn has_reached_breakpoint(&mut self) -> bool {
-        // Check for a breakpoint address
-        if self.has_breakpoint_addr && self.gbemu.get_cpu().get_pc()-1 == self.breakpoint_addr {
-            self.has_breakpoint_addr = false;
+    /* Trace the instruction about to run, stopping where it differs from the reference */
+    fn trace(&mut self) {
+        match self.gbemu.trace() {
+            Ok(()) => {},
+            Err(TraceError::Diverged(divergence)) => {
+                self.console.print(&divergence.to_string());
+                self.breakpoints.set_mode(RunMode::Step(0));
+            },
+            Err(err) => {
+                self.console.print(&format!("Error: stopped tracing: {}", err));
+                self.gbemu.take_tracer();
+            }
         }
-
-        return !self.has_breakpoint_addr;
     }
 
-    /* Update the debugger state and render the TUI */
+    /* Update the debugger state and render the TUI when it has to stop */
     fn update(&mut self) {
-        // If it has a breakpoint, check if it has reached it
-        if !self.has_reached_breakpoint() {
-            return;
+        let bus = self.gbemu.get_bus();
+        let Some(stop) = self.breakpoints.check(self.gbemu.get_cpu(), &mut bus.borrow_mut()) else { return; };
+
+        // Add only the actual instruction
+        let instr = disassemble(&bus.borrow(), &self.symbols, self.gbemu.get_cpu().instr_addr(), 1).remove(0);
+        self.last_instrs.push_front(instr);
+        if self.last_instrs.len() > 100 {
+            self.last_instrs.pop_back();
         }
 
-        // Dont update the UI for n M-Cycles depending on user input
-        if self.wait_instr_n == 0 {
-            self.dissasemble_instrs();
+        let message = stop.to_string();
+        if !message.is_empty() {
+            self.console.print(&message);
+        }
+        self.tui.reset_cursor();
+
+        loop {
+            // Again after every command, which can change the PC or the memory
+            self.instrs = disassemble(&bus.borrow(), &self.symbols, self.gbemu.get_cpu().instr_addr(), N_SHOWN_INSTRS);
+            let video_image = self.video.render(&bus.borrow());
+            #[cfg(feature = "sdl")]
+            if let Some(window) = &mut self.video_window {
+                window.draw(&self.video.to_string(), &video_image);
+            }
 
-            let bus = self.gbemu.get_bus();
-            let bus = bus.borrow();
-            let cpu :&CPU = self.gbemu.get_cpu();
+            let input = self.tui.update(&View {
+                instrs: &self.instrs,
+                last_instrs: &self.last_instrs,
+                cpu: self.gbemu.get_cpu(),
+                bus: &bus.borrow(),
+                search: &self.search,
+                breakpoints: &self.breakpoints,
+                console: &self.console,
+                memory: &self.memory,
+                video: self.video,
+                video_image: &video_image,
+            });
 
-            self.wait_instr_n = self.tui.update(
-                &self.instrs, &self.last_instrs,
-                cpu,  &bus
-            );
-        } else {
-            self.wait_instr_n -= 1;
+            match input {
+                Input::Step(n) => { self.breakpoints.set_mode(RunMode::Step(n)); break; },
+                Input::Continue => { self.breakpoints.set_mode(RunMode::Continue); break; },
+                Input::StepOver => { self.breakpoints.step_over(self.gbemu.get_cpu()); break; },
+                Input::StepOut => { self.breakpoints.step_out(self.gbemu.get_cpu()); break; },
+                Input::RunTo(addr) => { self.breakpoints.run_to(addr); break; },
+                Input::ToggleBreakpoint(addr) => self.breakpoints.toggle(addr),
+                Input::Command(line) => {
+                    let outcome = self.console.run(&mut self.gbemu, &mut self.breakpoints, &mut self.search, &self.symbols, &line);
+                    if outcome == Outcome::Resume {
+                        break;
+                    }
+                },
+                Input::Search(line) => {
+                    let result = self.search.command(&mut bus.borrow_mut(), &line);
+                    self.console.print_result(result);
+                },
+                Input::Memory(input) => {
+                    if let Err(err) = self.memory.input(&mut bus.borrow_mut(), &self.symbols, input) {
+                        self.console.print(&format!("Error: {}", err));
+                    }
+                },
+                Input::Video(VideoInput::NextView) => self.video = self.video.next(),
+                Input::Video(VideoInput::ToggleWindow) => self.toggle_video_window(),
+                Input::Video(VideoInput::Export(path)) => {
+                    let result = video::export(&bus.borrow(), self.video, path.trim());
+                    self.console.print_result(result);
+                },
+            }
         }
+
+        // The reads of the debugger itself aren't the game's
+        bus.borrow().watchpoints().take_hit();
+        self.memory.take_snapshot(&bus.borrow());
     }
 
-    /* Replace instruction text variables like {n} or {nn} */
-    fn replace_variables(&self, s :String, pc :u16) -> String {
-        let bus = self.gbemu.get_bus();
-        let bus = bus.borrow();
-
-        // Replace n
-        let mut s = s.replace("{n}", &format!("{:02X}h",
-            bus.read(pc+1)
-        // Replace pc + signed integer
-        )).replace("{pc+n_i8}", &format!("{:04X}h",
-            (pc+2).wrapping_add((bus.read(pc+1) as i8) as u16)
-        // Replace nn
-        )).replace("{nn}", &format!("{:04X}h",
-            self.to_u16(bus.read(pc+2), bus.read(pc+1))
-        ));
-
-        // Replace 0xff00 + unsigned integer
-        if s.contains("{io+n}") {
-            let io_port = 0xFF00 + bus.read(pc+1) as u16;
-            let tmp;
-
-            s = s.replace("{io+n}", if self.io_table.contains_key(&io_port) {
-                self.io_table.get(&io_port).unwrap()
-            } else {
-                tmp = format!("{:04X}h", io_port);
-                &tmp
-            });
+    /* Open or close the SDL window of the video viewers */
+    #[cfg(feature = "sdl")]
+    fn toggle_video_window(&mut self) {
+        use crate::screen::ImageWindow;
+
+        if self.video_window.take().is_some() {
+            return;
+        }
+
+        let window = match self.gbemu.sdl_context() {
+            Some(sdl_context) => ImageWindow::new(sdl_context, VIDEO_WINDOW_SCALE),
+            None => Err("there is no SDL display".to_string())
+        };
+        match window {
+            Ok(window) => self.video_window = Some(window),
+            Err(err) => self.console.print(&format!("Error: couldn't open the video window: {}", err))
         }
- 
-        return s;
     }
-   
-    /* Dissasemble ROM instructions */
-    fn dissasemble_instrs(&mut self) {
-        let bus = self.gbemu.get_bus();
-        let bus = bus.borrow();
-        self.instrs = vec![];
 
-        // TODO: 1/1? Is this a bug?
-        // TODO: Why is this needed?
-        let mut pc :u16 = if self.gbemu.get_cpu().get_pc() == 0 {
-            0
+    #[cfg(not(feature = "sdl"))]
+    fn toggle_video_window(&mut self) {
+        self.console.print("Error: built without SDL, the video viewers only show in the TUI");
+    }
+}
+
+/* Convert two u8 to u16. Utility function. */
+fn to_u16(hi :u8, lo :u8) -> u16 {
+    return ((hi as u16) << 8) | (lo as u16);
+}
+
+/* Replace instruction text variables like {n} or {nn}, addresses by their label if they have one */
+fn replace_variables(bus :&Bus, symbols :&Symbols, s :String, pc :u16) -> String {
+    let n = bus.peek(pc.wrapping_add(1));
+    let nn = to_u16(bus.peek(pc.wrapping_add(2)), n);
+    let addr_text = |addr :u16| symbols.label_at(bus, addr).map_or(format!("{:04X}h", addr), |label| label.to_string());
+
+    // Replace n
+    let mut s = s.replace("{n}", &format!("{:02X}h",
+        n
+    // Replace pc + signed integer
+    )).replace("{pc+n_i8}", &addr_text(
+        pc.wrapping_add(2).wrapping_add((n as i8) as u16)
+    // Replace nn
+    )).replace("{nn}", &addr_text(
+        nn
+    ));
+
+    // Replace 0xff00 + unsigned integer
+    if s.contains("{io+n}") {
+        let io_port = 0xFF00 + n as u16;
+
+        s = s.replace("{io+n}", &match IO_ADDR_TEXT.iter().find(|(addr, _)| *addr == io_port) {
+            Some((_, name)) => name.to_string(),
+            None => addr_text(io_port)
+        });
+    }
+
+    return s;
+}
+
+/* Dissasemble n instructions from addr, without going past FFFF */
+pub fn disassemble(bus :&Bus, symbols :&Symbols, addr :u16, n :usize) -> Vec<Instruction> {
+    let mut instrs = vec![];
+    let mut pc = addr;
+
+    while instrs.len() < n {
+        let opcode = bus.peek(pc) as u16;
+
+        // Fetch opcode
+        let mut text = if opcode == 0xcb {
+            instr_cb_text(bus.peek(pc.wrapping_add(1)))
         } else {
-            self.gbemu.get_cpu().get_pc() - if self.gbemu.get_cpu().get_opcode() == 0xcb {
-                1
-            } else {
-                1
-            }
+            instr_text(opcode as u8)
         };
-        
-        let actual_pc = pc;
-
-        // Parse the next 200 bytes, which is enough even considering that
-        // the next 100 instructions are 0xCB prefixed
-        while pc < 0xffff && pc-actual_pc < 200 {
-            let opcode = bus.read(pc) as u16;
-            
-            // Fetch opcode
-            let mut text = if opcode == 0xcb {
-                self.instr_cb_text(bus.read(pc+1))
-            } else {
-                self.instr_text(opcode as u8)
-            };
-
-            // cb-prefixed opcodes dont have replacements
-            text = self.replace_variables(text, pc);
-
-            // Build the instruction
-            let instr = Instruction::new(
-                pc,
-                if opcode == 0xcb { self.to_u16(opcode as u8, bus.read(pc+1)) } else {opcode},
-                &text
-            );
-
-            // Add only the actual instruction
-            if instr.get_pos() == actual_pc {
-                self.last_instrs.push_front(instr.clone());
-            
-                if self.last_instrs.len() > 100 {
-                    self.last_instrs.pop_back();
-                }
-            }
 
-            // Add instruction
-            self.instrs.push(instr);
+        // cb-prefixed opcodes dont have replacements
+        text = replace_variables(bus, symbols, text, pc);
+
+        // Build the instruction
+        let mut instr = Instruction::new(
+            pc,
+            if opcode == 0xcb { to_u16(opcode as u8, bus.peek(pc.wrapping_add(1))) } else {opcode},
+            &text
+        );
+        instr.label = symbols.label_at(bus, pc).map(|label| label.to_string());
+
+        // Add instruction
+        instrs.push(instr);
 
-            // Increment pc
-            if text == "undefined" { pc += 1; }
-            else if opcode == 0xcb { pc += 2; }
-            else { pc += OP_BYTE_LEN[opcode as usize] as u16; }
+        // Increment pc
+        let len = if text == "undefined" { 1 }
+            else if opcode == 0xcb { 2 }
+            else { OP_BYTE_LEN[opcode as usize] as u16 };
+
+        match pc.checked_add(len) {
+            Some(next) => pc = next,
+            None => break
         }
     }
+
+    return instrs;
 }
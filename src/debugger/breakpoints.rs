@@ -0,0 +1,320 @@
+use std::cell::Cell;
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::consts::*;
+use crate::debugger::expr::{Condition, parse_addr};
+use crate::debugger::symbols::Symbols;
+
+/*
+ * Breakpoints, watchpoints and the stepping modes of the debugger.
+ *
+ * Breakpoints are written `[bank:]addr [if condition]` or `label [if condition]`, the bank only
+ * being checked in the switchable ROM bank. Watchpoints stop on reads, writes or the execution of a range of
+ * addresses: reads and writes are checked by the Bus as the CPU accesses memory, and the
+ * debugger stops at the end of the instruction.
+ */
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Breakpoint {
+    pub addr       :u16,
+    pub bank       :Option<u16>,
+    pub condition  :Option<Condition>,
+    pub text       :String, // As typed
+    pub is_enabled :bool,
+}
+
+impl Breakpoint {
+    pub fn new(addr :u16) -> Breakpoint {
+        return Breakpoint { addr, bank: None, condition: None, text: format!("{:04X}", addr), is_enabled: true };
+    }
+
+    pub fn parse(s :&str) -> Result<Breakpoint, String> {
+        return Breakpoint::parse_with_symbols(s, &Symbols::new());
+    }
+
+    /* The address can also be a label, which gives the bank */
+    pub fn parse_with_symbols(s :&str, symbols :&Symbols) -> Result<Breakpoint, String> {
+        let s = s.trim();
+        let (location, condition) = match s.split_once(" if ") {
+            Some((location, condition)) => (location.trim(), Some(Condition::parse(condition)?)),
+            None => (s, None)
+        };
+
+        let (bank, addr) = match location.split_once(':') {
+            Some((bank, addr)) => (Some(parse_addr(bank)?), parse_addr(addr)?),
+            None => symbols.resolve(location)?
+        };
+
+        return Ok(Breakpoint { addr, bank, condition, text: s.to_string(), is_enabled: true });
+    }
+
+    fn is_hit(&self, pc :u16, cpu :&CPU, bus :&Bus) -> bool {
+        return self.is_enabled && self.addr == pc
+            && self.bank.is_none_or(|bank| !(BANK1_START..=BANK1_END).contains(&pc) || bus.rom_bank(pc) == bank)
+            && self.condition.as_ref().is_none_or(|cond| cond.eval(cpu, bus));
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Access { Read, Write, Execute }
+
+impl fmt::Display for Access {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Access::Read => write!(f, "read"),
+            Access::Write => write!(f, "write"),
+            Access::Execute => write!(f, "execute"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watchpoint {
+    pub start   :u16,
+    pub end     :u16, // Included
+    pub read    :bool,
+    pub write   :bool,
+    pub execute :bool,
+}
+
+impl Watchpoint {
+    /* `[r][w][x] start[-end]`, on writes by default */
+    pub fn parse(s :&str) -> Result<Watchpoint, String> {
+        let words :Vec<&str> = s.split_whitespace().collect();
+        let (access, range) = match words[..] {
+            [range] => ("w", range),
+            [access, range] => (access, range),
+            _ => return Err("expected [r][w][x] <start>[-<end>]".to_string())
+        };
+
+        if access.is_empty() || !access.chars().all(|c| "rwx".contains(c)) {
+            return Err(format!("bad access {}, r, w and/or x", access));
+        }
+
+        let (start, end) = match range.split_once('-') {
+            Some((start, end)) => (parse_addr(start)?, parse_addr(end)?),
+            None => (parse_addr(range)?, parse_addr(range)?)
+        };
+        if end < start {
+            return Err(format!("empty range {}", range));
+        }
+
+        return Ok(Watchpoint {
+            start, end,
+            read: access.contains('r'),
+            write: access.contains('w'),
+            execute: access.contains('x'),
+        });
+    }
+
+    fn contains(&self, addr :u16) -> bool {
+        return addr >= self.start && addr <= self.end;
+    }
+}
+
+impl fmt::Display for Watchpoint {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        let access = format!("{}{}{}",
+            if self.read {"r"} else {""}, if self.write {"w"} else {""}, if self.execute {"x"} else {""});
+
+        if self.start == self.end {
+            write!(f, "{:<3} {:04X}", access, self.start)
+        } else {
+            write!(f, "{:<3} {:04X}-{:04X}", access, self.start, self.end)
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WatchHit {
+    pub index  :usize,
+    pub access :Access,
+    pub addr   :u16,
+    pub val    :u8,
+}
+
+/* Watchpoints checked by the Bus. Reads only borrow it, so the first hit is kept in a Cell. */
+#[derive(Default)]
+pub struct Watchpoints {
+    list :Vec<Watchpoint>,
+    hit  :Cell<Option<WatchHit>>,
+}
+
+impl Watchpoints {
+    pub fn new() -> Watchpoints {
+        return Watchpoints { list: vec![], hit: Cell::new(None) };
+    }
+
+    pub fn list(&self) -> &[Watchpoint] { return &self.list; }
+    pub fn add(&mut self, watchpoint :Watchpoint) { self.list.push(watchpoint); }
+
+    pub fn remove(&mut self, index :usize) -> Option<Watchpoint> {
+        return if index < self.list.len() { Some(self.list.remove(index)) } else { None };
+    }
+
+    fn check(&self, access :Access, addr :u16, val :u8) {
+        if self.list.is_empty() || self.hit.get().is_some() {
+            return;
+        }
+
+        let index = self.list.iter().position(|w| w.contains(addr) && match access {
+            Access::Read => w.read,
+            Access::Write => w.write,
+            Access::Execute => w.execute,
+        });
+        if let Some(index) = index {
+            self.hit.set(Some(WatchHit { index, access, addr, val }));
+        }
+    }
+
+    pub fn on_read(&self, addr :u16, val :u8)  { self.check(Access::Read, addr, val); }
+    pub fn on_write(&self, addr :u16, val :u8) { self.check(Access::Write, addr, val); }
+    pub fn on_execute(&self, addr :u16, opcode :u8) { self.check(Access::Execute, addr, opcode); }
+
+    /* First hit since the last call */
+    pub fn take_hit(&self) -> Option<WatchHit> { return self.hit.take(); }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RunMode {
+    Step(u16),                  // Stop after skipping n instructions
+    Continue,                   // Until a breakpoint or a watchpoint
+    Until { pc :u16, sp :u16 }, // Until pc is reached with the stack at sp or above
+    StepOut { sp :u16 },        // Until a return pops the frame at sp
+    Frame,                      // Until the next VBlank starts
+}
+
+/* Why the debugger stopped */
+#[derive(Debug, Clone, PartialEq)]
+pub enum Stop {
+    Step,
+    Reached,
+    Breakpoint(usize),
+    Watchpoint(WatchHit),
+}
+
+impl fmt::Display for Stop {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Stop::Step | Stop::Reached => Ok(()),
+            Stop::Breakpoint(index) => write!(f, "Breakpoint {}", index),
+            Stop::Watchpoint(hit) if hit.access == Access::Execute =>
+                write!(f, "Watchpoint w{}: execute {:04X}", hit.index, hit.addr),
+            Stop::Watchpoint(hit) =>
+                write!(f, "Watchpoint w{}: {} {:02X} at {:04X}", hit.index, hit.access, hit.val, hit.addr),
+        }
+    }
+}
+
+pub(crate) const CALL_OPCODES :[u8;5] = [0xCD, 0xC4, 0xCC, 0xD4, 0xDC];
+const RET_OPCODES  :[u8;6] = [0xC9, 0xD9, 0xC0, 0xC8, 0xD0, 0xD8];
+
+pub(crate) fn is_rst(opcode :u8) -> bool { return opcode & 0xC7 == 0xC7; }
+
+pub struct Breakpoints {
+    list        :Vec<Breakpoint>,
+    mode        :RunMode,
+    last_opcode :u8, // Of the previous instruction
+}
+
+// Not derived: it starts stepping
+impl Default for Breakpoints {
+    fn default() -> Breakpoints { return Breakpoints::new(); }
+}
+
+impl Breakpoints {
+    pub fn new() -> Breakpoints {
+        return Breakpoints { list: vec![], mode: RunMode::Step(0), last_opcode: 0x00 };
+    }
+
+    pub fn list(&self) -> &[Breakpoint] { return &self.list; }
+    pub fn mode(&self) -> RunMode { return self.mode; }
+    pub fn set_mode(&mut self, mode :RunMode) { self.mode = mode; }
+
+    pub fn add(&mut self, breakpoint :Breakpoint) { self.list.push(breakpoint); }
+
+    pub fn remove(&mut self, index :usize) -> Option<Breakpoint> {
+        return if index < self.list.len() { Some(self.list.remove(index)) } else { None };
+    }
+
+    pub fn set_enabled(&mut self, index :usize, is_enabled :bool) -> bool {
+        return match self.list.get_mut(index) {
+            Some(breakpoint) => { breakpoint.is_enabled = is_enabled; true },
+            None => false
+        }
+    }
+
+    /* Add an unconditional breakpoint at addr, or remove the ones there */
+    pub fn toggle(&mut self, addr :u16) {
+        let len = self.list.len();
+        self.list.retain(|b| b.addr != addr);
+
+        if self.list.len() == len {
+            self.add(Breakpoint::new(addr));
+        }
+    }
+
+    pub fn has_breakpoint(&self, addr :u16) -> bool {
+        return self.list.iter().any(|b| b.addr == addr && b.is_enabled);
+    }
+
+    /* Run the next instruction, stepping over calls and RSTs */
+    pub fn step_over(&mut self, cpu :&CPU) {
+        let pc = cpu.instr_addr();
+        let opcode = cpu.get_opcode();
+
+        self.mode = if CALL_OPCODES.contains(&opcode) {
+            RunMode::Until { pc: pc.wrapping_add(3), sp: cpu.get_sp() }
+        } else if is_rst(opcode) {
+            RunMode::Until { pc: pc.wrapping_add(1), sp: cpu.get_sp() }
+        } else {
+            RunMode::Step(0)
+        };
+    }
+
+    /* Run until the current function returns */
+    pub fn step_out(&mut self, cpu :&CPU) {
+        self.mode = RunMode::StepOut { sp: cpu.get_sp() };
+    }
+
+    pub fn run_to(&mut self, addr :u16) {
+        self.mode = RunMode::Until { pc: addr, sp: 0x0000 };
+    }
+
+    /* Run until the next frame, which never comes with the LCD off */
+    pub fn run_frame(&mut self) {
+        self.mode = RunMode::Frame;
+    }
+
+    /*
+     * Called when the CPU is about to run a new instruction.
+     * Returns why the debugger should stop there, if it should.
+     */
+    pub fn check(&mut self, cpu :&CPU, bus :&mut Bus) -> Option<Stop> {
+        let pc = cpu.instr_addr();
+        let last_opcode = std::mem::replace(&mut self.last_opcode, cpu.get_opcode());
+        // Taken at every instruction, so that it's only about the last one
+        let is_frame_done = bus.take_frame_done();
+
+        bus.watchpoints().on_execute(pc, cpu.get_opcode());
+        if let Some(hit) = bus.watchpoints().take_hit() {
+            return Some(Stop::Watchpoint(hit));
+        }
+
+        if let Some(index) = self.list.iter().position(|b| b.is_hit(pc, cpu, bus)) {
+            return Some(Stop::Breakpoint(index));
+        }
+
+        return match self.mode {
+            RunMode::Step(0) => Some(Stop::Step),
+            RunMode::Step(n) => { self.mode = RunMode::Step(n - 1); None },
+            RunMode::Continue => None,
+            RunMode::Until { pc: target, sp } if pc == target && cpu.get_sp() >= sp => Some(Stop::Reached),
+            RunMode::StepOut { sp } if RET_OPCODES.contains(&last_opcode) && cpu.get_sp() > sp => Some(Stop::Reached),
+            RunMode::Frame if is_frame_done => Some(Stop::Reached),
+            _ => None
+        }
+    }
+}
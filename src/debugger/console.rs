@@ -0,0 +1,315 @@
+use std::collections::VecDeque;
+
+use crate::bus::Bus;
+use crate::bus::recorder::{self, BusRecorder};
+use crate::gbemulator::GBEmulator;
+use crate::debugger::disassemble;
+use crate::debugger::expr::{Register, parse_value, parse_addr};
+use crate::debugger::breakpoints::{Breakpoint, Breakpoints, RunMode, Watchpoint};
+use crate::debugger::search::RamSearch;
+use crate::debugger::symbols::Symbols;
+use crate::debugger::video::{self, VideoView};
+
+/*
+ * Command line of the debugger.
+ *
+ * Commands print to an output log shown by the TUI, errors included, and the lines typed are
+ * kept in a history. Addresses and bytes are in hexadecimal, other values in decimal or in
+ * hexadecimal with a $ or 0x prefix. Where an address is expected a label can be given instead.
+ */
+
+const HELP :&str = "\
+break [bank:]addr [if cond]  add a breakpoint, at a label or an address
+watch [r][w][x] start[-end]  add a watchpoint, on writes by default
+delete n|wn                  delete the breakpoint n or the watchpoint n
+enable n, disable n          enable or disable the breakpoint n
+step [n]                     run n instructions, 1 by default
+continue, next, finish       run until a breakpoint, step over, step out
+until addr                   run until addr
+frame                        run until the next frame
+set [reg] r=val              set a register, PC jumping there
+poke addr byte...            write bytes to memory
+x[/n] addr                   dump n bytes of memory, 64 by default
+disas [addr] [n]             dissasemble n instructions, at PC by default
+search ...                   run a RAM search command
+oam                          list the 40 objects of the OAM
+export view file.png         save tiles [8000|8800], map [9800|9C00] or oam to a PNG
+bus on [n], bus off          record the last n accesses to the bus, or stop
+bus[/n] [start[-end]]        list the last n accesses recorded, 20 by default
+bus dump file                write the recorded accesses to a file
+reset                        reset the Game Boy";
+
+const MAX_OUTPUT :usize = 500;  // Lines kept
+const MAX_HISTORY :usize = 100;
+
+// Defaults of x and disas
+const DUMP_LEN :u16 = 64;
+const DISAS_LEN :usize = 10;
+const BUS_LEN :usize = 20;
+
+/* What a command does once run */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Outcome {
+    Stay,       // Wait for another command
+    Resume,     // Run the game
+}
+
+#[derive(Default)]
+pub struct Console {
+    output  :VecDeque<String>,
+    history :Vec<String>,
+}
+
+impl Console {
+    pub fn new() -> Console {
+        return Console { output: VecDeque::new(), history: vec![] };
+    }
+
+    pub fn output(&self) -> &VecDeque<String> { return &self.output; }
+    pub fn history(&self) -> &[String] { return &self.history; }
+
+    pub fn print(&mut self, text :&str) {
+        for line in text.lines() {
+            self.output.push_back(line.to_string());
+        }
+        while self.output.len() > MAX_OUTPUT {
+            self.output.pop_front();
+        }
+    }
+
+    pub fn print_result(&mut self, result :Result<String, String>) {
+        match result {
+            Ok(message) => self.print(&message),
+            Err(err) => self.print(&format!("Error: {}", err)),
+        }
+    }
+
+    /* Run a line typed in the TUI, printing what it outputs */
+    pub fn run(&mut self, gbemu :&mut GBEmulator, breakpoints :&mut Breakpoints, search :&mut RamSearch,
+               symbols :&Symbols, line :&str) -> Outcome
+    {
+        let line = line.trim();
+        if line.is_empty() {
+            return Outcome::Stay;
+        }
+
+        self.print(&format!("> {}", line));
+        if self.history.last().map(String::as_str) != Some(line) {
+            self.history.push(line.to_string());
+            if self.history.len() > MAX_HISTORY {
+                self.history.remove(0);
+            }
+        }
+
+        return match self.command(gbemu, breakpoints, search, symbols, line) {
+            Ok(outcome) => outcome,
+            Err(err) => { self.print(&format!("Error: {}", err)); Outcome::Stay }
+        };
+    }
+
+    fn command(&mut self, gbemu :&mut GBEmulator, breakpoints :&mut Breakpoints, search :&mut RamSearch,
+               symbols :&Symbols, line :&str) -> Result<Outcome, String>
+    {
+        let (command, args) = line.split_once(' ').unwrap_or((line, ""));
+        let args = args.trim();
+        let words :Vec<&str> = args.split_whitespace().collect();
+
+        let index = |s :&str| s.parse::<usize>().map_err(|_| format!("bad index {}", s));
+        let addr = |s :&str| symbols.resolve(s).map(|(_, addr)| addr);
+        let bus = gbemu.get_bus();
+
+        // x/n
+        if let Some(len) = command.strip_prefix("x") {
+            let len = match len.strip_prefix('/') {
+                Some(len) => len.parse::<u16>().map_err(|_| format!("bad length {}", len))?,
+                None if len.is_empty() => DUMP_LEN,
+                None => return Err(format!("unknown command {}", command))
+            };
+            let addr = addr(words.first().ok_or("missing address")?)?;
+
+            self.print(&dump(&bus.borrow(), addr, len));
+            return Ok(Outcome::Stay);
+        }
+
+        // bus/n
+        if let Some(len) = command.strip_prefix("bus") {
+            let len = match len.strip_prefix('/') {
+                Some(len) => len.parse::<usize>().map_err(|_| format!("bad count {}", len))?,
+                None if len.is_empty() => BUS_LEN,
+                None => return Err(format!("unknown command {}", command))
+            };
+
+            let result = bus_log(&mut bus.borrow_mut(), symbols, len, &words);
+            self.print_result(result);
+            return Ok(Outcome::Stay);
+        }
+
+        match command {
+            "break" | "b" => {
+                let breakpoint = Breakpoint::parse_with_symbols(args, symbols)?;
+                self.print(&format!("Breakpoint {} at {}", breakpoints.list().len(), breakpoint.text));
+                breakpoints.add(breakpoint);
+            },
+            "watch" | "w" => {
+                let watchpoint = Watchpoint::parse(args)?;
+                self.print(&format!("Watchpoint w{} on {}", bus.borrow().watchpoints().list().len(), watchpoint));
+                bus.borrow_mut().watchpoints_mut().add(watchpoint);
+            },
+            "delete" | "d" => {
+                let removed = match args.strip_prefix('w') {
+                    Some(n) => bus.borrow_mut().watchpoints_mut().remove(index(n)?).is_some(),
+                    None => breakpoints.remove(index(args)?).is_some()
+                };
+                if !removed {
+                    return Err(format!("no breakpoint {}", args));
+                }
+                self.print(&format!("Deleted {}", args));
+            },
+            "enable" | "disable" => {
+                if !breakpoints.set_enabled(index(args)?, command == "enable") {
+                    return Err(format!("no breakpoint {}", args));
+                }
+                self.print(&format!("Breakpoint {} {}d", args, command));
+            },
+            "step" | "s" => {
+                let n = if args.is_empty() { 1 } else { parse_value(args)? };
+                if n == 0 {
+                    return Err("step at least 1 instruction".to_string());
+                }
+                breakpoints.set_mode(RunMode::Step(n - 1));
+                return Ok(Outcome::Resume);
+            },
+            "continue" | "c" => { breakpoints.set_mode(RunMode::Continue); return Ok(Outcome::Resume); },
+            "next" | "n" => { breakpoints.step_over(gbemu.get_cpu()); return Ok(Outcome::Resume); },
+            "finish" => { breakpoints.step_out(gbemu.get_cpu()); return Ok(Outcome::Resume); },
+            "until" => { breakpoints.run_to(addr(args)?); return Ok(Outcome::Resume); },
+            "frame" => { breakpoints.run_frame(); return Ok(Outcome::Resume); },
+            "set" => {
+                let assignment = args.strip_prefix("reg ").unwrap_or(args);
+                let (reg, val) = assignment.split_once('=').ok_or("expected r=val")?;
+                let reg = Register::parse(reg.trim()).ok_or(format!("unknown register {}", reg.trim()))?;
+                let val = parse_value(val.trim())?;
+
+                if !reg.is_16bit() && val > 0xFF {
+                    return Err(format!("{} doesn't fit in {:?}", val, reg));
+                }
+                reg.write(gbemu.get_cpu_mut(), val);
+                self.print(&format!("{:?} = {:X}", reg, reg.read(gbemu.get_cpu())));
+            },
+            "poke" => {
+                let addr = addr(words.first().ok_or("missing address")?)?;
+                let bytes = words[1..].iter().map(|s| parse_byte(s)).collect::<Result<Vec<u8>, String>>()?;
+                if bytes.is_empty() {
+                    return Err("missing bytes".to_string());
+                }
+
+                for (i, byte) in bytes.iter().enumerate() {
+                    bus.borrow_mut().poke(addr.wrapping_add(i as u16), *byte);
+                }
+                self.print(&format!("Wrote {} bytes at {:04X}", bytes.len(), addr));
+            },
+            "disas" => {
+                let addr = match words.first() {
+                    Some(s) => addr(s)?,
+                    None => gbemu.get_cpu().instr_addr()
+                };
+                let n = match words.get(1) {
+                    Some(n) => n.parse::<usize>().map_err(|_| format!("bad count {}", n))?,
+                    None => DISAS_LEN
+                };
+
+                for instr in disassemble(&bus.borrow(), symbols, addr, n) {
+                    if let Some(label) = instr.get_label() {
+                        self.print(&format!("{}:", label));
+                    }
+                    self.print(&instr.to_string());
+                }
+            },
+            "search" => {
+                let result = search.command(&mut bus.borrow_mut(), args);
+                self.print_result(result);
+            },
+            "oam" => {
+                for line in video::object_list(&bus.borrow()) {
+                    self.print(&line);
+                }
+            },
+            "export" => {
+                let (view, path) = args.rsplit_once(' ').ok_or("missing view or file")?;
+                let result = video::export(&bus.borrow(), VideoView::parse(view)?, path);
+                self.print(&result?);
+            },
+            "reset" => {
+                gbemu.reset();
+                breakpoints.set_mode(RunMode::Step(0));
+                self.print("Reset");
+            },
+            "help" | "h" => self.print(HELP),
+            _ => return Err(format!("unknown command {}, help lists them", command))
+        }
+
+        return Ok(Outcome::Stay);
+    }
+}
+
+/* Start, stop or dump the bus recorder, or list the last n accesses */
+fn bus_log(bus :&mut Bus, symbols :&Symbols, n :usize, words :&[&str]) -> Result<String, String> {
+    match words {
+        ["on"] | ["on", _] => {
+            let capacity = match words.get(1) {
+                Some(n) => n.parse::<usize>().map_err(|_| format!("bad count {}", n))?,
+                None => recorder::DEFAULT_CAPACITY
+            };
+            bus.set_recorder(Some(BusRecorder::new(capacity)));
+            return Ok(format!("Recording the last {} bus accesses", capacity));
+        },
+        ["off"] => {
+            bus.set_recorder(None);
+            return Ok("Stopped recording the bus".to_string());
+        },
+        _ => {}
+    }
+
+    let recorder = bus.recorder().ok_or("not recording the bus, bus on starts")?;
+    if let ["dump", path] = words {
+        let n = recorder.dump(path).map_err(|err| format!("couldn't write {}: {}", path, err))?;
+        return Ok(format!("Wrote {} bus accesses to {}", n, path));
+    }
+
+    let addr = |s :&str| symbols.resolve(s).map(|(_, addr)| addr);
+    let (start, end) = match words {
+        [] => (0x0000, 0xFFFF),
+        [range] => match range.split_once('-') {
+            Some((start, end)) => (addr(start)?, addr(end)?),
+            None => (addr(range)?, addr(range)?)
+        },
+        _ => return Err("expected bus[/n] [start[-end]]".to_string())
+    };
+
+    let accesses = recorder.last(n, start, end);
+    if accesses.is_empty() {
+        return Ok("No access recorded".to_string());
+    }
+    return Ok(accesses.iter().map(|access| access.to_string()).collect::<Vec<String>>().join("\n"));
+}
+
+/* Byte in hexadecimal, like in the dumps */
+fn parse_byte(s :&str) -> Result<u8, String> {
+    return parse_addr(s).ok().and_then(|val| u8::try_from(val).ok()).ok_or(format!("bad byte {}", s));
+}
+
+/* 16 bytes per line, with their characters */
+fn dump(bus :&Bus, addr :u16, len :u16) -> String {
+    let mut text = String::new();
+
+    for line_start in (0..len).step_by(16) {
+        let line_addr = addr.wrapping_add(line_start);
+        let bytes :Vec<u8> = (0..16.min(len - line_start)).map(|i| bus.peek(line_addr.wrapping_add(i))).collect();
+
+        let hex :Vec<String> = bytes.iter().map(|byte| format!("{:02X}", byte)).collect();
+        let chars :String = bytes.iter().map(|&byte| if byte.is_ascii_graphic() || byte == b' ' { byte as char } else { '.' }).collect();
+        text += &format!("{:04X}  {:<47}  {}\n", line_addr, hex.join(" "), chars);
+    }
+
+    return text;
+}
@@ -0,0 +1,257 @@
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::consts::*;
+
+/*
+ * Conditions of the breakpoints, like `A == 0x3C && [C0A0] > 5`.
+ *
+ * Operands are registers, numbers (decimal, or hexadecimal with a $ or 0x prefix) and bytes
+ * of memory, whose address is in hexadecimal or a 16-bit register: [C0A0], [HL].
+ * Comparisons can be joined with && and ||, && binding tighter, and grouped with parentheses.
+ */
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Register { A, B, C, D, E, F, H, L, AF, BC, DE, HL, SP, PC }
+
+impl Register {
+    pub fn parse(s :&str) -> Option<Register> {
+        return match s.to_ascii_uppercase().as_str() {
+            "A" => Some(Register::A),   "B" => Some(Register::B),
+            "C" => Some(Register::C),   "D" => Some(Register::D),
+            "E" => Some(Register::E),   "F" => Some(Register::F),
+            "H" => Some(Register::H),   "L" => Some(Register::L),
+            "AF" => Some(Register::AF), "BC" => Some(Register::BC),
+            "DE" => Some(Register::DE), "HL" => Some(Register::HL),
+            "SP" => Some(Register::SP), "PC" => Some(Register::PC),
+            _ => None
+        }
+    }
+
+    /* PC is the address of the instruction about to run */
+    pub fn read(&self, cpu :&CPU) -> u16 {
+        return match self {
+            Register::A => cpu.reg(REG_A) as u16,
+            Register::B => cpu.reg(REG_B) as u16,
+            Register::C => cpu.reg(REG_C) as u16,
+            Register::D => cpu.reg(REG_D) as u16,
+            Register::E => cpu.reg(REG_E) as u16,
+            Register::F => cpu.reg(REG_F) as u16,
+            Register::H => cpu.reg(REG_H) as u16,
+            Register::L => cpu.reg(REG_L) as u16,
+            Register::AF => cpu.reg16(REG_A, REG_F) & 0xFFF0,
+            Register::BC => cpu.reg16(REG_B, REG_C),
+            Register::DE => cpu.reg16(REG_D, REG_E),
+            Register::HL => cpu.reg16(REG_H, REG_L),
+            Register::SP => cpu.get_sp(),
+            Register::PC => cpu.instr_addr(),
+        }
+    }
+
+    pub fn is_16bit(&self) -> bool {
+        return matches!(self, Register::AF | Register::BC | Register::DE | Register::HL | Register::SP | Register::PC);
+    }
+
+    /* Writing PC jumps there, fetching the instruction again */
+    pub fn write(&self, cpu :&mut CPU, val :u16) {
+        match self {
+            Register::A => cpu.set_reg(REG_A, val as u8),
+            Register::B => cpu.set_reg(REG_B, val as u8),
+            Register::C => cpu.set_reg(REG_C, val as u8),
+            Register::D => cpu.set_reg(REG_D, val as u8),
+            Register::E => cpu.set_reg(REG_E, val as u8),
+            Register::F => cpu.set_reg(REG_F, val as u8),
+            Register::H => cpu.set_reg(REG_H, val as u8),
+            Register::L => cpu.set_reg(REG_L, val as u8),
+            Register::AF => cpu.set_reg16(REG_A, REG_F, val),
+            Register::BC => cpu.set_reg16(REG_B, REG_C, val),
+            Register::DE => cpu.set_reg16(REG_D, REG_E, val),
+            Register::HL => cpu.set_reg16(REG_H, REG_L, val),
+            Register::SP => cpu.set_sp(val),
+            Register::PC => { cpu.set_pc(val); cpu.prefetch_opcode(); },
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Operand {
+    Value(u16),
+    Reg(Register),
+    Mem(Box<Operand>),
+}
+
+impl Operand {
+    fn eval(&self, cpu :&CPU, bus :&Bus) -> u16 {
+        return match self {
+            Operand::Value(val) => *val,
+            Operand::Reg(reg) => reg.read(cpu),
+            Operand::Mem(addr) => bus.peek(addr.eval(cpu, bus)) as u16,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CompareOp { Eq, Ne, Gt, Ge, Lt, Le }
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Condition {
+    Compare(Operand, CompareOp, Operand),
+    And(Box<Condition>, Box<Condition>),
+    Or(Box<Condition>, Box<Condition>),
+}
+
+impl Condition {
+    pub fn parse(s :&str) -> Result<Condition, String> {
+        let tokens = tokenize(s)?;
+        let mut parser = Parser { tokens, pos: 0 };
+
+        let cond = parser.or()?;
+        if let Some(token) = parser.tokens.get(parser.pos) {
+            return Err(format!("unexpected {}", token));
+        }
+
+        return Ok(cond);
+    }
+
+    pub fn eval(&self, cpu :&CPU, bus :&Bus) -> bool {
+        return match self {
+            Condition::Compare(a, op, b) => {
+                let (a, b) = (a.eval(cpu, bus), b.eval(cpu, bus));
+                match op {
+                    CompareOp::Eq => a == b,
+                    CompareOp::Ne => a != b,
+                    CompareOp::Gt => a > b,
+                    CompareOp::Ge => a >= b,
+                    CompareOp::Lt => a < b,
+                    CompareOp::Le => a <= b,
+                }
+            },
+            Condition::And(a, b) => a.eval(cpu, bus) && b.eval(cpu, bus),
+            Condition::Or(a, b) => a.eval(cpu, bus) || b.eval(cpu, bus),
+        }
+    }
+}
+
+/* Number in decimal, or in hexadecimal with a $ or 0x prefix */
+pub fn parse_value(s :&str) -> Result<u16, String> {
+    let hex = s.strip_prefix('$').or_else(|| s.strip_prefix("0x"));
+    let val = match hex {
+        Some(hex) => u16::from_str_radix(hex, 16),
+        None => s.parse::<u16>()
+    };
+
+    return val.map_err(|_| format!("bad value {}", s));
+}
+
+/* Address in hexadecimal, with an optional $ or 0x prefix */
+pub fn parse_addr(s :&str) -> Result<u16, String> {
+    let hex = s.strip_prefix('$').or_else(|| s.strip_prefix("0x")).unwrap_or(s);
+    return u16::from_str_radix(hex, 16).map_err(|_| format!("bad address {}", s));
+}
+
+const OPERATORS :[&str;10] = ["&&", "||", "==", "!=", ">=", "<=", ">", "<", "(", ")"];
+
+fn tokenize(s :&str) -> Result<Vec<String>, String> {
+    let mut tokens = vec![];
+    let mut rest = s.trim_start();
+
+    while !rest.is_empty() {
+        if let Some(op) = OPERATORS.iter().find(|op| rest.starts_with(*op)) {
+            tokens.push(op.to_string());
+            rest = &rest[op.len()..];
+        } else if rest.starts_with('[') {
+            let end = rest.find(']').ok_or("missing ]")?;
+            tokens.push(rest[..=end].to_string());
+            rest = &rest[end+1..];
+        } else {
+            let end = rest.find(|c :char| !(c.is_ascii_alphanumeric() || c == '$')).unwrap_or(rest.len());
+            if end == 0 {
+                return Err(format!("unexpected {}", &rest[..1]));
+            }
+            tokens.push(rest[..end].to_string());
+            rest = &rest[end..];
+        }
+
+        rest = rest.trim_start();
+    }
+
+    return Ok(tokens);
+}
+
+struct Parser {
+    tokens :Vec<String>,
+    pos    :usize,
+}
+
+impl Parser {
+    fn next(&mut self) -> Result<String, String> {
+        let token = self.tokens.get(self.pos).cloned().ok_or("incomplete condition")?;
+        self.pos += 1;
+        return Ok(token);
+    }
+
+    fn is_next(&mut self, token :&str) -> bool {
+        if self.tokens.get(self.pos).is_some_and(|t| t == token) {
+            self.pos += 1;
+            return true;
+        }
+        return false;
+    }
+
+    fn or(&mut self) -> Result<Condition, String> {
+        let mut cond = self.and()?;
+        while self.is_next("||") {
+            cond = Condition::Or(Box::new(cond), Box::new(self.and()?));
+        }
+        return Ok(cond);
+    }
+
+    fn and(&mut self) -> Result<Condition, String> {
+        let mut cond = self.compare()?;
+        while self.is_next("&&") {
+            cond = Condition::And(Box::new(cond), Box::new(self.compare()?));
+        }
+        return Ok(cond);
+    }
+
+    fn compare(&mut self) -> Result<Condition, String> {
+        if self.is_next("(") {
+            let cond = self.or()?;
+            if !self.is_next(")") {
+                return Err("missing )".to_string());
+            }
+            return Ok(cond);
+        }
+
+        let a = self.operand()?;
+        let op = match self.next()?.as_str() {
+            "==" => CompareOp::Eq,
+            "!=" => CompareOp::Ne,
+            ">"  => CompareOp::Gt,
+            ">=" => CompareOp::Ge,
+            "<"  => CompareOp::Lt,
+            "<=" => CompareOp::Le,
+            token => return Err(format!("expected a comparison, not {}", token))
+        };
+        let b = self.operand()?;
+
+        return Ok(Condition::Compare(a, op, b));
+    }
+
+    fn operand(&mut self) -> Result<Operand, String> {
+        let token = self.next()?;
+
+        if let Some(addr) = token.strip_prefix('[').and_then(|t| t.strip_suffix(']')) {
+            let addr = addr.trim();
+            return match Register::parse(addr) {
+                Some(reg) => Ok(Operand::Mem(Box::new(Operand::Reg(reg)))),
+                None => Ok(Operand::Mem(Box::new(Operand::Value(parse_addr(addr)?))))
+            };
+        }
+
+        if let Some(reg) = Register::parse(&token) {
+            return Ok(Operand::Reg(reg));
+        }
+
+        return Ok(Operand::Value(parse_value(&token)?));
+    }
+}
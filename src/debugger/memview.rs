@@ -0,0 +1,296 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::consts::*;
+use crate::debugger::expr::parse_addr;
+use crate::debugger::symbols::Symbols;
+
+/*
+ * Hex view of the memory in the debugger, a region at a time.
+ *
+ * Regions are shown at the addresses the CPU sees them at, ROM and cartridge RAM banks whichever
+ * bank is mapped. The VRAM and the OAM are read and written whatever the PPU is doing. Bytes are
+ * edited by typing their two digits, and the ones that changed since the debugger last stopped
+ * are marked.
+ */
+
+// Banks of cartridge RAM looked at for changes, the most an MBC has
+const MAX_RAM_BANKS :u8 = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Region {
+    Rom(u16),
+    Vram,
+    Sram(u8),
+    Wram,
+    Oam,
+    Io,
+    Hram, // And IE
+}
+
+impl fmt::Display for Region {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Region::Rom(bank) => write!(f, "ROM bank {}", bank),
+            Region::Vram => write!(f, "VRAM"),
+            Region::Sram(bank) => write!(f, "SRAM bank {}", bank),
+            Region::Wram => write!(f, "WRAM"),
+            Region::Oam => write!(f, "OAM"),
+            Region::Io => write!(f, "IO"),
+            Region::Hram => write!(f, "HRAM"),
+        }
+    }
+}
+
+impl Region {
+    /* `rom [bank]`, `vram`, `sram [bank]`, `wram`, `oam`, `io` or `hram` */
+    pub fn parse(s :&str) -> Result<Region, String> {
+        let words :Vec<&str> = s.split_whitespace().collect();
+        let bank = || words.get(1).map_or(Ok(0), |bank| bank.parse::<u16>().map_err(|_| format!("bad bank {}", bank)));
+
+        let region = match words.first().map(|word| word.to_ascii_lowercase()).as_deref() {
+            Some("rom") => Region::Rom(bank()?),
+            Some("vram") => Region::Vram,
+            Some("sram") => Region::Sram(u8::try_from(bank()?).map_err(|_| "bad bank".to_string())?),
+            Some("wram") => Region::Wram,
+            Some("oam") => Region::Oam,
+            Some("io") => Region::Io,
+            Some("hram") => Region::Hram,
+            _ => return Err(format!("unknown region {}", s))
+        };
+
+        if words.len() > if matches!(region, Region::Rom(_) | Region::Sram(_)) {2} else {1} {
+            return Err(format!("unknown region {}", s));
+        }
+        return Ok(region);
+    }
+
+    /* Region of an address as mapped now. The echo RAM is shown as the WRAM. */
+    pub fn at(bus :&Bus, addr :u16) -> Option<Region> {
+        return match addr {
+            BANK0_START..=BANK1_END => Some(Region::Rom(bus.rom_bank(addr))),
+            VRAM_START..=VRAM_END => Some(Region::Vram),
+            EXT_RAM_START..=EXT_RAM_END => Some(Region::Sram(bus.ram_bank())),
+            WORK_RAM_START..=ECHO_RAM_END => Some(Region::Wram),
+            OAM_START..=OAM_END => Some(Region::Oam),
+            0xFEA0..=0xFEFF => None,
+            0xFF00..=0xFF7F => Some(Region::Io),
+            _ => Some(Region::Hram)
+        }
+    }
+
+    /* First and last addresses */
+    pub fn range(&self) -> (u16, u16) {
+        return match self {
+            Region::Rom(0) => (BANK0_START, BANK0_END),
+            Region::Rom(_) => (BANK1_START, BANK1_END),
+            Region::Vram => (VRAM_START, VRAM_END),
+            Region::Sram(_) => (EXT_RAM_START, EXT_RAM_END),
+            Region::Wram => (WORK_RAM_START, WORK_RAM_END),
+            Region::Oam => (OAM_START, OAM_END),
+            Region::Io => (0xFF00, 0xFF7F),
+            Region::Hram => (HRAM_START, 0xFFFF),
+        }
+    }
+
+    pub fn read(&self, bus :&Bus, addr :u16) -> u8 {
+        return match self {
+            Region::Rom(bank) => bus.peek_rom_bank(*bank, addr),
+            Region::Vram => bus.vram()[(addr - VRAM_START) as usize],
+            Region::Oam => bus.oam()[(addr - OAM_START) as usize],
+            Region::Sram(bank) => bus.peek_ram_bank(*bank, addr).unwrap_or(0xFF),
+            Region::Wram | Region::Io | Region::Hram => bus.peek(addr),
+        }
+    }
+
+    /* The IO registers are written like the CPU does, with their side effects */
+    pub fn write(&self, bus :&mut Bus, addr :u16, val :u8) -> Result<(), String> {
+        match self {
+            Region::Rom(_) => return Err("the ROM is read-only".to_string()),
+            Region::Vram | Region::Oam => bus.poke_video(addr, val),
+            Region::Sram(bank) => {
+                if bus.peek_ram_bank(*bank, addr).is_none() {
+                    return Err(format!("no cartridge RAM bank {}", bank));
+                }
+                bus.poke_ram_bank(*bank, addr, val);
+            },
+            Region::Wram | Region::Io | Region::Hram => bus.poke(addr, val),
+        }
+        return Ok(());
+    }
+
+    /* Cycled through with Tab */
+    fn next(&self) -> Region {
+        return match self {
+            Region::Rom(_) => Region::Vram,
+            Region::Vram => Region::Sram(0),
+            Region::Sram(_) => Region::Wram,
+            Region::Wram => Region::Oam,
+            Region::Oam => Region::Io,
+            Region::Io => Region::Hram,
+            Region::Hram => Region::Rom(0),
+        }
+    }
+}
+
+/* What is done in the memory pane */
+#[derive(Debug, Clone, PartialEq)]
+pub enum MemoryInput {
+    Move(i32),      // The cursor, by n bytes
+    Digit(u8),      // Typed over the selected byte
+    NextRegion,
+    Bank(i32),      // Previous or next bank of the ROM or the cartridge RAM
+    Goto(String),   // A region, an address or a label
+}
+
+/* A line of the hex view */
+pub struct Line {
+    pub addr  :u16,
+    pub bytes :Vec<(u8, bool)>, // And whether it changed
+}
+
+pub struct MemoryView {
+    region      :Region,
+    cursor      :u16,           // Address of the selected byte
+    high_digit  :Option<u8>,    // First digit typed of the selected byte
+    snapshot    :HashMap<Region, Vec<u8>>, // Of the RAM, when the debugger last resumed
+}
+
+// Not derived: it starts on the WRAM
+impl Default for MemoryView {
+    fn default() -> MemoryView { return MemoryView::new(); }
+}
+
+impl MemoryView {
+    pub fn new() -> MemoryView {
+        return MemoryView {
+            region: Region::Wram,
+            cursor: WORK_RAM_START,
+            high_digit: None,
+            snapshot: HashMap::new(),
+        };
+    }
+
+    pub fn region(&self) -> Region { return self.region; }
+    pub fn cursor(&self) -> u16 { return self.cursor; }
+    pub fn high_digit(&self) -> Option<u8> { return self.high_digit; }
+
+    pub fn set_region(&mut self, region :Region) {
+        self.region = region;
+        self.cursor = region.range().0;
+        self.high_digit = None;
+    }
+
+    /* Jump to a region, to `bank:addr`, or to an address or label in the region mapped there */
+    pub fn goto(&mut self, bus :&Bus, symbols :&Symbols, s :&str) -> Result<(), String> {
+        if let Ok(region) = Region::parse(s) {
+            self.set_region(region);
+            return Ok(());
+        }
+
+        let (bank, addr) = match s.trim().split_once(':') {
+            Some((bank, addr)) => (Some(parse_addr(bank)?), parse_addr(addr)?),
+            None => symbols.resolve(s.trim())?
+        };
+        let addr = if (ECHO_RAM_START..=ECHO_RAM_END).contains(&addr) { addr - 0x2000 } else { addr };
+
+        let region = match (Region::at(bus, addr), bank) {
+            (None, _) => return Err(format!("{:04X} is not usable", addr)),
+            (Some(Region::Rom(_)), Some(bank)) => Region::Rom(bank),
+            (Some(Region::Sram(_)), Some(bank)) => Region::Sram(u8::try_from(bank).map_err(|_| format!("bad bank {}", bank))?),
+            (Some(region), _) => region
+        };
+
+        self.region = region;
+        self.cursor = addr.clamp(region.range().0, region.range().1);
+        self.high_digit = None;
+        return Ok(());
+    }
+
+    pub fn move_cursor(&mut self, delta :i32) {
+        let (start, end) = self.region.range();
+        self.cursor = (self.cursor as i32 + delta).clamp(start as i32, end as i32) as u16;
+        self.high_digit = None;
+    }
+
+    /* The second digit writes the byte and selects the next one */
+    pub fn type_digit(&mut self, bus :&mut Bus, digit :u8) -> Result<(), String> {
+        let Some(high) = self.high_digit.take() else {
+            self.high_digit = Some(digit & 0xF);
+            return Ok(());
+        };
+
+        self.region.write(bus, self.cursor, high << 4 | (digit & 0xF))?;
+        self.move_cursor(1);
+        return Ok(());
+    }
+
+    pub fn input(&mut self, bus :&mut Bus, symbols :&Symbols, input :MemoryInput) -> Result<(), String> {
+        match input {
+            MemoryInput::Move(delta) => self.move_cursor(delta),
+            MemoryInput::Digit(digit) => return self.type_digit(bus, digit),
+            MemoryInput::NextRegion => self.set_region(self.region.next()),
+            MemoryInput::Bank(delta) => {
+                let offset = self.cursor - self.region.range().0;
+                let region = match self.region {
+                    Region::Rom(bank) => {
+                        let n_banks = (bus.rom().len() / 0x4000).max(1) as i32;
+                        Region::Rom((bank as i32 + delta).rem_euclid(n_banks) as u16)
+                    },
+                    Region::Sram(bank) => {
+                        let n_banks = (0..MAX_RAM_BANKS).take_while(|bank| bus.peek_ram_bank(*bank, EXT_RAM_START).is_some()).count().max(1) as i32;
+                        Region::Sram((bank as i32 + delta).rem_euclid(n_banks) as u8)
+                    },
+                    _ => return Err(format!("{} has no banks", self.region))
+                };
+
+                self.set_region(region);
+                self.move_cursor(offset as i32);
+            },
+            MemoryInput::Goto(s) => return self.goto(bus, symbols, &s),
+        }
+        return Ok(());
+    }
+
+    /* Remember the RAM before running again, to mark what changed at the next stop */
+    pub fn take_snapshot(&mut self, bus :&Bus) {
+        let ram_banks = (0..MAX_RAM_BANKS).take_while(|bank| bus.peek_ram_bank(*bank, EXT_RAM_START).is_some()).map(Region::Sram);
+        let regions = [Region::Vram, Region::Wram, Region::Oam, Region::Io, Region::Hram].into_iter().chain(ram_banks);
+
+        self.snapshot = regions
+            .map(|region| {
+                let (start, end) = region.range();
+                (region, (start..=end).map(|addr| region.read(bus, addr)).collect())
+            })
+            .collect();
+    }
+
+    /*
+     * n lines of bytes_per_line bytes around the cursor, which is kept in the middle line
+     * unless the region starts or ends before
+     */
+    pub fn lines(&self, bus :&Bus, n_lines :u16, bytes_per_line :u16) -> Vec<Line> {
+        let (start, end) = self.region.range();
+        let n_region_lines = (end - start) / bytes_per_line + 1;
+        let cursor_line = (self.cursor - start) / bytes_per_line;
+        let first_line = cursor_line.saturating_sub(n_lines / 2).min(n_region_lines.saturating_sub(n_lines));
+
+        let snapshot = self.snapshot.get(&self.region);
+
+        return (first_line..(first_line + n_lines).min(n_region_lines))
+            .map(|line| {
+                let addr = start + line * bytes_per_line;
+                let bytes = (addr..=addr.saturating_add(bytes_per_line - 1).min(end))
+                    .map(|addr| {
+                        let val = self.region.read(bus, addr);
+                        let was = snapshot.and_then(|snapshot| snapshot.get((addr - start) as usize));
+                        (val, was.is_some_and(|was| *was != val))
+                    })
+                    .collect();
+
+                Line { addr, bytes }
+            })
+            .collect();
+    }
+}
@@ -0,0 +1,276 @@
+use crate::bus::Bus;
+use crate::cheats::Cheat;
+use crate::consts::*;
+use crate::debugger::expr::{parse_value, parse_addr};
+
+/*
+ * RAM search, to find where a game keeps a variable.
+ *
+ * A search starts with every address of the work RAM, high RAM and the mapped bank of the
+ * cartridge RAM, and each filter keeps the candidates whose value compares with a given value,
+ * or with the value they had at the previous filter. The game usually runs for a few frames
+ * between filters, e.g. until a life is lost.
+ *
+ * Found addresses can be watched, or frozen with GameShark codes so that the game reads the
+ * same value every frame.
+ */
+
+const REGIONS :[(u16, u16);3] = [
+    (EXT_RAM_START, EXT_RAM_END),
+    (WORK_RAM_START, WORK_RAM_END),
+    (HRAM_START, HRAM_END)
+];
+
+// Shown in the TUI
+const MAX_SHOWN_CANDIDATES :usize = 16;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Width { Byte, Word } // Words are little endian, like the CPU's
+
+impl Width {
+    pub fn read(&self, bus :&Bus, addr :u16) -> u16 {
+        return match self {
+            Width::Byte => bus.peek(addr) as u16,
+            Width::Word => ((bus.peek(addr.wrapping_add(1)) as u16) << 8) | bus.peek(addr) as u16
+        }
+    }
+
+    fn mask(&self) -> u16 { return match self { Width::Byte => 0xFF, Width::Word => 0xFFFF }; }
+    fn len(&self) -> u16 { return match self { Width::Byte => 1, Width::Word => 2 }; }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Compare { Eq, Ne, Gt, Lt }
+
+impl Compare {
+    fn test(&self, val :u16, other :u16) -> bool {
+        return match self {
+            Compare::Eq => val == other,
+            Compare::Ne => val != other,
+            Compare::Gt => val > other,
+            Compare::Lt => val < other,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Value(Compare, u16),    // With a value
+    Previous(Compare),      // With the value at the previous filter
+    ChangedBy(i32),         // Since the previous filter, wrapping around
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Candidate {
+    pub addr :u16,
+    pub val  :u16, // At the previous filter
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Watch {
+    pub addr   :u16,
+    pub width  :Width,
+    pub frozen :Option<u16>,
+}
+
+pub struct RamSearch {
+    width      :Width,
+    candidates :Vec<Candidate>,
+    watches    :Vec<Watch>,
+}
+
+// Not derived: it searches bytes first
+impl Default for RamSearch {
+    fn default() -> RamSearch { return RamSearch::new(); }
+}
+
+impl RamSearch {
+    pub fn new() -> RamSearch {
+        return RamSearch {
+            width: Width::Byte,
+            candidates: vec![],
+            watches: vec![],
+        }
+    }
+
+    pub fn width(&self) -> Width { return self.width; }
+    pub fn candidates(&self) -> &[Candidate] { return &self.candidates; }
+    pub fn watches(&self) -> &[Watch] { return &self.watches; }
+
+    /* Snapshot the RAM, every address being a candidate */
+    pub fn start(&mut self, bus :&Bus, width :Width) {
+        self.width = width;
+        self.candidates = REGIONS.iter()
+            .flat_map(|(start, end)| *start..=(end + 1 - width.len()))
+            .map(|addr| Candidate { addr, val: width.read(bus, addr) })
+            .collect();
+    }
+
+    /* Keep the candidates matching the filter. Returns how many are left. */
+    pub fn filter(&mut self, bus :&Bus, filter :Filter) -> usize {
+        let width = self.width;
+
+        self.candidates.retain_mut(|candidate| {
+            let val = width.read(bus, candidate.addr);
+            let is_match = match filter {
+                Filter::Value(compare, other) => compare.test(val, other & width.mask()),
+                Filter::Previous(compare) => compare.test(val, candidate.val),
+                Filter::ChangedBy(n) => val.wrapping_sub(candidate.val) & width.mask() == (n as u16) & width.mask(),
+            };
+
+            candidate.val = val;
+            return is_match;
+        });
+
+        return self.candidates.len();
+    }
+
+    pub fn watch(&mut self, addr :u16, width :Width) {
+        if !self.watches.iter().any(|w| w.addr == addr) {
+            self.watches.push(Watch { addr, width, frozen: None });
+        }
+    }
+
+    /* Stop watching the address, and unfreeze it */
+    pub fn unwatch(&mut self, bus :&mut Bus, addr :u16) {
+        self.unfreeze(bus, addr);
+        self.watches.retain(|w| w.addr != addr);
+    }
+
+    /* Write the value at every frame, with a GameShark code per byte */
+    pub fn freeze(&mut self, bus :&mut Bus, addr :u16, width :Width, val :u16) {
+        self.unwatch(bus, addr);
+
+        for i in 0..width.len() {
+            let byte = (val >> (8*i)) as u8;
+            let byte_addr = addr.wrapping_add(i);
+            let code = format!("01{:02X}{:02X}{:02X}", byte, byte_addr & 0xFF, byte_addr >> 8);
+
+            // Only fails for addresses out of the RAM
+            if bus.cheats_mut().add(&code, &format!("Freeze {:04X}", addr)).is_ok() {
+                bus.poke(byte_addr, byte);
+            }
+        }
+
+        self.watches.push(Watch { addr, width, frozen: Some(val & width.mask()) });
+    }
+
+    pub fn unfreeze(&mut self, bus :&mut Bus, addr :u16) {
+        let Some(watch) = self.watches.iter_mut().find(|w| w.addr == addr) else { return; };
+        if watch.frozen.take().is_none() {
+            return;
+        }
+
+        let end = addr.wrapping_add(watch.width.len());
+        let cheats = bus.cheats_mut();
+        for index in (0..cheats.entries().len()).rev() {
+            if let Cheat::GameShark { bank: None, addr: a, .. } = cheats.entries()[index].cheat {
+                if a >= addr && a < end {
+                    cheats.remove(index);
+                }
+            }
+        }
+    }
+
+    /*
+     * Run a command typed in the debugger. Returns the status to show.
+     *
+     * new [8|16]           start a search
+     * eq|ne|gt|lt [value]  compare with a value, or with the previous values
+     * changed <n>          changed by n, which can be negative
+     * watch <addr>         watch an address, with the width of the search
+     * freeze <addr> [val]  freeze an address, at its current value by default
+     * unwatch <addr>       stop watching or freezing it
+     *
+     * Addresses are in hexadecimal, values in decimal or hexadecimal with a $ or 0x prefix.
+     */
+    pub fn command(&mut self, bus :&mut Bus, line :&str) -> Result<String, String> {
+        let words :Vec<&str> = line.split_whitespace().collect();
+        let arg = |i :usize| words.get(i).copied().ok_or(format!("missing argument to {}", words[0]));
+
+        if words.is_empty() {
+            return Err("empty command".to_string());
+        }
+
+        let compare = match words[0] {
+            "eq" => Some(Compare::Eq),
+            "ne" => Some(Compare::Ne),
+            "gt" => Some(Compare::Gt),
+            "lt" => Some(Compare::Lt),
+            _ => None
+        };
+
+        let filter = match (words[0], compare) {
+            ("new", _) => {
+                let width = match words.get(1).copied() {
+                    None | Some("8") => Width::Byte,
+                    Some("16") => Width::Word,
+                    Some(width) => return Err(format!("bad width {}, 8 or 16", width))
+                };
+                self.start(bus, width);
+                return Ok(format!("{} candidates", self.candidates.len()));
+            },
+            (_, Some(compare)) => match words.get(1) {
+                Some(val) => Filter::Value(compare, parse_value(val)?),
+                None => Filter::Previous(compare)
+            },
+            ("changed", _) => {
+                let n = arg(1)?;
+                let n = match n.strip_prefix('-') {
+                    Some(n) => -(parse_value(n)? as i32),
+                    None => parse_value(n.trim_start_matches('+'))? as i32
+                };
+                Filter::ChangedBy(n)
+            },
+            ("watch", _) => {
+                let addr = parse_addr(arg(1)?)?;
+                self.watch(addr, self.width);
+                return Ok(format!("Watching {:04X}", addr));
+            },
+            ("freeze", _) => {
+                let addr = parse_addr(arg(1)?)?;
+                let val = match words.get(2) {
+                    Some(val) => parse_value(val)?,
+                    None => self.width.read(bus, addr)
+                };
+                self.freeze(bus, addr, self.width, val);
+                return Ok(format!("Froze {:04X} at {}", addr, val & self.width.mask()));
+            },
+            ("unwatch", _) => {
+                let addr = parse_addr(arg(1)?)?;
+                self.unwatch(bus, addr);
+                return Ok(format!("Stopped watching {:04X}", addr));
+            },
+            (command, _) => return Err(format!("unknown command {}", command))
+        };
+
+        if self.candidates.is_empty() {
+            return Err("no candidates, start a search with new".to_string());
+        }
+        return Ok(format!("{} candidates", self.filter(bus, filter)));
+    }
+
+    /* Text of the search pane */
+    pub fn summary(&self, bus :&Bus) -> String {
+        let bits = match self.width { Width::Byte => 8, Width::Word => 16 };
+        let mut text = format!("{} candidates ({}-bit)\n", self.candidates.len(), bits);
+
+        for candidate in self.candidates.iter().take(MAX_SHOWN_CANDIDATES) {
+            text += &format!("  {:04X}  {:>5}  was {}\n",
+                candidate.addr, self.width.read(bus, candidate.addr), candidate.val);
+        }
+        if self.candidates.len() > MAX_SHOWN_CANDIDATES {
+            text += "  ...\n";
+        }
+
+        if !self.watches.is_empty() {
+            text += "\nWatches\n";
+        }
+        for watch in self.watches.iter() {
+            text += &format!("  {:04X}  {:>5}  {}\n",
+                watch.addr, watch.width.read(bus, watch.addr), if watch.frozen.is_some() {"frozen"} else {""});
+        }
+
+        return text;
+    }
+}
@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::consts::*;
+use crate::debugger::expr::parse_addr;
+
+/*
+ * Symbol files of RGBDS (.sym) and no$gmb, to show labels in the disassembly.
+ *
+ * Both have a label per line, after its bank and address in hexadecimal: `01:4A3F UpdateSprites`.
+ * Lines starting with ';' are comments. As every ROM bank is mapped at the same addresses,
+ * labels in 4000-7FFF are only shown for the bank mapped at the time.
+ */
+
+#[derive(Debug)]
+pub enum SymbolError {
+    Io(std::io::Error),
+    BadLine(usize, String), // Line number, from 1
+}
+
+impl fmt::Display for SymbolError {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SymbolError::Io(err) => write!(f, "I/O error: {}", err),
+            SymbolError::BadLine(n, line) => write!(f, "line {} is not `bank:addr label`: {}", n, line)
+        }
+    }
+}
+
+impl From<std::io::Error> for SymbolError {
+    fn from(err :std::io::Error) -> SymbolError {
+        return SymbolError::Io(err);
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Symbol {
+    pub bank :u16,
+    pub addr :u16,
+    pub name :String,
+}
+
+#[derive(Default)]
+pub struct Symbols {
+    by_name :HashMap<String, Symbol>,
+    by_addr :HashMap<u16, Vec<Symbol>>, // In the order of the file
+}
+
+impl Symbols {
+    pub fn new() -> Symbols {
+        return Symbols { by_name: HashMap::new(), by_addr: HashMap::new() };
+    }
+
+    pub fn load(path :impl AsRef<Path>) -> Result<Symbols, SymbolError> {
+        return Symbols::parse(&std::fs::read_to_string(path)?);
+    }
+
+    pub fn parse(text :&str) -> Result<Symbols, SymbolError> {
+        let mut symbols = Symbols::new();
+
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split(';').next().unwrap().trim();
+            if line.is_empty() {
+                continue;
+            }
+
+            let bad_line = || SymbolError::BadLine(i + 1, line.to_string());
+            let (location, name) = line.split_once(char::is_whitespace).ok_or_else(bad_line)?;
+            let (bank, addr) = location.split_once(':').ok_or_else(bad_line)?;
+
+            symbols.add(Symbol {
+                bank: u16::from_str_radix(bank, 16).map_err(|_| bad_line())?,
+                addr: u16::from_str_radix(addr, 16).map_err(|_| bad_line())?,
+                name: name.trim().to_string(),
+            });
+        }
+
+        return Ok(symbols);
+    }
+
+    pub fn add(&mut self, symbol :Symbol) {
+        self.by_addr.entry(symbol.addr).or_default().push(symbol.clone());
+        self.by_name.insert(symbol.name.clone(), symbol);
+    }
+
+    pub fn len(&self) -> usize { return self.by_name.len(); }
+    pub fn is_empty(&self) -> bool { return self.by_name.is_empty(); }
+
+    pub fn get(&self, name :&str) -> Option<&Symbol> {
+        return self.by_name.get(name);
+    }
+
+    /* A label with its bank, or an address in hexadecimal */
+    pub fn resolve(&self, s :&str) -> Result<(Option<u16>, u16), String> {
+        return match self.get(s) {
+            Some(symbol) => Ok((Some(symbol.bank), symbol.addr)),
+            None => parse_addr(s).map(|addr| (None, addr)).map_err(|_| format!("no label or address {}", s))
+        };
+    }
+
+    /* Label of an address in a given bank */
+    pub fn label(&self, bank :u16, addr :u16) -> Option<&str> {
+        return self.by_addr.get(&addr)?.iter()
+            .find(|symbol| symbol.bank == bank)
+            .map(|symbol| symbol.name.as_str());
+    }
+
+    /* Closest label at or before an address in a bank, with the offset from it */
+    pub fn label_before(&self, bank :u16, addr :u16) -> Option<(&str, u16)> {
+        return self.by_addr.values().flatten()
+            .filter(|symbol| symbol.bank == bank && symbol.addr <= addr)
+            .max_by_key(|symbol| symbol.addr)
+            .map(|symbol| (symbol.name.as_str(), addr - symbol.addr));
+    }
+
+    /*
+     * Label of an address as the CPU sees it now. In the ROM it has to be in the mapped bank,
+     * elsewhere the first one is used, the banks of the RAM not being followed.
+     */
+    pub fn label_at(&self, bus :&Bus, addr :u16) -> Option<&str> {
+        if addr <= BANK1_END {
+            return self.label(bus.rom_bank(addr), addr);
+        }
+        return self.by_addr.get(&addr)?.first().map(|symbol| symbol.name.as_str());
+    }
+}
@@ -1,16 +1,20 @@
 use std::{collections::VecDeque, io};
 use std::cell::Ref;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 use tui::{
     backend::CrosstermBackend,
     widgets::{List, Block, Borders, ListItem, ListState, Paragraph},
     layout::Rect,
-    style::{Style, Modifier},
+    style::{Style, Modifier, Color},
+    text::{Span, Spans},
     Terminal
 };
 
 use crossterm::{
-    event::{DisableMouseCapture, EnableMouseCapture, read, Event, KeyCode},
+    event::{DisableMouseCapture, EnableMouseCapture, poll, read, Event, KeyCode},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
@@ -18,15 +22,58 @@ use crossterm::{
 use crate::cpu::CPU;
 use crate::bus::Bus;
 use crate::debugger::Instruction;
+use crate::debugger::search::RamSearch;
+use crate::debugger::breakpoints::Breakpoints;
+use crate::debugger::console::Console;
+use crate::debugger::memview::{MemoryView, MemoryInput};
+use crate::debugger::video::{self, Image, VideoView, VideoInput};
 use crate::consts::*;
 
 pub type TerminalCrossterm = Terminal<CrosstermBackend<io::Stdout>>;
 
-/* Builds the instruction dissasembly widget */
-fn build_instrs_list(instrs :&Vec<Instruction>) -> (List, ListState) {
+/* What the TUI shows */
+pub struct View<'a> {
+    pub instrs      :&'a Vec<Instruction>,
+    pub last_instrs :&'a VecDeque<Instruction>,
+    pub cpu         :&'a CPU,
+    pub bus         :&'a Ref<'a, Bus>,
+    pub search      :&'a RamSearch,
+    pub breakpoints :&'a Breakpoints,
+    pub console     :&'a Console,
+    pub memory      :&'a MemoryView,
+    pub video       :VideoView,
+    pub video_image :&'a Image,   // Of the video view
+}
+
+/* What the user asked for */
+pub enum Input {
+    Step(u16),              // Run n instructions before stopping again
+    Continue,
+    StepOver,
+    StepOut,
+    RunTo(u16),
+    ToggleBreakpoint(u16),
+    Command(String),        // Debugger command
+    Search(String),         // RAM search command
+    Memory(MemoryInput),    // In the memory pane
+    Video(VideoInput),      // In the video pane
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PromptKind { Command, Search, Goto, Export }
+
+/* Builds the instruction dissasembly widget, with the breakpoints marked */
+fn build_instrs_list<'a>(instrs :&[Instruction], breakpoints :&Breakpoints, cursor :usize) -> (List<'a>, ListState) {
     let list_items = instrs
         .iter()
-        .map(|instr| ListItem::new(format!("{}", instr)))
+        .map(|instr| {
+            let mark = if breakpoints.has_breakpoint(instr.get_pos()) {"*"} else {" "};
+            let line = format!("{}{}", mark, instr);
+            ListItem::new(match instr.get_label() {
+                Some(label) => format!(" {}:\n{}", label, line),
+                None => line
+            })
+        })
         .collect::<Vec<ListItem>>();
 
     let list = List::new(list_items)
@@ -38,13 +85,13 @@ fn build_instrs_list(instrs :&Vec<Instruction>) -> (List, ListState) {
         .highlight_symbol(">>");
 
     let mut state = ListState::default();
-    state.select(Some(0));
+    state.select(Some(cursor));
 
     return (list, state);
 }
 
 /* Builds the last executed instructions widget */
-fn build_lastinstrs_list(instrs :&VecDeque<Instruction>) -> List {
+fn build_lastinstrs_list<'a>(instrs :&VecDeque<Instruction>) -> List<'a> {
     let instrs = instrs.clone();
 
     let list_items = instrs
@@ -65,7 +112,7 @@ fn build_lastinstrs_list(instrs :&VecDeque<Instruction>) -> List {
 
 
 /* Builds the cpu state widget */
-fn build_cpustate_text<'a>(cpu :&'a CPU, bus :&'a Ref<Bus>) -> Paragraph<'a> {
+fn build_cpustate_text<'a>(cpu :&CPU, bus :&Bus) -> Paragraph<'a> {
     let pc :u16 = if cpu.get_pc() == 0 {
         0
     } else {
@@ -85,8 +132,8 @@ fn build_cpustate_text<'a>(cpu :&'a CPU, bus :&'a Ref<Bus>) -> Paragraph<'a> {
         _ => ""
     };
 
-    let timer_freq = [1024, 16, 64, 256][(bus.read(ADDR_TAC) & 3) as usize];
-    let is_timer_enabled = (bus.read(ADDR_TAC) >> 2) & 1 == 1;
+    let timer_freq = [1024, 16, 64, 256][(bus.peek(ADDR_TAC) & 3) as usize];
+    let is_timer_enabled = (bus.peek(ADDR_TAC) >> 2) & 1 == 1;
 
     let pc :u16 = if cpu.get_pc() == 0 {
         0
@@ -115,14 +162,14 @@ fn build_cpustate_text<'a>(cpu :&'a CPU, bus :&'a Ref<Bus>) -> Paragraph<'a> {
     timer {}/{}  {}
     div   {:04X}
 
-    ", pc,                  cpu.get_sp(),           ((cpu.read(cpu.get_sp()+1) as u16)<<8) | cpu.read(cpu.get_sp()) as u16,
+    ", pc,                  cpu.get_sp(),           ((bus.peek(cpu.get_sp()+1) as u16)<<8) | bus.peek(cpu.get_sp()) as u16,
        cpu.reg(REG_A),      cpu.reg(REG_F),         cpu.reg16(REG_A, REG_F),
        cpu.reg(REG_B),      cpu.reg(REG_C),         cpu.reg16(REG_B, REG_C),
        cpu.reg(REG_D),      cpu.reg(REG_E),         cpu.reg16(REG_D, REG_E),
        cpu.reg(REG_H),      cpu.reg(REG_L),         cpu.reg16(REG_H, REG_L),
        cpu.flag_z(),        cpu.get_ime(),
-       cpu.flag_h(),        bus.read(ADDR_IE)&0x1f,
-       cpu.flag_n(),        bus.read(ADDR_IF)&0x1f,
+       cpu.flag_h(),        bus.peek(ADDR_IE)&0x1f,
+       cpu.flag_n(),        bus.peek(ADDR_IF)&0x1f,
        cpu.flag_c(),        int,
        bus.timer_counter(), timer_freq, if is_timer_enabled {"ON"} else {"OFF"},
        bus.div_counter(),
@@ -138,7 +185,7 @@ fn build_cpustate_text<'a>(cpu :&'a CPU, bus :&'a Ref<Bus>) -> Paragraph<'a> {
 }
 
 /* Builds the hardware register widget */
-fn build_hwreg_text<'a>(bus :&'a Ref<'a, Bus>) -> Paragraph<'a> {
+fn build_hwreg_text<'a>(bus :&Bus) -> Paragraph<'a> {
     let text = format!("
     Timer registers
 
@@ -165,19 +212,19 @@ fn build_hwreg_text<'a>(bus :&'a Ref<'a, Bus>) -> Paragraph<'a> {
     DMA register
 
     DMA  {:02X}
-    ",  bus.read(ADDR_DIV),  bus.read(ADDR_TIMA), bus.read(ADDR_TMA),
-        bus.read(ADDR_TAC),  bus.read(ADDR_LCDC), bus.read(ADDR_STAT),
-        bus.read(ADDR_SCY),  bus.read(ADDR_SCX),  bus.read(ADDR_LY),
-        bus.read(ADDR_LYC),  bus.read(ADDR_WY),   bus.read(ADDR_WX),
-        bus.read(ADDR_BGP),  bus.read(ADDR_OBP0), bus.read(ADDR_OBP1),
-        bus.read(ADDR_NR11), bus.read(ADDR_NR12), bus.read(ADDR_NR13),
-        bus.read(ADDR_NR14), bus.read(ADDR_NR21), bus.read(ADDR_NR22),
-        bus.read(ADDR_NR23), bus.read(ADDR_NR24), bus.read(ADDR_NR30),
-        bus.read(ADDR_NR31), bus.read(ADDR_NR32), bus.read(ADDR_NR33),
-        bus.read(ADDR_NR34), bus.read(ADDR_NR41), bus.read(ADDR_NR42),
-        bus.read(ADDR_NR43), bus.read(ADDR_NR44), bus.read(ADDR_NR50),
-        bus.read(ADDR_NR51), bus.read(ADDR_NR52),
-        bus.read(ADDR_P1),   bus.read(ADDR_DMA)
+    ",  bus.peek(ADDR_DIV),  bus.peek(ADDR_TIMA), bus.peek(ADDR_TMA),
+        bus.peek(ADDR_TAC),  bus.peek(ADDR_LCDC), bus.peek(ADDR_STAT),
+        bus.peek(ADDR_SCY),  bus.peek(ADDR_SCX),  bus.peek(ADDR_LY),
+        bus.peek(ADDR_LYC),  bus.peek(ADDR_WY),   bus.peek(ADDR_WX),
+        bus.peek(ADDR_BGP),  bus.peek(ADDR_OBP0), bus.peek(ADDR_OBP1),
+        bus.peek(ADDR_NR11), bus.peek(ADDR_NR12), bus.peek(ADDR_NR13),
+        bus.peek(ADDR_NR14), bus.peek(ADDR_NR21), bus.peek(ADDR_NR22),
+        bus.peek(ADDR_NR23), bus.peek(ADDR_NR24), bus.peek(ADDR_NR30),
+        bus.peek(ADDR_NR31), bus.peek(ADDR_NR32), bus.peek(ADDR_NR33),
+        bus.peek(ADDR_NR34), bus.peek(ADDR_NR41), bus.peek(ADDR_NR42),
+        bus.peek(ADDR_NR43), bus.peek(ADDR_NR44), bus.peek(ADDR_NR50),
+        bus.peek(ADDR_NR51), bus.peek(ADDR_NR52),
+        bus.peek(ADDR_P1),   bus.peek(ADDR_DMA)
     );
 
     let text_state = Paragraph::new(text)
@@ -189,9 +236,167 @@ fn build_hwreg_text<'a>(bus :&'a Ref<'a, Bus>) -> Paragraph<'a> {
     return text_state;
 }
 
+/* Builds the breakpoints and watchpoints widget */
+fn build_breakpoints_list<'a>(breakpoints :&Breakpoints, bus :&Bus) -> List<'a> {
+    let breakpoints = breakpoints.list().iter().enumerate()
+        .map(|(i, b)| format!("{:>3} {}{}", i, b.text, if b.is_enabled {""} else {" (disabled)"}));
+    let watchpoints = bus.watchpoints().list().iter().enumerate()
+        .map(|(i, w)| format!("{:>3} {}", format!("w{}", i), w));
+
+    let list_items = breakpoints.chain(watchpoints)
+        .map(ListItem::new)
+        .collect::<Vec<ListItem>>();
+
+    return List::new(list_items)
+        .block( Block::default()
+            .title("Breakpoints")
+            .borders(Borders::ALL)
+        );
+}
+
+/* Builds the console widget, with the last lines that fit and the prompt */
+fn build_console_text<'a>(console :&Console, prompt :&str, height :u16) -> Paragraph<'a> {
+    let n_lines = (height as usize).saturating_sub(3); // Borders and prompt
+    let output = console.output();
+
+    let mut text = output.iter().skip(output.len().saturating_sub(n_lines))
+        .cloned()
+        .collect::<Vec<String>>()
+        .join("\n");
+    text += &format!("\n{}", prompt);
+
+    return Paragraph::new(text)
+        .block( Block::default()
+            .title("Console")
+            .borders(Borders::ALL)
+        );
+}
+
+/* Bytes per line of the memory pane that fit in a width, with the borders */
+fn fit_memory_line_len(width :u16) -> u16 {
+    // "C000  " then "00 " and a character per byte
+    return match width.saturating_sub(2 + 7) / 4 {
+        16.. => 16,
+        8..=15 => 8,
+        _ => 4
+    };
+}
+
+/* Builds the memory widget, with the changed bytes and the selected one highlighted */
+fn build_memory_text<'a>(memory :&MemoryView, bus :&Bus, width :u16, height :u16, is_focused :bool) -> Paragraph<'a> {
+    let line_len = fit_memory_line_len(width);
+
+    let lines = memory.lines(bus, height.saturating_sub(2), line_len).into_iter()
+        .map(|line| {
+            let mut spans = vec![Span::raw(format!("{:04X}  ", line.addr))];
+
+            for (i, (val, is_changed)) in line.bytes.iter().enumerate() {
+                let addr = line.addr + i as u16;
+                let mut style = Style::default();
+                if *is_changed {
+                    style = style.fg(Color::Yellow).add_modifier(Modifier::BOLD);
+                }
+
+                let text = if addr == memory.cursor() && is_focused {
+                    style = style.add_modifier(Modifier::REVERSED);
+                    memory.high_digit().map_or(format!("{:02X}", val), |digit| format!("{:X}_", digit))
+                } else {
+                    format!("{:02X}", val)
+                };
+
+                spans.push(Span::styled(text, style));
+                spans.push(Span::raw(" "));
+            }
+
+            let chars :String = line.bytes.iter()
+                .map(|(val, _)| if val.is_ascii_graphic() || *val == b' ' { *val as char } else { '.' })
+                .collect();
+            spans.push(Span::raw(format!(" {}", chars)));
+
+            Spans::from(spans)
+        })
+        .collect::<Vec<Spans>>();
+
+    let title = format!("Memory: {}  {:04X}{}", memory.region(), memory.cursor(), if is_focused {"  [editing]"} else {""});
+    return Paragraph::new(lines)
+        .block( Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+        );
+}
+
+/*
+ * Builds the video widget, drawing two pixels per character with half blocks. Images too large
+ * are shrunk, keeping a pixel out of n.
+ */
+fn build_video_image<'a>(image :&Image, view :VideoView, width :u16, height :u16, is_focused :bool) -> Paragraph<'a> {
+    let (cols, rows) = (width.saturating_sub(2).max(1) as usize, height.saturating_sub(2).max(1) as usize);
+    let step = image.width.div_ceil(cols).max(image.height.div_ceil(2*rows)).max(1);
+    let color = |(r, g, b) :(u8, u8, u8)| Color::Rgb(r, g, b);
+
+    let lines = (0..image.height.div_ceil(2*step))
+        .map(|row| {
+            let (top, bottom) = (2*row*step, (2*row + 1)*step);
+            let spans = (0..image.width.div_ceil(step))
+                .map(|col| {
+                    let mut style = Style::default().fg(color(image.pixel(col*step, top)));
+                    if bottom < image.height {
+                        style = style.bg(color(image.pixel(col*step, bottom)));
+                    }
+                    Span::styled("\u{2580}", style)
+                })
+                .collect::<Vec<Span>>();
+
+            Spans::from(spans)
+        })
+        .collect::<Vec<Spans>>();
+
+    let scale = if step > 1 {format!("  1:{}", step)} else {String::new()};
+    let title = format!("Video: {}{}{}", view, scale, if is_focused {"  [Tab: next view, W: window, E: export]"} else {""});
+    return Paragraph::new(lines)
+        .block( Block::default()
+            .title(title)
+            .borders(Borders::ALL)
+        );
+}
+
+/* Builds the list of the OAM entries */
+fn build_objects_list<'a>(bus :&Bus) -> List<'a> {
+    let list_items = video::object_list(bus).into_iter()
+        .map(ListItem::new)
+        .collect::<Vec<ListItem>>();
+
+    return List::new(list_items)
+        .block( Block::default()
+            .title("Objects")
+            .borders(Borders::ALL)
+        );
+}
+
+/* Builds the RAM search widget */
+fn build_search_text<'a>(search :&RamSearch, bus :&Bus) -> Paragraph<'a> {
+    return Paragraph::new(search.summary(bus))
+        .block( Block::default()
+            .title("RAM Search")
+            .borders(Borders::ALL)
+        );
+}
+
+// Rows of the console, at the bottom
+const CONSOLE_HEIGHT :u16 = 10;
+
 pub struct DebuggerTUI {
     terminal :TerminalCrossterm,
-    is_done :bool
+    is_done :bool,
+    prompt  :Option<(PromptKind, String)>, // Command being typed
+    history_pos :Option<usize>, // Command of the history shown in the prompt
+    cursor  :usize,          // Selected instruction
+    is_memory_shown   :bool, // Instead of the hardware registers
+    is_memory_focused :bool, // Keys go to the memory pane
+    memory_line_len   :u16,  // Bytes per line of the memory pane, as last rendered
+    is_video_shown    :bool, // Instead of the CPU state and what is on its right
+    is_video_focused  :bool, // Keys go to the video pane
+    quit_signal :Option<Arc<AtomicBool>>, // Set by the SIGINT/SIGTERM handler
 }
 
 impl DebuggerTUI {
@@ -201,88 +406,305 @@ impl DebuggerTUI {
 
         return DebuggerTUI {
             terminal: Terminal::new(backend).unwrap(),
-            is_done: false
+            is_done: false,
+            prompt: None,
+            history_pos: None,
+            cursor: 0,
+            is_memory_shown: false,
+            is_memory_focused: false,
+            memory_line_len: 16,
+            is_video_shown: false,
+            is_video_focused: false,
+            quit_signal: None,
         };
     }
 
+    /* Stop waiting for input and close once `flag` is set */
+    pub fn set_quit_signal(&mut self, flag :Option<Arc<AtomicBool>>) { self.quit_signal = flag; }
+
     /* Returns whether the TUI has finished running */
     pub fn is_done(&self) -> bool { return self.is_done; }
 
+    /* Back to the instruction about to run */
+    pub fn reset_cursor(&mut self) { self.cursor = 0; }
+
     /* Render screen and read user input */
-    pub fn update(&mut self,
-        instrs      :&Vec<Instruction>,
-        last_instrs :&VecDeque<Instruction>,
-        cpu :&CPU,
-        bus :&Ref<Bus>
-    ) -> u16 {
-        self.render(instrs, last_instrs, cpu, bus);
-        return self.read_input(instrs, last_instrs, cpu, bus);
+    pub fn update(&mut self, view :&View) -> Input {
+        self.render(view);
+        return self.read_input(view);
     }
 
     /* Builds and renders all screen widgets */
-    fn render(&mut self,
-        instrs      :&Vec<Instruction>,
-        last_instrs :&VecDeque<Instruction>,
-        cpu :&CPU,
-        bus :&Ref<Bus>
-    ) {
+    fn render(&mut self, view :&View) {
+        let prompt = match &self.prompt {
+            Some((PromptKind::Command, line)) => format!(": {}_", line),
+            Some((PromptKind::Search, line)) => format!("search> {}_", line),
+            Some((PromptKind::Goto, line)) => format!("go to> {}_", line),
+            Some((PromptKind::Export, line)) => format!("export to PNG> {}_", line),
+            None => String::new()
+        };
+        let cursor = self.cursor;
+        let (is_memory_shown, is_memory_focused) = (self.is_memory_shown, self.is_memory_focused);
+        let mut memory_line_len = self.memory_line_len;
+        let (is_video_shown, is_video_focused) = (self.is_video_shown, self.is_video_focused);
+
         self.terminal.draw( |f| {
             let size = f.size();
+            let bus :&Bus = view.bus;
+
+            // Height of the columns above the console
+            let console_height = CONSOLE_HEIGHT.min(size.height);
+            let height = size.height - console_height;
 
-            let (list_instrs, mut state_instrs) = build_instrs_list(instrs);
-            let list_lastinstrs = build_lastinstrs_list(last_instrs);
-            let text_state = build_cpustate_text(cpu, bus);
+            let (list_instrs, mut state_instrs) = build_instrs_list(view.instrs, view.breakpoints, cursor);
+            let list_lastinstrs = build_lastinstrs_list(view.last_instrs);
+            let text_state = build_cpustate_text(view.cpu, bus);
             let text_reg   = build_hwreg_text(bus);
             
             f.render_stateful_widget(list_instrs,
-                Rect::new(0, 0, size.width/3, size.height),
+                Rect::new(0, 0, size.width/3, height),
                 &mut state_instrs
             );
+
+            // The video takes the two other columns, with the list of the objects on the right
+            if is_video_shown {
+                let video_width = size.width - size.width/3;
+                let image_width = if view.video == VideoView::Objects {
+                    f.render_widget(build_objects_list(bus),
+                        Rect::new(2*size.width/3, 0, size.width - 2*size.width/3, height)
+                    );
+                    2*size.width/3 - size.width/3
+                } else {
+                    video_width
+                };
+
+                f.render_widget(build_video_image(view.video_image, view.video, image_width, height, is_video_focused),
+                    Rect::new(size.width/3, 0, image_width, height)
+                );
+                f.render_widget(build_console_text(view.console, &prompt, console_height),
+                    Rect::new(0, height, size.width, console_height)
+                );
+                return;
+            }
+
             f.render_widget(text_state,
-                Rect::new(size.width/3, 0, size.width/3, size.height/2)
+                Rect::new(size.width/3, 0, size.width/3, height/2)
             );
-            f.render_widget(list_lastinstrs,
-                Rect::new(size.width/3, size.height - size.height/2, size.width/3, size.height/2)
-            );
-            f.render_widget(text_reg,
-                Rect::new(2*size.width/3, 0, size.width/3, size.height)
+
+            // The breakpoints share the middle column with the last instructions
+            if view.breakpoints.list().is_empty() && bus.watchpoints().list().is_empty() {
+                f.render_widget(list_lastinstrs,
+                    Rect::new(size.width/3, height - height/2, size.width/3, height/2)
+                );
+            } else {
+                f.render_widget(list_lastinstrs,
+                    Rect::new(size.width/3, height/2, size.width/3, height/4)
+                );
+                f.render_widget(build_breakpoints_list(view.breakpoints, bus),
+                    Rect::new(size.width/3, height/2 + height/4, size.width/3, height - height/2 - height/4)
+                );
+            }
+
+            // The search shares the last column once started
+            let top_height = if view.search.candidates().is_empty() && view.search.watches().is_empty() {
+                height
+            } else {
+                f.render_widget(build_search_text(view.search, bus),
+                    Rect::new(2*size.width/3, height/2, size.width/3, height - height/2)
+                );
+                height/2
+            };
+
+            // The memory replaces the hardware registers when shown
+            if is_memory_shown {
+                memory_line_len = fit_memory_line_len(size.width/3);
+                f.render_widget(build_memory_text(view.memory, bus, size.width/3, top_height, is_memory_focused),
+                    Rect::new(2*size.width/3, 0, size.width/3, top_height)
+                );
+            } else {
+                f.render_widget(text_reg,
+                    Rect::new(2*size.width/3, 0, size.width/3, top_height)
+                );
+            }
+
+            f.render_widget(build_console_text(view.console, &prompt, console_height),
+                Rect::new(0, height, size.width, console_height)
             );
         }).unwrap();
+
+        self.memory_line_len = memory_line_len;
     }
 
     /*
      * Blocks the debugger until it receives input.
-     * Returns number of cycles to skip.
+     * Returns what to run next, or the command typed.
      */
-    fn read_input(&mut self,
-        instrs      :&Vec<Instruction>,
-        last_instrs :&VecDeque<Instruction>,
-        cpu :&CPU,
-        bus :&Ref<Bus>
-    ) -> u16 {
+    fn read_input(&mut self, view :&View) -> Input {
         loop {
+            // Check for a quit signal while waiting
+            if !poll(Duration::from_millis(100)).expect("Failed to poll events") {
+                if self.quit_signal.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed)) {
+                    self.close();
+                    return Input::Step(0);
+                }
+                continue;
+            }
+
             match read().expect("Failed to read event") {
+                // Typing a command
+                Event::Key(key_event) if self.prompt.is_some() => {
+                    let (kind, prompt) = self.prompt.as_mut().unwrap();
+                    let kind = *kind;
+
+                    let history = view.console.history();
+
+                    match key_event.code {
+                        KeyCode::Char(c) => prompt.push(c),
+                        KeyCode::Backspace => { prompt.pop(); },
+                        // Browse the commands typed before
+                        KeyCode::Up if kind == PromptKind::Command && !history.is_empty() => {
+                            let pos = self.history_pos.map_or(history.len() - 1, |pos| pos.saturating_sub(1));
+                            *prompt = history[pos].clone();
+                            self.history_pos = Some(pos);
+                        },
+                        KeyCode::Down if kind == PromptKind::Command => {
+                            self.history_pos = self.history_pos.map(|pos| pos + 1).filter(|pos| *pos < history.len());
+                            *prompt = self.history_pos.map_or(String::new(), |pos| history[pos].clone());
+                        },
+                        KeyCode::Esc => self.prompt = None,
+                        KeyCode::Enter => {
+                            self.history_pos = None;
+                            let (_, line) = self.prompt.take().unwrap();
+                            return match kind {
+                                PromptKind::Command => Input::Command(line),
+                                PromptKind::Search  => Input::Search(line),
+                                PromptKind::Goto    => Input::Memory(MemoryInput::Goto(line)),
+                                PromptKind::Export  => Input::Video(VideoInput::Export(line)),
+                            };
+                        },
+                        _ => {}
+                    }
+                    self.render(view);
+                },
+                // Moving around and editing the memory
+                Event::Key(key_event) if self.is_memory_focused => {
+                    let line_len = self.memory_line_len as i32;
+
+                    let input = match key_event.code {
+                        KeyCode::Left => MemoryInput::Move(-1),
+                        KeyCode::Right => MemoryInput::Move(1),
+                        KeyCode::Up => MemoryInput::Move(-line_len),
+                        KeyCode::Down => MemoryInput::Move(line_len),
+                        KeyCode::PageUp => MemoryInput::Move(-16*line_len),
+                        KeyCode::PageDown => MemoryInput::Move(16*line_len),
+                        KeyCode::Tab => MemoryInput::NextRegion,
+                        KeyCode::Char('[') => MemoryInput::Bank(-1),
+                        KeyCode::Char(']') => MemoryInput::Bank(1),
+                        KeyCode::Char(c) if c.is_ascii_hexdigit() => MemoryInput::Digit(c.to_digit(16).unwrap() as u8),
+                        KeyCode::Char('g') | KeyCode::Char('G') => {
+                            self.prompt = Some((PromptKind::Goto, String::new()));
+                            self.render(view);
+                            continue;
+                        },
+                        KeyCode::Char('m') | KeyCode::Char('M') => {
+                            self.is_memory_shown = false;
+                            self.is_memory_focused = false;
+                            self.render(view);
+                            continue;
+                        },
+                        KeyCode::Esc => {
+                            self.is_memory_focused = false;
+                            self.render(view);
+                            continue;
+                        },
+                        _ => continue
+                    };
+
+                    return Input::Memory(input);
+                },
+                // Switching between the views of the graphics
+                Event::Key(key_event) if self.is_video_focused => {
+                    let input = match key_event.code {
+                        KeyCode::Tab => VideoInput::NextView,
+                        KeyCode::Char('w') | KeyCode::Char('W') => VideoInput::ToggleWindow,
+                        KeyCode::Char('e') | KeyCode::Char('E') => {
+                            self.prompt = Some((PromptKind::Export, String::new()));
+                            self.render(view);
+                            continue;
+                        },
+                        KeyCode::Char('v') | KeyCode::Char('V') => {
+                            self.is_video_shown = false;
+                            self.is_video_focused = false;
+                            self.render(view);
+                            continue;
+                        },
+                        KeyCode::Esc => {
+                            self.is_video_focused = false;
+                            self.render(view);
+                            continue;
+                        },
+                        _ => continue
+                    };
+
+                    return Input::Video(input);
+                },
                 // Key input
                 Event::Key(key_event) => {
+                    let cursor_addr = view.instrs.get(self.cursor).map(|instr| instr.get_pos());
+
                     match key_event.code {
                         KeyCode::Char(c) => {
                             match c {
                                 // Quit
-                                'q' | 'Q' => {self.close(); return 0;},
+                                'q' | 'Q' => {self.close(); return Input::Step(0);},
                                 // Skip n instrs (-target instr)
-                                '1'       => return 10    - 1,
-                                '2'       => return 100   - 1,
-                                '3'       => return 1000  - 1,
-                                '4'       => return 10000 - 1,
+                                '1'       => return Input::Step(10    - 1),
+                                '2'       => return Input::Step(100   - 1),
+                                '3'       => return Input::Step(1000  - 1),
+                                '4'       => return Input::Step(10000 - 1),
+                                'c' | 'C' => return Input::Continue,
+                                'o' | 'O' => return Input::StepOver,
+                                'u' | 'U' => return Input::StepOut,
+                                // At the selected instruction
+                                'r' | 'R' => if let Some(addr) = cursor_addr { return Input::RunTo(addr); },
+                                'b' | 'B' => if let Some(addr) = cursor_addr { return Input::ToggleBreakpoint(addr); },
+                                // Commands
+                                ':' => {
+                                    self.prompt = Some((PromptKind::Command, String::new()));
+                                    self.render(view);
+                                },
+                                's' | 'S' => {
+                                    self.prompt = Some((PromptKind::Search, String::new()));
+                                    self.render(view);
+                                },
+                                'm' | 'M' => {
+                                    self.is_memory_shown = true;
+                                    self.is_memory_focused = true;
+                                    self.is_video_shown = false;
+                                    self.render(view);
+                                },
+                                'v' | 'V' => {
+                                    self.is_video_shown = true;
+                                    self.is_video_focused = true;
+                                    self.render(view);
+                                },
                                 _         => {}
                             }
                         },
-                        KeyCode::Enter => return 0,
+                        KeyCode::Up => {
+                            self.cursor = self.cursor.saturating_sub(1);
+                            self.render(view);
+                        },
+                        KeyCode::Down => {
+                            self.cursor = (self.cursor + 1).min(view.instrs.len().saturating_sub(1));
+                            self.render(view);
+                        },
+                        KeyCode::Enter => return Input::Step(0),
                         _ => {}
                     }
                 },
                 // Re-render the screen on resize
-                Event::Resize(_w, _h) => self.render(instrs, last_instrs, cpu, bus),
+                Event::Resize(_w, _h) => self.render(view),
                 _  => {}
             }
         }
@@ -0,0 +1,308 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter};
+use std::path::Path;
+
+use crate::bus::Bus;
+use crate::consts::*;
+use crate::frontend::SHADES;
+
+/*
+ * Viewers of the graphics in the VRAM and the OAM, to look into rendering glitches.
+ *
+ * The tiles are shown 16 per row in the order of their index, with the 0x8000 (unsigned) or the
+ * 0x8800 (signed, index 0 at 0x9000) addressing, so that the index in a tilemap is where the tile
+ * is in the grid. The tilemaps use the addressing and the palette the PPU uses now, with the
+ * viewport at SCX/SCY and the window outlined. The objects are shown in the order of the OAM.
+ */
+
+const TILE_SIZE :usize = 16; // Bytes
+const TILEMAP_0 :u16 = 0x9800;
+const TILEMAP_1 :u16 = 0x9C00;
+
+const N_OBJECTS :usize = 40;
+const OBJECTS_PER_ROW :usize = 8;
+
+const VIEWPORT_COLOR :(u8, u8, u8) = (255, 0, 0);
+const WINDOW_COLOR   :(u8, u8, u8) = (0, 128, 255);
+const GRID_COLOR     :(u8, u8, u8) = (128, 128, 128);
+const BACKDROP_COLOR :(u8, u8, u8) = (192, 224, 224); // Transparent pixels of the objects
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum VideoView {
+    Tiles8000,
+    Tiles8800,
+    Tilemap9800,
+    Tilemap9C00,
+    Objects,
+}
+
+impl fmt::Display for VideoView {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            VideoView::Tiles8000 => write!(f, "Tiles 8000"),
+            VideoView::Tiles8800 => write!(f, "Tiles 8800"),
+            VideoView::Tilemap9800 => write!(f, "Tilemap 9800"),
+            VideoView::Tilemap9C00 => write!(f, "Tilemap 9C00"),
+            VideoView::Objects => write!(f, "OAM"),
+        }
+    }
+}
+
+impl VideoView {
+    /* `tiles [8000|8800]`, `map [9800|9C00]` or `oam` */
+    pub fn parse(s :&str) -> Result<VideoView, String> {
+        let words :Vec<String> = s.split_whitespace().map(|word| word.to_ascii_lowercase()).collect();
+        let words :Vec<&str> = words.iter().map(String::as_str).collect();
+
+        return match words[..] {
+            ["tiles"] | ["tiles", "8000"] => Ok(VideoView::Tiles8000),
+            ["tiles", "8800"] => Ok(VideoView::Tiles8800),
+            ["map"] | ["map", "9800"] => Ok(VideoView::Tilemap9800),
+            ["map", "9c00"] => Ok(VideoView::Tilemap9C00),
+            ["oam"] => Ok(VideoView::Objects),
+            _ => Err(format!("unknown view {}, tiles [8000|8800], map [9800|9C00] or oam", s))
+        };
+    }
+
+    /* Cycled through with Tab */
+    pub fn next(&self) -> VideoView {
+        return match self {
+            VideoView::Tiles8000 => VideoView::Tiles8800,
+            VideoView::Tiles8800 => VideoView::Tilemap9800,
+            VideoView::Tilemap9800 => VideoView::Tilemap9C00,
+            VideoView::Tilemap9C00 => VideoView::Objects,
+            VideoView::Objects => VideoView::Tiles8000,
+        }
+    }
+
+    pub fn render(&self, bus :&Bus) -> Image {
+        return match self {
+            VideoView::Tiles8000 => render_tiles(bus, true),
+            VideoView::Tiles8800 => render_tiles(bus, false),
+            VideoView::Tilemap9800 => render_tilemap(bus, TILEMAP_0),
+            VideoView::Tilemap9C00 => render_tilemap(bus, TILEMAP_1),
+            VideoView::Objects => render_objects(bus),
+        }
+    }
+}
+
+/* What is done in the video pane */
+#[derive(Debug, Clone, PartialEq)]
+pub enum VideoInput {
+    NextView,
+    ToggleWindow,   // Show the view in an SDL window too
+    Export(String), // To a PNG file
+}
+
+/* RGB image, a row after the other */
+pub struct Image {
+    pub width  :usize,
+    pub height :usize,
+    pub pixels :Vec<u8>, // 3 bytes per pixel
+}
+
+impl Image {
+    pub fn new(width :usize, height :usize, color :(u8, u8, u8)) -> Image {
+        let pixels = [color.0, color.1, color.2].repeat(width * height);
+        return Image { width, height, pixels };
+    }
+
+    pub fn pixel(&self, x :usize, y :usize) -> (u8, u8, u8) {
+        let i = 3 * (y * self.width + x);
+        return (self.pixels[i], self.pixels[i + 1], self.pixels[i + 2]);
+    }
+
+    pub fn set_pixel(&mut self, x :usize, y :usize, color :(u8, u8, u8)) {
+        let i = 3 * (y * self.width + x);
+        self.pixels[i..i + 3].copy_from_slice(&[color.0, color.1, color.2]);
+    }
+
+    pub fn save_png(&self, path :impl AsRef<Path>) -> io::Result<()> {
+        let file = BufWriter::new(File::create(path)?);
+        let mut encoder = png::Encoder::new(file, self.width as u32, self.height as u32);
+        encoder.set_color(png::ColorType::Rgb);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&self.pixels).map_err(io::Error::other)?;
+
+        return Ok(());
+    }
+}
+
+/* An entry of the OAM */
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Object {
+    pub y     :u8,
+    pub x     :u8,
+    pub tile  :u8,
+    pub attrs :u8,
+}
+
+impl Object {
+    pub fn is_behind_bg(&self) -> bool { return self.attrs & 0x80 != 0; }
+    pub fn is_y_flipped(&self) -> bool { return self.attrs & 0x40 != 0; }
+    pub fn is_x_flipped(&self) -> bool { return self.attrs & 0x20 != 0; }
+    pub fn is_obp1(&self) -> bool { return self.attrs & 0x10 != 0; }
+
+    /* Whether some of it is on the screen, with objects 8 or 16 pixels high */
+    pub fn is_visible(&self, height :u8) -> bool {
+        return self.x > 0 && self.x < 168 && self.y as u16 + height as u16 > 16 && self.y < 160;
+    }
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "X {:3}  Y {:3}  tile {:02X}  {}", self.x, self.y, self.tile, if self.is_obp1() {"OBP1"} else {"OBP0"})?;
+        if self.is_x_flipped() { write!(f, " x-flip")?; }
+        if self.is_y_flipped() { write!(f, " y-flip")?; }
+        if self.is_behind_bg() { write!(f, " behind BG")?; }
+        return Ok(());
+    }
+}
+
+/* The 40 entries of the OAM */
+pub fn objects(bus :&Bus) -> Vec<Object> {
+    return bus.oam().chunks(4).take(N_OBJECTS)
+        .map(|entry| Object { y: entry[0], x: entry[1], tile: entry[2], attrs: entry[3] })
+        .collect();
+}
+
+/* Height of the objects, from LCDC */
+pub fn object_height(bus :&Bus) -> u8 {
+    return if bus.peek(ADDR_LCDC) & 0x04 != 0 {16} else {8};
+}
+
+/* A line per object, with its index and whether it is off screen */
+pub fn object_list(bus :&Bus) -> Vec<String> {
+    let height = object_height(bus);
+
+    return objects(bus).iter().enumerate()
+        .map(|(i, obj)| format!("{:2}  {}{}", i, obj, if obj.is_visible(height) {""} else {" (hidden)"}))
+        .collect();
+}
+
+/* Save what a view shows to a PNG file */
+pub fn export(bus :&Bus, view :VideoView, path :&str) -> Result<String, String> {
+    view.render(bus).save_png(path).map_err(|err| format!("couldn't write {}: {}", path, err))?;
+    return Ok(format!("Saved the {} view to {}", view, path));
+}
+
+fn shade_color(palette :u8, color_id :u8) -> (u8, u8, u8) {
+    return SHADES[((palette >> (2 * color_id)) & 3) as usize];
+}
+
+/* Address of a tile from its index */
+fn tile_addr(index :u8, is_unsigned :bool) -> u16 {
+    return if is_unsigned {
+        0x8000 + index as u16 * TILE_SIZE as u16
+    } else {
+        (0x9000 + (index as i8 as i32) * TILE_SIZE as i32) as u16
+    };
+}
+
+/* Color id (0-3) of a pixel of a tile */
+fn tile_color_id(vram :&[u8], addr :u16, x :usize, y :usize) -> u8 {
+    let i = (addr - VRAM_START) as usize + 2 * y;
+    let bit = 7 - x;
+    return ((vram[i + 1] >> bit) & 1) << 1 | ((vram[i] >> bit) & 1);
+}
+
+fn draw_tile(image :&mut Image, vram :&[u8], addr :u16, left :usize, top :usize, palette :u8) {
+    for y in 0..8 {
+        for x in 0..8 {
+            image.set_pixel(left + x, top + y, shade_color(palette, tile_color_id(vram, addr, x, y)));
+        }
+    }
+}
+
+/* Border of a rectangle, wrapping around the image */
+fn draw_outline(image :&mut Image, left :usize, top :usize, width :usize, height :usize, color :(u8, u8, u8)) {
+    let (w, h) = (image.width, image.height);
+
+    for x in left..left + width {
+        image.set_pixel(x % w, top % h, color);
+        image.set_pixel(x % w, (top + height - 1) % h, color);
+    }
+    for y in top..top + height {
+        image.set_pixel(left % w, y % h, color);
+        image.set_pixel((left + width - 1) % w, y % h, color);
+    }
+}
+
+/* The 256 tiles with an addressing, in their own colors */
+fn render_tiles(bus :&Bus, is_unsigned :bool) -> Image {
+    let mut image = Image::new(16 * 8, 16 * 8, SHADES[0]);
+
+    for index in 0..=255u8 {
+        let (col, row) = (index as usize % 16, index as usize / 16);
+        draw_tile(&mut image, bus.vram(), tile_addr(index, is_unsigned), col * 8, row * 8, 0xE4);
+    }
+
+    return image;
+}
+
+fn render_tilemap(bus :&Bus, map_addr :u16) -> Image {
+    let lcdc = bus.peek(ADDR_LCDC);
+    let bgp = bus.peek(ADDR_BGP);
+    let is_unsigned = lcdc & 0x10 != 0;
+    let vram = bus.vram();
+
+    let mut image = Image::new(256, 256, SHADES[0]);
+    for i in 0..32 * 32 {
+        let index = vram[(map_addr - VRAM_START) as usize + i];
+        draw_tile(&mut image, vram, tile_addr(index, is_unsigned), (i % 32) * 8, (i / 32) * 8, bgp);
+    }
+
+    let bg_map = if lcdc & 0x08 != 0 {TILEMAP_1} else {TILEMAP_0};
+    if map_addr == bg_map {
+        let (scx, scy) = (bus.peek(ADDR_SCX) as usize, bus.peek(ADDR_SCY) as usize);
+        draw_outline(&mut image, scx, scy, SCREEN_WIDTH as usize, SCREEN_HEIGHT as usize, VIEWPORT_COLOR);
+    }
+
+    // The window starts at the top left of its map, and covers the screen from WX-7, WY
+    let window_map = if lcdc & 0x40 != 0 {TILEMAP_1} else {TILEMAP_0};
+    let (wx, wy) = (bus.peek(ADDR_WX) as usize, bus.peek(ADDR_WY) as usize);
+    if map_addr == window_map && lcdc & 0x20 != 0 && wx < SCREEN_WIDTH as usize + 7 && wy < SCREEN_HEIGHT as usize {
+        let width = SCREEN_WIDTH as usize - wx.saturating_sub(7);
+        draw_outline(&mut image, 0, 0, width, SCREEN_HEIGHT as usize - wy, WINDOW_COLOR);
+    }
+
+    return image;
+}
+
+/* 8 objects per row, in cells 16 pixels high separated by a grid */
+fn render_objects(bus :&Bus) -> Image {
+    let n_rows = N_OBJECTS / OBJECTS_PER_ROW;
+    let mut image = Image::new(OBJECTS_PER_ROW * 9 + 1, n_rows * 17 + 1, GRID_COLOR);
+    let height = object_height(bus) as usize;
+    let vram = bus.vram();
+
+    for (i, obj) in objects(bus).iter().enumerate() {
+        let (left, top) = (1 + (i % OBJECTS_PER_ROW) * 9, 1 + (i / OBJECTS_PER_ROW) * 17);
+        let palette = bus.peek(if obj.is_obp1() {ADDR_OBP1} else {ADDR_OBP0});
+        let tile = if height == 16 {obj.tile & 0xFE} else {obj.tile};
+
+        for y in 0..16 {
+            for x in 0..8 {
+                // The second tile of 8x16 objects follows the first
+                let color = if y < height {
+                    let tile_y = if obj.is_y_flipped() {height - 1 - y} else {y};
+                    let tile_x = if obj.is_x_flipped() {7 - x} else {x};
+                    let addr = tile_addr(tile, true) + (tile_y / 8 * TILE_SIZE) as u16;
+                    match tile_color_id(vram, addr, tile_x, tile_y % 8) {
+                        0 => BACKDROP_COLOR,
+                        color_id => shade_color(palette, color_id)
+                    }
+                } else {
+                    BACKDROP_COLOR
+                };
+
+                image.set_pixel(left + x, top + y, color);
+            }
+        }
+    }
+
+    return image;
+}
@@ -0,0 +1,99 @@
+/*
+ * Video, audio and input backends.
+ *
+ * The emulator core only talks to these traits, so it can run either with
+ * the SDL frontend (window, audio queue and gamepad) or fully headless.
+ */
+
+use crate::consts::*;
+
+pub mod headless;
+#[cfg(feature = "sdl")]
+pub mod sdl;
+
+// RGB value of each of the 4 DMG shades, from lightest to darkest
+pub const SHADES :[(u8, u8, u8);4] = [
+    (255, 255, 255),
+    (160, 160, 160),
+    (100, 100, 100),
+    (0,   0,   0)
+];
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Button {
+    A,
+    B,
+    Start,
+    Select,
+    Up,
+    Down,
+    Left,
+    Right
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputEvent {
+    Quit,
+    Press(Button),
+    Release(Button),
+    // Analog stick position, in the range -32768..32767
+    AxisX(i16),
+    AxisY(i16),
+    // Held while rewinding
+    Rewind(bool),
+    // Emulation speed
+    TogglePause,
+    FrameAdvance,
+    ToggleUncapped,
+    SpeedUp,
+    SpeedDown,
+    ToggleCheats,
+    // Save state slots
+    SaveState(u8),
+    LoadState(u8),
+}
+
+/*
+ Receives the frames produced by the PPU. The framebuffer is SCREEN_WIDTH*SCREEN_HEIGHT
+ bytes long, one shade index (0-3) per pixel with the palettes already applied.
+ */
+pub trait VideoSink {
+    fn init(&mut self);
+    fn clear(&mut self);
+    fn draw_frame(&mut self, framebuffer :&[u8]);
+    fn set_title_fps(&mut self, fps :u16);
+
+    // RGBA32 screenshot of the last drawn frame
+    fn get_pixels(&self) -> Vec<u8>;
+}
+
+/* Receives the samples mixed by the APU */
+pub trait AudioSink {
+    fn queue(&mut self, val_left :u8, val_right :u8, n_samples :u16);
+    fn pause(&mut self);
+    fn resume(&mut self);
+}
+
+/* Source of joypad and window events, polled once per frame */
+pub trait InputSource {
+    fn poll_events(&mut self) -> Vec<InputEvent>;
+}
+
+/* Scale a framebuffer of shade indexes to an RGBA32 pixel array */
+pub fn framebuffer_to_rgba(framebuffer :&[u8], screen_mult :u16) -> Vec<u8> {
+    let width  = SCREEN_WIDTH  * screen_mult;
+    let height = SCREEN_HEIGHT * screen_mult;
+    let mut pixels = Vec::with_capacity(width as usize * height as usize * 4);
+
+    for y in 0..height {
+        for x in 0..width {
+            let i = (y/screen_mult) as usize * SCREEN_WIDTH as usize
+                  + (x/screen_mult) as usize;
+            let (r, g, b) = SHADES[framebuffer[i] as usize];
+
+            pixels.extend_from_slice(&[r, g, b, 0xFF]);
+        }
+    }
+
+    return pixels;
+}
@@ -0,0 +1,64 @@
+use crate::consts::*;
+use crate::frontend::{VideoSink, AudioSink, InputSource, InputEvent, framebuffer_to_rgba};
+
+/*
+ Video sink which keeps the last frame in memory. The screen multiplier is only
+ used by get_pixels, so that screenshots match the ones taken from the SDL window.
+ */
+pub struct HeadlessVideo {
+    framebuffer :Vec<u8>,
+    screen_mult :u16,
+    frame_n     :u64
+}
+
+impl HeadlessVideo {
+    pub fn new(screen_mult :u8) -> HeadlessVideo {
+        return HeadlessVideo {
+            framebuffer: vec![0;(SCREEN_WIDTH*SCREEN_HEIGHT) as usize],
+            screen_mult: screen_mult as u16,
+            frame_n: 0
+        }
+    }
+
+    pub fn framebuffer(&self) -> &[u8] { return &self.framebuffer; }
+    pub fn frame_n(&self)     -> u64   { return self.frame_n; }
+}
+
+impl VideoSink for HeadlessVideo {
+    fn init(&mut self) {
+        self.clear();
+    }
+
+    fn clear(&mut self) {
+        self.framebuffer.iter_mut().for_each(|px| *px = 0);
+    }
+
+    fn draw_frame(&mut self, framebuffer :&[u8]) {
+        self.framebuffer.copy_from_slice(framebuffer);
+        self.frame_n += 1;
+    }
+
+    fn set_title_fps(&mut self, _fps :u16) {}
+
+    fn get_pixels(&self) -> Vec<u8> {
+        return framebuffer_to_rgba(&self.framebuffer, self.screen_mult);
+    }
+}
+
+/* Audio sink which discards every sample */
+pub struct NullAudio;
+
+impl AudioSink for NullAudio {
+    fn queue(&mut self, _val_left :u8, _val_right :u8, _n_samples :u16) {}
+    fn pause(&mut self) {}
+    fn resume(&mut self) {}
+}
+
+/* Input source which never produces any event */
+pub struct NullInput;
+
+impl InputSource for NullInput {
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        return vec![];
+    }
+}
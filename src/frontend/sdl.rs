@@ -0,0 +1,124 @@
+extern crate sdl2;
+use sdl2::event::Event;
+use sdl2::keyboard::{Keycode, Mod};
+use sdl2::controller::{Axis, GameController};
+
+use crate::frontend::{InputSource, InputEvent, Button};
+
+/* Keyboard and gamepad input read from the SDL event pump */
+pub struct SdlInput {
+    events :sdl2::EventPump,
+
+    // While the controller variable is not used, it works due to existing and being owned by
+    // an existing object.
+    #[allow(dead_code)]
+    controller :Option<GameController>,
+}
+
+impl SdlInput {
+    pub fn new(sdl_context :&sdl2::Sdl) -> SdlInput {
+        // The gamepad is optional
+        let controller = sdl_context.game_controller().ok()
+            .and_then(|subsystem| subsystem.open(0).ok());
+
+        return SdlInput {
+            events: sdl_context.event_pump().unwrap(),
+            controller,
+        }
+    }
+
+    fn keycode_button(keycode :Keycode) -> Option<Button> {
+        return match keycode {
+            Keycode::F => Some(Button::Start),
+            Keycode::G => Some(Button::Select),
+            Keycode::A => Some(Button::Left),
+            Keycode::S => Some(Button::Down),
+            Keycode::W => Some(Button::Up),
+            Keycode::D => Some(Button::Right),
+            Keycode::K => Some(Button::B),
+            Keycode::L => Some(Button::A),
+            _ => None
+        }
+    }
+
+    // F1-F8 select the save state slots 1-8
+    fn keycode_slot(keycode :Keycode) -> Option<u8> {
+        return match keycode {
+            Keycode::F1 => Some(1), Keycode::F2 => Some(2),
+            Keycode::F3 => Some(3), Keycode::F4 => Some(4),
+            Keycode::F5 => Some(5), Keycode::F6 => Some(6),
+            Keycode::F7 => Some(7), Keycode::F8 => Some(8),
+            _ => None
+        }
+    }
+
+    fn controller_button(button :sdl2::controller::Button) -> Option<Button> {
+        return match button {
+            sdl2::controller::Button::A     => Some(Button::A),
+            sdl2::controller::Button::X     => Some(Button::B),
+            sdl2::controller::Button::Start => Some(Button::Start),
+            sdl2::controller::Button::Back  => Some(Button::Select),
+            _ => None
+        }
+    }
+}
+
+impl InputSource for SdlInput {
+    fn poll_events(&mut self) -> Vec<InputEvent> {
+        let mut input_events = vec![];
+
+        for event in self.events.poll_iter() {
+            let input_event = match event {
+                Event::Quit {..} |
+                Event::KeyDown { keycode: Some(Keycode::Escape), .. }
+                    => Some(InputEvent::Quit),
+                Event::KeyDown { keycode: Some(Keycode::P), repeat: false, .. }
+                    => Some(InputEvent::TogglePause),
+                Event::KeyDown { keycode: Some(Keycode::N), .. }
+                    => Some(InputEvent::FrameAdvance),
+                Event::KeyDown { keycode: Some(Keycode::Tab), repeat: false, .. }
+                    => Some(InputEvent::ToggleUncapped),
+                Event::KeyDown { keycode: Some(Keycode::Equals | Keycode::KpPlus), .. }
+                    => Some(InputEvent::SpeedUp),
+                Event::KeyDown { keycode: Some(Keycode::Minus | Keycode::KpMinus), .. }
+                    => Some(InputEvent::SpeedDown),
+                Event::KeyDown { keycode: Some(Keycode::C), repeat: false, .. }
+                    => Some(InputEvent::ToggleCheats),
+                Event::KeyDown { keycode: Some(Keycode::Backspace), .. }
+                    => Some(InputEvent::Rewind(true)),
+                Event::KeyUp { keycode: Some(Keycode::Backspace), .. }
+                    => Some(InputEvent::Rewind(false)),
+                // Shift+F<n> saves to slot n, F<n> loads it
+                Event::KeyDown { keycode: Some(keycode), keymod, repeat: false, .. }
+                    if Self::keycode_slot(keycode).is_some() => {
+                    let slot = Self::keycode_slot(keycode).unwrap();
+
+                    if keymod.intersects(Mod::LSHIFTMOD | Mod::RSHIFTMOD) {
+                        Some(InputEvent::SaveState(slot))
+                    } else {
+                        Some(InputEvent::LoadState(slot))
+                    }
+                },
+                Event::KeyDown { keycode: Some(keycode), .. }
+                    => Self::keycode_button(keycode).map(InputEvent::Press),
+                Event::KeyUp { keycode: Some(keycode), .. }
+                    => Self::keycode_button(keycode).map(InputEvent::Release),
+                Event::ControllerButtonDown { button, .. }
+                    => Self::controller_button(button).map(InputEvent::Press),
+                Event::ControllerButtonUp { button, .. }
+                    => Self::controller_button(button).map(InputEvent::Release),
+                Event::ControllerAxisMotion { axis: Axis::LeftX, value, .. }
+                    => Some(InputEvent::AxisX(value)),
+                Event::ControllerAxisMotion { axis: Axis::LeftY, value, .. }
+                    => Some(InputEvent::AxisY(value)),
+                _ => None
+            };
+
+            if let Some(input_event) = input_event {
+                input_events.push(input_event);
+            }
+        }
+
+        return input_events;
+    }
+}
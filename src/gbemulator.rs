@@ -1,51 +1,123 @@
 use std::cell::RefCell;
+use std::path::Path;
 use std::rc::Rc;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
 
 use crate::cpu::CPU;
 use crate::ppu::PPU;
 use crate::apu::APU;
 use crate::bus::Bus;
+use crate::bus::recorder::BusRecorder;
 use crate::joypad::Joypad;
 use crate::interruptManager::InterruptManager;
-use crate::screen::Screen;
-
-extern crate sdl2;
-use sdl2::event::Event;
-use sdl2::keyboard::Keycode;
+use crate::frontend::{VideoSink, AudioSink, InputSource, InputEvent, Button};
+use crate::frontend::headless::{HeadlessVideo, NullAudio, NullInput};
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
+use crate::movie::{Movie, DEFAULT_HASH_INTERVAL};
+use crate::link::LinkEndpoint;
+use crate::cartridge::{self, LoadError, LoadOptions, load_rom, new_cartridge};
+use crate::cheats::CheatError;
+use crate::trace::{Tracer, TraceError};
+use crate::profiler::Profiler;
 
 mod clock;
+mod rewind;
 use clock::Clock;
+use rewind::Rewind;
+pub use rewind::DEFAULT_REWIND_BUDGET;
 
 // Global constants
-const TICKS_PER_FRAME :u32 = 69905; // 4194304 hz / 60fps/s
+const DOTS_PER_FRAME :u32 = 70224; // 154 lines * 456 dots, ~59.7275 Hz at 4194304 Hz
+const SPEEDS :[f64;7] = [0.25, 0.5, 1.0, 1.5, 2.0, 3.0, 4.0]; // Speed up/down hotkeys
+// The cartridge RAM is saved once the game stops writing to it for this long
+pub const SAVE_FLUSH_DELAY :Duration = Duration::from_secs(3);
 
 pub struct GBEmulator {
-//    path            : String,
+    path            : String,
+    rom_md5         : [u8;16],   // Identifies the ROM of the save states
     cpu             : CPU,
     bus             : Rc<RefCell<Bus>>,
     joypad          : Rc<RefCell<Joypad>>,
     clock           : Clock,
+    rewind          : Rewind,
+    is_rewinding    : bool,
+    is_paused       : bool,
+    is_frame_advance: bool,    // Run a single frame while paused
+    frame_cycles    : u32,     // T-cycles run by the last frame
+    power_on_state  : Vec<u8>, // State right after init, for resets
+
+    // Input movie
+    movie           : Option<Movie>,
+    is_movie_playing: bool,
+    movie_frame     : u32,
+    movie_desync    : Option<u32>, // First frame whose hash didn't match
+
+    tracer          : Option<Tracer>,
+    bus_log         : Option<String>, // Written on quit
+    profiler        : Option<(Profiler, String)>, // With the path of the report
 
-    screen          : Rc<RefCell<Screen>>,
+    // Battery save
+    ram_written_at  : Option<Instant>, // Last write to the external RAM not saved yet
+    save_flush_delay: Duration,
+    is_ram_detached : bool,            // The external RAM is a movie's, not the save's
 
-    #[allow(dead_code)]
-    controller      : sdl2::controller::GameController,
+    screen          : Rc<RefCell<dyn VideoSink>>,
+    // To open more windows, like the viewers of the debugger
+    #[cfg(feature = "sdl")]
+    sdl_context     : Option<sdl2::Sdl>,
 
     // Input
-    events :sdl2::EventPump,
-    is_quit :bool,
+    input       :Box<dyn InputSource>,
+    is_quit     :bool,
+    quit_signal :Option<Arc<AtomicBool>>,
 }
 
 impl GBEmulator {
-    pub fn new(rom_path: &str, screen_mult: u8) -> GBEmulator {
+    /* Emulator with an SDL window, audio queue and keyboard/gamepad input */
+    #[cfg(feature = "sdl")]
+    pub fn new(rom_path: &str, screen_mult: u8, options :&LoadOptions) -> Result<GBEmulator, LoadError> {
+        use crate::screen::Screen;
+        use crate::apu::audio::Audio;
+        use crate::frontend::sdl::SdlInput;
+
         let sdl_context = sdl2::init().unwrap();
 
         let screen = Rc::new(RefCell::new(
             Screen::new(&sdl_context, rom_path.to_string(), screen_mult)
         ));
+        let audio = Box::new(Audio::new(sdl_context.audio().unwrap()));
+        let input = Box::new(SdlInput::new(&sdl_context));
 
-        let audio = sdl_context.audio().unwrap();
-        let apu   = APU::new(audio);
+        let mut gbemu = GBEmulator::with_backends(rom_path, options, screen, audio, input)?;
+        gbemu.sdl_context = Some(sdl_context);
+        return Ok(gbemu);
+    }
+
+    /*
+     Emulator without display, audio or input devices. The screen multiplier only
+     scales the pixels returned by VideoSink::get_pixels.
+     */
+    pub fn new_headless(rom_path: &str, screen_mult: u8, options :&LoadOptions) -> Result<GBEmulator, LoadError> {
+        return GBEmulator::with_backends(
+            rom_path,
+            options,
+            Rc::new(RefCell::new(HeadlessVideo::new(screen_mult))),
+            Box::new(NullAudio),
+            Box::new(NullInput)
+        );
+    }
+
+    /* Fails if the ROM can't be read or patched, or if its header is invalid (see LoadOptions) */
+    pub fn with_backends(rom_path   :&str,
+                         options    :&LoadOptions,
+                         screen     :Rc<RefCell<dyn VideoSink>>,
+                         audio      :Box<dyn AudioSink>,
+                         input      :Box<dyn InputSource>) -> Result<GBEmulator, LoadError> {
+        let (rom, rom_path) = load_rom(rom_path, options)?;
+        let cart = new_cartridge(rom, &rom_path, options)?;
+        let apu = APU::new(audio);
 
         let int: Rc<RefCell<InterruptManager>> = Rc::new(RefCell::new(
             InterruptManager::new()
@@ -58,37 +130,244 @@ impl GBEmulator {
         ));
 
         let bus  = Rc::new(RefCell::new(
-            Bus::new(ppu, apu, int.clone(), joypad.clone(), rom_path)
+            Bus::new(ppu, apu, int.clone(), joypad.clone(), cart)
         ));
 
-        return GBEmulator {
-//            path            : rom_path.to_string(),
+        let rom_md5 = md5::compute(bus.borrow().rom()).0;
+
+        return Ok(GBEmulator {
+            path            : rom_path,
+            rom_md5,
             cpu             : CPU::new(bus.clone(), int.clone()),
             bus             : bus.clone(),
             joypad          : joypad.clone(),
             clock           : Clock::new(),
+            rewind          : Rewind::new(DEFAULT_REWIND_BUDGET),
+            is_rewinding    : false,
+            is_paused       : false,
+            is_frame_advance: false,
+            frame_cycles    : 0,
+            power_on_state  : vec![],
+
+            movie           : None,
+            is_movie_playing: false,
+            movie_frame     : 0,
+            movie_desync    : None,
+
+            tracer          : None,
+            bus_log         : None,
+            profiler        : None,
 
-            events: sdl_context.event_pump().unwrap(),
-            screen: screen.clone(),
+            ram_written_at  : None,
+            save_flush_delay: SAVE_FLUSH_DELAY,
+            is_ram_detached : false,
 
-            // While the controller variable is not used, it works due to existing and being owned by
-            // an existing object.
-            controller: sdl_context.game_controller().unwrap().open(0).unwrap(),
+            screen,
+            #[cfg(feature = "sdl")]
+            sdl_context: None,
+            input,
 
             is_quit: false,
-        }
+            quit_signal: None,
+        });
     }
 
     pub fn get_cpu(&self) -> &CPU { return &self.cpu; }
     pub fn get_cpu_mut(&mut self) -> &mut CPU { return &mut self.cpu; }
     pub fn get_bus(&self) -> Rc<RefCell<Bus>> { return self.bus.clone(); }
-    pub fn get_screen(&self) -> Rc<RefCell<Screen>> { return self.screen.clone(); }
+    pub fn get_screen(&self) -> Rc<RefCell<dyn VideoSink>> { return self.screen.clone(); }
     pub fn is_quit(&self) -> bool { return self.is_quit; }
+    #[cfg(feature = "sdl")]
+    pub fn sdl_context(&self) -> Option<&sdl2::Sdl> { return self.sdl_context.as_ref(); }
+
+    /* Maximum memory used by the rewind buffer in bytes. 0 disables it. */
+    pub fn set_rewind_budget(&mut self, bytes :usize) { self.rewind.set_budget(bytes); }
+    pub fn set_rewinding(&mut self, val :bool) { self.is_rewinding = val; }
+    pub fn rewind_frames(&self) -> usize { return self.rewind.n_frames(); }
+    pub fn is_paused(&self) -> bool { return self.is_paused; }
+    pub fn frame_cycles(&self) -> u32 { return self.frame_cycles; }
+
+    /* Plug a link cable into the serial port */
+    pub fn set_link(&mut self, link :Box<dyn LinkEndpoint>) {
+        self.bus.borrow_mut().set_link(link);
+    }
+
+    /* Speed multiplier, i.e. 2.0 for 2x or 0.25 for slow motion */
+    pub fn set_speed(&mut self, speed :f64) {
+        self.clock.set_speed(speed);
+        self.update_audio_speed();
+    }
+
+    /* Run as fast as possible. The audio is dropped meanwhile. */
+    pub fn set_uncapped(&mut self, val :bool) {
+        self.clock.set_uncapped(val);
+        self.update_audio_speed();
+    }
+
+    pub fn set_paused(&mut self, val :bool) {
+        self.is_paused = val;
+        self.is_frame_advance = false;
+
+        if val {
+            self.bus.borrow_mut().pause_audio();
+        } else {
+            self.bus.borrow_mut().resume_audio();
+        }
+    }
+
+    /* Run the next frame and pause again */
+    pub fn frame_advance(&mut self) {
+        if !self.is_paused {
+            self.set_paused(true);
+        }
+
+        self.is_frame_advance = true;
+    }
+
+    fn update_audio_speed(&mut self) {
+        let speed = if self.clock.is_uncapped() { f64::INFINITY } else { self.clock.speed() };
+        self.bus.borrow_mut().set_audio_speed(speed);
+    }
+
+    // Move to the next/previous speed of SPEEDS
+    fn step_speed(&mut self, is_up :bool) {
+        let speed = self.clock.speed();
+        let next = if is_up {
+            SPEEDS.iter().find(|s| **s > speed).copied()
+        } else {
+            SPEEDS.iter().rev().find(|s| **s < speed).copied()
+        };
+
+        if let Some(next) = next {
+            self.set_speed(next);
+            println!("Speed: {}x", next);
+        }
+    }
 
     pub fn init(&mut self) {
         self.cpu.init();
         self.bus.borrow_mut().init();
         self.screen.borrow_mut().init();
+
+        self.power_on_state = self.save_state();
+    }
+
+    /* Go back to the state right after init, but for the cartridge RAM and RTC like a console */
+    pub fn reset(&mut self) {
+        let battery = self.bus.borrow().save_battery();
+        let state = self.power_on_state.clone();
+
+        self.load_state(&state).expect("The power-on state must be valid");
+        self.bus.borrow_mut().load_battery(&battery).expect("The cartridge RAM must be the same size");
+        self.rewind.clear();
+    }
+
+    pub fn rom_md5(&self) -> [u8;16] { return self.rom_md5; }
+    pub fn is_playing_movie(&self) -> bool { return self.movie.is_some() && self.is_movie_playing; }
+    pub fn is_recording_movie(&self) -> bool { return self.movie.is_some() && !self.is_movie_playing; }
+    pub fn movie_desync(&self) -> Option<u32> { return self.movie_desync; }
+
+    /* Trace the instructions run to a log or against a reference, see trace.rs */
+    pub fn set_tracer(&mut self, tracer :Tracer) { self.tracer = Some(tracer); }
+    pub fn tracer(&self) -> Option<&Tracer> { return self.tracer.as_ref(); }
+    pub fn take_tracer(&mut self) -> Option<Tracer> { return self.tracer.take(); }
+
+    /* Called before every tick, to trace the instruction about to run */
+    pub fn trace(&mut self) -> Result<(), TraceError> {
+        return match &mut self.tracer {
+            Some(tracer) => tracer.tick(&self.cpu, &self.bus.borrow()),
+            None => Ok(())
+        };
+    }
+
+    /* Flush the trace log, and tell how much of the reference the trace matched */
+    pub fn finish_trace(&mut self) {
+        let Some(tracer) = &mut self.tracer else { return; };
+
+        if let Err(err) = tracer.flush() {
+            println!("Couldn't write the trace: {}", err);
+        }
+        if tracer.is_comparing() {
+            println!("The trace matches the first {} lines of the reference", tracer.n_lines());
+        }
+    }
+
+    /* Record the last accesses to the bus, writing them to a file on quit */
+    pub fn set_bus_log(&mut self, path :&str, capacity :usize) {
+        self.bus.borrow_mut().set_recorder(Some(BusRecorder::new(capacity)));
+        self.bus_log = Some(path.to_string());
+    }
+
+    pub fn finish_bus_log(&mut self) {
+        let Some(path) = &self.bus_log else { return; };
+        let Some(recorder) = self.bus.borrow().recorder().map(|recorder| recorder.dump(path)) else { return; };
+
+        match recorder {
+            Ok(n) => println!("Wrote the last {} bus accesses to {}", n, path),
+            Err(err) => println!("Couldn't write the bus log: {}", err)
+        }
+    }
+
+    /* Profile the code run, writing the report to a file on quit. See profiler.rs */
+    pub fn set_profiler(&mut self, profiler :Profiler, report_path :&str) {
+        self.profiler = Some((profiler, report_path.to_string()));
+    }
+    pub fn profiler(&self) -> Option<&Profiler> { return self.profiler.as_ref().map(|(profiler, _)| profiler); }
+
+    /* Called before every tick */
+    pub fn profile(&mut self) {
+        if let Some((profiler, _)) = &mut self.profiler {
+            profiler.tick(&self.cpu, &self.bus.borrow());
+        }
+    }
+
+    pub fn finish_profile(&mut self) {
+        let Some((profiler, path)) = &self.profiler else { return; };
+
+        match std::fs::write(path, profiler.report()) {
+            Ok(()) => println!("Wrote the profile to {}", path),
+            Err(err) => println!("Couldn't write the profile: {}", err)
+        }
+    }
+
+    /* Record the input from now on, or from power-on without a save */
+    pub fn record_movie(&mut self, from_state :bool) {
+        let start_state = if from_state {
+            Some(self.save_state())
+        } else {
+            self.power_on();
+            None
+        };
+
+        self.movie = Some(Movie::new(self.rom_md5, start_state, DEFAULT_HASH_INTERVAL));
+        self.is_movie_playing = false;
+        self.movie_frame = 0;
+        self.movie_desync = None;
+    }
+
+    pub fn play_movie(&mut self, movie :Movie) -> Result<(), StateError> {
+        match movie.start_state() {
+            // The state holds the cartridge RAM of the movie, which mustn't replace the save
+            Some(state) => {
+                self.save_ram();
+                self.load_state(state)?;
+                self.is_ram_detached = true;
+                self.rewind.clear();
+            },
+            None => self.power_on()
+        }
+
+        self.movie = Some(movie);
+        self.is_movie_playing = true;
+        self.movie_frame = 0;
+        self.movie_desync = None;
+
+        return Ok(());
+    }
+
+    /* Stop recording or playing. Returns the movie. */
+    pub fn stop_movie(&mut self) -> Option<Movie> {
+        return self.movie.take();
     }
 
     pub fn run(&mut self) {
@@ -101,88 +380,326 @@ impl GBEmulator {
         }
 
         // Save RAM on quit
-        self.bus.borrow().save_ram();
+        self.save_ram();
+        self.finish_trace();
+        self.finish_bus_log();
+        self.finish_profile();
+    }
+
+    /* Keep the battery save in <dir> instead of next to the ROM. Call it before init. */
+    pub fn set_save_dir(&mut self, dir :&str) {
+        let path = cartridge::save_path(&self.path, Some(Path::new(dir)));
+        self.bus.borrow_mut().set_save_path(path);
+    }
+
+    pub fn set_save_flush_delay(&mut self, delay :Duration) { self.save_flush_delay = delay; }
+
+    /* Quit at the end of the frame once `flag` is set, e.g. by a signal handler */
+    pub fn set_quit_signal(&mut self, flag :Arc<AtomicBool>) { self.quit_signal = Some(flag); }
+    pub fn quit_signal(&self) -> Option<Arc<AtomicBool>> { return self.quit_signal.clone(); }
+
+    pub fn is_quit_signaled(&self) -> bool {
+        return self.quit_signal.as_ref().is_some_and(|flag| flag.load(Ordering::Relaxed));
+    }
+
+    pub fn save_ram(&mut self) {
+        if !self.is_ram_detached {
+            self.bus.borrow().save_ram();
+        }
+        self.ram_written_at = None;
+    }
+
+    /*
+     * Reset with the cartridge RAM and RTC cleared, for the movies from power-on. The RAM of the
+     * session is saved before, and from then on the save isn't written anymore.
+     */
+    fn power_on(&mut self) {
+        self.save_ram();
+        self.is_ram_detached = true;
+        self.reset();
+        self.bus.borrow_mut().clear_battery();
+    }
+
+    /* Save the RAM once the game has been done writing to it for a while */
+    fn flush_ram(&mut self) {
+        if self.bus.borrow_mut().take_ext_ram_written() {
+            self.ram_written_at = Some(Instant::now());
+        }
+
+        if self.ram_written_at.is_some_and(|at| at.elapsed() >= self.save_flush_delay) {
+            self.save_ram();
+        }
     }
 
     pub fn run_frame(&mut self) {
-        // Time adjustment for 4.19MHz / 60 fps
+        // Time adjustment for 4.19MHz / 59.7275 fps
         self.clock.wait_next_frame();
 
         // Set title FPS
         let fps = self.clock.get_fps();
         self.screen.borrow_mut().set_title_fps(fps);
 
-        for _ in 0..TICKS_PER_FRAME {
-            self.bus.borrow_mut().tick();
-            self.cpu.tick();
+        if self.is_rewinding && self.rewind.is_enabled() && !self.is_playing_movie() {
+            self.rewind_frame();
+        } else if !self.is_paused || self.is_frame_advance {
+            self.is_frame_advance = false;
+
+            self.movie_input();
+            self.run_until_vblank();
+            self.movie_check_sync();
+
+            self.rewind.push(self.save_state());
         }
 
         // Process input
         self.event_loop();
+
+        self.flush_ram();
+        if self.is_quit_signaled() {
+            self.is_quit = true;
+        }
+    }
+
+    /*
+     Run until the PPU enters VBlank. While the LCD is off there are no frames, so it
+     stops after the cycles of a full frame instead.
+     */
+    fn run_until_vblank(&mut self) {
+        self.frame_cycles = 0;
+
+        while self.frame_cycles < DOTS_PER_FRAME {
+            // Quit at the first instruction that differs from the reference
+            match self.trace() {
+                Ok(()) => {},
+                Err(TraceError::Diverged(divergence)) => {
+                    println!("{}", divergence);
+                    self.is_quit = true;
+                    return;
+                },
+                Err(err) => {
+                    println!("Stopped tracing: {}", err);
+                    self.tracer = None;
+                }
+            }
+
+            self.profile();
+
+            self.bus.borrow_mut().tick();
+            self.cpu.tick();
+            self.frame_cycles += 1;
+
+            if self.bus.borrow_mut().take_frame_done() {
+                return;
+            }
+        }
+    }
+
+    /* Feed the joypad from the movie, or record it */
+    fn movie_input(&mut self) {
+        let mut joypad = self.joypad.borrow_mut();
+
+        match &mut self.movie {
+            Some(movie) if self.is_movie_playing => match movie.input(self.movie_frame) {
+                Some(lines) => joypad.set_lines(lines),
+                None => {
+                    println!("Movie finished after {} frames", self.movie_frame);
+                    self.movie = None;
+                }
+            },
+            Some(movie) => movie.push_input(joypad.lines()),
+            None => {}
+        }
+    }
+
+    /* Store or compare the hashes of the movie checkpoints */
+    fn movie_check_sync(&mut self) {
+        let movie = match &mut self.movie {
+            Some(movie) => movie,
+            None => return
+        };
+
+        self.movie_frame += 1;
+        // Movie::new doesn't check it, unlike loading
+        let interval = movie.hash_interval();
+        if interval == 0 || !self.movie_frame.is_multiple_of(interval) {
+            return;
+        }
+
+        let hash = self.bus.borrow().sync_hash();
+
+        if !self.is_movie_playing {
+            movie.push_hash(self.movie_frame, hash);
+        } else if movie.hash(self.movie_frame).is_some_and(|h| h != hash) && self.movie_desync.is_none() {
+            println!("Movie desynced at frame {}", self.movie_frame);
+            self.movie_desync = Some(self.movie_frame);
+        }
+    }
+
+    /* Go back one frame, if there is any left in the rewind buffer */
+    fn rewind_frame(&mut self) {
+        if let Some(state) = self.rewind.pop() {
+            match self.load_state(&state) {
+                Ok(()) => {
+                    self.bus.borrow().redraw_screen();
+
+                    // Rewinding while recording drops the frames of the movie
+                    if let Some(movie) = &mut self.movie {
+                        self.movie_frame = self.movie_frame.saturating_sub(1);
+                        movie.truncate(self.movie_frame);
+                    }
+                },
+                Err(err) => {
+                    println!("Couldn't rewind: {}", err);
+                    self.rewind.clear();
+                }
+            }
+        }
+    }
+
+    /* Serialize the whole machine */
+    pub fn save_state(&self) -> Vec<u8> {
+        let mut w = StateWriter::with_header(self.rom_md5);
+
+        self.cpu.save_state(&mut w);
+        self.bus.borrow().save_state(&mut w);
+
+        return w.into_bytes();
+    }
+
+    /* Restore a state made by save_state. The machine is left untouched if it fails. */
+    pub fn load_state(&mut self, data :&[u8]) -> Result<(), StateError> {
+        let mut r = StateReader::with_header(data, self.rom_md5)?;
+        let backup = self.save_state();
+
+        let mut result = self.cpu.load_state(&mut r)
+            .and_then(|_| self.bus.borrow_mut().load_state(&mut r));
+
+        if result.is_ok() && !r.is_done() {
+            result = Err(StateError::Corrupt("trailing data".to_string()));
+        }
+
+        if result.is_err() {
+            let mut r = StateReader::with_header(&backup, self.rom_md5)?;
+            self.cpu.load_state(&mut r)?;
+            self.bus.borrow_mut().load_state(&mut r)?;
+        }
+
+        return result;
+    }
+
+    /* Cheat file loaded by default, next to the ROM */
+    pub fn cheats_path(&self) -> String {
+        return Path::new(&self.path).with_extension("cht").to_string_lossy().to_string();
+    }
+
+    /* Symbol file loaded by default by the debugger, next to the ROM */
+    pub fn symbols_path(&self) -> String {
+        return Path::new(&self.path).with_extension("sym").to_string_lossy().to_string();
+    }
+
+    pub fn load_cheats(&mut self, path :&str) -> Result<(), CheatError> {
+        return self.bus.borrow_mut().cheats_mut().load(path);
+    }
+
+    pub fn set_cheats_enabled(&mut self, is_enabled :bool) {
+        self.bus.borrow_mut().cheats_mut().set_all_enabled(is_enabled);
+    }
+
+    pub fn state_slot_path(&self, slot :u8) -> String {
+        return format!("{}.ss{}", self.path, slot);
+    }
+
+    pub fn save_state_slot(&self, slot :u8) -> Result<(), StateError> {
+        std::fs::write(self.state_slot_path(slot), self.save_state())?;
+        return Ok(());
+    }
+
+    pub fn load_state_slot(&mut self, slot :u8) -> Result<(), StateError> {
+        let data = std::fs::read(self.state_slot_path(slot))?;
+        return self.load_state(&data);
     }
 
     fn event_loop(&mut self) {
-        for event in self.events.poll_iter() {
+        for event in self.input.poll_events() {
+            // The joypad is driven by the movie while it plays
+            let is_joypad_event = matches!(event, InputEvent::Press(_) | InputEvent::Release(_)
+                | InputEvent::AxisX(_) | InputEvent::AxisY(_));
+            if is_joypad_event && self.is_playing_movie() {
+                continue;
+            }
+
             match event {
-                Event::Quit {..} |
-                Event::KeyDown { keycode: Some(Keycode::Escape), .. }
-                    => self.is_quit = true,
-                Event::KeyDown { keycode: Some(Keycode::R), .. } => {
-                    //self.handle_reload()
+                InputEvent::Quit           => self.is_quit = true,
+                InputEvent::Press(button)   => self.press(button),
+                InputEvent::Release(button) => self.release(button),
+                InputEvent::Rewind(is_held) => self.is_rewinding = is_held,
+                InputEvent::TogglePause     => self.set_paused(!self.is_paused),
+                InputEvent::FrameAdvance    => self.frame_advance(),
+                InputEvent::ToggleUncapped  => self.set_uncapped(!self.clock.is_uncapped()),
+                InputEvent::SpeedUp         => self.step_speed(true),
+                InputEvent::SpeedDown       => self.step_speed(false),
+                InputEvent::ToggleCheats    => {
+                    let is_enabled = !self.bus.borrow().cheats().is_enabled();
+                    self.set_cheats_enabled(is_enabled);
+                    println!("Cheats {}", if is_enabled {"enabled"} else {"disabled"});
                 },
-                Event::KeyDown { keycode: Some(Keycode::F), .. } => self.joypad.borrow_mut().press_start(),
-                Event::KeyUp   { keycode: Some(Keycode::F), .. } => self.joypad.borrow_mut().release_start(),
-                Event::KeyDown { keycode: Some(Keycode::G), .. } => self.joypad.borrow_mut().press_select(),
-                Event::KeyUp   { keycode: Some(Keycode::G), .. } => self.joypad.borrow_mut().release_select(),
-                Event::KeyDown { keycode: Some(Keycode::A), .. } => self.joypad.borrow_mut().press_left(),
-                Event::KeyUp   { keycode: Some(Keycode::A), .. } => self.joypad.borrow_mut().release_left(),
-                Event::KeyDown { keycode: Some(Keycode::S), .. } => self.joypad.borrow_mut().press_down(),
-                Event::KeyUp   { keycode: Some(Keycode::S), .. } => self.joypad.borrow_mut().release_down(),
-                Event::KeyDown { keycode: Some(Keycode::W), .. } => self.joypad.borrow_mut().press_up(),
-                Event::KeyUp   { keycode: Some(Keycode::W), .. } => self.joypad.borrow_mut().release_up(),
-                Event::KeyDown { keycode: Some(Keycode::D), .. } => self.joypad.borrow_mut().press_right(),
-                Event::KeyUp   { keycode: Some(Keycode::D), .. } => self.joypad.borrow_mut().release_right(),
-                Event::KeyDown { keycode: Some(Keycode::K), .. } => self.joypad.borrow_mut().press_b(),
-                Event::KeyUp   { keycode: Some(Keycode::K), .. } => self.joypad.borrow_mut().release_b(),
-                Event::KeyDown { keycode: Some(Keycode::L), .. } => self.joypad.borrow_mut().press_a(),
-                Event::KeyUp   { keycode: Some(Keycode::L), .. } => self.joypad.borrow_mut().release_a(),
-                Event::ControllerButtonDown { button, .. } => {
-                    match button {
-                        sdl2::controller::Button::A => self.joypad.borrow_mut().press_a(),
-                        sdl2::controller::Button::X => self.joypad.borrow_mut().press_b(),
-                        sdl2::controller::Button::Start => self.joypad.borrow_mut().press_start(),
-                        sdl2::controller::Button::Back => self.joypad.borrow_mut().press_select(),
-                        _ => {}
-                    }
+                InputEvent::SaveState(slot) => match self.save_state_slot(slot) {
+                    Ok(())   => println!("Saved state {}", slot),
+                    Err(err) => println!("Couldn't save state {}: {}", slot, err)
                 },
-                Event::ControllerButtonUp { button, .. } => {
-                    match button {
-                        sdl2::controller::Button::A => self.joypad.borrow_mut().release_a(),
-                        sdl2::controller::Button::X => self.joypad.borrow_mut().release_b(),
-                        sdl2::controller::Button::Start => self.joypad.borrow_mut().release_start(),
-                        sdl2::controller::Button::Back => self.joypad.borrow_mut().release_select(),
-                        _ => {}
-                    }
+                InputEvent::LoadState(_) if self.movie.is_some() =>
+                    println!("Can't load a state while a movie is recording or playing"),
+                InputEvent::LoadState(slot) => match self.load_state_slot(slot) {
+                    Ok(())   => println!("Loaded state {}", slot),
+                    Err(err) => println!("Couldn't load state {}: {}", slot, err)
                 },
-                Event::ControllerAxisMotion { axis, value, .. } => {
-                    match axis {
-                        // Left: Negative (0..-32000), Down: The opposite
-                        sdl2::controller::Axis::LeftX =>  {
-                            if      value < -16000 { self.joypad.borrow_mut().controller_left(); }
-                            else if value >  16000 { self.joypad.borrow_mut().controller_right(); }
-                            else { self.joypad.borrow_mut().controller_no_x(); }
-                        },
-                        // Up: Negative (0..-32000), Down: The opposite
-                        sdl2::controller::Axis::LeftY => {
-                            if      value < -16000 { self.joypad.borrow_mut().controller_up(); }
-                            else if value >  16000 { self.joypad.borrow_mut().controller_down(); }
-                            else { self.joypad.borrow_mut().controller_no_y(); }
-                        },
-                        _ => {}
-                    }
+                // Left: Negative (0..-32000), Right: The opposite
+                InputEvent::AxisX(value) => {
+                    let mut joypad = self.joypad.borrow_mut();
+
+                    if      value < -16000 { joypad.controller_left(); }
+                    else if value >  16000 { joypad.controller_right(); }
+                    else { joypad.controller_no_x(); }
+                },
+                // Up: Negative (0..-32000), Down: The opposite
+                InputEvent::AxisY(value) => {
+                    let mut joypad = self.joypad.borrow_mut();
+
+                    if      value < -16000 { joypad.controller_up(); }
+                    else if value >  16000 { joypad.controller_down(); }
+                    else { joypad.controller_no_y(); }
                 },
-                _ => {}
             }
         }
     }
+
+    fn press(&mut self, button :Button) {
+        let mut joypad = self.joypad.borrow_mut();
+
+        match button {
+            Button::A      => joypad.press_a(),
+            Button::B      => joypad.press_b(),
+            Button::Start  => joypad.press_start(),
+            Button::Select => joypad.press_select(),
+            Button::Up     => joypad.press_up(),
+            Button::Down   => joypad.press_down(),
+            Button::Left   => joypad.press_left(),
+            Button::Right  => joypad.press_right(),
+        }
+    }
+
+    fn release(&mut self, button :Button) {
+        let mut joypad = self.joypad.borrow_mut();
+
+        match button {
+            Button::A      => joypad.release_a(),
+            Button::B      => joypad.release_b(),
+            Button::Start  => joypad.release_start(),
+            Button::Select => joypad.release_select(),
+            Button::Up     => joypad.release_up(),
+            Button::Down   => joypad.release_down(),
+            Button::Left   => joypad.release_left(),
+            Button::Right  => joypad.release_right(),
+        }
+    }
 }
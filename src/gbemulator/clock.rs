@@ -3,12 +3,14 @@ use std::thread::sleep;
 
 
 // GLobal constants
-const S_PER_FRAME: f64 = 1.0/60.0;
+const S_PER_FRAME: f64 = 70224.0/4194304.0; // ~59.7275 Hz
 
 
 pub struct Clock {
     t_start_frame   :SystemTime,
     dur_frame       :Duration,
+    speed           :f64,
+    is_uncapped     :bool,
 
     dur_s_per_frame :Duration,
     time_start      :SystemTime,
@@ -26,6 +28,10 @@ impl Clock {
             t_start_frame: SystemTime::now(),
             // Frame duration
             dur_frame: Duration::from_secs(0),
+            // Speed multiplier
+            speed: 1.0,
+            // Run as fast as possible
+            is_uncapped: false,
 
             // FPS
             // Start time of the frame
@@ -39,12 +45,27 @@ impl Clock {
         }
     }
 
+    pub fn speed(&self)       -> f64  { return self.speed; }
+    pub fn is_uncapped(&self) -> bool { return self.is_uncapped; }
+
+    // Speed multiplier, i.e. 2.0 runs twice as fast and 0.25 at a quarter of the speed
+    pub fn set_speed(&mut self, speed :f64) {
+        assert!(speed > 0.0, "The speed multiplier must be positive");
+
+        self.speed = speed;
+        self.dur_s_per_frame = Duration::from_secs_f64(S_PER_FRAME / speed);
+    }
+
+    pub fn set_uncapped(&mut self, val :bool) {
+        self.is_uncapped = val;
+    }
+
     // Spend time until next tick to regulate ticks/s
     pub fn wait_next_frame(&mut self) {
         self.dur_frame = self.t_start_frame.elapsed().unwrap();
 
-        // Wait until the frame is over
-        if self.dur_frame < self.dur_s_per_frame {
+        // Wait until the frame is over, unless it runs uncapped
+        if !self.is_uncapped && self.dur_frame < self.dur_s_per_frame {
             //println!("Frame OK {:?}", self.dur_frame);
             sleep(self.dur_s_per_frame - self.dur_frame);
         } else {
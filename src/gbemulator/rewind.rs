@@ -0,0 +1,160 @@
+use std::collections::VecDeque;
+
+/*
+ * Rewind buffer.
+ *
+ * Only the newest save state is kept whole. Every older one is stored as the XOR against the
+ * state that came after it, with the runs of zeros (bytes which didn't change between frames)
+ * collapsed. Going back one frame undoes the newest delta, and once the memory budget is
+ * exceeded the oldest deltas are dropped.
+ */
+
+pub const DEFAULT_REWIND_BUDGET :usize = 32 * 1024 * 1024; // 32 MiB
+
+pub struct Rewind {
+    deltas :VecDeque<Vec<u8>>,
+    last   :Vec<u8>,     // Newest state, uncompressed
+    used   :usize,       // Bytes used by the deltas and the last state
+    budget :usize,       // 0 disables rewinding
+}
+
+impl Rewind {
+    pub fn new(budget :usize) -> Rewind {
+        return Rewind {
+            deltas: VecDeque::new(),
+            last: vec![],
+            used: 0,
+            budget
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool  { return self.budget > 0; }
+    pub fn n_frames(&self)   -> usize { return self.deltas.len(); }
+
+    pub fn set_budget(&mut self, budget :usize) {
+        self.budget = budget;
+
+        if budget == 0 {
+            self.clear();
+        } else {
+            self.shrink();
+        }
+    }
+
+    pub fn clear(&mut self) {
+        self.deltas.clear();
+        self.last = vec![];
+        self.used = 0;
+    }
+
+    /* Store the state of a new frame */
+    pub fn push(&mut self, state :Vec<u8>) {
+        if !self.is_enabled() {
+            return;
+        }
+
+        if !self.last.is_empty() {
+            let delta = encode_delta(&state, &self.last);
+            self.used += delta.len();
+            self.deltas.push_back(delta);
+        }
+
+        self.used = self.used - self.last.len() + state.len();
+        self.last = state;
+        self.shrink();
+    }
+
+    /* Go back one frame. Returns the state to load, or None if there is nothing left. */
+    pub fn pop(&mut self) -> Option<Vec<u8>> {
+        let delta = self.deltas.pop_back()?;
+        let state = decode_delta(&self.last, &delta);
+
+        self.used = self.used - delta.len() - self.last.len() + state.len();
+        self.last = state.clone();
+
+        return Some(state);
+    }
+
+    // Drop the oldest frames until the buffer fits in the budget
+    fn shrink(&mut self) {
+        while self.used > self.budget {
+            match self.deltas.pop_front() {
+                Some(delta) => self.used -= delta.len(),
+                None => break
+            }
+        }
+    }
+}
+
+fn write_varint(buf :&mut Vec<u8>, mut val :usize) {
+    while val >= 0x80 {
+        buf.push((val as u8) | 0x80);
+        val >>= 7;
+    }
+
+    buf.push(val as u8);
+}
+
+fn read_varint(buf :&[u8], pos :&mut usize) -> usize {
+    let mut val = 0;
+    let mut shift = 0;
+
+    loop {
+        let byte = buf[*pos];
+        *pos += 1;
+        val |= ((byte & 0x7F) as usize) << shift;
+        shift += 7;
+
+        if byte & 0x80 == 0 {
+            return val;
+        }
+    }
+}
+
+/*
+ Delta which turns `newer` into `older`:
+    older length | (zero run length, literal length, literals)...
+ */
+fn encode_delta(newer :&[u8], older :&[u8]) -> Vec<u8> {
+    let mut buf = vec![];
+    write_varint(&mut buf, older.len());
+
+    let xor = |i :usize| older[i] ^ newer.get(i).copied().unwrap_or(0);
+    let mut i = 0;
+
+    while i < older.len() {
+        let zeros_start = i;
+        while i < older.len() && xor(i) == 0 { i += 1; }
+
+        let literals_start = i;
+        while i < older.len() && xor(i) != 0 { i += 1; }
+
+        write_varint(&mut buf, literals_start - zeros_start);
+        write_varint(&mut buf, i - literals_start);
+        buf.extend((literals_start..i).map(xor));
+    }
+
+    return buf;
+}
+
+fn decode_delta(newer :&[u8], delta :&[u8]) -> Vec<u8> {
+    let mut pos = 0;
+    let len = read_varint(delta, &mut pos);
+
+    let mut older :Vec<u8> = (0..len).map(|i| newer.get(i).copied().unwrap_or(0)).collect();
+    let mut i = 0;
+
+    while pos < delta.len() {
+        i += read_varint(delta, &mut pos);
+        let n_literals = read_varint(delta, &mut pos);
+
+        for byte in delta[pos..pos+n_literals].iter() {
+            older[i] ^= byte;
+            i += 1;
+        }
+
+        pos += n_literals;
+    }
+
+    return older;
+}
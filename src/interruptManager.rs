@@ -1,4 +1,5 @@
 use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 pub struct InterruptManager {
     IE :u8,
@@ -93,3 +94,19 @@ impl ComponentWithMemory for InterruptManager {
         }
     }
 }
+
+impl Savestate for InterruptManager {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.IE);
+        w.u8(self.IF);
+        w.bool(self.IME);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.IE  = r.u8()?;
+        self.IF  = r.u8()?;
+        self.IME = r.bool()?;
+
+        return Ok(());
+    }
+}
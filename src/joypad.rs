@@ -3,6 +3,7 @@ use std::rc::Rc;
 
 use crate::interruptManager::InterruptManager;
 use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 pub struct Joypad {
     int: Rc<RefCell<InterruptManager>>,
@@ -67,6 +68,15 @@ impl Joypad {
     pub fn release_left(&mut self)   { self.release(0b11101101); }
     pub fn release_right(&mut self)  { self.release(0b11101110); }
 
+    /* State of the direction and action lines, for input movies */
+    pub fn lines(&self) -> (u8, u8) {
+        return (self.direction, self.action);
+    }
+
+    pub fn set_lines(&mut self, lines :(u8, u8)) {
+        (self.direction, self.action) = lines;
+    }
+
     pub fn controller_left(&mut self) {
         if self.is_controller_right {
             self.is_controller_right = false;
@@ -169,3 +179,29 @@ impl ComponentWithMemory for Joypad {
         }
     }
 }
+
+impl Savestate for Joypad {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.u8(self.p1);
+        w.u8(self.mask);
+        w.u8(self.direction);
+        w.u8(self.action);
+        w.bool(self.is_controller_up);
+        w.bool(self.is_controller_down);
+        w.bool(self.is_controller_left);
+        w.bool(self.is_controller_right);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        self.p1 = r.u8()?;
+        self.mask = r.u8()?;
+        self.direction = r.u8()?;
+        self.action = r.u8()?;
+        self.is_controller_up = r.bool()?;
+        self.is_controller_down = r.bool()?;
+        self.is_controller_left = r.bool()?;
+        self.is_controller_right = r.bool()?;
+
+        return Ok(());
+    }
+}
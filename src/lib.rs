@@ -5,8 +5,16 @@ pub mod bus;
 pub mod ppu;
 pub mod apu;
 pub mod joypad;
+#[cfg(feature = "sdl")]
 pub mod screen;
+pub mod frontend;
 #[allow(non_snake_case)]
 pub mod interruptManager;
 pub mod cartridge;
+pub mod cheats;
 pub mod consts;
+pub mod savestate;
+pub mod movie;
+pub mod trace;
+pub mod profiler;
+pub mod link;
@@ -0,0 +1,45 @@
+/*
+ * Link cable endpoints.
+ *
+ * The serial port only sees the other side through this trait. The transfers are done a byte
+ * at a time: the master sends its byte with `transfer` and gets the one of the slave back, at
+ * once or later from `reply`, while the slave keeps calling `poll` with the byte it has ready
+ * until a master takes it.
+ */
+
+pub mod loopback;
+pub mod printer;
+pub mod tcp;
+
+pub trait LinkEndpoint {
+    /*
+     Master side. Returns the byte of the other side, or 0xFF if nobody answers. None if it
+     isn't there yet, e.g. over a network: the serial port then calls `reply` until it is.
+     */
+    fn transfer(&mut self, val :u8) -> Option<u8>;
+
+    /* Byte of the other side for the last transfer which returned None, once it's there */
+    fn reply(&mut self) -> Option<u8> {
+        return Some(0xFF);
+    }
+
+    /*
+     Called periodically by the serial port. `slave` is the byte in SB while it waits for an
+     external clock, or None otherwise. Returns the byte sent by the master once it has
+     taken the one of the slave.
+     */
+    fn poll(&mut self, slave :Option<u8>) -> Option<u8>;
+}
+
+/* Nothing connected: the data line is pulled up, so every transfer reads 0xFF */
+pub struct NoLink;
+
+impl LinkEndpoint for NoLink {
+    fn transfer(&mut self, _val :u8) -> Option<u8> {
+        return Some(0xFF);
+    }
+
+    fn poll(&mut self, _slave :Option<u8>) -> Option<u8> {
+        return None;
+    }
+}
@@ -0,0 +1,61 @@
+use std::cell::RefCell;
+use std::rc::Rc;
+
+use crate::link::LinkEndpoint;
+
+/*
+ Cable between two emulators running in the same process, e.g. alternating their frames.
+ Each side publishes the byte it has ready while it waits as slave, and the master swaps it
+ with its own byte.
+ */
+struct Cable {
+    ready :[Option<u8>;2], // Byte of each side while it waits as slave
+    inbox :[Option<u8>;2], // Byte sent by the master to each side
+}
+
+pub struct LoopbackEnd {
+    cable :Rc<RefCell<Cable>>,
+    side  :usize
+}
+
+/* Create both ends of a cable */
+pub fn loopback_cable() -> (LoopbackEnd, LoopbackEnd) {
+    let cable = Rc::new(RefCell::new(Cable {
+        ready: [None, None],
+        inbox: [None, None]
+    }));
+
+    return (
+        LoopbackEnd { cable: cable.clone(), side: 0 },
+        LoopbackEnd { cable, side: 1 }
+    );
+}
+
+impl LinkEndpoint for LoopbackEnd {
+    fn transfer(&mut self, val :u8) -> Option<u8> {
+        let mut cable = self.cable.borrow_mut();
+        let other = 1 - self.side;
+
+        return match cable.ready[other].take() {
+            Some(other_val) => {
+                cable.inbox[other] = Some(val);
+                Some(other_val)
+            },
+            None => Some(0xFF)
+        }
+    }
+
+    fn poll(&mut self, slave :Option<u8>) -> Option<u8> {
+        let mut cable = self.cable.borrow_mut();
+
+        if slave.is_none() {
+            cable.ready[self.side] = None;
+            return None;
+        }
+
+        let received = cable.inbox[self.side].take();
+        cable.ready[self.side] = if received.is_some() { None } else { slave };
+
+        return received;
+    }
+}
@@ -0,0 +1,316 @@
+use std::fs::{self, File};
+use std::io::{self, BufWriter};
+use std::path::{Path, PathBuf};
+
+use crate::link::LinkEndpoint;
+
+/*
+ * Game Boy Printer.
+ *
+ * The printer is always the slave: the game clocks every byte of a packet and gets the answer
+ * of the printer back at the same time. A packet looks like
+ *    0x88 0x33 | command | compression | length (LE) | data | checksum (LE) | 0x00 0x00
+ * and the printer answers 0x81 to the first trailing byte and its status to the second one.
+ * The checksum is the 16-bit sum of every byte from the command to the end of the data.
+ *
+ * The tiles received with data packets are printed on a strip of paper, which is torn off and
+ * written as a PNG when a print command feeds some paper after the image.
+ */
+
+const MAGIC_1 :u8 = 0x88;
+const MAGIC_2 :u8 = 0x33;
+const ALIVE   :u8 = 0x81;
+
+const CMD_INIT   :u8 = 0x01;
+const CMD_PRINT  :u8 = 0x02;
+const CMD_DATA   :u8 = 0x04;
+const CMD_STATUS :u8 = 0x0F;
+
+const STATUS_CHECKSUM_ERROR :u8 = 0x01;
+const STATUS_PRINTING       :u8 = 0x02;
+const STATUS_IMAGE_FULL     :u8 = 0x04;
+const STATUS_UNPROCESSED    :u8 = 0x08;
+
+// 9 data packets of 40 tiles: 160x144 pixels
+const IMAGE_CAPACITY :usize = 0x2280;
+const PAPER_WIDTH    :usize = 160;
+// Pixel rows fed for each unit of the margins
+const MARGIN_ROWS    :usize = 8;
+// Packets during which the printer reports it is busy after a print command
+const PRINT_PACKETS  :u8 = 4;
+
+const SHADES :[u8;4] = [0xFF, 0xAA, 0x55, 0x00];
+
+#[derive(Clone, Copy, PartialEq)]
+enum Stage {
+    Magic1,
+    Magic2,
+    Command,
+    Compression,
+    LengthLow,
+    LengthHigh,
+    Data,
+    ChecksumLow,
+    ChecksumHigh,
+    Alive,
+    Status
+}
+
+pub struct Printer {
+    out_dir :PathBuf,
+
+    stage       :Stage,
+    command     :u8,
+    compression :u8,
+    length      :u16,
+    data        :Vec<u8>,
+    sum         :u16,   // Checksum computed over the packet
+    checksum    :u16,   // Checksum sent by the game
+
+    status        :u8,
+    print_packets :u8,
+
+    image :Vec<u8>,   // Tile data waiting to be printed
+    paper :Vec<u8>,   // Printed pixels, PAPER_WIDTH per row
+}
+
+impl Printer {
+    /* The printouts are written to `out_dir`, which is created if needed */
+    pub fn new(out_dir :impl AsRef<Path>) -> Printer {
+        return Printer {
+            out_dir: out_dir.as_ref().to_path_buf(),
+
+            stage: Stage::Magic1,
+            command: 0,
+            compression: 0,
+            length: 0,
+            data: vec![],
+            sum: 0,
+            checksum: 0,
+
+            status: 0,
+            print_packets: 0,
+
+            image: vec![],
+            paper: vec![]
+        }
+    }
+
+    fn add_to_sum(&mut self, val :u8) {
+        self.sum = self.sum.wrapping_add(val as u16);
+    }
+
+    fn end_packet(&mut self) {
+        if self.sum != self.checksum {
+            self.status |= STATUS_CHECKSUM_ERROR;
+            return;
+        }
+        self.status &= !STATUS_CHECKSUM_ERROR;
+
+        if self.print_packets > 0 {
+            self.print_packets -= 1;
+            if self.print_packets == 0 {
+                self.status &= !STATUS_PRINTING;
+            }
+        }
+
+        match self.command {
+            CMD_INIT => {
+                self.image.clear();
+                self.status = 0;
+                self.print_packets = 0;
+            },
+            CMD_DATA => {
+                let data = if self.compression != 0 { decompress(&self.data) } else { self.data.clone() };
+                let n = data.len().min(IMAGE_CAPACITY - self.image.len());
+                self.image.extend_from_slice(&data[..n]);
+
+                if !self.image.is_empty() {
+                    self.status |= STATUS_UNPROCESSED;
+                }
+                if self.image.len() == IMAGE_CAPACITY {
+                    self.status |= STATUS_IMAGE_FULL;
+                }
+            },
+            CMD_PRINT if self.data.len() == 4 => {
+                // data[3] is the exposure, which only changes the darkness on paper
+                self.print(self.data[0], self.data[1], self.data[2]);
+                self.status = STATUS_PRINTING | STATUS_IMAGE_FULL;
+                self.print_packets = PRINT_PACKETS;
+            },
+            CMD_STATUS => {},
+            _ => {}
+        }
+    }
+
+    fn print(&mut self, sheets :u8, margins :u8, palette :u8) {
+        // Some games send 0 for the usual palette
+        let palette = if palette == 0 { 0xE4 } else { palette };
+
+        self.feed((margins >> 4) as usize);
+
+        for _ in 0..sheets {
+            let n_tile_rows = self.image.len() / (16 * PAPER_WIDTH / 8);
+
+            for y in 0..n_tile_rows * 8 {
+                for x in 0..PAPER_WIDTH {
+                    let tile = (y / 8) * (PAPER_WIDTH / 8) + x / 8;
+                    let addr = tile * 16 + (y % 8) * 2;
+                    let bit = 7 - (x % 8);
+
+                    let color = (((self.image[addr + 1] >> bit) & 1) << 1) | ((self.image[addr] >> bit) & 1);
+                    let shade = (palette >> (color * 2)) & 0b11;
+                    self.paper.push(SHADES[shade as usize]);
+                }
+            }
+        }
+
+        self.image.clear();
+        self.feed((margins & 0x0F) as usize);
+
+        if margins & 0x0F != 0 {
+            self.tear_off();
+        }
+    }
+
+    fn feed(&mut self, margin :usize) {
+        let n = self.paper.len() + margin * MARGIN_ROWS * PAPER_WIDTH;
+        self.paper.resize(n, SHADES[0]);
+    }
+
+    /* Write the paper printed so far to the next free file of the output directory */
+    fn tear_off(&mut self) {
+        if self.paper.is_empty() {
+            return;
+        }
+
+        match self.write_png() {
+            Ok(path) => println!("Printed {}", path.display()),
+            Err(err) => println!("Couldn't write the printout to {}: {}", self.out_dir.display(), err)
+        }
+
+        self.paper.clear();
+    }
+
+    fn write_png(&self) -> io::Result<PathBuf> {
+        fs::create_dir_all(&self.out_dir)?;
+
+        let mut index = 0;
+        let path = loop {
+            let path = self.out_dir.join(format!("printout_{:03}.png", index));
+            if !path.exists() {
+                break path;
+            }
+            index += 1;
+        };
+
+        let file = BufWriter::new(File::create(&path)?);
+        let mut encoder = png::Encoder::new(file, PAPER_WIDTH as u32, (self.paper.len() / PAPER_WIDTH) as u32);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.set_depth(png::BitDepth::Eight);
+
+        let mut writer = encoder.write_header().map_err(io::Error::other)?;
+        writer.write_image_data(&self.paper).map_err(io::Error::other)?;
+
+        return Ok(path);
+    }
+}
+
+/*
+ RLE used by data packets: a control byte with bit 7 set repeats the next byte
+ (ctrl & 0x7F) + 2 times, otherwise the next ctrl + 1 bytes are copied as is.
+ */
+fn decompress(data :&[u8]) -> Vec<u8> {
+    let mut out = vec![];
+    let mut i = 0;
+
+    while i < data.len() {
+        let ctrl = data[i];
+        i += 1;
+
+        if ctrl & 0x80 != 0 {
+            if let Some(val) = data.get(i) {
+                out.extend(std::iter::repeat_n(*val, (ctrl & 0x7F) as usize + 2));
+            }
+            i += 1;
+        } else {
+            let end = (i + ctrl as usize + 1).min(data.len());
+            out.extend_from_slice(&data[i..end]);
+            i = end;
+        }
+    }
+
+    return out;
+}
+
+impl LinkEndpoint for Printer {
+    fn transfer(&mut self, val :u8) -> Option<u8> {
+        let mut reply = 0x00;
+
+        self.stage = match self.stage {
+            Stage::Magic1 if val == MAGIC_1 => Stage::Magic2,
+            Stage::Magic1 => Stage::Magic1,
+            Stage::Magic2 if val == MAGIC_2 => Stage::Command,
+            Stage::Magic2 if val == MAGIC_1 => Stage::Magic2,
+            Stage::Magic2 => Stage::Magic1,
+            Stage::Command => {
+                self.command = val;
+                self.sum = 0;
+                self.add_to_sum(val);
+                Stage::Compression
+            },
+            Stage::Compression => {
+                self.compression = val;
+                self.add_to_sum(val);
+                Stage::LengthLow
+            },
+            Stage::LengthLow => {
+                self.length = val as u16;
+                self.add_to_sum(val);
+                Stage::LengthHigh
+            },
+            Stage::LengthHigh => {
+                self.length |= (val as u16) << 8;
+                self.add_to_sum(val);
+                self.data.clear();
+                if self.length == 0 { Stage::ChecksumLow } else { Stage::Data }
+            },
+            Stage::Data => {
+                self.data.push(val);
+                self.add_to_sum(val);
+                if self.data.len() == self.length as usize { Stage::ChecksumLow } else { Stage::Data }
+            },
+            Stage::ChecksumLow => {
+                self.checksum = val as u16;
+                Stage::ChecksumHigh
+            },
+            Stage::ChecksumHigh => {
+                self.checksum |= (val as u16) << 8;
+                self.end_packet();
+                Stage::Alive
+            },
+            Stage::Alive => {
+                reply = ALIVE;
+                Stage::Status
+            },
+            Stage::Status => {
+                reply = self.status;
+                Stage::Magic1
+            }
+        };
+
+        return Some(reply);
+    }
+
+    /* The printer never drives the clock */
+    fn poll(&mut self, _slave :Option<u8>) -> Option<u8> {
+        return None;
+    }
+}
+
+/* Don't lose what was printed without a final feed */
+impl Drop for Printer {
+    fn drop(&mut self) {
+        self.tear_off();
+    }
+}
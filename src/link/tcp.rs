@@ -0,0 +1,170 @@
+use std::collections::VecDeque;
+use std::io::{self, Read, Write};
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+use std::time::{Duration, Instant};
+
+use crate::link::LinkEndpoint;
+
+/*
+ Link cable over a TCP socket. Every message is 2 bytes long:
+    [MSG_TRANSFER, byte]: sent by the master when it starts a transfer
+    [MSG_REPLY, byte]:    answer of the other side, 0xFF if it wasn't waiting as slave
+
+ The master doesn't wait for the reply: the serial port polls for it with `reply`.
+ */
+
+const MSG_TRANSFER :u8 = 0;
+const MSG_REPLY    :u8 = 1;
+
+// Time the master waits for the reply before reading 0xFF, as if nothing was connected
+const REPLY_TIMEOUT :Duration = Duration::from_millis(500);
+
+pub struct TcpLink {
+    stream   :Option<TcpStream>,   // None once the other side disconnects
+    buf      :Vec<u8>,
+    out      :Vec<u8>,             // Bytes the socket didn't take yet
+    messages :VecDeque<(u8, u8)>,
+    sent_at  :Option<Instant>,     // Of the transfer waiting for its reply
+}
+
+impl TcpLink {
+    /* Wait for the other emulator to connect */
+    pub fn listen(addr :impl ToSocketAddrs) -> io::Result<TcpLink> {
+        let listener = TcpListener::bind(addr)?;
+        let (stream, _) = listener.accept()?;
+
+        return TcpLink::from_stream(stream);
+    }
+
+    pub fn connect(addr :impl ToSocketAddrs) -> io::Result<TcpLink> {
+        return TcpLink::from_stream(TcpStream::connect(addr)?);
+    }
+
+    fn from_stream(stream :TcpStream) -> io::Result<TcpLink> {
+        stream.set_nodelay(true)?;
+        stream.set_nonblocking(true)?;
+
+        return Ok(TcpLink {
+            stream: Some(stream),
+            buf: vec![],
+            out: vec![],
+            messages: VecDeque::new(),
+            sent_at: None
+        });
+    }
+
+    pub fn is_connected(&self) -> bool { return self.stream.is_some(); }
+
+    fn send(&mut self, kind :u8, val :u8) {
+        if self.is_connected() {
+            self.out.extend_from_slice(&[kind, val]);
+            self.flush();
+        }
+    }
+
+    // Write what the socket takes without blocking, the rest is sent on the next call
+    fn flush(&mut self) {
+        while !self.out.is_empty() {
+            let result = match &mut self.stream {
+                Some(stream) => stream.write(&self.out),
+                None => return
+            };
+
+            match result {
+                Ok(0) => { self.disconnect(); return; },
+                Ok(n) => { self.out.drain(..n); },
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => return,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => { self.disconnect(); return; }
+            }
+        }
+    }
+
+    // Read every complete message available without blocking
+    fn receive(&mut self) {
+        let mut data = [0;64];
+
+        self.flush();
+
+        loop {
+            let result = match &mut self.stream {
+                Some(stream) => stream.read(&mut data),
+                None => return
+            };
+
+            match result {
+                Ok(0) => { self.disconnect(); return; },
+                Ok(n) => self.buf.extend_from_slice(&data[..n]),
+                Err(err) if err.kind() == io::ErrorKind::WouldBlock => break,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(_) => { self.disconnect(); return; }
+            }
+        }
+
+        while self.buf.len() >= 2 {
+            self.messages.push_back((self.buf[0], self.buf[1]));
+            self.buf.drain(..2);
+        }
+    }
+
+    fn disconnect(&mut self) {
+        self.out.clear();
+        if self.stream.take().is_some() {
+            println!("Link cable disconnected");
+        }
+    }
+}
+
+impl LinkEndpoint for TcpLink {
+    fn transfer(&mut self, val :u8) -> Option<u8> {
+        if !self.is_connected() {
+            return Some(0xFF);
+        }
+
+        self.send(MSG_TRANSFER, val);
+        self.sent_at = Some(Instant::now());
+
+        return None;
+    }
+
+    fn reply(&mut self) -> Option<u8> {
+        self.receive();
+
+        while let Some((kind, other_val)) = self.messages.pop_front() {
+            if kind == MSG_REPLY {
+                self.sent_at = None;
+                return Some(other_val);
+            }
+
+            // Both sides started a transfer as master at the same time
+            self.send(MSG_REPLY, 0xFF);
+        }
+
+        // Nobody answered, e.g. after loading a state taken while waiting
+        if !self.is_connected() || self.sent_at.is_none_or(|at| at.elapsed() >= REPLY_TIMEOUT) {
+            self.sent_at = None;
+            return Some(0xFF);
+        }
+
+        return None;
+    }
+
+    fn poll(&mut self, slave :Option<u8>) -> Option<u8> {
+        self.receive();
+
+        while let Some((kind, val)) = self.messages.pop_front() {
+            // Late reply to a transfer which timed out
+            if kind != MSG_TRANSFER {
+                continue;
+            }
+
+            self.send(MSG_REPLY, slave.unwrap_or(0xFF));
+
+            if slave.is_some() {
+                return Some(val);
+            }
+        }
+
+        return None;
+    }
+}
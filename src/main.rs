@@ -1,6 +1,20 @@
 use std::env;
+use std::sync::Arc;
+use std::sync::atomic::AtomicBool;
+use signal_hook::consts::{SIGINT, SIGTERM};
 use gb::consts::Config;
+use gb::bus::recorder;
+use gb::cartridge::{LoadError, LoadOptions, header_checksum, global_checksum};
+use gb::cartridge::header::CartridgeHeader;
+use gb::cartridge::archive::read_rom;
 use gb::debugger::Debugger;
+use gb::debugger::breakpoints::{Breakpoint, RunMode};
+use gb::movie::Movie;
+use gb::link::printer::Printer;
+use gb::link::tcp::TcpLink;
+use gb::trace::Tracer;
+use gb::profiler::Profiler;
+use gb::debugger::symbols::Symbols;
 
 
 fn parse_args() -> Config {
@@ -23,13 +37,177 @@ fn parse_args() -> Config {
         args.remove(index);
     }
 
-    // --debug
-    let has_breakpoint = args.contains(&"--breakpoint".to_string());
-    let mut breakpoint_addr :u16 = 0x0000;
-    if has_breakpoint {
-        let index = args.iter().position(|s| *s == "--breakpoint").unwrap();
-        breakpoint_addr = u16::from_str_radix(&args[index+1].clone(), 16).unwrap();
-     
+    // --breakpoint <[bank:]addr|label>, run the debugger until there. Can be given several times
+    let mut breakpoints = vec![];
+    while let Some(index) = args.iter().position(|s| *s == "--breakpoint") {
+        breakpoints.push(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --rewind-mb, memory used by the rewind buffer. 0 disables it
+    let mut rewind_budget = gb::gbemulator::DEFAULT_REWIND_BUDGET;
+    if let Some(index) = args.iter().position(|s| *s == "--rewind-mb") {
+        let mb :usize = args[index+1].parse().unwrap();
+        rewind_budget = mb * 1024 * 1024;
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --speed, emulation speed multiplier
+    let mut speed = 1.0;
+    if let Some(index) = args.iter().position(|s| *s == "--speed") {
+        speed = args[index+1].parse().unwrap();
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --uncapped, run as fast as possible
+    let is_uncapped = args.contains(&"--uncapped".to_string());
+    if is_uncapped {
+        let index = args.iter().position(|s| *s == "--uncapped").unwrap();
+        args.remove(index);
+    }
+
+    // --record <file>, record an input movie from power-on
+    let mut record_movie = None;
+    if let Some(index) = args.iter().position(|s| *s == "--record") {
+        record_movie = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --record-from <slot>, start the movie from a save state instead
+    let mut record_from_slot = None;
+    if let Some(index) = args.iter().position(|s| *s == "--record-from") {
+        record_from_slot = Some(args[index+1].parse().unwrap());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --play <file>, play an input movie
+    let mut play_movie = None;
+    if let Some(index) = args.iter().position(|s| *s == "--play") {
+        play_movie = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --link-listen <addr>, wait for another instance to connect its link cable
+    let mut link_listen = None;
+    if let Some(index) = args.iter().position(|s| *s == "--link-listen") {
+        link_listen = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --link-connect <addr>, connect the link cable to another instance
+    let mut link_connect = None;
+    if let Some(index) = args.iter().position(|s| *s == "--link-connect") {
+        link_connect = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --printer <dir>, plug a Game Boy Printer writing its printouts to <dir>
+    let mut printer_dir = None;
+    if let Some(index) = args.iter().position(|s| *s == "--printer") {
+        printer_dir = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --save-dir <dir>, directory of the battery saves. Next to the ROM by default
+    let mut save_dir = None;
+    if let Some(index) = args.iter().position(|s| *s == "--save-dir") {
+        save_dir = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --lenient, skip the header checks, for headerless homebrew
+    let is_lenient = args.contains(&"--lenient".to_string());
+    if is_lenient {
+        let index = args.iter().position(|s| *s == "--lenient").unwrap();
+        args.remove(index);
+    }
+
+    // --patch <file>, IPS/UPS/BPS patch to apply to the ROM. Can be given several times
+    let mut patches = vec![];
+    while let Some(index) = args.iter().position(|s| *s == "--patch") {
+        patches.push(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --cheats <file>, cheat file to load instead of the one next to the ROM
+    let mut cheats_file = None;
+    if let Some(index) = args.iter().position(|s| *s == "--cheats") {
+        cheats_file = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --symbols <file>, RGBDS or no$gmb symbol file for the debugger, instead of the one next to the ROM
+    let mut symbols_file = None;
+    if let Some(index) = args.iter().position(|s| *s == "--symbols") {
+        symbols_file = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --trace <file>, log every instruction run in the Gameboy Doctor format
+    let mut trace_file = None;
+    if let Some(index) = args.iter().position(|s| *s == "--trace") {
+        trace_file = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --trace-compare <file>, compare the trace with a reference log, quitting where they differ
+    let mut trace_reference = None;
+    if let Some(index) = args.iter().position(|s| *s == "--trace-compare") {
+        trace_reference = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --ly-stub, LY always reads 0x90, as the Gameboy Doctor logs expect
+    let is_ly_stubbed = args.contains(&"--ly-stub".to_string());
+    if is_ly_stubbed {
+        let index = args.iter().position(|s| *s == "--ly-stub").unwrap();
+        args.remove(index);
+    }
+
+    // --bus-log <file>, record the last accesses to the bus and write them on quit
+    let mut bus_log = None;
+    if let Some(index) = args.iter().position(|s| *s == "--bus-log") {
+        bus_log = Some(args[index+1].clone());
+
+        args.remove(index);
+        args.remove(index);
+    }
+
+    // --profile <file>, profile the code run and write the report on quit
+    let mut profile = None;
+    if let Some(index) = args.iter().position(|s| *s == "--profile") {
+        profile = Some(args[index+1].clone());
+
         args.remove(index);
         args.remove(index);
     }
@@ -45,26 +223,225 @@ fn parse_args() -> Config {
     return Config {
         rom_path,
         is_debug,
-        has_breakpoint,
-        breakpoint_addr,
-        screen_mult
+        breakpoints,
+        screen_mult,
+        rewind_budget,
+        speed,
+        is_uncapped,
+        record_movie,
+        record_from_slot,
+        play_movie,
+        link_listen,
+        link_connect,
+        printer_dir,
+        save_dir,
+        is_lenient,
+        patches,
+        cheats_file,
+        symbols_file,
+        trace_file,
+        trace_reference,
+        is_ly_stubbed,
+        bus_log,
+        profile
+    };
+}
+
+/* gb info <rom> [--json], print the cartridge header without running the ROM */
+fn info(args :&[String]) {
+    let is_json = args.contains(&"--json".to_string());
+    let Some(path) = args.iter().find(|s| *s != "--json") else {
+        println!("Usage: gb info <rom> [--json]");
+        return;
     };
+
+    let rom = match read_rom(path) {
+        Ok((rom, _)) if rom.len() < 0x150 => Err(LoadError::TooShort(rom.len())),
+        Ok((rom, _)) => Ok(rom),
+        Err(err) => Err(err)
+    };
+    let rom = match rom {
+        Ok(rom) => rom,
+        Err(err) => {
+            eprintln!("Couldn't load {}: {}", path, err);
+            std::process::exit(1);
+        }
+    };
+
+    let header = CartridgeHeader::parse(&rom);
+    if is_json {
+        println!("{}", serde_json::to_string_pretty(&header.to_json(&rom)).unwrap());
+        return;
+    }
+
+    println!("{}", header);
+    if header.header_checksum != header_checksum(&rom) {
+        println!("Bad header checksum, 0x{:02X} computed", header_checksum(&rom));
+    }
+    if header.global_checksum != global_checksum(&rom) {
+        println!("Bad global checksum, 0x{:04X} computed", global_checksum(&rom));
+    }
 }
 
 fn main() {
+    let args :Vec<String> = env::args().collect();
+    if args.get(1).is_some_and(|s| s == "info") {
+        info(&args[2..]);
+        return;
+    }
+
     let config = parse_args();
+    let load_options = LoadOptions {
+        is_lenient: config.is_lenient,
+        patches: config.patches.clone(),
+        ..Default::default()
+    };
 
-    let mut gbemu = gb::gbemulator::GBEmulator::new(
+    #[cfg(feature = "sdl")]
+    let gbemu = gb::gbemulator::GBEmulator::new(
+        &config.rom_path,
+        config.screen_mult,
+        &load_options
+    );
+    // Without SDL there is nothing to display, but the ROM can still be run
+    #[cfg(not(feature = "sdl"))]
+    let gbemu = gb::gbemulator::GBEmulator::new_headless(
         &config.rom_path,
-        config.screen_mult
+        config.screen_mult,
+        &load_options
     );
 
+    let mut gbemu = match gbemu {
+        Ok(gbemu) => gbemu,
+        Err(err) => {
+            eprintln!("Couldn't load {}: {}", config.rom_path, err);
+            std::process::exit(1);
+        }
+    };
+
+    if let Some(dir) = &config.save_dir {
+        gbemu.set_save_dir(dir);
+    }
+
+    // The default cheat file is optional
+    let cheats_file = config.cheats_file.clone().unwrap_or_else(|| gbemu.cheats_path());
+    if config.cheats_file.is_some() || std::path::Path::new(&cheats_file).exists() {
+        match gbemu.load_cheats(&cheats_file) {
+            Ok(()) => println!("Loaded cheats from {}", cheats_file),
+            Err(err) => { println!("Couldn't load cheats from {}: {}", cheats_file, err); return; }
+        }
+    }
+
+    gbemu.set_rewind_budget(config.rewind_budget);
+    gbemu.set_speed(config.speed);
+    gbemu.set_uncapped(config.is_uncapped);
+
+    let link = if let Some(addr) = &config.link_listen {
+        println!("Waiting for the link cable on {}", addr);
+        Some(TcpLink::listen(addr.as_str()))
+    } else {
+        config.link_connect.as_ref().map(|addr| TcpLink::connect(addr.as_str()))
+    };
+
+    match link {
+        Some(Ok(link)) => gbemu.set_link(Box::new(link)),
+        Some(Err(err)) => { println!("Couldn't connect the link cable: {}", err); return; },
+        None => {}
+    }
+
+    if let Some(dir) = &config.printer_dir {
+        gbemu.set_link(Box::new(Printer::new(dir)));
+    }
+
+    if config.trace_file.is_some() || config.trace_reference.is_some() {
+        match Tracer::open(config.trace_file.as_deref(), config.trace_reference.as_deref()) {
+            Ok(tracer) => gbemu.set_tracer(tracer),
+            Err(err) => { println!("Couldn't open the trace: {}", err); return; }
+        }
+    }
+    if config.is_ly_stubbed {
+        gbemu.get_bus().borrow_mut().set_ly_stub(Some(0x90));
+    }
+    if let Some(path) = &config.bus_log {
+        gbemu.set_bus_log(path, recorder::DEFAULT_CAPACITY);
+    }
+    if let Some(path) = &config.profile {
+        // The routines are named after the same symbols as in the debugger
+        let symbols_file = config.symbols_file.clone().unwrap_or_else(|| gbemu.symbols_path());
+        let symbols = if config.symbols_file.is_some() || std::path::Path::new(&symbols_file).exists() {
+            match Symbols::load(&symbols_file) {
+                Ok(symbols) => symbols,
+                Err(err) => { println!("Couldn't load symbols from {}: {}", symbols_file, err); return; }
+            }
+        } else {
+            Symbols::new()
+        };
+        gbemu.set_profiler(Profiler::new(symbols), path);
+    }
+
+    // Quit cleanly on SIGINT/SIGTERM, saving the cartridge RAM
+    let quit = Arc::new(AtomicBool::new(false));
+    for signal in [SIGINT, SIGTERM] {
+        if let Err(err) = signal_hook::flag::register(signal, quit.clone()) {
+            println!("Couldn't handle signal {}: {}", signal, err);
+        }
+    }
+    gbemu.set_quit_signal(quit);
+
     if config.is_debug {
-        let mut debugger = Debugger::new(gbemu, config.has_breakpoint, config.breakpoint_addr);
+        let symbols_file = config.symbols_file.clone().unwrap_or_else(|| gbemu.symbols_path());
+        let mut debugger = Debugger::new(gbemu);
+
+        if config.symbols_file.is_some() || std::path::Path::new(&symbols_file).exists() {
+            match debugger.load_symbols(&symbols_file) {
+                Ok(n) => println!("Loaded {} symbols from {}", n, symbols_file),
+                Err(err) => { println!("Couldn't load symbols from {}: {}", symbols_file, err); return; }
+            }
+        }
+
+        for spec in &config.breakpoints {
+            match Breakpoint::parse_with_symbols(spec, debugger.symbols()) {
+                Ok(breakpoint) => debugger.breakpoints_mut().add(breakpoint),
+                Err(err) => { println!("Bad breakpoint {}: {}", spec, err); return; }
+            }
+        }
+        // Don't stop before the first one
+        if !config.breakpoints.is_empty() {
+            debugger.breakpoints_mut().set_mode(RunMode::Continue);
+        }
         debugger.init();
         debugger.run();
     } else {
         gbemu.init();
-        gbemu.run();   
+
+        if let Some(path) = &config.play_movie {
+            let movie = Movie::load(path, gbemu.rom_md5())
+                .and_then(|movie| gbemu.play_movie(movie));
+
+            if let Err(err) = movie {
+                println!("Couldn't play {}: {}", path, err);
+                return;
+            }
+        }
+
+        if config.record_movie.is_some() {
+            if let Some(slot) = config.record_from_slot {
+                if let Err(err) = gbemu.load_state_slot(slot) {
+                    println!("Couldn't load state {}: {}", slot, err);
+                    return;
+                }
+            }
+
+            gbemu.record_movie(config.record_from_slot.is_some());
+        }
+
+        gbemu.run();
+
+        if let (Some(path), Some(movie)) = (&config.record_movie, gbemu.stop_movie()) {
+            match movie.save(path) {
+                Ok(()) => println!("Saved movie to {} ({} frames)", path, movie.n_frames()),
+                Err(err) => println!("Couldn't save movie to {}: {}", path, err)
+            }
+        }
     }
 }
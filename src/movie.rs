@@ -0,0 +1,129 @@
+/*
+ * Input movies.
+ *
+ * A movie stores the joypad lines of every frame, so that a play session can be replayed
+ * bit-exactly. It starts either at power-on, with the cartridge RAM and RTC cleared, or from
+ * an embedded save state, and every `hash_interval` frames it stores a hash of the framebuffer
+ * and RAM to detect desyncs:
+ *
+ *   "GBMV" | version: u16 | rom md5: [u8;16] | has state: bool | [state] | hash interval: u32
+ *   | n frames: u32 | (direction: u8, action: u8)... | n hashes: u32 | (frame: u32, [md5])...
+ *
+ * The layout is written with the save state StateWriter/StateReader.
+ */
+
+use crate::savestate::{StateWriter, StateReader, StateError};
+
+const MOVIE_MAGIC :&[u8;4] = b"GBMV";
+pub const MOVIE_VERSION :u16 = 1;
+pub const DEFAULT_HASH_INTERVAL :u32 = 60; // Once per second
+
+pub struct Movie {
+    rom_md5       :[u8;16],
+    start_state   :Option<Vec<u8>>,   // None when the movie starts at power-on
+    hash_interval :u32,
+    inputs        :Vec<(u8, u8)>,     // Joypad direction and action lines of every frame
+    hashes        :Vec<(u32, [u8;16])>
+}
+
+impl Movie {
+    pub fn new(rom_md5 :[u8;16], start_state :Option<Vec<u8>>, hash_interval :u32) -> Movie {
+        return Movie {
+            rom_md5,
+            start_state,
+            hash_interval,
+            inputs: vec![],
+            hashes: vec![]
+        }
+    }
+
+    pub fn start_state(&self)   -> Option<&[u8]> { return self.start_state.as_deref(); }
+    pub fn hash_interval(&self) -> u32           { return self.hash_interval; }
+    pub fn n_frames(&self)      -> u32           { return self.inputs.len() as u32; }
+
+    pub fn input(&self, frame :u32) -> Option<(u8, u8)> {
+        return self.inputs.get(frame as usize).copied();
+    }
+
+    pub fn push_input(&mut self, lines :(u8, u8)) {
+        self.inputs.push(lines);
+    }
+
+    /* Hash stored for a frame, if it is a checkpoint */
+    pub fn hash(&self, frame :u32) -> Option<[u8;16]> {
+        return self.hashes.iter().find(|(f, _)| *f == frame).map(|(_, hash)| *hash);
+    }
+
+    pub fn push_hash(&mut self, frame :u32, hash :[u8;16]) {
+        self.hashes.push((frame, hash));
+    }
+
+    /* Drop every frame from `n_frames` on, e.g. after rewinding while recording */
+    pub fn truncate(&mut self, n_frames :u32) {
+        self.inputs.truncate(n_frames as usize);
+        self.hashes.retain(|(frame, _)| *frame <= n_frames);
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut w = StateWriter::with_magic(MOVIE_MAGIC, MOVIE_VERSION, self.rom_md5);
+
+        w.bool(self.start_state.is_some());
+        if let Some(state) = &self.start_state {
+            w.bytes(state);
+        }
+
+        w.u32(self.hash_interval);
+
+        w.u32(self.inputs.len() as u32);
+        for (direction, action) in self.inputs.iter() {
+            w.u8(*direction);
+            w.u8(*action);
+        }
+
+        w.u32(self.hashes.len() as u32);
+        for (frame, hash) in self.hashes.iter() {
+            w.u32(*frame);
+            w.bytes(hash);
+        }
+
+        return w.into_bytes();
+    }
+
+    pub fn from_bytes(data :&[u8], rom_md5 :[u8;16]) -> Result<Movie, StateError> {
+        let mut r = StateReader::with_magic(data, MOVIE_MAGIC, MOVIE_VERSION, rom_md5)?;
+
+        let start_state = if r.bool()? { Some(r.bytes()?) } else { None };
+        let hash_interval = r.u32()?;
+        if hash_interval == 0 {
+            return Err(StateError::Corrupt("hash interval of 0 frames".to_string()));
+        }
+        let mut movie = Movie::new(rom_md5, start_state, hash_interval);
+
+        for _ in 0..r.u32()? {
+            movie.inputs.push((r.u8()?, r.u8()?));
+        }
+
+        for _ in 0..r.u32()? {
+            let frame = r.u32()?;
+            let mut hash = [0;16];
+            r.bytes_into(&mut hash)?;
+
+            movie.hashes.push((frame, hash));
+        }
+
+        if !r.is_done() {
+            return Err(StateError::Corrupt("trailing data".to_string()));
+        }
+
+        return Ok(movie);
+    }
+
+    pub fn save(&self, path :&str) -> Result<(), StateError> {
+        std::fs::write(path, self.to_bytes())?;
+        return Ok(());
+    }
+
+    pub fn load(path :&str, rom_md5 :[u8;16]) -> Result<Movie, StateError> {
+        return Movie::from_bytes(&std::fs::read(path)?, rom_md5);
+    }
+}
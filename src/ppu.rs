@@ -1,11 +1,10 @@
 use std::rc::Rc;
 use std::cell::RefCell;
 
-extern crate sdl2;
-
-use crate::screen::Screen;
+use crate::frontend::VideoSink;
 use crate::interruptManager::InterruptManager;
 use crate::consts::*;
+use crate::savestate::{Savestate, StateWriter, StateReader, StateError};
 
 const TILE_W    :u16 = 8;
 const TILE_H    :u16 = 8;
@@ -95,7 +94,8 @@ impl Pixel {
 }
 
 pub struct PPU {
-    screen      : Rc<RefCell<Screen>>,
+    video       : Rc<RefCell<dyn VideoSink>>,
+    framebuffer : [u8;(SCREEN_WIDTH*SCREEN_HEIGHT) as usize], // Shade of every pixel in the frame
     vram        : [u8;0x2000],  // 0x8000 - 0x9FFF
     oam         : [u8;0xA0],    // 0xFE00 - 0xFE9F
     current_dot : u16,
@@ -113,12 +113,16 @@ pub struct PPU {
     // Skip first frame at the start or after the ppu has been disabled
     // (helps avoiding artifacts on tests)
     has_drawn_first_frame: bool,
+
+    // Set when VBlank starts, until the emulator takes the frame
+    is_frame_done: bool,
 }
 
 impl PPU {
-    pub fn new(screen :Rc<RefCell<Screen>>, int :Rc<RefCell<InterruptManager>>) -> PPU {
+    pub fn new(video :Rc<RefCell<dyn VideoSink>>, int :Rc<RefCell<InterruptManager>>) -> PPU {
         return PPU {
-            screen,
+            video,
+            framebuffer : [0;(SCREEN_WIDTH*SCREEN_HEIGHT) as usize],
             vram        : [0;0x2000],
             oam         : [0;(OAM_END-OAM_START+1) as usize],
             current_dot : 0,
@@ -136,6 +140,8 @@ impl PPU {
             is_oam_dma       : false,
 
             has_drawn_first_frame : false,
+
+            is_frame_done    : false,
         };
     }
 
@@ -191,6 +197,7 @@ impl PPU {
             self.window_counter = 0;
             self.set_mode(STATMode::VBlank);
             self.int.borrow_mut().request_interrupt(Interrupt::VBlank);
+            self.is_frame_done = true;
         }
         // Reset LY
         else if self.ly == 154 {
@@ -430,6 +437,55 @@ impl PPU {
         }
     }
 
+    fn get_pixel_shade(&self, pixel :Pixel) -> u8 {
+        let palette = match pixel.get_palette() {
+            Palette::BGP  => self.bgp,
+            Palette::OBP0 => self.obp0,
+            Palette::OBP1 => self.obp1
+        };
+
+        return (palette >> (2*pixel.get_id())) & 3;
+    }
+
+    pub fn framebuffer(&self) -> &[u8] { return &self.framebuffer; }
+
+    // Whatever the mode, for the debugger
+    pub fn vram_data(&self) -> &[u8] { return &self.vram; }
+    pub fn oam_data(&self) -> &[u8] { return &self.oam; }
+    pub fn vram_data_mut(&mut self) -> &mut [u8] { return &mut self.vram; }
+    pub fn oam_data_mut(&mut self) -> &mut [u8] { return &mut self.oam; }
+
+    /* True during the first dot of VBlank */
+    pub fn is_vblank_start(&self) -> bool {
+        return self.is_lcd_enabled() && self.ly == 144 && self.current_dot == 0;
+    }
+
+    /* Returns true once per frame, when the PPU has entered VBlank */
+    pub fn take_frame_done(&mut self) -> bool {
+        let is_frame_done = self.is_frame_done;
+        self.is_frame_done = false;
+
+        return is_frame_done;
+    }
+
+    /* Send the framebuffer again, e.g. after loading a state */
+    pub fn redraw(&self) {
+        self.video.borrow_mut().draw_frame(&self.framebuffer);
+    }
+
+    /* Copy the linebuffer to the framebuffer and send the frame when the last line is drawn */
+    fn draw_linebuffer(&mut self) {
+        let offset = self.ly as usize * SCREEN_WIDTH as usize;
+
+        for x in 0..SCREEN_WIDTH as usize {
+            self.framebuffer[offset + x] = self.get_pixel_shade(self.linebuffer[x]);
+        }
+
+        if self.ly as u16 == SCREEN_HEIGHT-1 {
+            self.video.borrow_mut().draw_frame(&self.framebuffer);
+        }
+    }
+
     fn render_scanline(&mut self) {
         if self.is_lcd_enabled() {
             self.linebuffer.clear();
@@ -451,9 +507,7 @@ impl PPU {
             }
 
             if self.has_drawn_first_frame {
-                self.screen.borrow_mut().draw_linebuffer(
-                    &self.linebuffer, self.ly, self.bgp, self.obp0, self.obp1
-                );
+                self.draw_linebuffer();
             }
         } else {
             self.has_drawn_first_frame = false;
@@ -467,7 +521,7 @@ impl PPU {
 
         // Vblank: Increase LY every 456 dots
         if self.ly >= 144 {
-            if self.current_dot == 457 {
+            if self.current_dot == 456 {
                 self.write_ly(self.ly+1);
             }
         }
@@ -493,8 +547,8 @@ impl PPU {
                 },
                 // HBlank: 85...208 dots
                 372 => self.set_mode(STATMode::HBlank),
-                // 456: End of scanline
-                457 => self.write_ly(self.ly+1),
+                // 456: End of scanline, so that a frame takes 154*456 = 70224 dots
+                456 => self.write_ly(self.ly+1),
                 _ => {}
             }
         }
@@ -552,3 +606,84 @@ impl ComponentWithMemory for PPU {
         }
     }
 }
+
+impl Savestate for PPU {
+    fn save_state(&self, w :&mut StateWriter) {
+        w.bytes(&self.framebuffer);
+        w.bytes(&self.vram);
+        w.bytes(&self.oam);
+        w.u16(self.current_dot);
+
+        w.u32(self.linebuffer.len() as u32);
+        for px in self.linebuffer.iter() {
+            w.u8(px.color_id);
+            w.u8(px.palette as u8);
+        }
+
+        w.u32(self.line_objs.len() as u32);
+        for obj in self.line_objs.iter() {
+            let flags = ((obj.flags.bg_over_obj as u8) << 3) | ((obj.flags.y_flip as u8) << 2)
+                      | ((obj.flags.x_flip as u8) << 1) | (obj.flags.palette_n as u8);
+
+            w.u8(obj.y);
+            w.u8(obj.x);
+            w.u8(obj.tile_index);
+            w.u8(flags);
+        }
+
+        w.u8(self.ly);   w.u8(self.lyc);  w.u8(self.scx);  w.u8(self.scy);
+        w.u8(self.wx);   w.u8(self.wy);   w.u8(self.ldcd); w.u8(self.stat);
+        w.u8(self.bgp);  w.u8(self.obp0); w.u8(self.obp1);
+
+        w.bool(self.is_window_enable);
+        w.u8(self.window_counter);
+        w.bool(self.is_oam_dma);
+        w.bool(self.has_drawn_first_frame);
+    }
+
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError> {
+        r.bytes_into(&mut self.framebuffer)?;
+        r.bytes_into(&mut self.vram)?;
+        r.bytes_into(&mut self.oam)?;
+        self.current_dot = r.u16()?;
+
+        self.linebuffer.clear();
+        for _ in 0..r.u32()? {
+            let color_id = r.u8()?;
+            let palette = match r.u8()? {
+                0 => Palette::BGP,
+                1 => Palette::OBP0,
+                2 => Palette::OBP1,
+                n => return Err(StateError::Corrupt(format!("invalid palette {}", n)))
+            };
+
+            self.linebuffer.push(Pixel { color_id, palette });
+        }
+
+        self.line_objs.clear();
+        for _ in 0..r.u32()? {
+            let y          = r.u8()?;
+            let x          = r.u8()?;
+            let tile_index = r.u8()?;
+            let flags      = r.u8()?;
+
+            self.line_objs.push(Object { y, x, tile_index, flags: ObjFlags {
+                bg_over_obj: flags & 0b1000 != 0,
+                y_flip:      flags & 0b0100 != 0,
+                x_flip:      flags & 0b0010 != 0,
+                palette_n:   flags & 0b0001 != 0
+            }});
+        }
+
+        self.ly   = r.u8()?; self.lyc  = r.u8()?; self.scx  = r.u8()?; self.scy  = r.u8()?;
+        self.wx   = r.u8()?; self.wy   = r.u8()?; self.ldcd = r.u8()?; self.stat = r.u8()?;
+        self.bgp  = r.u8()?; self.obp0 = r.u8()?; self.obp1 = r.u8()?;
+
+        self.is_window_enable = r.bool()?;
+        self.window_counter = r.u8()?;
+        self.is_oam_dma = r.bool()?;
+        self.has_drawn_first_frame = r.bool()?;
+
+        return Ok(());
+    }
+}
@@ -0,0 +1,335 @@
+/*
+ * Profiler of where the M-cycles go.
+ *
+ * The M-cycles of every instruction are counted at its bank and address, and for the routine
+ * running it. Routines follow a shadow of the call stack: a taken CALL or RST and an interrupt
+ * dispatch push a frame, popped once SP is back above its return address, be it by RET, RETI or
+ * by code dropping the address. The cycles spent halted count for the HALT, and the 5 M-cycles of
+ * an interrupt dispatch for the first instruction of the handler.
+ *
+ * Frames start with VBlank, and the time spent in the VBlank handler, what it calls included, is
+ * kept for each of them.
+ */
+
+use std::collections::HashMap;
+use std::fmt;
+
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::consts::*;
+use crate::debugger::breakpoints::{CALL_OPCODES, is_rst};
+use crate::debugger::symbols::Symbols;
+
+// VBlank lasts 10 lines of 114 M-cycles, and a frame 154
+pub const VBLANK_M_CYCLES :u64 = 10 * 114;
+pub const FRAME_M_CYCLES  :u64 = 154 * 114;
+
+const VBLANK_VECTOR :u16 = 0x0040;
+
+// Lines of the report
+const HOT_INSTRS :usize = 20;
+const WORST_FRAMES :usize = 10;
+const MIN_NODE_SHARE :u64 = 1000; // Call graph nodes under 1/1000 of the cycles are left out
+
+/* Where code runs. Outside of the ROM the bank is 0, the RAM banks not being followed. */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Location {
+    pub bank :u16,
+    pub addr :u16,
+}
+
+impl Location {
+    pub fn at(bus :&Bus, addr :u16) -> Location {
+        let bank = if addr <= BANK1_END { bus.rom_bank(addr) } else { 0 };
+        return Location { bank, addr };
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:02X}:{:04X}", self.bank, self.addr)
+    }
+}
+
+/* A routine in the call graph, for one path of calls to it */
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub routine      :Option<Location>, // None for the top level
+    pub parent       :Option<usize>,
+    pub children     :Vec<usize>,
+    pub self_cycles  :u64,
+    pub calls        :u64,
+    pub is_interrupt :bool,
+}
+
+struct Frame {
+    node      :usize,
+    return_sp :u16, // SP once it returns
+    is_vblank :bool,
+}
+
+/* Instruction being run */
+#[derive(Clone, Copy)]
+struct Instr {
+    location :Location,
+    opcode   :u8,
+    sp       :u16, // Before it
+}
+
+pub struct Profiler {
+    symbols        :Symbols,
+    instrs         :HashMap<Location, u64>, // M-cycles per instruction
+    nodes          :Vec<Node>,              // Call graph, the top level first
+    node_index     :HashMap<(usize, Location, bool), usize>, // By parent, routine and interrupt
+    stack          :Vec<Frame>,
+    current        :Option<Instr>,
+    t_cycles       :u64,  // Since the current instruction started
+    is_halted      :bool,
+    is_dispatching :bool, // An interrupt is being dispatched
+    frames         :Vec<u64>, // M-cycles in the VBlank handler per frame
+}
+
+impl Profiler {
+    /* The symbols name the routines in the report */
+    pub fn new(symbols :Symbols) -> Profiler {
+        let top = Node { routine: None, parent: None, children: vec![], self_cycles: 0, calls: 0, is_interrupt: false };
+
+        return Profiler {
+            symbols,
+            instrs: HashMap::new(),
+            nodes: vec![top],
+            node_index: HashMap::new(),
+            stack: vec![Frame { node: 0, return_sp: 0, is_vblank: false }],
+            current: None,
+            t_cycles: 0,
+            is_halted: false,
+            is_dispatching: false,
+            frames: vec![],
+        };
+    }
+
+    pub fn instrs(&self) -> &HashMap<Location, u64> { return &self.instrs; }
+    pub fn nodes(&self) -> &[Node] { return &self.nodes; }
+    pub fn frames(&self) -> &[u64] { return &self.frames; }
+    /* Routines on the shadow stack, the innermost last */
+    pub fn stack(&self) -> Vec<Location> {
+        return self.stack.iter().filter_map(|frame| self.nodes[frame.node].routine).collect();
+    }
+
+    pub fn total_cycles(&self) -> u64 {
+        return self.nodes.iter().map(|node| node.self_cycles).sum();
+    }
+
+    /*
+     * Called before every tick. When an instruction is about to run, the cycles since the last one
+     * are counted for it, like trace.rs finds them.
+     */
+    pub fn tick(&mut self, cpu :&CPU, bus :&Bus) {
+        if bus.is_vblank_start() {
+            self.frames.push(0);
+        }
+
+        let is_about_to_run = if cpu.is_new_instr() {
+            self.is_halted = cpu.is_halted();
+            !cpu.is_halted()
+        } else if self.is_halted && !cpu.is_halted() {
+            self.is_halted = false;
+            true
+        } else {
+            false
+        };
+
+        if is_about_to_run {
+            self.next_instr(cpu, bus);
+        }
+        self.t_cycles += 1;
+    }
+
+    fn next_instr(&mut self, cpu :&CPU, bus :&Bus) {
+        let m_cycles = std::mem::replace(&mut self.t_cycles, 0) / 4;
+        let sp = cpu.get_sp();
+
+        // Before a dispatch PC is back at the prefetched opcode, where the handler returns
+        let addr = if cpu.is_interrupt_next() { cpu.get_pc() } else { cpu.instr_addr() };
+        let location = Location::at(bus, addr);
+
+        if self.is_dispatching {
+            // The handler's frame, returning where SP was before the dispatch
+            self.is_dispatching = false;
+            self.push(location, sp.wrapping_add(2), true);
+            self.count(location, m_cycles);
+        } else if let Some(instr) = self.current {
+            self.count(instr.location, m_cycles);
+            self.follow_stack(instr, sp, location);
+        }
+
+        if cpu.is_interrupt_next() {
+            self.is_dispatching = true;
+            self.current = None;
+        } else {
+            self.current = Some(Instr { location, opcode: cpu.get_opcode(), sp });
+        }
+    }
+
+    /* After an instruction ran, with SP now at sp and the next one at next */
+    fn follow_stack(&mut self, instr :Instr, sp :u16, next :Location) {
+        while self.stack.len() > 1 && self.stack.last().unwrap().return_sp <= sp {
+            self.stack.pop();
+        }
+
+        let is_call = CALL_OPCODES.contains(&instr.opcode) || is_rst(instr.opcode);
+        if is_call && sp == instr.sp.wrapping_sub(2) {
+            self.push(next, instr.sp, false);
+        }
+    }
+
+    fn push(&mut self, routine :Location, return_sp :u16, is_interrupt :bool) {
+        let parent = self.stack.last().unwrap().node;
+        let node = match self.node_index.get(&(parent, routine, is_interrupt)) {
+            Some(node) => *node,
+            None => {
+                self.nodes.push(Node {
+                    routine: Some(routine), parent: Some(parent), children: vec![],
+                    self_cycles: 0, calls: 0, is_interrupt,
+                });
+                let node = self.nodes.len() - 1;
+                self.nodes[parent].children.push(node);
+                self.node_index.insert((parent, routine, is_interrupt), node);
+                node
+            }
+        };
+
+        self.nodes[node].calls += 1;
+        let is_vblank = is_interrupt && routine.addr == VBLANK_VECTOR;
+        self.stack.push(Frame { node, return_sp, is_vblank });
+    }
+
+    fn count(&mut self, location :Location, m_cycles :u64) {
+        *self.instrs.entry(location).or_default() += m_cycles;
+        let node = self.stack.last().unwrap().node;
+        self.nodes[node].self_cycles += m_cycles;
+
+        if self.stack.iter().any(|frame| frame.is_vblank) {
+            if let Some(frame) = self.frames.last_mut() {
+                *frame += m_cycles;
+            }
+        }
+    }
+
+    /* Totals of the nodes, what they call included */
+    fn node_totals(&self) -> Vec<u64> {
+        let mut totals :Vec<u64> = self.nodes.iter().map(|node| node.self_cycles).collect();
+
+        // Children come after their parent
+        for (i, node) in self.nodes.iter().enumerate().rev() {
+            if let Some(parent) = node.parent {
+                totals[parent] += totals[i];
+            }
+        }
+        return totals;
+    }
+
+    fn routine_name(&self, routine :Option<Location>) -> String {
+        return match routine {
+            Some(location) => match self.symbols.label(location.bank, location.addr) {
+                Some(label) => label.to_string(),
+                None => location.to_string()
+            },
+            None => "(top level)".to_string()
+        };
+    }
+
+    fn instr_name(&self, location :Location) -> String {
+        return match self.symbols.label_before(location.bank, location.addr) {
+            Some((label, 0)) => label.to_string(),
+            Some((label, offset)) => format!("{}+{}", label, offset),
+            None => String::new()
+        };
+    }
+
+    /* Flat and hierarchical reports, then the time spent in the VBlank handler per frame */
+    pub fn report(&self) -> String {
+        let total = self.total_cycles();
+        let totals = self.node_totals();
+        let percent = |cycles :u64| if total == 0 { 0.0 } else { cycles as f64 * 100.0 / total as f64 };
+        let mut text = format!("Profile of {} M-cycles, {:.1} frames\n",
+            total, total as f64 / FRAME_M_CYCLES as f64);
+
+        // Routines, their recursive calls counted once in the total
+        let mut routines :HashMap<Option<Location>, (u64, u64, u64)> = HashMap::new();
+        for (i, node) in self.nodes.iter().enumerate() {
+            let entry = routines.entry(node.routine).or_default();
+            entry.0 += node.self_cycles;
+            entry.2 += node.calls;
+
+            let mut parent = node.parent;
+            while let Some(p) = parent.filter(|p| self.nodes[*p].routine != node.routine) {
+                parent = self.nodes[p].parent;
+            }
+            if parent.is_none() {
+                entry.1 += totals[i];
+            }
+        }
+        let mut routines :Vec<(Option<Location>, (u64, u64, u64))> = routines.into_iter().collect();
+        routines.sort_by_key(|(routine, (self_cycles, _, _))| (std::cmp::Reverse(*self_cycles), *routine));
+
+        text += "\nRoutines\n      self       %      total       %     calls  routine\n";
+        for (routine, (self_cycles, routine_total, calls)) in routines {
+            text += &format!("{:>10}  {:5.1}%  {:>9}  {:5.1}%  {:>8}  {}\n", self_cycles, percent(self_cycles),
+                routine_total, percent(routine_total), calls, self.routine_name(routine));
+        }
+
+        let mut instrs :Vec<(&Location, &u64)> = self.instrs.iter().collect();
+        instrs.sort_by_key(|(location, cycles)| (std::cmp::Reverse(**cycles), **location));
+
+        text += "\nInstructions\n    cycles       %  location\n";
+        for (location, cycles) in instrs.into_iter().take(HOT_INSTRS) {
+            text += &format!("{:>10}  {:5.1}%  {}  {}\n", cycles, percent(*cycles), location, self.instr_name(*location));
+        }
+
+        text += "\nCall graph\n     total       %       self     calls  routine\n";
+        let mut pending = vec![(0, 0)];
+        while let Some((i, depth)) = pending.pop() {
+            let node = &self.nodes[i];
+            text += &format!("{:>10}  {:5.1}%  {:>9}  {:>8}  {}{}{}\n", totals[i], percent(totals[i]), node.self_cycles,
+                node.calls, "  ".repeat(depth), self.routine_name(node.routine), if node.is_interrupt {" (interrupt)"} else {""});
+
+            let mut children :Vec<usize> = node.children.iter().copied()
+                .filter(|child| totals[*child] * MIN_NODE_SHARE >= total && totals[*child] > 0)
+                .collect();
+            children.sort_by_key(|child| totals[*child]); // The biggest last, popped first
+            pending.extend(children.into_iter().map(|child| (child, depth + 1)));
+        }
+
+        text += &self.vblank_report();
+        return text;
+    }
+
+    fn vblank_report(&self) -> String {
+        let handled :Vec<u64> = self.frames.iter().copied().filter(|cycles| *cycles > 0).collect();
+        if handled.is_empty() {
+            return format!("\nVBlank handler\nNot run in {} frames\n", self.frames.len());
+        }
+
+        let mean = handled.iter().sum::<u64>() / handled.len() as u64;
+        let n_over = handled.iter().filter(|cycles| **cycles > VBLANK_M_CYCLES).count();
+        let mut text = format!("\nVBlank handler\nRun in {} of {} frames: mean {}, min {}, max {} M-cycles, over the {} of VBlank in {}\n",
+            handled.len(), self.frames.len(), mean, handled.iter().min().unwrap(), handled.iter().max().unwrap(),
+            VBLANK_M_CYCLES, n_over);
+
+        let mut worst :Vec<(usize, u64)> = self.frames.iter().copied().enumerate().collect();
+        worst.sort_by_key(|(frame, cycles)| (std::cmp::Reverse(*cycles), *frame));
+        text += "     frame     cycles  of VBlank\n";
+        for (frame, cycles) in worst.into_iter().take(WORST_FRAMES).filter(|(_, cycles)| *cycles > 0) {
+            text += &format!("{:>10}  {:>9}  {:8.1}%\n", frame + 1, cycles, cycles as f64 * 100.0 / VBLANK_M_CYCLES as f64);
+        }
+
+        text += "\nPer frame\n";
+        for (i, line) in self.frames.chunks(10).enumerate() {
+            let cycles :Vec<String> = line.iter().map(|cycles| format!("{:>6}", cycles)).collect();
+            text += &format!("{:>10}  {}\n", i*10 + 1, cycles.join(""));
+        }
+
+        return text;
+    }
+}
@@ -0,0 +1,167 @@
+/*
+ * Binary save states.
+ *
+ * Every component writes its fields in a fixed order with StateWriter and reads them back
+ * in the same order with StateReader. A state starts with a header containing a magic
+ * number, the format version and the MD5 of the ROM it was taken from:
+ *
+ *   "GBSS" | version: u16 | rom md5: [u8;16] | component data...
+ *
+ * All the integers are little endian. Bump SAVESTATE_VERSION every time the layout of
+ * any component changes.
+ */
+
+use std::fmt;
+
+const SAVESTATE_MAGIC :&[u8;4] = b"GBSS";
+pub const SAVESTATE_VERSION :u16 = 4;
+
+#[derive(Debug)]
+pub enum StateError {
+    Io(std::io::Error),
+    BadMagic,
+    Version(u16),      // Version of the rejected state
+    RomMismatch,       // The state was taken from another ROM
+    Truncated,
+    Corrupt(String)
+}
+
+impl fmt::Display for StateError {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            StateError::Io(err)     => write!(f, "I/O error: {}", err),
+            StateError::BadMagic    => write!(f, "not a save state"),
+            StateError::Version(v)  => write!(f, "unsupported version {}", v),
+            StateError::RomMismatch => write!(f, "the save state belongs to a different ROM"),
+            StateError::Truncated   => write!(f, "the save state is truncated"),
+            StateError::Corrupt(s)  => write!(f, "corrupt save state: {}", s)
+        }
+    }
+}
+
+impl From<std::io::Error> for StateError {
+    fn from(err :std::io::Error) -> StateError {
+        return StateError::Io(err);
+    }
+}
+
+/* Implemented by every component that has to be saved */
+pub trait Savestate {
+    fn save_state(&self, w :&mut StateWriter);
+    fn load_state(&mut self, r :&mut StateReader) -> Result<(), StateError>;
+}
+
+#[derive(Default)]
+pub struct StateWriter {
+    buf :Vec<u8>
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        return StateWriter { buf: vec![] };
+    }
+
+    /* Start a state with its header */
+    pub fn with_header(rom_md5 :[u8;16]) -> StateWriter {
+        return StateWriter::with_magic(SAVESTATE_MAGIC, SAVESTATE_VERSION, rom_md5);
+    }
+
+    /* Same header layout, for other files tied to a ROM */
+    pub fn with_magic(magic :&[u8;4], version :u16, rom_md5 :[u8;16]) -> StateWriter {
+        let mut w = StateWriter::new();
+
+        w.buf.extend_from_slice(magic);
+        w.u16(version);
+        w.buf.extend_from_slice(&rom_md5);
+
+        return w;
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> { return self.buf; }
+
+    pub fn u8(&mut self, val :u8)     { self.buf.push(val); }
+    pub fn bool(&mut self, val :bool) { self.buf.push(val as u8); }
+    pub fn u16(&mut self, val :u16)   { self.buf.extend_from_slice(&val.to_le_bytes()); }
+    pub fn u32(&mut self, val :u32)   { self.buf.extend_from_slice(&val.to_le_bytes()); }
+    pub fn u64(&mut self, val :u64)   { self.buf.extend_from_slice(&val.to_le_bytes()); }
+    pub fn i64(&mut self, val :i64)   { self.buf.extend_from_slice(&val.to_le_bytes()); }
+
+    /* Length-prefixed byte array */
+    pub fn bytes(&mut self, val :&[u8]) {
+        self.u32(val.len() as u32);
+        self.buf.extend_from_slice(val);
+    }
+}
+
+pub struct StateReader<'a> {
+    buf :&'a [u8],
+    pos :usize
+}
+
+impl<'a> StateReader<'a> {
+    pub fn new(buf :&'a [u8]) -> StateReader<'a> {
+        return StateReader { buf, pos: 0 };
+    }
+
+    /* Validate the header of a state. Returns a reader placed after it. */
+    pub fn with_header(buf :&'a [u8], rom_md5 :[u8;16]) -> Result<StateReader<'a>, StateError> {
+        return StateReader::with_magic(buf, SAVESTATE_MAGIC, SAVESTATE_VERSION, rom_md5);
+    }
+
+    pub fn with_magic(buf :&'a [u8], magic :&[u8;4], expected_version :u16, rom_md5 :[u8;16])
+        -> Result<StateReader<'a>, StateError> {
+        let mut r = StateReader::new(buf);
+
+        if r.take(4).map_err(|_| StateError::BadMagic)? != magic {
+            return Err(StateError::BadMagic);
+        }
+
+        let version = r.u16()?;
+        if version != expected_version {
+            return Err(StateError::Version(version));
+        }
+
+        if r.take(16)? != rom_md5 {
+            return Err(StateError::RomMismatch);
+        }
+
+        return Ok(r);
+    }
+
+    pub fn is_done(&self) -> bool { return self.pos == self.buf.len(); }
+
+    fn take(&mut self, n :usize) -> Result<&'a [u8], StateError> {
+        if self.pos + n > self.buf.len() {
+            return Err(StateError::Truncated);
+        }
+
+        let slice = &self.buf[self.pos..self.pos+n];
+        self.pos += n;
+
+        return Ok(slice);
+    }
+
+    pub fn u8(&mut self)   -> Result<u8, StateError>   { return Ok(self.take(1)?[0]); }
+    pub fn bool(&mut self) -> Result<bool, StateError> { return Ok(self.u8()? != 0); }
+    pub fn u16(&mut self)  -> Result<u16, StateError>  { return Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap())); }
+    pub fn u32(&mut self)  -> Result<u32, StateError>  { return Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap())); }
+    pub fn u64(&mut self)  -> Result<u64, StateError>  { return Ok(u64::from_le_bytes(self.take(8)?.try_into().unwrap())); }
+    pub fn i64(&mut self)  -> Result<i64, StateError>  { return Ok(i64::from_le_bytes(self.take(8)?.try_into().unwrap())); }
+
+    pub fn bytes(&mut self) -> Result<Vec<u8>, StateError> {
+        let len = self.u32()? as usize;
+        return Ok(self.take(len)?.to_vec());
+    }
+
+    /* Read a byte array into a buffer which must have the same length */
+    pub fn bytes_into(&mut self, dst :&mut [u8]) -> Result<(), StateError> {
+        let len = self.u32()? as usize;
+
+        if len != dst.len() {
+            return Err(StateError::Corrupt(format!("expected {} bytes, found {}", dst.len(), len)));
+        }
+
+        dst.copy_from_slice(self.take(len)?);
+        return Ok(());
+    }
+}
@@ -4,7 +4,8 @@ use sdl2::rect::Rect;
 use sdl2::pixels::PixelFormatEnum;
 
 use crate::consts::*;
-use crate::ppu::{Pixel, Palette::*};
+use crate::frontend::{VideoSink, SHADES};
+use crate::debugger::video::Image;
 
 
 pub struct Screen {
@@ -32,19 +33,26 @@ impl Screen {
         }
     }
 
-    pub fn init(&mut self) {
+    fn shade_color(&self, shade :usize) -> Color {
+        let (r, g, b) = SHADES[shade];
+        return Color::RGB(r, g, b);
+    }
+}
+
+impl VideoSink for Screen {
+    fn init(&mut self) {
         self.canvas.set_draw_color(Color::WHITE);
         self.canvas.clear();
         self.canvas.present();
     }
 
-    pub fn clear(&mut self) {
+    fn clear(&mut self) {
         self.canvas.set_draw_color(Color::WHITE);
         self.canvas.clear();
         self.canvas.present();
     }
 
-    pub fn get_pixels(&self) -> Vec<u8> {
+    fn get_pixels(&self) -> Vec<u8> {
         return self.canvas.read_pixels(
             Rect::new(0, 0,
                 (SCREEN_WIDTH*self.screen_mult) as u32, (SCREEN_HEIGHT*self.screen_mult) as u32
@@ -53,50 +61,66 @@ impl Screen {
         ).unwrap()
     }
 
-    fn get_pixel_color_index(&self, pixel :Pixel, bgp :u8, obp0 :u8, obp1 :u8) -> u8 {
-        return match pixel.get_palette() {
-            BGP  => (bgp  >> (2*(pixel.get_id())))&3,
-            OBP0 => (obp0 >> (2*(pixel.get_id())))&3,
-            OBP1 => (obp1 >> (2*(pixel.get_id())))&3
+    fn draw_frame(&mut self, framebuffer :&[u8]) {
+        for y in 0..SCREEN_HEIGHT {
+            for x in 0..SCREEN_WIDTH {
+                let x_mult = self.screen_mult*x;
+                let y_mult = self.screen_mult*y;
+                let id = framebuffer[(y*SCREEN_WIDTH + x) as usize];
+
+                self.rects[id as usize].push(
+                    Rect::new(x_mult as i32, y_mult as i32, self.screen_mult as u32, self.screen_mult as u32)
+                );
+            }
         }
-    }
 
-    pub fn draw_linebuffer(&mut self, linebuffer :&Vec<Pixel>, y :u8, bgp :u8, obp0 :u8, obp1 :u8) {
-        // Temporary linebuffer to prevent drawing if there is no change
-        let mut tmp_linebuffer :[u8;SCREEN_WIDTH as usize] = [0;SCREEN_WIDTH as usize];
+        for shade in 0..4 {
+            let color = self.shade_color(shade);
+            self.canvas.set_draw_color(color);
+            self.canvas.fill_rects(&self.rects[shade]).unwrap();
+        }
 
-        for x in 0..SCREEN_WIDTH {
-            let x_mult = self.screen_mult*x;
-            let y_mult = self.screen_mult*y as u16;
-            let id = self.get_pixel_color_index(linebuffer[x as usize], bgp, obp0, obp1);
+        self.canvas.present();
 
-            self.rects[id as usize].push(
-                Rect::new(x_mult as i32, y_mult as i32, self.screen_mult as u32, self.screen_mult as u32)
-            );
-            tmp_linebuffer[x as usize] = id;
-        }
+        self.rects = vec![vec![], vec![], vec![], vec![]]
+    }
 
-        if y as u16 == SCREEN_HEIGHT-1 {
-            self.draw_frame();
-        }
+    fn set_title_fps(&mut self, fps :u16) {
+        self.canvas.window_mut().set_title(&format!("fps {} | {}", fps, self.rom_path)).unwrap();
     }
+}
 
-    fn draw_frame(&mut self) {
-        self.canvas.set_draw_color(Color::WHITE);
-        self.canvas.fill_rects(&self.rects[0b00]).unwrap();
-        self.canvas.set_draw_color(Color::RGB(160, 160, 160));
-        self.canvas.fill_rects(&self.rects[0b01]).unwrap();
-        self.canvas.set_draw_color(Color::RGB(100, 100, 100));
-        self.canvas.fill_rects(&self.rects[0b10]).unwrap();
-        self.canvas.set_draw_color(Color::BLACK);
-        self.canvas.fill_rects(&self.rects[0b11]).unwrap();
+/* Window of its own showing an image of the debugger, such as the tile viewer, scaled up */
+pub struct ImageWindow {
+    canvas :sdl2::render::Canvas<sdl2::video::Window>,
+    scale  :u32,
+}
 
-        self.canvas.present();
+impl ImageWindow {
+    pub fn new(sdl_context :&sdl2::Sdl, scale :u32) -> Result<ImageWindow, String> {
+        let video_subsystem = sdl_context.video()?;
+        let window = video_subsystem.window("Video", 256*scale, 256*scale)
+            .build()
+            .map_err(|err| err.to_string())?;
+        let canvas = window.into_canvas().build().map_err(|err| err.to_string())?;
 
-        self.rects = vec![vec![], vec![], vec![], vec![]]
+        return Ok(ImageWindow { canvas, scale });
     }
 
-    pub fn set_title_fps(&mut self, fps :u16) {
-        self.canvas.window_mut().set_title(&format!("fps {} | {}", fps, self.rom_path)).unwrap();
+    pub fn draw(&mut self, title :&str, image :&Image) {
+        let (width, height) = (image.width as u32, image.height as u32);
+
+        let window = self.canvas.window_mut();
+        if window.size() != (width*self.scale, height*self.scale) {
+            window.set_size(width*self.scale, height*self.scale).unwrap();
+        }
+        window.set_title(title).unwrap();
+
+        let texture_creator = self.canvas.texture_creator();
+        let mut texture = texture_creator.create_texture_streaming(PixelFormatEnum::RGB24, width, height).unwrap();
+        texture.update(None, &image.pixels, 3*image.width).unwrap();
+
+        self.canvas.copy(&texture, None, None).unwrap();
+        self.canvas.present();
     }
 }
@@ -0,0 +1,199 @@
+/*
+ * Execution traces in the Gameboy Doctor format.
+ *
+ * A line is written before every instruction runs, with the registers and the 4 bytes at PC:
+ *
+ *   A:01 F:B0 B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,13,02
+ *
+ * The trace can also be compared with a reference log as it runs, stopping at the first line
+ * that differs. Gameboy Doctor's logs start at 0100 after the boot ROM and expect LY to always
+ * read 0x90 (see Bus::set_ly_stub). Interrupt dispatches aren't instructions and have no line.
+ */
+
+use std::collections::VecDeque;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+
+use crate::bus::Bus;
+use crate::cpu::CPU;
+use crate::consts::*;
+
+// Lines shown before the one that diverged
+const CONTEXT_LINES :usize = 8;
+
+/* Where the trace stopped matching the reference */
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    pub line     :u64,              // From 1
+    pub expected :Option<String>,   // None when the reference ended before
+    pub actual   :String,
+    pub context  :Vec<String>,      // Matching lines before
+}
+
+impl Divergence {
+    /* Fields (A, F, ..., PCMEM) that differ */
+    pub fn fields(&self) -> Vec<&str> {
+        let Some(expected) = &self.expected else { return vec![]; };
+
+        return self.actual.split_whitespace().zip(expected.split_whitespace())
+            .filter(|(actual, expected)| actual != expected)
+            .map(|(actual, _)| actual.split(':').next().unwrap())
+            .collect();
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "The trace diverges from the reference at line {}:", self.line)?;
+        for (i, line) in self.context.iter().enumerate() {
+            writeln!(f, "  {:>8}  {}", self.line - (self.context.len() - i) as u64, line)?;
+        }
+
+        match &self.expected {
+            Some(expected) => {
+                writeln!(f, "expected  {}", expected)?;
+                writeln!(f, "got       {}", self.actual)?;
+                write!(f, "differs in {}", self.fields().join(", "))
+            },
+            None => {
+                writeln!(f, "got       {}", self.actual)?;
+                write!(f, "after the end of the reference")
+            }
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceError {
+    Io(io::Error),
+    Diverged(Box<Divergence>),
+}
+
+impl fmt::Display for TraceError {
+    fn fmt(&self, f :&mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TraceError::Io(err) => write!(f, "I/O error: {}", err),
+            TraceError::Diverged(divergence) => write!(f, "{}", divergence)
+        }
+    }
+}
+
+impl From<io::Error> for TraceError {
+    fn from(err :io::Error) -> TraceError {
+        return TraceError::Io(err);
+    }
+}
+
+/* The line of the instruction about to run */
+pub fn doctor_line(cpu :&CPU, bus :&Bus) -> String {
+    let pc = cpu.instr_addr();
+    let pcmem :Vec<String> = (0..4).map(|i| format!("{:02X}", bus.peek(pc.wrapping_add(i)))).collect();
+
+    return format!("A:{:02X} F:{:02X} B:{:02X} C:{:02X} D:{:02X} E:{:02X} H:{:02X} L:{:02X} SP:{:04X} PC:{:04X} PCMEM:{}",
+        cpu.reg(REG_A), cpu.reg(REG_F), cpu.reg(REG_B), cpu.reg(REG_C), cpu.reg(REG_D),
+        cpu.reg(REG_E), cpu.reg(REG_H), cpu.reg(REG_L), cpu.get_sp(), pc, pcmem.join(","));
+}
+
+pub struct Tracer {
+    log         :Option<Box<dyn Write>>,
+    reference   :Option<Box<dyn BufRead>>,
+    n_lines     :u64,
+    context     :VecDeque<String>,
+    is_halted   :bool, // The instruction after HALT waits for the CPU to wake up
+}
+
+impl Tracer {
+    /* Write the trace to a log, compare it with a reference, or both */
+    pub fn new(log :Option<Box<dyn Write>>, reference :Option<Box<dyn BufRead>>) -> Tracer {
+        return Tracer {
+            log,
+            reference,
+            n_lines: 0,
+            context: VecDeque::with_capacity(CONTEXT_LINES),
+            is_halted: false,
+        };
+    }
+
+    pub fn open(log_path :Option<&str>, reference_path :Option<&str>) -> io::Result<Tracer> {
+        let log = match log_path {
+            Some(path) => Some(Box::new(BufWriter::new(File::create(path)?)) as Box<dyn Write>),
+            None => None
+        };
+        let reference = match reference_path {
+            Some(path) => Some(Box::new(BufReader::new(File::open(path)?)) as Box<dyn BufRead>),
+            None => None
+        };
+
+        return Ok(Tracer::new(log, reference));
+    }
+
+    /* Lines traced so far */
+    pub fn n_lines(&self) -> u64 { return self.n_lines; }
+    pub fn is_comparing(&self) -> bool { return self.reference.is_some(); }
+
+    /*
+     * Called before every tick. Traces the instruction about to run, if there is one: the one
+     * prefetched when CPU::is_new_instr becomes true, unless an interrupt is dispatched first
+     * or the CPU halts, in which case it runs once the CPU wakes up without an interrupt.
+     *
+     * Once the trace diverges the reference is dropped, the log going on.
+     */
+    pub fn tick(&mut self, cpu :&CPU, bus :&Bus) -> Result<(), TraceError> {
+        let is_about_to_run = if cpu.is_new_instr() {
+            self.is_halted = cpu.is_halted();
+            !cpu.is_halted() && !cpu.is_interrupt_next()
+        } else if self.is_halted && !cpu.is_halted() {
+            self.is_halted = false;
+            !cpu.is_interrupt_next()
+        } else {
+            false
+        };
+
+        if is_about_to_run {
+            self.trace(doctor_line(cpu, bus))?;
+        }
+        return Ok(());
+    }
+
+    fn trace(&mut self, line :String) -> Result<(), TraceError> {
+        self.n_lines += 1;
+
+        if let Some(log) = &mut self.log {
+            writeln!(log, "{}", line)?;
+        }
+
+        if let Some(reference) = &mut self.reference {
+            let mut expected = String::new();
+            let expected = match reference.read_line(&mut expected)? {
+                0 => None,
+                _ => Some(expected.trim().to_string())
+            };
+
+            if expected.as_deref() != Some(line.as_str()) {
+                self.reference = None;
+                return Err(TraceError::Diverged(Box::new(Divergence {
+                    line: self.n_lines,
+                    expected,
+                    actual: line,
+                    context: self.context.drain(..).collect(),
+                })));
+            }
+
+            if self.context.len() == CONTEXT_LINES {
+                self.context.pop_front();
+            }
+            self.context.push_back(line);
+        }
+
+        return Ok(());
+    }
+
+    /* Write what is left of the log */
+    pub fn flush(&mut self) -> io::Result<()> {
+        if let Some(log) = &mut self.log {
+            log.flush()?;
+        }
+        return Ok(());
+    }
+}
@@ -5,6 +5,8 @@
 #[cfg(test)]
 mod tests {
     use gb::consts::*;
+    use gb::bus::recorder::{BusAccess, BusRecorder, Initiator};
+    use gb::cartridge::LoadOptions;
     use gb::gbemulator::GBEmulator;
     type CPU = gb::cpu::CPU;
 
@@ -22,7 +24,7 @@ mod tests {
         name      :String,
         s_initial :TestState,
         s_final   :TestState,
-        s_cycles  :Vec<(u16, u8)> // State in every cycle
+        s_cycles  :Vec<Option<(u16, u8, String)>> // Bus access in every M-cycle, read or write
     }
 
     /*
@@ -47,6 +49,22 @@ mod tests {
         v
     }
 
+    /*
+     Parses the cycles in the format [[u16, u8, "read"|"write"] | null...], null being a cycle
+     without any access
+     */
+    fn parse_cycles(arr :&serde_json::Value) -> Vec<Option<(u16, u8, String)>> {
+        return arr.as_array().unwrap().iter().map(|cycle| {
+            if cycle.is_null() {
+                None
+            } else {
+                Some((cycle[0].as_u64().unwrap() as u16,
+                      cycle[1].as_u64().unwrap() as u8,
+                      cycle[2].as_str().unwrap().to_owned()))
+            }
+        }).collect();
+    }
+
     /*
      Parse a JSON test file
      */
@@ -89,7 +107,7 @@ mod tests {
                     ram: parse_tuple_array(&_final["ram"]),
                 },
 
-                s_cycles: parse_tuple_array(&t["cycles"]),
+                s_cycles: parse_cycles(&t["cycles"]),
             };
 
             tests.push(test);
@@ -149,6 +167,25 @@ mod tests {
         }
     }
 
+    /*
+     Check the accesses to the bus recorded in every M-cycle, from the T-cycle start of the bus
+     */
+    fn check_bus_activity(test :&Test, accesses :&[BusAccess], start :u64) {
+        for (i, cycle) in test.s_cycles.iter().enumerate() {
+            let actual :Vec<(u16, u8, String)> = accesses.iter()
+                .filter(|access| (access.cycle - start) / 4 == i as u64)
+                .map(|access| (access.addr, access.val, access.access.to_string()))
+                .collect();
+            let expected :Vec<(u16, u8, String)> = cycle.iter().cloned().collect();
+
+            assert_eq!(actual, expected, "{}: bus in M-cycle {}", test.name, i);
+        }
+
+        assert!(accesses.iter().all(|access| access.initiator == Initiator::Cpu), "{}: DMA", test.name);
+        assert!(accesses.iter().all(|access| (access.cycle - start) / 4 < test.s_cycles.len() as u64),
+            "{}: bus after the last M-cycle", test.name);
+    }
+
     fn run_test(path :String, gbemu :&mut GBEmulator) {
         let tests = parse_tests(&path);
 
@@ -162,6 +199,7 @@ mod tests {
             let bus = gbemu.get_bus();
             let mut cpu = gbemu.get_cpu_mut();
             cpu.init();
+            bus.borrow_mut().set_recorder(Some(BusRecorder::new(64)));
 
             init_test(&test, &mut cpu);
 
@@ -171,13 +209,16 @@ mod tests {
                 bus.borrow_mut().tick();
             }
 
+            let start = bus.borrow().cycle();
             for _ in 0..final_cycle_n {
                 cpu.tick();
                 bus.borrow_mut().tick();
                 cycle_n += 1;
             }
 
+            let accesses = bus.borrow().recorder().unwrap().since(start);
             check_test_result(&test, &cpu, cycle_n);
+            check_bus_activity(&test, &accesses, start);
             println!(" ok");
         }
     }
@@ -186,7 +227,7 @@ mod tests {
     fn test_ops() {
         // IMPORTANT: To pass this test, there is need to have unbounded access to RAM,
         // that is, to use a mock RAM in the Bus instead of the usual methods.
-        let mut gbemu = GBEmulator::new(TEST_ROM_PATH, 2);
+        let mut gbemu = GBEmulator::new_headless(TEST_ROM_PATH, 2, &LoadOptions { is_lenient: true, ..Default::default() }).unwrap();
 
         // Omitting 0x10: STOP, 0x76: HALT, 0xF3: DI, 0xFB: EI
         let tests = [
@@ -216,7 +257,7 @@ mod tests {
 
     #[test]
     fn test_ops_cb() {
-        let mut gbemu = GBEmulator::new(TEST_ROM_PATH, 1);
+        let mut gbemu = GBEmulator::new_headless(TEST_ROM_PATH, 1, &LoadOptions { is_lenient: true, ..Default::default() }).unwrap();
         
         println!("Testing cb");
         run_test(format!("{}/cb.json", JSON_PATH), &mut gbemu);
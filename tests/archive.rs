@@ -0,0 +1,121 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Write;
+    use std::path::PathBuf;
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use zip::ZipWriter;
+    use zip::write::FileOptions;
+    use gb::cartridge::{LoadError, LoadOptions};
+    use gb::consts::ComponentWithMemory;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, LOOP};
+
+    /* 32 KiB MBC1+RAM+BATTERY ROM looping forever, with a marker at 0x7FFF */
+    fn build_rom(marker :u8) -> Vec<u8> {
+        let mut rom = common::rom(0x03, 0x00, 0x02, &LOOP);
+        rom[0x7FFF] = marker;
+        common::set_checksums(&mut rom);
+
+        return rom;
+    }
+
+    /* Directory of its own for each test, as saves are written next to the archives */
+    fn test_dir(name :&str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gb_archive_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+
+        return dir;
+    }
+
+    fn write_zip(path :&PathBuf, entries :&[(&str, Vec<u8>)]) {
+        let mut zip = ZipWriter::new(std::fs::File::create(path).unwrap());
+        for (name, data) in entries {
+            zip.start_file(*name, FileOptions::default()).unwrap();
+            zip.write_all(data).unwrap();
+        }
+        zip.finish().unwrap();
+    }
+
+    /* Load the ROM, check its marker and write its save */
+    fn run(path :&str, marker :u8) {
+        let mut gbemu = GBEmulator::new_headless(path, 1, &LoadOptions::default()).unwrap();
+        gbemu.init();
+
+        let bus = gbemu.get_bus();
+        assert_eq!(bus.borrow().read(0x7FFF), marker);
+
+        bus.borrow_mut().write(0x0000, 0x0A);
+        bus.borrow_mut().write(0xA000, 0x42);
+        gbemu.save_ram();
+    }
+
+    #[test]
+    fn gzip() {
+        let dir = test_dir("gzip");
+        let path = dir.join("game.gb.gz");
+
+        let mut gz = GzEncoder::new(std::fs::File::create(&path).unwrap(), Compression::default());
+        gz.write_all(&build_rom(0x11)).unwrap();
+        gz.finish().unwrap();
+
+        run(path.to_str().unwrap(), 0x11);
+
+        assert_eq!(std::fs::read(dir.join("game.sav")).unwrap()[0], 0x42);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_first_rom() {
+        let dir = test_dir("zip_first");
+        let path = dir.join("collection.zip");
+        write_zip(&path, &[
+            ("readme.txt", b"Not a ROM".to_vec()),
+            ("roms/First.gbc", build_rom(0x22)),
+            ("Second.gb", build_rom(0x33)),
+        ]);
+
+        run(path.to_str().unwrap(), 0x22);
+
+        assert_eq!(std::fs::read(dir.join("First.sav")).unwrap()[0], 0x42);
+        assert!(!dir.join("collection.sav").exists());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_named_entry() {
+        let dir = test_dir("zip_named");
+        let path = dir.join("collection.zip");
+        write_zip(&path, &[
+            ("First.gb", build_rom(0x22)),
+            ("Second.gb", build_rom(0x33)),
+        ]);
+
+        run(&format!("{}#Second.gb", path.to_str().unwrap()), 0x33);
+
+        assert_eq!(std::fs::read(dir.join("Second.sav")).unwrap()[0], 0x42);
+        assert!(!dir.join("First.sav").exists());
+
+        let err = GBEmulator::new_headless(&format!("{}#Third.gb", path.to_str().unwrap()), 1, &LoadOptions::default()).err();
+        assert!(matches!(err, Some(LoadError::Archive(_))));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn zip_without_rom() {
+        let dir = test_dir("zip_empty");
+        let path = dir.join("empty.zip");
+        write_zip(&path, &[("readme.txt", b"Not a ROM".to_vec())]);
+
+        let err = GBEmulator::new_headless(path.to_str().unwrap(), 1, &LoadOptions::default()).err().unwrap();
+        assert!(err.to_string().contains("no .gb or .gbc file"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
+
+fn main() {}
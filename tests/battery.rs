@@ -0,0 +1,219 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+    use gb::consts::ComponentWithMemory;
+    use gb::cartridge::LoadOptions;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, LOOP, TestDir};
+
+    // LD A, 0x0A; LD (0x0000), A; LD A, 0x42; LD (0xA000), A; JR -2
+    const WRITE_RAM :[u8;12] = [0x3E, 0x0A, 0xEA, 0x00, 0x00, 0x3E, 0x42, 0xEA, 0x00, 0xA0, 0x18, 0xFE];
+
+    /* 32 KiB ROM of the given cartridge type in the directory of the test, looping forever */
+    fn build_rom(dir :&TestDir, cart_type :u8, ram_size :u8) -> String {
+        return build_rom_with(dir, cart_type, ram_size, &LOOP);
+    }
+
+    fn build_rom_with(dir :&TestDir, cart_type :u8, ram_size :u8, program :&[u8]) -> String {
+        let path = dir.file("game.gb");
+        common::write_rom_at(&path, common::rom(cart_type, 0, ram_size, program));
+
+        return path;
+    }
+
+    /* Next to the ROM by default */
+    fn save_path(dir :&TestDir) -> String {
+        return dir.file("game.sav");
+    }
+
+    fn new_emulator(rom :&str) -> GBEmulator {
+        let gbemu = common::new_emulator(rom);
+
+        // Enable the RAM
+        gbemu.get_bus().borrow_mut().write(0x0000, 0x0A);
+
+        return gbemu;
+    }
+
+    #[test]
+    fn mbc2_builtin_ram() {
+        let dir = TestDir::new("battery_mbc2");
+        let rom = build_rom(&dir, 0x06, 0x00);
+
+        let gbemu = new_emulator(&rom);
+        let bus = gbemu.get_bus();
+
+        // Only the lower nibble is stored, and the 512 half-bytes are repeated up to 0xBFFF
+        bus.borrow_mut().write(0xA000, 0x12);
+        bus.borrow_mut().write(0xB3FF, 0x07);
+        assert_eq!(bus.borrow().read(0xA000), 0xF2);
+        assert_eq!(bus.borrow().read(0xA200), 0xF2);
+        assert_eq!(bus.borrow().read(0xBE00), 0xF2);
+        assert_eq!(bus.borrow().read(0xA1FF), 0xF7);
+
+        bus.borrow().save_ram();
+        assert_eq!(std::fs::read(save_path(&dir)).unwrap().len(), 512);
+
+        let gbemu = new_emulator(&rom);
+        let bus = gbemu.get_bus();
+
+        assert_eq!(bus.borrow().read(0xA000), 0xF2);
+        assert_eq!(bus.borrow().read(0xA1FF), 0xF7);
+    }
+
+    #[test]
+    fn mbc5_ext_ram() {
+        let dir = TestDir::new("battery_mbc5");
+        let rom = build_rom(&dir, 0x1B, 0x03);
+
+        let gbemu = new_emulator(&rom);
+        let bus = gbemu.get_bus();
+
+        bus.borrow_mut().write(0x4000, 0x02);
+        bus.borrow_mut().write(0xA123, 0x42);
+        bus.borrow().save_ram();
+        assert_eq!(std::fs::read(save_path(&dir)).unwrap().len(), 32*1024);
+
+        let gbemu = new_emulator(&rom);
+        let bus = gbemu.get_bus();
+
+        assert_eq!(bus.borrow().read(0xA123), 0x00);
+        bus.borrow_mut().write(0x4000, 0x02);
+        assert_eq!(bus.borrow().read(0xA123), 0x42);
+    }
+
+    #[test]
+    fn no_battery_no_save() {
+        for (name, cart_type) in [("mbc1_ram", 0x02), ("mbc5_ram", 0x1A)] {
+            let dir = TestDir::new(&format!("battery_{}", name));
+            let rom = build_rom(&dir, cart_type, 0x02);
+
+            let gbemu = new_emulator(&rom);
+            let bus = gbemu.get_bus();
+            bus.borrow_mut().write(0xA000, 0x42);
+            bus.borrow().save_ram();
+
+            assert!(!std::path::Path::new(&save_path(&dir)).exists());
+        }
+    }
+
+    #[test]
+    fn save_named_like_the_rom() {
+        // Older versions named the save like the ROM itself, in a directory of their own
+        let dir = TestDir::new("battery_old_name");
+        let rom = build_rom(&dir, 0x03, 0x02);
+        let legacy_dir = dir.file("saves");
+        std::fs::create_dir_all(&legacy_dir).unwrap();
+        std::fs::write(dir.file("saves/game.gb"), vec![0x33;0x2000]).unwrap();
+
+        let options = LoadOptions { legacy_save_dir: Some(legacy_dir), ..Default::default() };
+        let mut gbemu = GBEmulator::new_headless(&rom, 1, &options).unwrap();
+        gbemu.init();
+        gbemu.get_bus().borrow_mut().write(0x0000, 0x0A);
+
+        assert_eq!(gbemu.get_bus().borrow().read(0xA000), 0x33);
+
+        // Saved to the new place
+        gbemu.get_bus().borrow().save_ram();
+        assert_eq!(std::fs::read(save_path(&dir)).unwrap(), vec![0x33;0x2000]);
+    }
+
+    #[test]
+    fn save_dir() {
+        let rom_dir = TestDir::new("battery_save_dir");
+        let rom = build_rom(&rom_dir, 0x03, 0x02);
+        let dir = rom_dir.path.join("saves");
+
+        let mut gbemu = GBEmulator::new_headless(&rom, 1, &LoadOptions::default()).unwrap();
+        gbemu.set_save_dir(dir.to_str().unwrap());
+        gbemu.init();
+
+        gbemu.get_bus().borrow_mut().write(0x0000, 0x0A);
+        gbemu.get_bus().borrow_mut().write(0xA000, 0x42);
+        gbemu.save_ram();
+
+        // The directory is created, and the temporary file renamed
+        let files :Vec<_> = std::fs::read_dir(&dir).unwrap().map(|f| f.unwrap().file_name()).collect();
+        assert_eq!(files, ["game.sav"]);
+        assert!(!std::path::Path::new(&save_path(&rom_dir)).exists());
+
+        let mut gbemu = GBEmulator::new_headless(&rom, 1, &LoadOptions::default()).unwrap();
+        gbemu.set_save_dir(dir.to_str().unwrap());
+        gbemu.init();
+        gbemu.get_bus().borrow_mut().write(0x0000, 0x0A);
+
+        assert_eq!(gbemu.get_bus().borrow().read(0xA000), 0x42);
+    }
+
+    #[test]
+    fn kept_on_reset() {
+        let dir = TestDir::new("battery_reset");
+        let rom = build_rom(&dir, 0x10, 0x02);
+
+        let mut gbemu = new_emulator(&rom);
+        let bus = gbemu.get_bus();
+        bus.borrow_mut().write(0xA000, 0x42);
+        // 30 seconds on the RTC
+        bus.borrow_mut().write(0x4000, 0x08);
+        bus.borrow_mut().write(0xA000, 30);
+
+        gbemu.reset();
+        assert_eq!(gbemu.get_cpu().instr_addr(), 0x0100);
+        assert_eq!(bus.borrow().peek_ram_bank(0, 0xA000), Some(0x42));
+
+        // Latched after the reset
+        for (addr, val) in [(0x0000, 0x0A), (0x4000, 0x08), (0x6000, 0x00), (0x6000, 0x01)] {
+            bus.borrow_mut().write(addr, val);
+        }
+        assert_eq!(bus.borrow().read(0xA000), 30);
+    }
+
+    #[test]
+    fn movie_from_power_on() {
+        let dir = TestDir::new("battery_movie");
+        let rom = build_rom(&dir, 0x03, 0x02);
+        std::fs::write(save_path(&dir), vec![0x42;0x2000]).unwrap();
+
+        // Recorded without the save, which is left alone
+        let mut gbemu = new_emulator(&rom);
+        let bus = gbemu.get_bus();
+        gbemu.record_movie(false);
+        assert_eq!(bus.borrow().peek_ram_bank(0, 0xA000), Some(0x00));
+
+        bus.borrow_mut().write(0x0000, 0x0A);
+        bus.borrow_mut().write(0xA000, 0x33);
+        gbemu.save_ram();
+        assert_eq!(std::fs::read(save_path(&dir)).unwrap(), vec![0x42;0x2000]);
+
+        // Played back the same way
+        let movie = gbemu.stop_movie().unwrap();
+        let mut gbemu = new_emulator(&rom);
+        gbemu.play_movie(movie).unwrap();
+        assert_eq!(gbemu.get_bus().borrow().peek_ram_bank(0, 0xA000), Some(0x00));
+        gbemu.save_ram();
+
+        assert_eq!(std::fs::read(save_path(&dir)).unwrap(), vec![0x42;0x2000]);
+    }
+
+    #[test]
+    fn flushed_after_last_write() {
+        let dir = TestDir::new("battery_flush");
+        let rom = build_rom_with(&dir, 0x03, 0x02, &WRITE_RAM);
+
+        let mut gbemu = common::new_emulator(&rom);
+        gbemu.set_uncapped(true);
+        gbemu.set_save_flush_delay(Duration::from_millis(200));
+
+        gbemu.run_frame();
+        assert!(!std::path::Path::new(&save_path(&dir)).exists());
+
+        std::thread::sleep(Duration::from_millis(250));
+        gbemu.run_frame();
+
+        assert_eq!(std::fs::read(save_path(&dir)).unwrap()[0], 0x42);
+    }
+}
+
+fn main() {}
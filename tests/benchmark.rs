@@ -2,6 +2,7 @@
 mod tests {
     use std::fs;
     use std::time::SystemTime;
+    use gb::cartridge::LoadOptions;
     use gb::gbemulator::GBEmulator;
 
     const ROMS_PATH :&str = "tests/roms/benchmark";
@@ -15,7 +16,7 @@ mod tests {
     fn run_benchmark(path :&str) -> BenchmarkResult {            
         let mut cycle_n = 0;
 
-        let mut gbemu = GBEmulator::new(&path, 2);
+        let mut gbemu = GBEmulator::new_headless(&path, 2, &LoadOptions::default()).unwrap();
 
         gbemu.init();
         let bus = gbemu.get_bus();
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use std::time::SystemTime;
+    use gb::cartridge::LoadOptions;
     use gb::gbemulator::GBEmulator;
 
     const ROMS_FOLDER :&str = "tests/roms/blargg";
@@ -28,7 +29,7 @@ mod tests {
         let file = file.split("/").into_iter().last().unwrap();
         let hash = load_md5(&file);
 
-        let mut gbemu = GBEmulator::new(&path, 2);
+        let mut gbemu = GBEmulator::new_headless(&path, 2, &LoadOptions::default()).unwrap();
         gbemu.init();
 
         let screen = gbemu.get_screen();
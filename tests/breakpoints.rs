@@ -0,0 +1,216 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::*;
+    use gb::debugger::breakpoints::{Breakpoint, Breakpoints, Watchpoint, WatchHit, Access, RunMode, Stop};
+    use gb::debugger::expr::Condition;
+    use gb::gbemulator::GBEmulator;
+    use crate::common;
+
+    // Calls 4000 in bank 2 then in bank 3
+    const MAIN :[u8;18] = [
+        0x3E, 0x02,         // 0150 LD A, 2
+        0xEA, 0x00, 0x20,   // 0152 LD (2000), A
+        0xCD, 0x00, 0x40,   // 0155 CALL 4000
+        0x3E, 0x03,         // 0158 LD A, 3
+        0xEA, 0x00, 0x20,   // 015A LD (2000), A
+        0xCD, 0x00, 0x40,   // 015D CALL 4000
+        0x18, 0xFE,         // 0160 JR -2
+    ];
+
+    // At 4000 in every bank
+    const FUNCTION :[u8;7] = [
+        0xEA, 0xA0, 0xC0,   // 4000 LD (C0A0), A
+        0xCD, 0x10, 0x40,   // 4003 CALL 4010
+        0xC9,               // 4006 RET
+    ];
+    const INNER :[u8;2] = [
+        0x00,               // 4010 NOP
+        0xC9,               // 4011 RET
+    ];
+
+    /* 128 KiB MBC5 ROM */
+    fn build_rom(name :&str) -> String {
+        let mut rom = common::rom(0x19, 0x02, 0x00, &MAIN);
+        for bank in rom.chunks_mut(0x4000).skip(1) {
+            bank[..FUNCTION.len()].copy_from_slice(&FUNCTION);
+            bank[0x10..0x10+INNER.len()].copy_from_slice(&INNER);
+        }
+
+        return common::write_rom(&format!("breakpoints_{}", name), rom);
+    }
+
+    fn new_emulator(name :&str) -> GBEmulator {
+        return common::new_emulator(&build_rom(name));
+    }
+
+    /* Resume like the debugger until it stops again. Returns why and where. */
+    fn run(gbemu :&mut GBEmulator, breakpoints :&mut Breakpoints) -> (Stop, u16) {
+        for _ in 0..100_000 {
+            gbemu.get_bus().borrow_mut().tick();
+            gbemu.get_cpu_mut().tick();
+
+            if gbemu.get_cpu().is_new_instr() {
+                let bus = gbemu.get_bus();
+                let stop = breakpoints.check(gbemu.get_cpu(), &mut bus.borrow_mut());
+                if let Some(stop) = stop {
+                    return (stop, gbemu.get_cpu().instr_addr());
+                }
+            }
+        }
+
+        panic!("The debugger didn't stop");
+    }
+
+    #[test]
+    fn conditions() {
+        let mut gbemu = new_emulator("conditions");
+        let bus = gbemu.get_bus();
+
+        gbemu.get_cpu_mut().set_reg(REG_A, 0x3C);
+        gbemu.get_cpu_mut().set_reg16(REG_H, REG_L, 0xC0A1);
+        bus.borrow_mut().write(0xC0A0, 6);
+        bus.borrow_mut().write(0xC0A1, 0xFF);
+
+        let eval = |s :&str| Condition::parse(s).unwrap().eval(gbemu.get_cpu(), &bus.borrow());
+        assert!(eval("A == 0x3C && [C0A0] > 5"));
+        assert!(!eval("A == 0x3C && [C0A0] > 6"));
+        assert!(eval("a != 60 || [C0A0] >= 6"));
+        assert!(eval("[HL] == $FF && HL == 0xC0A1"));
+        assert!(eval("B == 1 || A == 2 || (SP <= 0xFFFE && PC == 0x100)"));
+        assert!(!eval("(B == 1 || A == 2) && SP < 0xFFFE"));
+
+        for bad in ["A", "A == ", "A = 1", "[C0A0 > 1", "Q == 1", "(A == 1", "A == 1 B == 2"] {
+            assert!(Condition::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn parse() {
+        let breakpoint = Breakpoint::parse("03:4567 if A == 1").unwrap();
+        assert_eq!((breakpoint.addr, breakpoint.bank, breakpoint.condition.is_some()), (0x4567, Some(3), true));
+        assert_eq!(Breakpoint::parse("$0150").unwrap().bank, None);
+        assert!(Breakpoint::parse("4567 if").is_err());
+        assert!(Breakpoint::parse("zz:4567").is_err());
+
+        assert_eq!(Watchpoint::parse("C000-C0FF").unwrap(),
+                   Watchpoint { start: 0xC000, end: 0xC0FF, read: false, write: true, execute: false });
+        assert_eq!(Watchpoint::parse("rx FF80").unwrap(),
+                   Watchpoint { start: 0xFF80, end: 0xFF80, read: true, write: false, execute: true });
+        assert!(Watchpoint::parse("q C000").is_err());
+        assert!(Watchpoint::parse("w C0FF-C000").is_err());
+    }
+
+    #[test]
+    fn bank_and_condition() {
+        let mut gbemu = new_emulator("banks");
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.set_mode(RunMode::Continue);
+
+        breakpoints.add(Breakpoint::parse("03:4000").unwrap());
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Breakpoint(0), 0x4000));
+        assert_eq!(gbemu.get_cpu().reg(REG_A), 3);
+
+        let mut gbemu = new_emulator("conditional");
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.set_mode(RunMode::Continue);
+
+        breakpoints.add(Breakpoint::parse("4000 if A == 3").unwrap());
+        breakpoints.add(Breakpoint::parse("4003").unwrap());
+        breakpoints.set_enabled(1, false);
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Breakpoint(0), 0x4000));
+        assert_eq!(gbemu.get_bus().borrow().rom_bank(0x4000), 3);
+
+        // Stopping again after the breakpoint
+        breakpoints.set_enabled(1, true);
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Breakpoint(1), 0x4003));
+    }
+
+    #[test]
+    fn watchpoints() {
+        let mut gbemu = new_emulator("watchpoints");
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.set_mode(RunMode::Continue);
+
+        let bus = gbemu.get_bus();
+        bus.borrow_mut().watchpoints_mut().add(Watchpoint::parse("w C0A0").unwrap());
+        bus.borrow_mut().watchpoints_mut().add(Watchpoint::parse("r FFFC-FFFD").unwrap());
+        bus.borrow_mut().watchpoints_mut().add(Watchpoint::parse("x 4010-4011").unwrap());
+
+        // After the instruction that wrote
+        assert_eq!(run(&mut gbemu, &mut breakpoints),
+                   (Stop::Watchpoint(WatchHit { index: 0, access: Access::Write, addr: 0xC0A0, val: 2 }), 0x4003));
+        assert_eq!(run(&mut gbemu, &mut breakpoints),
+                   (Stop::Watchpoint(WatchHit { index: 2, access: Access::Execute, addr: 0x4010, val: 0x00 }), 0x4010));
+        assert_eq!(run(&mut gbemu, &mut breakpoints),
+                   (Stop::Watchpoint(WatchHit { index: 2, access: Access::Execute, addr: 0x4011, val: 0xC9 }), 0x4011));
+        // The outer RET pops the return address
+        assert_eq!(run(&mut gbemu, &mut breakpoints),
+                   (Stop::Watchpoint(WatchHit { index: 1, access: Access::Read, addr: 0xFFFC, val: 0x58 }), 0x0158));
+
+        // The debugger itself doesn't trigger them
+        bus.borrow().peek(0xFFFC);
+        assert_eq!(bus.borrow().watchpoints().take_hit(), None);
+
+        bus.borrow_mut().watchpoints_mut().remove(0);
+        assert_eq!(run(&mut gbemu, &mut breakpoints).1, 0x4010);
+    }
+
+    #[test]
+    fn stepping() {
+        let mut gbemu = new_emulator("stepping");
+        let mut breakpoints = Breakpoints::new();
+
+        // NOP, JP 0150
+        let stop = breakpoints.check(gbemu.get_cpu(), &mut gbemu.get_bus().borrow_mut());
+        assert_eq!((stop, gbemu.get_cpu().instr_addr()), (Some(Stop::Step), 0x0100));
+        breakpoints.set_mode(RunMode::Step(1));
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Step, 0x0150));
+
+        breakpoints.run_to(0x0155);
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Reached, 0x0155));
+
+        // Over the CALL, and what it calls
+        breakpoints.step_over(gbemu.get_cpu());
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Reached, 0x0158));
+        breakpoints.step_over(gbemu.get_cpu());
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Step, 0x015A));
+
+        // Out of the inner function then out of the outer one
+        breakpoints.run_to(0x4010);
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Reached, 0x4010));
+        breakpoints.step_out(gbemu.get_cpu());
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Reached, 0x4006));
+        breakpoints.step_out(gbemu.get_cpu());
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Reached, 0x0160));
+
+        // Breakpoints still stop while stepping over
+        let mut gbemu = new_emulator("stepping_breakpoint");
+        breakpoints.run_to(0x0155);
+        run(&mut gbemu, &mut breakpoints);
+
+        breakpoints.toggle(0x4011);
+        assert!(breakpoints.has_breakpoint(0x4011));
+        breakpoints.step_over(gbemu.get_cpu());
+        assert_eq!(run(&mut gbemu, &mut breakpoints), (Stop::Breakpoint(0), 0x4011));
+
+        breakpoints.toggle(0x4011);
+        assert!(breakpoints.list().is_empty());
+    }
+
+    #[test]
+    fn frames_with_ly_stubbed() {
+        let mut gbemu = new_emulator("frames_ly_stub");
+        gbemu.get_bus().borrow_mut().set_ly_stub(Some(0x90));
+        let mut breakpoints = Breakpoints::new();
+
+        // LY always reads as in VBlank, but each frame still ends once
+        for _ in 0..2 {
+            breakpoints.run_frame();
+            assert_eq!(run(&mut gbemu, &mut breakpoints).0, Stop::Reached);
+        }
+    }
+}
+
+fn main() {}
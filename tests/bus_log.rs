@@ -0,0 +1,157 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::*;
+    use gb::bus::recorder::{BusAccess, BusRecorder, Initiator};
+    use gb::debugger::breakpoints::{Access, Breakpoints};
+    use gb::debugger::console::Console;
+    use gb::debugger::search::RamSearch;
+    use gb::debugger::symbols::Symbols;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, ROM_ONLY};
+
+    const MAIN :[u8;14] = [
+        0x3E, 0x05,         // 0150 LD A, 5
+        0xEA, 0x00, 0xC0,   // 0152 LD (C000), A
+        0xFA, 0x00, 0xC0,   // 0155 LD A, (C000)
+        0x3E, 0xC1,         // 0158 LD A, C1
+        0xE0, 0x46,         // 015A LDH (46), A, OAM DMA from C100
+        0x18, 0xFE,         // 015C JR -2
+    ];
+
+    /* 32 KiB ROM without a mapper */
+    fn build_rom(name :&str) -> String {
+        return common::build_rom(&format!("bus_log_{}", name), ROM_ONLY, 0x00, 0x00, &MAIN);
+    }
+
+    fn new_emulator(name :&str) -> GBEmulator {
+        return common::new_emulator(&build_rom(name));
+    }
+
+    fn run(gbemu :&mut GBEmulator, n_cycles :u32) {
+        for _ in 0..n_cycles {
+            gbemu.get_bus().borrow_mut().tick();
+            gbemu.get_cpu_mut().tick();
+        }
+    }
+
+    fn access(cycle :u64, addr :u16, val :u8, access :Access) -> BusAccess {
+        return BusAccess { cycle, addr, val, access, initiator: Initiator::Cpu };
+    }
+
+    #[test]
+    fn cpu_and_dma() {
+        let mut gbemu = new_emulator("cpu_and_dma");
+        for i in 0..0xA0 {
+            gbemu.get_bus().borrow_mut().write(0xC100 + i, i as u8);
+        }
+        let bus = gbemu.get_bus();
+        bus.borrow_mut().set_recorder(Some(BusRecorder::new(4096)));
+        run(&mut gbemu, 1200);
+
+        let accesses = bus.borrow().recorder().unwrap().accesses();
+        let cpu :Vec<&BusAccess> = accesses.iter().filter(|a| a.initiator == Initiator::Cpu).collect();
+
+        // An access every M-cycle but the internal one of JP, the opcodes being prefetched
+        assert_eq!((cpu[0].addr, cpu[0].val, cpu[0].access), (0x0101, 0xC3, Access::Read));
+        let main = cpu.iter().position(|a| a.addr == 0x0150).unwrap();
+        assert_eq!(cpu[main].cycle - cpu[main - 1].cycle, 8);
+        for pair in cpu[main..main + 14].windows(2) {
+            assert_eq!(pair[1].cycle - pair[0].cycle, 4, "{} {}", pair[0], pair[1]);
+        }
+        let write = cpu.iter().find(|a| a.access == Access::Write).unwrap();
+        assert_eq!((write.addr, write.val), (0xC000, 0x05));
+        let read = cpu.iter().find(|a| a.addr == 0xC000 && a.access == Access::Read).unwrap();
+        assert_eq!((read.val, read.cycle - write.cycle), (0x05, 16));
+        assert!(cpu.iter().any(|a| a.addr == ADDR_DMA && a.val == 0xC1 && a.access == Access::Write));
+
+        // The DMA copies a byte every M-cycle
+        let dma :Vec<&BusAccess> = accesses.iter().filter(|a| a.initiator == Initiator::Dma).collect();
+        assert_eq!(dma.len(), 2*0xA0);
+        for (i, pair) in dma.chunks(2).enumerate() {
+            assert_eq!((pair[0].addr, pair[0].val, pair[0].access), (0xC100 + i as u16, i as u8, Access::Read));
+            assert_eq!((pair[1].addr, pair[1].val, pair[1].access), (OAM_START + i as u16, i as u8, Access::Write));
+            assert_eq!(pair[0].cycle, pair[1].cycle);
+        }
+        assert!(dma.windows(2).all(|pair| pair[1].cycle - pair[0].cycle <= 4));
+        assert_eq!(bus.borrow().oam()[0x9F], 0x9F);
+
+        // Peeks aren't recorded
+        let n_recorded = bus.borrow().recorder().unwrap().n_recorded();
+        bus.borrow().peek(0xC000);
+        assert_eq!(bus.borrow().recorder().unwrap().n_recorded(), n_recorded);
+    }
+
+    #[test]
+    fn ring_buffer() {
+        let recorder = BusRecorder::new(4);
+        for i in 0..6 {
+            recorder.record(access(i*4, 0xC000 + i as u16 % 2, i as u8, Access::Read));
+        }
+
+        assert_eq!((recorder.len(), recorder.n_recorded()), (4, 6));
+        assert_eq!(recorder.accesses()[0].val, 2);
+        assert_eq!(recorder.since(16).len(), 2);
+
+        let last = recorder.last(2, 0xC000, 0xC000);
+        assert_eq!(last, [access(8, 0xC000, 2, Access::Read), access(16, 0xC000, 4, Access::Read)]);
+        assert_eq!(last[1].to_string(), "          16  CPU  read   C000  04");
+
+        recorder.clear();
+        assert!(recorder.is_empty());
+    }
+
+    #[test]
+    fn cycle_in_state() {
+        let mut gbemu = new_emulator("cycle_in_state");
+        run(&mut gbemu, 100);
+        let state = gbemu.save_state();
+
+        run(&mut gbemu, 100);
+        assert_eq!(gbemu.get_bus().borrow().cycle(), 200);
+        gbemu.load_state(&state).unwrap();
+        assert_eq!(gbemu.get_bus().borrow().cycle(), 100);
+    }
+
+    #[test]
+    fn console() {
+        let mut gbemu = new_emulator("console");
+        let mut console = Console::new();
+        let mut command = |gbemu :&mut GBEmulator, line :&str| -> Vec<String> {
+            let n_lines = console.output().len();
+            console.run(gbemu, &mut Breakpoints::new(), &mut RamSearch::new(), &Symbols::new(), line);
+            return console.output().iter().skip(n_lines + 1).cloned().collect();
+        };
+
+        assert_eq!(command(&mut gbemu, "bus"), ["Error: not recording the bus, bus on starts"]);
+        assert_eq!(command(&mut gbemu, "bus on 100"), ["Recording the last 100 bus accesses"]);
+        run(&mut gbemu, 80);
+
+        let lines = command(&mut gbemu, "bus/3 0150-0157");
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].ends_with("CPU  read   0155  FA"), "{:?}", lines);
+        assert!(lines[2].ends_with("CPU  read   0157  C0"), "{:?}", lines);
+        assert!(command(&mut gbemu, "bus C000")[0].ends_with("CPU  write  C000  05"));
+        assert_eq!(command(&mut gbemu, "bus/5").len(), 5);
+        assert_eq!(command(&mut gbemu, "bus D000"), ["No access recorded"]);
+
+        // Not the CPU
+        command(&mut gbemu, "poke D000 12");
+        assert_eq!(command(&mut gbemu, "bus D000"), ["No access recorded"]);
+        assert_eq!(gbemu.get_bus().borrow().peek(0xD000), 0x12);
+
+        let path = std::env::temp_dir().join("gb_bus_log_console.txt");
+        let path_str = path.to_str().unwrap();
+        let n = gbemu.get_bus().borrow().recorder().unwrap().len();
+        assert_eq!(command(&mut gbemu, &format!("bus dump {}", path_str)), [format!("Wrote {} bus accesses to {}", n, path_str)]);
+        let dump = std::fs::read_to_string(&path).unwrap();
+        assert_eq!(dump.lines().count(), n + 1);
+        assert!(dump.lines().nth(1).unwrap().ends_with("CPU  read   0101  C3"));
+
+        assert_eq!(command(&mut gbemu, "bus off"), ["Stopped recording the bus"]);
+        assert!(gbemu.get_bus().borrow().recorder().is_none());
+    }
+}
+
+fn main() {}
@@ -0,0 +1,179 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::ComponentWithMemory;
+    use gb::bus::recorder::{BusRecorder, DEFAULT_CAPACITY};
+    use gb::cheats::{Cheat, Cheats};
+    use gb::debugger::breakpoints::Watchpoint;
+    use crate::common::{self, LOOP, new_emulator};
+
+    /* ROM of the given cartridge type looping forever, with the number of each bank at 0x?000 */
+    fn build_rom(name :&str, cart_type :u8, rom_size :u8, ram_size :u8) -> String {
+        let mut rom = common::rom(cart_type, rom_size, ram_size, &LOOP);
+
+        for (bank, chunk) in rom.chunks_mut(0x4000).enumerate() {
+            chunk[0x1000] = bank as u8;
+        }
+
+        return common::write_rom(&format!("cheats_{}", name), rom);
+    }
+
+    #[test]
+    fn parse_codes() {
+        // Value 0x3E at 0x5150
+        assert_eq!(Cheat::parse("3E1-50A").unwrap(),
+                   Cheat::GameGenie { addr: 0x5150, val: 0x3E, compare: None, bank: None });
+        // Only if it was 0x42: E3 rotated right by 2 and XORed with 0xBA
+        assert_eq!(Cheat::parse("3E1-50A-EE3@5").unwrap(),
+                   Cheat::GameGenie { addr: 0x5150, val: 0x3E, compare: Some(0x42), bank: Some(5) });
+        assert_eq!(Cheat::parse("0163C1DA").unwrap(),
+                   Cheat::GameShark { bank: None, addr: 0xDAC1, val: 0x63 });
+        assert_eq!(Cheat::parse("82FF10A0").unwrap(),
+                   Cheat::GameShark { bank: Some(2), addr: 0xA010, val: 0xFF });
+
+        // Game Genie codes only patch the ROM, GameShark codes only the RAM
+        assert!(Cheat::parse("3E1-507").is_err());
+        assert!(Cheat::parse("01630040").is_err());
+        assert!(Cheat::parse("0263C1DA").is_err());
+        assert!(Cheat::parse("0163C1DA@1").is_err());
+        assert!(Cheat::parse("3G1-50A").is_err());
+        assert!(Cheat::parse("3E1-50").is_err());
+    }
+
+    #[test]
+    fn game_genie_compare() {
+        let gbemu = new_emulator(&build_rom("compare", 0x00, 0x00, 0x00));
+        let bus = gbemu.get_bus();
+
+        // 0x18 at 0x0150, 0xFE at 0x0151. The compare byte is 0x18.
+        bus.borrow_mut().cheats_mut().add("C91-50F-89A", "").unwrap();
+        bus.borrow_mut().cheats_mut().add("C91-51F-89A", "").unwrap();
+        assert_eq!(bus.borrow().read(0x0150), 0xC9);
+        assert_eq!(bus.borrow().read(0x0151), 0xFE);
+    }
+
+    #[test]
+    fn game_genie_banks() {
+        // MBC5 and MBC1, 128 KiB
+        for (name, cart_type) in [("mbc5", 0x19), ("mbc1", 0x01)] {
+            let gbemu = new_emulator(&build_rom(name, cart_type, 0x02, 0x00));
+            let bus = gbemu.get_bus();
+
+            bus.borrow_mut().cheats_mut().add("990-00A@2", "").unwrap();
+            bus.borrow_mut().cheats_mut().add("880-00E@0", "").unwrap();
+            assert_eq!(bus.borrow().read(0x5000), 0x01);
+            assert_eq!(bus.borrow().read(0x1000), 0x88);
+
+            bus.borrow_mut().write(0x2000, 0x02);
+            assert_eq!(bus.borrow().read(0x5000), 0x99);
+
+            bus.borrow_mut().write(0x2000, 0x03);
+            assert_eq!(bus.borrow().read(0x5000), 0x03);
+        }
+    }
+
+    #[test]
+    fn game_shark_at_vblank() {
+        let mut gbemu = new_emulator(&build_rom("gameshark", 0x1B, 0x00, 0x03));
+        gbemu.set_uncapped(true);
+        let bus = gbemu.get_bus();
+
+        bus.borrow_mut().cheats_mut().add("0142C1DA", "").unwrap();
+        bus.borrow_mut().cheats_mut().add("8233FFA0", "").unwrap();
+        bus.borrow_mut().write(0xDAC1, 0x00);
+
+        gbemu.run_frame();
+        bus.borrow_mut().write(0x0000, 0x0A);
+        assert_eq!(bus.borrow().read(0xDAC1), 0x42);
+        assert_eq!(bus.borrow().read(0xA0FF), 0x00);
+
+        // Written again at the next frame
+        bus.borrow_mut().write(0xDAC1, 0x00);
+        gbemu.run_frame();
+        assert_eq!(bus.borrow().read(0xDAC1), 0x42);
+
+        bus.borrow_mut().write(0x4000, 0x02);
+        assert_eq!(bus.borrow().read(0xA0FF), 0x33);
+    }
+
+    #[test]
+    fn game_shark_isnt_the_cpu() {
+        let mut gbemu = new_emulator(&build_rom("not_cpu", 0x1B, 0x00, 0x03));
+        gbemu.set_uncapped(true);
+        let bus = gbemu.get_bus();
+
+        bus.borrow_mut().cheats_mut().add("0142C1DA", "").unwrap();
+        bus.borrow_mut().cheats_mut().add("0133FFA0", "").unwrap();
+        bus.borrow_mut().watchpoints_mut().add(Watchpoint::parse("w A000-DFFF").unwrap());
+        bus.borrow_mut().set_recorder(Some(BusRecorder::new(DEFAULT_CAPACITY)));
+        gbemu.run_frame();
+
+        // Written, SRAM in the mapped bank even though it's disabled
+        assert_eq!(bus.borrow().peek(0xDAC1), 0x42);
+        assert_eq!(bus.borrow().peek_ram_bank(0, 0xA0FF), Some(0x33));
+
+        // Without a watchpoint hit, bus access nor save of the SRAM
+        assert!(bus.borrow().watchpoints().take_hit().is_none());
+        assert!(bus.borrow().recorder().unwrap().last(1, 0xA000, 0xDFFF).is_empty());
+        assert!(!bus.borrow_mut().take_ext_ram_written());
+    }
+
+    #[test]
+    fn cheat_file() {
+        let mut gbemu = new_emulator(&build_rom("file", 0x00, 0x00, 0x00));
+        gbemu.set_uncapped(true);
+
+        let path = gbemu.cheats_path();
+        assert!(path.ends_with("gb_cheats_file.cht"));
+        std::fs::write(&path, "# Infinite lives\n0109C1DA Lives\n\n-0163C2DA Disabled\n990-00E\n").unwrap();
+        gbemu.load_cheats(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let bus = gbemu.get_bus();
+        {
+            let bus = bus.borrow();
+            let entries = bus.cheats().entries();
+            assert_eq!(entries.len(), 3);
+            assert_eq!((entries[0].code.as_str(), entries[0].name.as_str(), entries[0].is_enabled), ("0109C1DA", "Lives", true));
+            assert_eq!((entries[1].code.as_str(), entries[1].name.as_str(), entries[1].is_enabled), ("0163C2DA", "Disabled", false));
+            assert_eq!((entries[2].code.as_str(), entries[2].name.as_str(), entries[2].is_enabled), ("990-00E", "", true));
+        }
+
+        gbemu.run_frame();
+        assert_eq!(bus.borrow().read(0xDAC1), 0x09);
+        assert_eq!(bus.borrow().read(0xDAC2), 0x00);
+        assert_eq!(bus.borrow().read(0x1000), 0x99);
+
+        let mut cheats = Cheats::new();
+        std::fs::write(&path, "0109C1DA\nnot-a-code\n").unwrap();
+        assert!(cheats.load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn toggle() {
+        let mut gbemu = new_emulator(&build_rom("toggle", 0x00, 0x00, 0x00));
+        gbemu.set_uncapped(true);
+        let bus = gbemu.get_bus();
+
+        bus.borrow_mut().cheats_mut().add("990-00E", "").unwrap();
+        bus.borrow_mut().cheats_mut().add("0109C1DA", "").unwrap();
+
+        gbemu.set_cheats_enabled(false);
+        gbemu.run_frame();
+        assert_eq!(bus.borrow().read(0x1000), 0x00);
+        assert_ne!(bus.borrow().read(0xDAC1), 0x09);
+
+        gbemu.set_cheats_enabled(true);
+        gbemu.run_frame();
+        assert_eq!(bus.borrow().read(0x1000), 0x99);
+        assert_eq!(bus.borrow().read(0xDAC1), 0x09);
+
+        // One at a time
+        bus.borrow_mut().cheats_mut().set_enabled(0, false);
+        assert_eq!(bus.borrow().read(0x1000), 0x00);
+    }
+}
+
+fn main() {}
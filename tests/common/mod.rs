@@ -0,0 +1,97 @@
+#![allow(dead_code)] // Each test file uses some of the helpers
+
+/*
+ * ROMs built by the tests.
+ *
+ * The ROM jumps from the entry point to the program at 0x150. Test files fill the rest of it
+ * themselves before writing it, e.g. with routines in other banks.
+ */
+
+use std::path::PathBuf;
+use gb::cartridge::{LoadOptions, header_checksum, global_checksum};
+use gb::gbemulator::GBEmulator;
+
+pub const ROM_ONLY :u8 = 0x00;
+
+pub const LOOP :[u8;2] = [0x18, 0xFE]; // JR -2
+
+/*
+ ROM of a cartridge type with the size codes of 0x148 and 0x149 (32 KiB << rom_size, and
+ 0 for no RAM, 2 for 8 KiB, 3 for 32 KiB...), running the program
+ */
+pub fn rom(cart_type :u8, rom_size :u8, ram_size :u8, program :&[u8]) -> Vec<u8> {
+    let mut rom = vec![0;0x8000 << rom_size];
+
+    rom[0x100..0x104].copy_from_slice(&[0x00, 0xC3, 0x50, 0x01]); // NOP; JP 0150
+    rom[0x150..0x150+program.len()].copy_from_slice(program);
+    rom[0x147] = cart_type;
+    rom[0x148] = rom_size;
+    rom[0x149] = ram_size;
+
+    return rom;
+}
+
+/* Once the ROM is done */
+pub fn set_checksums(rom :&mut [u8]) {
+    rom[0x14D] = header_checksum(rom);
+
+    let global = global_checksum(rom);
+    rom[0x14E] = (global >> 8) as u8;
+    rom[0x14F] = (global & 0xFF) as u8;
+}
+
+/* Write the ROM with its checksums to gb_<name>.gb in the temporary directory. Returns the path. */
+pub fn write_rom(name :&str, rom :Vec<u8>) -> String {
+    let path = std::env::temp_dir().join(format!("gb_{}.gb", name));
+    let path = path.to_str().unwrap().to_string();
+    write_rom_at(&path, rom);
+
+    return path;
+}
+
+/* Same at the given path */
+pub fn write_rom_at(path :&str, mut rom :Vec<u8>) {
+    set_checksums(&mut rom);
+    std::fs::write(path, rom).unwrap();
+}
+
+pub fn build_rom(name :&str, cart_type :u8, rom_size :u8, ram_size :u8, program :&[u8]) -> String {
+    return write_rom(name, rom(cart_type, rom_size, ram_size, program));
+}
+
+/* Headless, with a single screen scale */
+pub fn new_emulator(rom :&str) -> GBEmulator {
+    let mut gbemu = GBEmulator::new_headless(rom, 1, &LoadOptions::default()).unwrap();
+    gbemu.init();
+
+    return gbemu;
+}
+
+/*
+ Directory of its own for a test, for what it writes next to the ROM like saves. Named after the
+ process too, so that runs at the same time don't share it, and removed when dropped, even when
+ the test fails.
+ */
+pub struct TestDir {
+    pub path :PathBuf,
+}
+
+impl TestDir {
+    pub fn new(name :&str) -> TestDir {
+        let path = std::env::temp_dir().join(format!("gb_{}_{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&path);
+        std::fs::create_dir_all(&path).unwrap();
+
+        return TestDir { path };
+    }
+
+    pub fn file(&self, name :&str) -> String {
+        return self.path.join(name).to_str().unwrap().to_string();
+    }
+}
+
+impl Drop for TestDir {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.path);
+    }
+}
@@ -0,0 +1,176 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::*;
+    use gb::debugger::breakpoints::{Breakpoints, RunMode};
+    use gb::debugger::console::{Console, Outcome};
+    use gb::debugger::search::RamSearch;
+    use gb::debugger::symbols::Symbols;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, ROM_ONLY};
+
+    const MAIN :[u8;7] = [
+        0x3E, 0x05,         // 0150 LD A, 5
+        0xEA, 0x00, 0xC0,   // 0152 LD (C000), A
+        0x18, 0xFE,         // 0155 JR -2
+    ];
+
+    /* 32 KiB ROM without a mapper */
+    fn build_rom(name :&str) -> String {
+        return common::build_rom(&format!("console_{}", name), ROM_ONLY, 0x00, 0x00, &MAIN);
+    }
+
+    struct Session {
+        gbemu       :GBEmulator,
+        breakpoints :Breakpoints,
+        search      :RamSearch,
+        symbols     :Symbols,
+        console     :Console,
+    }
+
+    impl Session {
+        fn new(name :&str) -> Session {
+            return Session {
+                gbemu: common::new_emulator(&build_rom(name)),
+                breakpoints: Breakpoints::new(),
+                search: RamSearch::new(),
+                symbols: Symbols::new(),
+                console: Console::new()
+            };
+        }
+
+        fn run(&mut self, line :&str) -> Outcome {
+            return self.console.run(&mut self.gbemu, &mut self.breakpoints, &mut self.search, &self.symbols, line);
+        }
+
+        fn last_line(&self) -> &str {
+            return self.console.output().back().unwrap();
+        }
+
+        /* Resume like the debugger until it stops again */
+        fn resume(&mut self) -> u16 {
+            for _ in 0..1_000_000 {
+                self.gbemu.get_bus().borrow_mut().tick();
+                self.gbemu.get_cpu_mut().tick();
+
+                if self.gbemu.get_cpu().is_new_instr() {
+                    let bus = self.gbemu.get_bus();
+                    let stop = self.breakpoints.check(self.gbemu.get_cpu(), &mut bus.borrow_mut());
+                    if stop.is_some() {
+                        return self.gbemu.get_cpu().instr_addr();
+                    }
+                }
+            }
+
+            panic!("The debugger didn't stop");
+        }
+    }
+
+    #[test]
+    fn memory() {
+        let mut session = Session::new("memory");
+        let bus = session.gbemu.get_bus();
+
+        assert_eq!(session.run("poke C000 12 34 ab"), Outcome::Stay);
+        assert_eq!((bus.borrow().read(0xC000), bus.borrow().read(0xC001), bus.borrow().read(0xC002)), (0x12, 0x34, 0xAB));
+        assert_eq!(session.last_line(), "Wrote 3 bytes at C000");
+
+        session.run("x/20 C000");
+        let lines :Vec<&String> = session.console.output().iter().rev().take(2).collect();
+        assert!(lines[1].starts_with("C000  12 34 AB 00"), "{}", lines[1]);
+        assert_eq!(lines[0], &format!("C010  00 00 00 00{}  ....", " ".repeat(36)));
+
+        session.run("x FF80");
+        assert!(session.last_line().starts_with("FFB0  "));
+
+        for bad in ["poke C000 100", "poke C000", "poke", "x/zz C000", "x", "xyz"] {
+            session.run(bad);
+            assert!(session.last_line().starts_with("Error: "), "{}", bad);
+        }
+        assert_eq!(bus.borrow().read(0xC000), 0x12);
+    }
+
+    #[test]
+    fn registers() {
+        let mut session = Session::new("registers");
+
+        session.run("set reg A=0x12");
+        assert_eq!(session.gbemu.get_cpu().reg(REG_A), 0x12);
+        assert_eq!(session.last_line(), "A = 12");
+
+        session.run("set HL = $C0FF");
+        session.run("set f=255");
+        assert_eq!(session.gbemu.get_cpu().reg16(REG_H, REG_L), 0xC0FF);
+        assert_eq!(session.gbemu.get_cpu().reg(REG_F), 0xF0);
+
+        // Jumping to the store
+        session.run("set pc=0x152");
+        assert_eq!(session.gbemu.get_cpu().instr_addr(), 0x0152);
+        assert_eq!(session.gbemu.get_cpu().get_opcode(), 0xEA);
+        assert_eq!(session.run("step"), Outcome::Resume);
+        assert_eq!(session.resume(), 0x0155);
+        assert_eq!(session.gbemu.get_bus().borrow().read(0xC000), 0x12);
+
+        for bad in ["set Q=1", "set A=0x100", "set A", "set reg A=zz"] {
+            session.run(bad);
+            assert!(session.last_line().starts_with("Error: "), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn running() {
+        let mut session = Session::new("running");
+
+        assert_eq!(session.run("step 3"), Outcome::Resume);
+        assert_eq!(session.breakpoints.mode(), RunMode::Step(2));
+        assert_eq!(session.resume(), 0x0152);
+
+        assert_eq!(session.run("frame"), Outcome::Resume);
+        session.resume();
+        assert_eq!(session.gbemu.get_bus().borrow().read(ADDR_LY), 144);
+
+        assert_eq!(session.run("b 0155 if [C000] == 5"), Outcome::Stay);
+        assert_eq!(session.run("continue"), Outcome::Resume);
+        assert_eq!(session.resume(), 0x0155);
+
+        session.run("reset");
+        assert_eq!(session.gbemu.get_cpu().instr_addr(), 0x0100);
+        assert_eq!(session.gbemu.get_cpu().reg(REG_A), 0x01);
+        assert_eq!(session.breakpoints.mode(), RunMode::Step(0));
+        assert_eq!(session.breakpoints.list().len(), 1);
+
+        session.run("step 0");
+        assert_eq!(session.last_line(), "Error: step at least 1 instruction");
+    }
+
+    #[test]
+    fn disassembly_and_history() {
+        let mut session = Session::new("history");
+
+        session.run("disas 0150 3");
+        let lines :Vec<&String> = session.console.output().iter().rev().take(3).collect();
+        assert!(lines[2].contains("[0150]") && lines[2].contains("LD A 05h"), "{}", lines[2]);
+        assert!(lines[1].contains("[0152]"));
+        assert!(lines[0].contains("[0155]"));
+
+        // At PC by default
+        session.run("disas");
+        let output = session.console.output();
+        assert!(output[output.len() - 10].contains("[0100]"));
+
+        session.run("search new");
+        assert!(session.last_line().ends_with("candidates"));
+
+        session.run("bogus");
+        assert_eq!(session.last_line(), "Error: unknown command bogus, help lists them");
+
+        // The same command twice in a row is kept once
+        session.run("  bogus  ");
+        session.run("");
+        assert_eq!(session.console.history(), ["disas 0150 3", "disas", "search new", "bogus"]);
+        assert_eq!(session.console.output().iter().filter(|line| line.starts_with("> ")).count(), 5);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,118 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::{Cell, RefCell};
+    use std::rc::Rc;
+    use gb::cartridge::LoadOptions;
+    use gb::gbemulator::GBEmulator;
+    use gb::frontend::AudioSink;
+    use gb::frontend::headless::{HeadlessVideo, NullInput};
+    use crate::common::{self, ROM_ONLY};
+
+    // LD HL, 0xC000; INC (HL); JR -3
+    const COUNT_LOOP :[u8;6] = [0x21, 0x00, 0xC0, 0x34, 0x18, 0xFD];
+    // LD A, 0; LDH (0x40), A; then COUNT_LOOP
+    const LCD_OFF_LOOP :[u8;10] = [0x3E, 0x00, 0xE0, 0x40, 0x21, 0x00, 0xC0, 0x34, 0x18, 0xFD];
+
+    // Counts the queued samples
+    struct CountingAudio {
+        n_samples :Rc<Cell<usize>>
+    }
+
+    impl AudioSink for CountingAudio {
+        fn queue(&mut self, _val_left :u8, _val_right :u8, n_samples :u16) {
+            self.n_samples.set(self.n_samples.get() + n_samples as usize);
+        }
+        fn pause(&mut self) {}
+        fn resume(&mut self) {}
+    }
+
+    fn new_emulator(name :&str, program :&[u8]) -> (GBEmulator, Rc<Cell<usize>>) {
+        let n_samples = Rc::new(Cell::new(0));
+        let mut gbemu = GBEmulator::with_backends(
+            &common::build_rom(&format!("controls_{}", name), ROM_ONLY, 0, 0, program),
+            &LoadOptions::default(),
+            Rc::new(RefCell::new(HeadlessVideo::new(1))),
+            Box::new(CountingAudio { n_samples: n_samples.clone() }),
+            Box::new(NullInput)
+        ).unwrap();
+        gbemu.init();
+
+        return (gbemu, n_samples);
+    }
+
+    #[test]
+    fn frames_follow_vblank() {
+        let (mut gbemu, _) = new_emulator("vblank", &COUNT_LOOP);
+
+        // The LCD starts at LY 0, so the first VBlank comes after 144 lines
+        gbemu.run_frame();
+        assert_eq!(gbemu.frame_cycles(), 144*456);
+
+        for _ in 0..3 {
+            gbemu.run_frame();
+            assert_eq!(gbemu.frame_cycles(), 70224);
+        }
+    }
+
+    #[test]
+    fn frames_with_lcd_off() {
+        let (mut gbemu, _) = new_emulator("lcd_off", &LCD_OFF_LOOP);
+
+        for _ in 0..3 {
+            gbemu.run_frame();
+            assert_eq!(gbemu.frame_cycles(), 70224);
+        }
+    }
+
+    #[test]
+    fn pause_and_frame_advance() {
+        let (mut gbemu, _) = new_emulator("pause", &COUNT_LOOP);
+        gbemu.run_frame();
+
+        gbemu.set_paused(true);
+        let paused = gbemu.save_state();
+        for _ in 0..3 { gbemu.run_frame(); }
+        assert_eq!(gbemu.save_state(), paused);
+
+        gbemu.frame_advance();
+        gbemu.run_frame();
+        let advanced = gbemu.save_state();
+        assert_ne!(advanced, paused);
+
+        gbemu.run_frame();
+        assert!(gbemu.is_paused());
+        assert_eq!(gbemu.save_state(), advanced);
+    }
+
+    #[test]
+    fn audio_follows_speed() {
+        let (mut gbemu, n_samples) = new_emulator("audio", &COUNT_LOOP);
+
+        let count_frames = |gbemu :&mut GBEmulator, n :usize| {
+            n_samples.set(0);
+            for _ in 0..n { gbemu.run_frame(); }
+            return n_samples.get();
+        };
+
+        // The first frame is shorter
+        gbemu.run_frame();
+
+        let normal = count_frames(&mut gbemu, 4);
+        assert!(normal > 0);
+
+        gbemu.set_speed(4.0);
+        let fast = count_frames(&mut gbemu, 4);
+        assert!(fast > normal/4 - 16 && fast < normal/4 + 16);
+
+        gbemu.set_speed(0.5);
+        let slow = count_frames(&mut gbemu, 4);
+        assert!(slow > normal*2 - 16 && slow < normal*2 + 16);
+
+        gbemu.set_uncapped(true);
+        assert_eq!(count_frames(&mut gbemu, 4), 0);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,72 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::cartridge::{CartridgeType, header_checksum};
+    use gb::cartridge::header::{CartridgeHeader, CgbSupport, Destination};
+    use crate::common::{self, ROM_ONLY};
+
+    fn build_rom(title :&[u8], fields :&[(usize, u8)]) -> Vec<u8> {
+        let mut rom = common::rom(ROM_ONLY, 0x00, 0x00, &[]);
+
+        rom[0x134..0x134+title.len()].copy_from_slice(title);
+        for (addr, val) in fields {
+            rom[*addr] = *val;
+        }
+        rom[0x14D] = header_checksum(&rom);
+
+        return rom;
+    }
+
+    #[test]
+    fn dmg_header() {
+        // Old licensee, 16-character title, MBC1+RAM+BATTERY, 256 KiB ROM, 8 KiB RAM
+        let rom = build_rom(b"SUPER MARIOLAND", &[(0x147, 0x03), (0x148, 0x03), (0x149, 0x02), (0x14B, 0x01), (0x14C, 0x01)]);
+        let header = CartridgeHeader::parse(&rom);
+
+        assert_eq!(header.title, "SUPER MARIOLAND");
+        assert_eq!(header.manufacturer_code, None);
+        assert_eq!(header.new_licensee_code, None);
+        assert_eq!(header.licensee(), Some("Nintendo"));
+        assert_eq!(header.cgb, CgbSupport::None);
+        assert!(!header.is_sgb);
+        assert_eq!(header.cartridge_type, CartridgeType::MBC1_RAM_BAT);
+        assert_eq!(header.rom_size, 256*1024);
+        assert_eq!(header.rom_bank_n, 16);
+        assert_eq!(header.ram_size, 8);
+        assert_eq!(header.ram_bank_n, 1);
+        assert_eq!(header.destination, Destination::Japan);
+        assert_eq!(header.version, 1);
+    }
+
+    #[test]
+    fn cgb_header() {
+        // Shorter title followed by the manufacturer code, new licensee code
+        let mut fields = vec![(0x143, 0xC0), (0x146, 0x03), (0x14A, 0x01), (0x14B, 0x33), (0x144, b'A'), (0x145, b'4')];
+        fields.extend(b"BXTE".iter().enumerate().map(|(i, c)| (0x13F + i, *c)));
+        let header = CartridgeHeader::parse(&build_rom(b"CGB GAME", &fields));
+
+        assert_eq!(header.title, "CGB GAME");
+        assert_eq!(header.manufacturer_code.as_deref(), Some("BXTE"));
+        assert_eq!(header.new_licensee_code.as_deref(), Some("A4"));
+        assert_eq!(header.licensee(), Some("Konami (Yu-Gi-Oh!)"));
+        assert_eq!(header.cgb, CgbSupport::Only);
+        assert!(header.is_sgb);
+        assert_eq!(header.destination, Destination::Overseas);
+    }
+
+    #[test]
+    fn json() {
+        let rom = build_rom(b"TEST", &[(0x14B, 0xFE)]);
+        let json = CartridgeHeader::parse(&rom).to_json(&rom);
+
+        assert_eq!(json["title"], "TEST");
+        assert_eq!(json["licensee"], serde_json::Value::Null);
+        assert_eq!(json["old_licensee_code"], "FE");
+        assert_eq!(json["cartridge_type"], "ROM");
+        assert_eq!(json["header_checksum_ok"], true);
+        assert_eq!(json["global_checksum_ok"], false);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,116 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::cartridge::{LoadError, LoadOptions, load_cartridge};
+    use gb::consts::ComponentWithMemory;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, LOOP, ROM_ONLY};
+
+    /* 32 KiB ROM with a valid header, looping forever */
+    fn valid_rom() -> Vec<u8> {
+        let mut rom = common::rom(ROM_ONLY, 0x00, 0x00, &LOOP);
+        common::set_checksums(&mut rom);
+
+        return rom;
+    }
+
+    fn write_rom(name :&str, rom :&[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("gb_loading_{}.gb", name));
+        std::fs::write(&path, rom).unwrap();
+
+        return path.to_str().unwrap().to_string();
+    }
+
+    fn load(name :&str, rom :&[u8]) -> Result<(), LoadError> {
+        return load_cartridge(&write_rom(name, rom), &LoadOptions::default()).map(|_| ());
+    }
+
+    #[test]
+    fn valid_header() {
+        assert!(load("valid", &valid_rom()).is_ok());
+    }
+
+    #[test]
+    fn missing_file() {
+        let path = std::env::temp_dir().join("gb_loading_missing.gb");
+        let err = load_cartridge(path.to_str().unwrap(), &LoadOptions::default()).err().unwrap();
+
+        assert!(matches!(err, LoadError::Io(_)));
+    }
+
+    #[test]
+    fn too_short() {
+        assert!(matches!(load("short", &[0;0x100]), Err(LoadError::TooShort(0x100))));
+    }
+
+    #[test]
+    fn header_checksum() {
+        let mut rom = valid_rom();
+        rom[0x134] = b'X';
+
+        let err = load("header_checksum", &rom).err().unwrap();
+        assert!(matches!(err, LoadError::HeaderChecksum { expected: 0xE7, actual: 0x8F }));
+        assert_eq!(err.to_string(), "bad header checksum: 0xE7 in the header, 0x8F computed");
+    }
+
+    #[test]
+    fn rom_size_mismatch() {
+        let mut rom = valid_rom();
+        rom[0x148] = 0x01;
+        common::set_checksums(&mut rom);
+
+        let err = load("rom_size", &rom).err().unwrap();
+        assert!(matches!(err, LoadError::RomSizeMismatch { expected: 0x10000, actual: 0x8000 }));
+    }
+
+    #[test]
+    fn global_checksum() {
+        let mut rom = valid_rom();
+        rom[0x7FFF] = 0x01;
+
+        let err = load("global_checksum", &rom).err().unwrap();
+        assert!(matches!(err, LoadError::GlobalChecksum { .. }));
+    }
+
+    #[test]
+    fn unsupported_type() {
+        for cart_type in [0x20, 0x22, 0xFC] {
+            let mut rom = valid_rom();
+            rom[0x147] = cart_type;
+            common::set_checksums(&mut rom);
+
+            let err = load("unsupported", &rom).err().unwrap();
+            assert!(matches!(err, LoadError::Unsupported(t) if t == cart_type));
+        }
+    }
+
+    #[test]
+    fn emulator_reports_errors() {
+        let path = write_rom("emulator", &[0;0x20]);
+
+        assert!(matches!(GBEmulator::new_headless(&path, 1, &LoadOptions::default()), Err(LoadError::TooShort(0x20))));
+    }
+
+    #[test]
+    fn lenient_headerless_rom() {
+        // LD A, 0x42; LD (0xC000), A; JR -2, from the entry point, without a header
+        let mut rom = vec![0;0x100];
+        rom.extend_from_slice(&[0x3E, 0x42, 0xEA, 0x00, 0xC0, 0x18, 0xFE]);
+        let path = write_rom("lenient", &rom);
+
+        assert!(GBEmulator::new_headless(&path, 1, &LoadOptions::default()).is_err());
+
+        let mut gbemu = GBEmulator::new_headless(&path, 1, &LoadOptions { is_lenient: true, ..Default::default() }).unwrap();
+        gbemu.init();
+        gbemu.set_uncapped(true);
+        gbemu.run_frame();
+
+        let bus = gbemu.get_bus();
+        assert_eq!(bus.borrow().read(0xC000), 0x42);
+        // Padded to 32 KiB
+        assert_eq!(bus.borrow().read(0x7FFF), 0x00);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,149 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::*;
+    use gb::debugger::memview::{MemoryView, MemoryInput, Region};
+    use gb::debugger::symbols::Symbols;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, LOOP};
+
+    /* 128 KiB MBC5 ROM with 32 KiB of RAM, each ROM bank starting with its number */
+    fn build_rom(name :&str) -> String {
+        let mut rom = common::rom(0x1A, 0x02, 0x03, &LOOP);
+        for (n, bank) in rom.chunks_mut(0x4000).enumerate() {
+            bank[0x10] = 0xB0 + n as u8;
+        }
+
+        return common::write_rom(&format!("memory_view_{}", name), rom);
+    }
+
+    fn new_emulator(name :&str) -> GBEmulator {
+        return common::new_emulator(&build_rom(name));
+    }
+
+    #[test]
+    fn regions() {
+        let gbemu = new_emulator("regions");
+        let bus = gbemu.get_bus();
+        let symbols = Symbols::parse("00:c123 wLives\n05:4010 Bank5Data\n").unwrap();
+        let mut memory = MemoryView::new();
+
+        assert_eq!(Region::parse("rom 3"), Ok(Region::Rom(3)));
+        assert_eq!(Region::parse("SRAM"), Ok(Region::Sram(0)));
+        assert_eq!(Region::parse("oam"), Ok(Region::Oam));
+        for bad in ["rom x", "vram 1", "sram 300", "stack"] {
+            assert!(Region::parse(bad).is_err(), "{}", bad);
+        }
+
+        assert_eq!(Region::at(&bus.borrow(), 0x4000), Some(Region::Rom(1)));
+        assert_eq!(Region::at(&bus.borrow(), 0xFEA0), None);
+        assert_eq!(Region::at(&bus.borrow(), 0xFF80), Some(Region::Hram));
+        assert_eq!(Region::Io.range(), (0xFF00, 0xFF7F));
+
+        // Banks that aren't mapped
+        memory.goto(&bus.borrow(), &symbols, "rom 3").unwrap();
+        assert_eq!((memory.region(), memory.cursor()), (Region::Rom(3), 0x4000));
+        assert_eq!(memory.lines(&bus.borrow(), 2, 16)[1].bytes[0].0, 0xB3);
+
+        memory.goto(&bus.borrow(), &symbols, "Bank5Data").unwrap();
+        assert_eq!((memory.region(), memory.cursor()), (Region::Rom(5), 0x4010));
+        memory.goto(&bus.borrow(), &symbols, "6:4010").unwrap();
+        assert_eq!(memory.region(), Region::Rom(6));
+        memory.input(&mut bus.borrow_mut(), &symbols, MemoryInput::Bank(2)).unwrap();
+        assert_eq!((memory.region(), memory.cursor()), (Region::Rom(0), 0x0010));
+        assert_eq!(memory.lines(&bus.borrow(), 1, 16)[0].bytes[0].0, 0xB0);
+
+        // The mapped bank by default, and the echo RAM is the WRAM
+        memory.goto(&bus.borrow(), &symbols, "$4010").unwrap();
+        assert_eq!(memory.region(), Region::Rom(1));
+        memory.goto(&bus.borrow(), &symbols, "E123").unwrap();
+        assert_eq!((memory.region(), memory.cursor()), (Region::Wram, 0xC123));
+        memory.goto(&bus.borrow(), &symbols, "wLives").unwrap();
+        assert_eq!(memory.cursor(), 0xC123);
+        assert!(memory.goto(&bus.borrow(), &symbols, "FEA0").is_err());
+
+        // The cursor stays in the region
+        memory.input(&mut bus.borrow_mut(), &symbols, MemoryInput::NextRegion).unwrap();
+        assert_eq!((memory.region(), memory.cursor()), (Region::Oam, 0xFE00));
+        memory.move_cursor(-16);
+        assert_eq!(memory.cursor(), 0xFE00);
+        memory.move_cursor(0x1000);
+        assert_eq!(memory.cursor(), 0xFE9F);
+        let lines = memory.lines(&bus.borrow(), 20, 16);
+        assert_eq!((lines.len(), lines[9].addr), (10, 0xFE90));
+        assert!(memory.input(&mut bus.borrow_mut(), &symbols, MemoryInput::Bank(1)).is_err());
+    }
+
+    #[test]
+    fn editing() {
+        let gbemu = new_emulator("editing");
+        let bus = gbemu.get_bus();
+        let symbols = Symbols::new();
+        let mut memory = MemoryView::new();
+
+        let type_byte = |memory :&mut MemoryView, high :u8, low :u8| -> Result<(), String> {
+            memory.input(&mut bus.borrow_mut(), &symbols, MemoryInput::Digit(high))?;
+            assert_eq!(memory.high_digit(), Some(high));
+            return memory.input(&mut bus.borrow_mut(), &symbols, MemoryInput::Digit(low));
+        };
+
+        memory.goto(&bus.borrow(), &symbols, "C000").unwrap();
+        type_byte(&mut memory, 0xA, 0xB).unwrap();
+        type_byte(&mut memory, 0x1, 0x2).unwrap();
+        assert_eq!((bus.borrow().read(0xC000), bus.borrow().read(0xC001)), (0xAB, 0x12));
+        assert_eq!((memory.cursor(), memory.high_digit()), (0xC002, None));
+
+        // The ROM can't be edited
+        memory.goto(&bus.borrow(), &symbols, "rom 2").unwrap();
+        assert!(type_byte(&mut memory, 0x1, 0x2).is_err());
+        assert_eq!(bus.borrow().peek_rom_bank(2, 0x4000), 0x00);
+
+        // A RAM bank that isn't mapped, whatever the RAM gate
+        memory.goto(&bus.borrow(), &symbols, "sram 2").unwrap();
+        type_byte(&mut memory, 0x5, 0xA).unwrap();
+        assert_eq!(bus.borrow().peek_ram_bank(2, 0xA000), Some(0x5A));
+        bus.borrow_mut().write(0x0000, 0x0A);
+        bus.borrow_mut().write(0x4000, 0x02);
+        assert_eq!(bus.borrow().read(0xA000), 0x5A);
+        memory.goto(&bus.borrow(), &symbols, "A001").unwrap();
+        assert_eq!(memory.region(), Region::Sram(2));
+
+        memory.input(&mut bus.borrow_mut(), &symbols, MemoryInput::Bank(2)).unwrap();
+        assert_eq!((memory.region(), memory.cursor()), (Region::Sram(0), 0xA001));
+        assert!(memory.goto(&bus.borrow(), &symbols, "sram 4").is_ok());
+        assert!(type_byte(&mut memory, 0x1, 0x2).is_err());
+
+        // The VRAM, even while the PPU draws
+        memory.goto(&bus.borrow(), &symbols, "vram").unwrap();
+        type_byte(&mut memory, 0xF, 0xF).unwrap();
+        assert_eq!(bus.borrow().vram()[0], 0xFF);
+    }
+
+    #[test]
+    fn changes() {
+        let gbemu = new_emulator("changes");
+        let bus = gbemu.get_bus();
+        let mut memory = MemoryView::new();
+
+        // Nothing to compare with before the first stop
+        bus.borrow_mut().write(0xC005, 0x42);
+        assert!(memory.lines(&bus.borrow(), 1, 16)[0].bytes.iter().all(|(_, is_changed)| !is_changed));
+
+        memory.take_snapshot(&bus.borrow());
+        bus.borrow_mut().write(0xC005, 0x43);
+        bus.borrow_mut().write(0xC011, 0x01);
+
+        let lines = memory.lines(&bus.borrow(), 2, 16);
+        let changed :Vec<u16> = lines.iter()
+            .flat_map(|line| line.bytes.iter().enumerate().filter(|(_, (_, is_changed))| *is_changed).map(move |(i, _)| line.addr + i as u16))
+            .collect();
+        assert_eq!(changed, [0xC005, 0xC011]);
+        assert_eq!(lines[0].bytes[5], (0x43, true));
+
+        memory.take_snapshot(&bus.borrow());
+        assert!(memory.lines(&bus.borrow(), 2, 16).iter().all(|line| line.bytes.iter().all(|(_, is_changed)| !is_changed)));
+    }
+}
+
+fn main() {}
@@ -0,0 +1,149 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use gb::cartridge::LoadOptions;
+    use gb::gbemulator::GBEmulator;
+    use gb::movie::Movie;
+    use gb::savestate::StateError;
+    use gb::frontend::{InputSource, InputEvent, Button};
+    use gb::frontend::headless::{HeadlessVideo, NullAudio, NullInput};
+    use crate::common::{self, ROM_ONLY};
+
+    const N_FRAMES :usize = 130;
+
+    /*
+     Keeps copying the direction buttons to 0xC000-0xC0FF:
+        0x0150: LD HL, 0xC000
+        0x0153: LD A, 0x20
+                LDH (0x00), A
+                LDH A, (0x00)
+                LD (HL), A
+                INC L
+                JR 0x0153
+     */
+    const PROGRAM :[u8;13] = [0x21, 0x00, 0xC0, 0x3E, 0x20, 0xE0, 0x00, 0xF0, 0x00, 0x77, 0x2C, 0x18, 0xF6];
+
+    // Sends the events of the script on their frame
+    struct ScriptInput {
+        frame  :usize,
+        script :Vec<(usize, InputEvent)>
+    }
+
+    impl InputSource for ScriptInput {
+        fn poll_events(&mut self) -> Vec<InputEvent> {
+            let events = self.script.iter()
+                .filter(|(frame, _)| *frame == self.frame)
+                .map(|(_, event)| *event)
+                .collect();
+            self.frame += 1;
+
+            return events;
+        }
+    }
+
+    fn new_emulator(name :&str, input :Box<dyn InputSource>) -> GBEmulator {
+        let mut gbemu = GBEmulator::with_backends(
+            &common::build_rom(&format!("movie_{}", name), ROM_ONLY, 0, 0, &PROGRAM),
+            &LoadOptions::default(),
+            Rc::new(RefCell::new(HeadlessVideo::new(1))),
+            Box::new(NullAudio),
+            input
+        ).unwrap();
+        gbemu.init();
+        gbemu.set_uncapped(true);
+
+        return gbemu;
+    }
+
+    fn script() -> Box<ScriptInput> {
+        return Box::new(ScriptInput { frame: 0, script: vec![
+            (3,  InputEvent::Press(Button::Right)),
+            (10, InputEvent::Release(Button::Right)),
+            (20, InputEvent::Press(Button::Down)),
+            (90, InputEvent::Release(Button::Down)),
+            (95, InputEvent::Press(Button::Left)),
+        ]});
+    }
+
+    fn sync_hash(gbemu :&GBEmulator) -> [u8;16] {
+        return gbemu.get_bus().borrow().sync_hash();
+    }
+
+    // Record the script from the current state
+    fn record(gbemu :&mut GBEmulator, from_state :bool) -> (Movie, [u8;16]) {
+        gbemu.record_movie(from_state);
+        for _ in 0..N_FRAMES { gbemu.run_frame(); }
+
+        let hash = sync_hash(gbemu);
+        let movie = gbemu.stop_movie().unwrap();
+        let movie = Movie::from_bytes(&movie.to_bytes(), gbemu.rom_md5()).unwrap();
+
+        return (movie, hash);
+    }
+
+    fn play(name :&str, movie :Movie) -> GBEmulator {
+        let mut gbemu = new_emulator(name, Box::new(NullInput));
+        gbemu.play_movie(movie).unwrap();
+        for _ in 0..N_FRAMES { gbemu.run_frame(); }
+
+        return gbemu;
+    }
+
+    #[test]
+    fn replay_from_power_on() {
+        let mut gbemu = new_emulator("replay_from_power_on", script());
+        for _ in 0..5 { gbemu.run_frame(); }
+
+        let (movie, hash) = record(&mut gbemu, false);
+        assert_eq!(movie.n_frames(), N_FRAMES as u32);
+        assert!(movie.start_state().is_none());
+
+        let gbemu = play("replay_from_power_on", movie);
+        assert_eq!(gbemu.movie_desync(), None);
+        assert_eq!(sync_hash(&gbemu), hash);
+    }
+
+    #[test]
+    fn replay_from_state() {
+        let mut gbemu = new_emulator("replay_from_state", script());
+        for _ in 0..15 { gbemu.run_frame(); }
+
+        let (movie, hash) = record(&mut gbemu, true);
+        assert!(movie.start_state().is_some());
+
+        let gbemu = play("replay_from_state", movie);
+        assert_eq!(gbemu.movie_desync(), None);
+        assert_eq!(sync_hash(&gbemu), hash);
+    }
+
+    #[test]
+    fn detect_desync() {
+        let mut gbemu = new_emulator("detect_desync", script());
+        let (movie, _) = record(&mut gbemu, false);
+
+        // Header (22 bytes), no state, hash interval and frame count, then 2 bytes per frame
+        let mut data = movie.to_bytes();
+        data[22 + 1 + 4 + 4 + 2*59] = 0b11101101; // Press left on the frame before the hash
+        let movie = Movie::from_bytes(&data, gbemu.rom_md5()).unwrap();
+
+        let gbemu = play("detect_desync", movie);
+        assert_eq!(gbemu.movie_desync(), Some(60));
+    }
+
+    #[test]
+    fn zero_hash_interval() {
+        let mut gbemu = new_emulator("zero_hash_interval", script());
+        let (movie, _) = record(&mut gbemu, false);
+
+        // Right after the header (22 bytes) and the flag of the state
+        let mut data = movie.to_bytes();
+        data[22 + 1..22 + 1 + 4].copy_from_slice(&0u32.to_le_bytes());
+
+        assert!(matches!(Movie::from_bytes(&data, gbemu.rom_md5()), Err(StateError::Corrupt(_))));
+    }
+}
+
+fn main() {}
@@ -1,6 +1,7 @@
 #[cfg(test)]
 mod tests {
     use std::time::SystemTime;
+    use gb::cartridge::LoadOptions;
     use gb::gbemulator::GBEmulator;
     use gb::consts::*;
 
@@ -37,7 +38,7 @@ mod tests {
     pub fn test_rom(file :&str) {
         let path = format!("{}/{}", ROM_FOLDER, file);
 
-        let mut gbemu = GBEmulator::new(&path, 2);
+        let mut gbemu = GBEmulator::new_headless(&path, 2, &LoadOptions::default()).unwrap();
         gbemu.init();
 
         let bus = gbemu.get_bus();
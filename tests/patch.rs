@@ -0,0 +1,258 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::cartridge::{LoadError, LoadOptions, load_rom};
+    use gb::cartridge::patch::PatchError;
+    use crate::common::{self, LOOP, ROM_ONLY};
+
+    /* 32 KiB ROM with a valid header */
+    fn build_rom() -> Vec<u8> {
+        let mut rom = common::rom(ROM_ONLY, 0x00, 0x00, &LOOP);
+        common::set_checksums(&mut rom);
+
+        return rom;
+    }
+
+    fn write_file(name :&str, data :&[u8]) -> String {
+        let path = std::env::temp_dir().join(format!("gb_patch_{}", name));
+        std::fs::write(&path, data).unwrap();
+
+        return path.to_str().unwrap().to_string();
+    }
+
+    fn load(rom :&str, patches :&[String]) -> Result<Vec<u8>, LoadError> {
+        let options = LoadOptions { patches: patches.to_vec(), ..Default::default() };
+        return load_rom(rom, &options).map(|(rom, _)| rom);
+    }
+
+    fn varint(mut val :usize) -> Vec<u8> {
+        let mut out = vec![];
+        loop {
+            let byte = (val & 0x7F) as u8;
+            val >>= 7;
+            if val == 0 {
+                out.push(byte | 0x80);
+                return out;
+            }
+            out.push(byte);
+            val -= 1;
+        }
+    }
+
+    fn add_footer(mut patch :Vec<u8>, source :&[u8], target :&[u8]) -> Vec<u8> {
+        patch.extend_from_slice(&crc32fast::hash(source).to_le_bytes());
+        patch.extend_from_slice(&crc32fast::hash(target).to_le_bytes());
+        let crc = crc32fast::hash(&patch);
+        patch.extend_from_slice(&crc.to_le_bytes());
+
+        return patch;
+    }
+
+    /* Sets 0x200-0x203 to 0x11, 0x7FFF to 0x22 and appends 0x33 */
+    fn ips() -> Vec<u8> {
+        let mut patch = b"PATCH".to_vec();
+        patch.extend_from_slice(&[0x00, 0x02, 0x00, 0x00, 0x00, 0x00, 0x04, 0x11]);
+        patch.extend_from_slice(&[0x00, 0x7F, 0xFF, 0x00, 0x02, 0x22, 0x33]);
+        patch.extend_from_slice(b"EOF");
+
+        return patch;
+    }
+
+    #[test]
+    fn ips_patch() {
+        let rom = write_file("ips.gb", &build_rom());
+        let patch = write_file("ips_explicit.ips", &ips());
+
+        let patched = load(&rom, &[patch]).unwrap();
+        assert_eq!(patched.len(), 0x8001);
+        assert_eq!(patched[0x1FF..0x205], [0x00, 0x11, 0x11, 0x11, 0x11, 0x00]);
+        assert_eq!(patched[0x7FFF..], [0x22, 0x33]);
+    }
+
+    #[test]
+    fn detected_next_to_the_rom() {
+        let rom = write_file("detected.gb", &build_rom());
+        let patch = write_file("detected.ips", &ips());
+
+        let patched = load(&rom, &[]);
+        std::fs::remove_file(patch).unwrap();
+
+        assert_eq!(patched.unwrap()[0x200], 0x11);
+        assert_eq!(load(&rom, &[]).unwrap(), build_rom());
+    }
+
+    fn ups(source :&[u8], target :&[u8]) -> Vec<u8> {
+        let mut patch = b"UPS1".to_vec();
+        patch.extend(varint(source.len()));
+        patch.extend(varint(target.len()));
+
+        // Each run of changed bytes, after the number of bytes skipped since the last one
+        let mut last = 0;
+        let mut pos = 0;
+        while pos < target.len() {
+            let xor = |i :usize| source.get(i).copied().unwrap_or(0) ^ target[i];
+            if xor(pos) == 0 {
+                pos += 1;
+                continue;
+            }
+
+            patch.extend(varint(pos - last));
+            while pos < target.len() && xor(pos) != 0 {
+                patch.push(xor(pos));
+                pos += 1;
+            }
+            patch.push(0);
+            pos += 1;
+            last = pos;
+        }
+
+        return add_footer(patch, source, target);
+    }
+
+    #[test]
+    fn ups_patch() {
+        let source = build_rom();
+        let mut target = source.clone();
+        target[0x1234..0x1238].copy_from_slice(b"UPS!");
+        target.extend_from_slice(&[0x44;0x10]);
+
+        let rom = write_file("ups.gb", &source);
+        let patch = write_file("ups_explicit.ups", &ups(&source, &target));
+
+        assert_eq!(load(&rom, &[patch]).unwrap(), target);
+    }
+
+    #[test]
+    fn ups_for_another_rom() {
+        let mut other = build_rom();
+        other[0x1000] = 0x01;
+        let mut target = other.clone();
+        target[0x2000] = 0x02;
+
+        let rom = write_file("ups_other.gb", &build_rom());
+        let patch = write_file("ups_other.ups", &ups(&other, &target));
+
+        let err = load(&rom, &[patch.clone()]).err().unwrap();
+        assert!(matches!(&err, LoadError::Patch(path, PatchError::SourceCrc { .. }) if *path == patch));
+        assert!(err.to_string().contains("the patch is for a ROM with CRC32"));
+    }
+
+    #[test]
+    fn corrupt_patch() {
+        let source = build_rom();
+        let mut target = source.clone();
+        target[0x2000] = 0x02;
+
+        let mut patch = ups(&source, &target);
+        patch[8] ^= 0xFF;
+
+        let rom = write_file("corrupt.gb", &source);
+        let patch = write_file("corrupt.ups", &patch);
+
+        assert!(matches!(load(&rom, &[patch]), Err(LoadError::Patch(_, PatchError::PatchCrc { .. }))));
+    }
+
+    #[test]
+    fn overlong_varint() {
+        let source = build_rom();
+        // A source size whose last 7 bits start at bit 63
+        let mut patch = b"UPS1".to_vec();
+        patch.extend_from_slice(&[0x00;9]);
+        patch.push(0xFF);
+        let patch = add_footer(patch, &source, &source);
+
+        let rom = write_file("overlong.gb", &source);
+        let patch = write_file("overlong.ups", &patch);
+
+        assert!(matches!(load(&rom, &[patch]), Err(LoadError::Patch(_, PatchError::OutOfBounds))));
+    }
+
+    /* Valid CRCs, but a target of 1 << 62 bytes */
+    fn huge_target(magic :&[u8], source :&[u8]) -> Vec<u8> {
+        let mut patch = magic.to_vec();
+        patch.extend(varint(source.len()));
+        patch.extend(varint(1 << 62));
+        patch.extend(varint(0));
+
+        return add_footer(patch, source, source);
+    }
+
+    #[test]
+    fn huge_ups_target() {
+        let source = build_rom();
+        let rom = write_file("huge_ups.gb", &source);
+        let patch = write_file("huge.ups", &huge_target(b"UPS1", &source));
+
+        assert!(matches!(load(&rom, &[patch]), Err(LoadError::Patch(_, PatchError::OutOfBounds))));
+    }
+
+    #[test]
+    fn huge_bps_target() {
+        let source = build_rom();
+        let rom = write_file("huge_bps.gb", &source);
+        let patch = write_file("huge.bps", &huge_target(b"BPS1", &source));
+
+        assert!(matches!(load(&rom, &[patch]), Err(LoadError::Patch(_, PatchError::OutOfBounds))));
+    }
+
+    #[test]
+    fn bps_patch() {
+        let source = build_rom();
+        let mut target = source[..0x7FFF].to_vec();
+        target.extend_from_slice(&[0x55;5]);
+        // The header and the last byte copied from the source, moved 0x10 bytes further
+        target[0x110..0x150].copy_from_slice(&source[0x100..0x140]);
+
+        let mut patch = b"BPS1".to_vec();
+        patch.extend(varint(source.len()));
+        patch.extend(varint(target.len()));
+        patch.extend(varint(0));
+
+        // Source read up to 0x110, source copy from 0x100, source read up to 0x7FFF
+        patch.extend(varint((0x110 - 1) << 2));
+        patch.extend(varint((0x40 - 1) << 2 | 2));
+        patch.extend(varint(0x100 << 1));
+        patch.extend(varint((0x7FFF - 0x150 - 1) << 2));
+        // Target read of one byte, then target copy of it overlapping itself
+        patch.extend(varint(1));
+        patch.push(0x55);
+        patch.extend(varint((4 - 1) << 2 | 3));
+        patch.extend(varint(0x7FFF << 1));
+
+        let rom = write_file("bps.gb", &source);
+        let patch = write_file("bps_explicit.bps", &add_footer(patch, &source, &target));
+
+        assert_eq!(load(&rom, &[patch]).unwrap(), target);
+    }
+
+    #[test]
+    fn several_patches_in_order() {
+        let source = build_rom();
+
+        // The UPS patch is made for the ROM patched with the IPS one
+        let mut ips_target = source.clone();
+        ips_target[0x200..0x204].copy_from_slice(&[0x11;4]);
+        ips_target[0x7FFF] = 0x22;
+        ips_target.push(0x33);
+        let mut ups_target = ips_target.clone();
+        ups_target[0x200] = 0x77;
+
+        let rom = write_file("several.gb", &source);
+        let ips = write_file("several.ips", &ips());
+        let ups = write_file("several.ups", &ups(&ips_target, &ups_target));
+
+        assert_eq!(load(&rom, &[ips.clone(), ups.clone()]).unwrap(), ups_target);
+        assert!(load(&rom, &[ups, ips]).is_err());
+    }
+
+    #[test]
+    fn not_a_patch() {
+        let rom = write_file("not_a_patch.gb", &build_rom());
+        let patch = write_file("not_a_patch.ips", b"NOT A PATCH");
+
+        assert!(matches!(load(&rom, &[patch]), Err(LoadError::Patch(_, PatchError::BadMagic))));
+    }
+}
+
+fn main() {}
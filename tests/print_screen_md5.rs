@@ -1,7 +1,8 @@
-#[cfg(test)]
+#[cfg(all(test, feature = "sdl"))]
 mod tests {
+    use gb::cartridge::LoadOptions;
     use gb::gbemulator::GBEmulator;
-    use gb::screen::Screen;
+    use gb::frontend::VideoSink;
     use md5;
     use std::cell::RefCell;
     use std::rc::Rc;
@@ -13,7 +14,7 @@ mod tests {
         let path = format!("{}/{}", ROMS_FOLDER, file);
         println!("path: {}", path);
 
-        let mut gbemu = GBEmulator::new(&path, 2);
+        let mut gbemu = GBEmulator::new(&path, 2, &LoadOptions::default()).unwrap();
         gbemu.init();
 
         let screen = gbemu.get_screen();
@@ -28,7 +29,7 @@ mod tests {
         }
     }
 
-    pub fn print_screen_hash(path :&str, screen :Rc<RefCell<Screen>>) {
+    pub fn print_screen_hash(path :&str, screen :Rc<RefCell<dyn VideoSink>>) {
         let rom_file :&str = path.split("/").into_iter().last().unwrap();
         let pixels = screen.borrow().get_pixels(); 
         let digest = md5::compute(&pixels);
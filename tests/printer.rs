@@ -0,0 +1,159 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::fs::File;
+    use std::path::PathBuf;
+    use gb::consts::ComponentWithMemory;
+    use gb::link::LinkEndpoint;
+    use gb::link::printer::Printer;
+    use crate::common::{self, ROM_ONLY};
+
+    fn out_dir(name :&str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("gb_printer_{}", name));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        return dir;
+    }
+
+    /* Send a whole packet and return the 2 bytes answered at its end */
+    fn send_packet(printer :&mut Printer, command :u8, compression :u8, data :&[u8]) -> (u8, u8) {
+        let mut packet = vec![command, compression, data.len() as u8, (data.len() >> 8) as u8];
+        packet.extend_from_slice(data);
+
+        let checksum = packet.iter().fold(0u16, |acc, byte| acc.wrapping_add(*byte as u16));
+        packet.extend_from_slice(&[checksum as u8, (checksum >> 8) as u8]);
+
+        for byte in [0x88, 0x33].iter().chain(packet.iter()) {
+            assert_eq!(printer.transfer(*byte), Some(0x00));
+        }
+
+        return (printer.transfer(0x00).unwrap(), printer.transfer(0x00).unwrap());
+    }
+
+    fn read_png(path :PathBuf) -> (u32, u32, Vec<u8>) {
+        let decoder = png::Decoder::new(File::open(path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut buf = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buf).unwrap();
+
+        assert_eq!(info.color_type, png::ColorType::Grayscale);
+        return (info.width, info.height, buf);
+    }
+
+    #[test]
+    fn print_with_margin() {
+        let dir = out_dir("margin");
+        let mut printer = Printer::new(&dir);
+
+        assert_eq!(send_packet(&mut printer, 0x01, 0, &[]), (0x81, 0x00));
+
+        // A row of black tiles, RLE compressed: 129 + 129 + 62 times 0xFF
+        let black = [0xFF, 0xFF, 0xFF, 0xFF, 0xBC, 0xFF];
+        assert_eq!(send_packet(&mut printer, 0x04, 1, &black), (0x81, 0x08));
+
+        // A row of light gray tiles
+        let light :Vec<u8> = (0..320).map(|i| if i % 2 == 0 { 0xFF } else { 0x00 }).collect();
+        assert_eq!(send_packet(&mut printer, 0x04, 0, &light), (0x81, 0x08));
+        assert_eq!(send_packet(&mut printer, 0x04, 0, &[]), (0x81, 0x08));
+
+        // 1 sheet, no margin before, 1 after, usual palette
+        assert_eq!(send_packet(&mut printer, 0x02, 0, &[0x01, 0x01, 0xE4, 0x40]), (0x81, 0x06));
+
+        // Busy for a while, then done
+        let mut n_busy = 0;
+        while send_packet(&mut printer, 0x0F, 0, &[]).1 == 0x06 {
+            n_busy += 1;
+            assert!(n_busy < 100);
+        }
+        assert_eq!(send_packet(&mut printer, 0x0F, 0, &[]), (0x81, 0x04));
+
+        let (width, height, pixels) = read_png(dir.join("printout_000.png"));
+        assert_eq!((width, height), (160, 24));
+        assert!(pixels[..160*8].iter().all(|p| *p == 0x00));
+        assert!(pixels[160*8..160*16].iter().all(|p| *p == 0xAA));
+        assert!(pixels[160*16..].iter().all(|p| *p == 0xFF));
+    }
+
+    #[test]
+    fn printout_continues_until_fed() {
+        let dir = out_dir("continue");
+        let mut printer = Printer::new(&dir);
+
+        // 1 tile row of color 1, printed twice with an inverted palette and no margin after
+        let light :Vec<u8> = (0..320).map(|i| if i % 2 == 0 { 0xFF } else { 0x00 }).collect();
+        for margins in [0x10, 0x00] {
+            send_packet(&mut printer, 0x01, 0, &[]);
+            send_packet(&mut printer, 0x04, 0, &light);
+            send_packet(&mut printer, 0x02, 0, &[0x01, margins, 0x1B, 0x40]);
+        }
+        assert!(!dir.exists());
+
+        // Torn off when the printer is unplugged
+        drop(printer);
+
+        let (_, height, pixels) = read_png(dir.join("printout_000.png"));
+        assert_eq!(height, 8 + 8 + 8);
+        assert!(pixels[..160*8].iter().all(|p| *p == 0xFF));
+        assert!(pixels[160*8..].iter().all(|p| *p == 0x55));
+    }
+
+    #[test]
+    fn bad_checksum() {
+        let dir = out_dir("checksum");
+        let mut printer = Printer::new(&dir);
+
+        send_packet(&mut printer, 0x01, 0, &[]);
+
+        // Data packet with a wrong checksum
+        for byte in [0x88, 0x33, 0x04, 0x00, 0x01, 0x00, 0xFF, 0x00, 0x00] {
+            printer.transfer(byte);
+        }
+        assert_eq!((printer.transfer(0x00), printer.transfer(0x00)), (Some(0x81), Some(0x01)));
+
+        // The data was dropped
+        assert_eq!(send_packet(&mut printer, 0x0F, 0, &[]), (0x81, 0x00));
+        assert_eq!(send_packet(&mut printer, 0x02, 0, &[0x01, 0x01, 0xE4, 0x40]), (0x81, 0x06));
+
+        let (_, height, _) = read_png(dir.join("printout_000.png"));
+        assert_eq!(height, 8);
+    }
+
+    /*
+     Send the status packet at 0x200 through the serial port and store the answers at 0xC000:
+        LD HL, 0x200; LD DE, 0xC000
+        loop: LD A, (HL+); LDH (SB), A; LD A, 0x81; LDH (SC), A
+        wait until SC bit 7 is cleared; LDH A, (SB); LD (DE), A; INC DE
+        loop while E != 10, then loop forever
+     */
+    const PROGRAM :[u8;30] = [
+        0x21, 0x00, 0x02, 0x11, 0x00, 0xC0,
+        0x2A, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02,
+        0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA,
+        0xF0, 0x01, 0x12, 0x13, 0x7B, 0xFE, 0x0A, 0x20, 0xEA,
+        0x18, 0xFE
+    ];
+    const STATUS_PACKET :[u8;10] = [0x88, 0x33, 0x0F, 0x00, 0x00, 0x00, 0x0F, 0x00, 0x00, 0x00];
+
+    fn build_rom(name :&str) -> String {
+        let mut rom = common::rom(ROM_ONLY, 0, 0, &PROGRAM);
+        rom[0x200..0x200+STATUS_PACKET.len()].copy_from_slice(&STATUS_PACKET);
+
+        return common::write_rom(&format!("printer_{}", name), rom);
+    }
+
+    #[test]
+    fn through_serial_port() {
+        let mut gbemu = common::new_emulator(&build_rom("serial"));
+        gbemu.set_uncapped(true);
+        gbemu.set_link(Box::new(Printer::new(out_dir("serial"))));
+
+        for _ in 0..3 { gbemu.run_frame(); }
+
+        let bus = gbemu.get_bus();
+        let answers :Vec<u8> = (0..10).map(|i| bus.borrow().read(0xC000 + i)).collect();
+        assert_eq!(answers, [0, 0, 0, 0, 0, 0, 0, 0, 0x81, 0x00]);
+    }
+}
+
+fn main() {}
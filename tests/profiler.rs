@@ -0,0 +1,158 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::debugger::symbols::Symbols;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, ROM_ONLY};
+    use gb::profiler::{Location, Node, Profiler, VBLANK_M_CYCLES};
+
+    const SYMBOLS :&str = "\
+00:0008 Rst08
+00:0040 VBlankInterrupt
+00:0150 Main
+00:0200 Work
+00:0210 Inner
+00:0300 VBlank";
+
+    const MAIN :[u8;12] = [
+        0xF3,               // 0150 DI
+        0x3E, 0x01,         // 0151 LD A, 1
+        0xE0, 0xFF,         // 0153 LDH (FF), A
+        0xAF,               // 0155 XOR A
+        0xE0, 0x0F,         // 0156 LDH (0F), A
+        0xFB,               // 0158 EI
+        0xCD, 0x00, 0x02,   // 0159 CALL Work
+    ];
+    const LOOP :[u8;3] = [
+        0x76,               // 015C HALT
+        0x18, 0xFA,         // 015D JR 0159
+    ];
+    const WORK :[u8;5] = [
+        0xCD, 0x10, 0x02,   // 0200 CALL Inner
+        0xCF,               // 0203 RST 08
+        0xC9,               // 0204 RET
+    ];
+    // 45 M-cycles with the RET
+    const INNER :[u8;6] = [
+        0x06, 0x0A,         // 0210 LD B, 10
+        0x05,               // 0212 DEC B
+        0x20, 0xFD,         // 0213 JR NZ, 0212
+        0xC9,               // 0215 RET
+    ];
+    const VBLANK :[u8;4] = [
+        0xCD, 0x10, 0x02,   // 0300 CALL Inner
+        0xD9,               // 0303 RETI
+    ];
+
+    /* 32 KiB ROM without a mapper */
+    fn build_rom(name :&str) -> String {
+        let mut rom = common::rom(ROM_ONLY, 0x00, 0x00, &MAIN);
+
+        rom[0x08] = 0xC9;                                           // RET
+        rom[0x40..0x43].copy_from_slice(&[0xC3, 0x00, 0x03]);       // JP VBlank
+        rom[0x15C..0x15F].copy_from_slice(&LOOP);
+        rom[0x200..0x205].copy_from_slice(&WORK);
+        rom[0x210..0x216].copy_from_slice(&INNER);
+        rom[0x300..0x304].copy_from_slice(&VBLANK);
+
+        return common::write_rom(&format!("profiler_{}", name), rom);
+    }
+
+    /* Profile n frames */
+    fn profile(name :&str, n_frames :u32) -> GBEmulator {
+        let mut gbemu = common::new_emulator(&build_rom(name));
+        gbemu.set_uncapped(true);
+
+        let report = std::env::temp_dir().join(format!("gb_profiler_{}.txt", name));
+        gbemu.set_profiler(Profiler::new(Symbols::parse(SYMBOLS).unwrap()), report.to_str().unwrap());
+        for _ in 0..n_frames {
+            gbemu.run_frame();
+        }
+        return gbemu;
+    }
+
+    fn location(addr :u16) -> Location {
+        return Location { bank: 0, addr };
+    }
+
+    /* The node of a routine under a parent */
+    fn child(nodes :&[Node], parent :usize, addr :u16) -> usize {
+        return *nodes[parent].children.iter().find(|child| nodes[**child].routine == Some(location(addr)))
+            .unwrap_or_else(|| panic!("No {:04X} under {:?}", addr, nodes[parent].routine));
+    }
+
+    #[test]
+    fn call_graph() {
+        let gbemu = profile("call_graph", 4);
+        let profiler = gbemu.profiler().unwrap();
+        let nodes = profiler.nodes();
+
+        // Main calls Work every frame, which calls Inner and RST 08
+        let work = child(nodes, 0, 0x0200);
+        let inner = child(nodes, work, 0x0210);
+        let rst = child(nodes, work, 0x0008);
+        assert!(nodes[work].calls >= 4 && !nodes[work].is_interrupt);
+        assert_eq!((nodes[inner].calls, nodes[rst].calls), (nodes[work].calls, nodes[work].calls));
+        assert_eq!(nodes[inner].self_cycles, 45 * nodes[inner].calls);
+        assert_eq!(nodes[rst].self_cycles, 4 * nodes[rst].calls); // RET
+        // CALL, RST and RET
+        assert_eq!(nodes[work].self_cycles, (6 + 4 + 4) * nodes[work].calls);
+
+        // The handler is entered by the interrupt, JP not making a frame
+        let handler = child(nodes, 0, 0x0040);
+        assert!(nodes[handler].is_interrupt);
+        let handler_inner = child(nodes, handler, 0x0210);
+        assert_eq!(nodes[handler_inner].self_cycles, 45 * nodes[handler].calls);
+        assert!(nodes[handler].children.len() == 1);
+
+        // Instructions, 10 DEC B per call of Inner
+        let n_inner = nodes[inner].calls + nodes[handler].calls;
+        assert_eq!(profiler.instrs()[&location(0x0212)], 10 * n_inner);
+        assert_eq!(profiler.total_cycles(), profiler.instrs().values().sum::<u64>());
+        // The main loop is halted most of the time
+        assert!(profiler.instrs()[&location(0x015C)] * 2 > profiler.total_cycles());
+    }
+
+    #[test]
+    fn vblank_frames() {
+        let gbemu = profile("vblank_frames", 5);
+        let profiler = gbemu.profiler().unwrap();
+        let frames = profiler.frames();
+
+        // Dispatch, JP, CALL, Inner and RETI
+        assert!(frames.len() >= 4, "{:?}", frames);
+        let handled :Vec<u64> = frames.iter().copied().filter(|cycles| *cycles > 0).collect();
+        assert!(handled.len() >= frames.len() - 1);
+        assert!(handled.iter().all(|cycles| *cycles == 5 + 4 + 6 + 45 + 4), "{:?}", frames);
+        assert!(handled[0] < VBLANK_M_CYCLES);
+    }
+
+    #[test]
+    fn report() {
+        let mut gbemu = profile("report", 3);
+        let report = gbemu.profiler().unwrap().report();
+
+        assert!(report.starts_with("Profile of "));
+        let routines = report.split("\nRoutines\n").nth(1).unwrap();
+        assert!(routines.lines().nth(1).unwrap().ends_with("(top level)"), "{}", routines);
+        assert!(routines.lines().any(|line| line.ends_with("  Inner")));
+        assert!(report.lines().any(|line| line.ends_with("00:0212  Inner+2")), "{}", report);
+
+        let graph = report.split("\nCall graph\n").nth(1).unwrap();
+        let lines :Vec<&str> = graph.lines().take_while(|line| !line.is_empty()).collect();
+        assert!(lines[1].trim_end().ends_with("  (top level)"));
+        assert!(lines.iter().any(|line| line.ends_with("    Inner")));
+        assert!(lines.iter().any(|line| line.ends_with("  VBlankInterrupt (interrupt)")));
+
+        assert!(report.contains("\nVBlank handler\nRun in "));
+        assert!(report.contains(&format!(" 64 M-cycles, over the {} of VBlank in 0\n", VBLANK_M_CYCLES)));
+
+        // Written on quit
+        gbemu.finish_profile();
+        let path = std::env::temp_dir().join("gb_profiler_report.txt");
+        assert_eq!(std::fs::read_to_string(path).unwrap(), report);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,162 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::ComponentWithMemory;
+    use gb::debugger::search::{RamSearch, Filter, Compare, Width, Watch};
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, LOOP};
+
+    /* 32 KiB ROM with 8 KiB of RAM, looping forever */
+    fn build_rom(name :&str) -> String {
+        return common::build_rom(&format!("ram_search_{}", name), 0x03, 0x00, 0x02, &LOOP);
+    }
+
+    fn new_emulator(name :&str) -> GBEmulator {
+        let mut gbemu = common::new_emulator(&build_rom(name));
+        gbemu.set_uncapped(true);
+
+        // Known RAM contents
+        let bus = gbemu.get_bus();
+        bus.borrow_mut().write(0x0000, 0x0A);
+        for addr in (0xA000..=0xDFFF).chain(0xFF80..=0xFFFE) {
+            bus.borrow_mut().write(addr, 0x00);
+        }
+
+        return gbemu;
+    }
+
+    #[test]
+    fn lives() {
+        let gbemu = new_emulator("lives");
+        let bus = gbemu.get_bus();
+        let mut search = RamSearch::new();
+
+        bus.borrow_mut().write(0xC123, 3);
+        bus.borrow_mut().write(0xC456, 3);
+        search.start(&bus.borrow(), Width::Byte);
+        assert_eq!(search.candidates().len(), 0x2000 + 0x2000 + 0x7F);
+
+        assert_eq!(search.filter(&bus.borrow(), Filter::Value(Compare::Eq, 3)), 2);
+
+        // Lose a life
+        bus.borrow_mut().write(0xC123, 2);
+        assert_eq!(search.filter(&bus.borrow(), Filter::Previous(Compare::Lt)), 1);
+        assert_eq!(search.candidates()[0].addr, 0xC123);
+        assert_eq!(search.candidates()[0].val, 2);
+    }
+
+    #[test]
+    fn filters() {
+        let gbemu = new_emulator("filters");
+        let bus = gbemu.get_bus();
+        let mut search = RamSearch::new();
+
+        for (addr, val) in [(0xA010, 10), (0xC000, 20), (0xFF90, 30)] {
+            bus.borrow_mut().write(addr, val);
+        }
+        search.start(&bus.borrow(), Width::Byte);
+
+        assert_eq!(search.filter(&bus.borrow(), Filter::Value(Compare::Ne, 0)), 3);
+        assert_eq!(search.filter(&bus.borrow(), Filter::Value(Compare::Gt, 10)), 2);
+        assert_eq!(search.filter(&bus.borrow(), Filter::Value(Compare::Lt, 30)), 1);
+        assert_eq!(search.candidates()[0].addr, 0xC000);
+
+        search.start(&bus.borrow(), Width::Byte);
+        search.filter(&bus.borrow(), Filter::Value(Compare::Ne, 0));
+
+        // 10 -> 15, 20 -> 20, 30 -> 25. Wraps around like the game would.
+        bus.borrow_mut().write(0xA010, 15);
+        bus.borrow_mut().write(0xFF90, 25);
+        assert_eq!(search.filter(&bus.borrow(), Filter::Previous(Compare::Eq)), 1);
+
+        search.start(&bus.borrow(), Width::Byte);
+        search.filter(&bus.borrow(), Filter::Value(Compare::Ne, 0));
+        bus.borrow_mut().write(0xA010, 20);
+        bus.borrow_mut().write(0xFF90, 20);
+        assert_eq!(search.filter(&bus.borrow(), Filter::ChangedBy(-5)), 1);
+        assert_eq!(search.candidates()[0].addr, 0xFF90);
+
+        bus.borrow_mut().write(0xFF90, 0xFF);
+        assert_eq!(search.filter(&bus.borrow(), Filter::ChangedBy(-21)), 1);
+    }
+
+    #[test]
+    fn words() {
+        let gbemu = new_emulator("words");
+        let bus = gbemu.get_bus();
+        let mut search = RamSearch::new();
+
+        // 1000 coins, little endian
+        bus.borrow_mut().write(0xD000, 0xE8);
+        bus.borrow_mut().write(0xD001, 0x03);
+        search.start(&bus.borrow(), Width::Word);
+        assert_eq!(search.candidates().len(), 0x1FFF + 0x1FFF + 0x7E);
+
+        assert_eq!(search.filter(&bus.borrow(), Filter::Value(Compare::Eq, 1000)), 1);
+        bus.borrow_mut().write(0xD000, 0xEA);
+        assert_eq!(search.filter(&bus.borrow(), Filter::ChangedBy(2)), 1);
+        assert_eq!(search.candidates()[0].addr, 0xD000);
+    }
+
+    #[test]
+    fn watch_and_freeze() {
+        let mut gbemu = new_emulator("freeze");
+        let bus = gbemu.get_bus();
+        let mut search = RamSearch::new();
+
+        search.watch(0xC010, Width::Byte);
+        search.freeze(&mut bus.borrow_mut(), 0xC020, Width::Word, 0x1234);
+        assert_eq!(search.watches(), [
+            Watch { addr: 0xC010, width: Width::Byte, frozen: None },
+            Watch { addr: 0xC020, width: Width::Word, frozen: Some(0x1234) },
+        ]);
+
+        // Written back at every frame
+        bus.borrow_mut().write(0xC020, 0x00);
+        bus.borrow_mut().write(0xC021, 0x00);
+        gbemu.run_frame();
+        assert_eq!(bus.borrow().read(0xC020), 0x34);
+        assert_eq!(bus.borrow().read(0xC021), 0x12);
+        assert_eq!(bus.borrow().cheats().entries().len(), 2);
+
+        search.unfreeze(&mut bus.borrow_mut(), 0xC020);
+        assert!(bus.borrow().cheats().entries().is_empty());
+        assert_eq!(search.watches()[1].frozen, None);
+        bus.borrow_mut().write(0xC020, 0x00);
+        gbemu.run_frame();
+        assert_eq!(bus.borrow().read(0xC020), 0x00);
+
+        search.unwatch(&mut bus.borrow_mut(), 0xC010);
+        assert_eq!(search.watches().len(), 1);
+    }
+
+    #[test]
+    fn commands() {
+        let gbemu = new_emulator("commands");
+        let bus = gbemu.get_bus();
+        let mut search = RamSearch::new();
+
+        assert!(search.command(&mut bus.borrow_mut(), "eq 3").is_err());
+
+        bus.borrow_mut().write(0xC123, 3);
+        assert!(search.command(&mut bus.borrow_mut(), "new").is_ok());
+        assert_eq!(search.command(&mut bus.borrow_mut(), "eq $03"), Ok("1 candidates".to_string()));
+
+        bus.borrow_mut().write(0xC123, 5);
+        assert_eq!(search.command(&mut bus.borrow_mut(), "changed +2"), Ok("1 candidates".to_string()));
+        assert_eq!(search.command(&mut bus.borrow_mut(), "ne"), Ok("0 candidates".to_string()));
+
+        assert!(search.command(&mut bus.borrow_mut(), "freeze C123").is_ok());
+        assert_eq!(search.watches()[0].frozen, Some(5));
+        assert!(search.command(&mut bus.borrow_mut(), "freeze C124 0x10").is_ok());
+        assert_eq!(bus.borrow().read(0xC124), 0x10);
+
+        assert!(search.command(&mut bus.borrow_mut(), "new 32").is_err());
+        assert!(search.command(&mut bus.borrow_mut(), "eq many").is_err());
+        assert!(search.command(&mut bus.borrow_mut(), "watch").is_err());
+        assert!(search.command(&mut bus.borrow_mut(), "find 3").is_err());
+    }
+}
+
+fn main() {}
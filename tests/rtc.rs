@@ -0,0 +1,177 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::cell::RefCell;
+    use std::rc::Rc;
+    use std::time::{SystemTime, UNIX_EPOCH};
+    use gb::bus::Bus;
+    use gb::consts::ComponentWithMemory;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, LOOP, TestDir};
+
+    /* 32 KiB MBC3+TIMER+RAM+BATTERY ROM with 8 KiB of RAM in the directory of the test, looping forever */
+    fn build_rom(dir :&TestDir) -> String {
+        let path = dir.file("game.gb");
+        common::write_rom_at(&path, common::rom(0x10, 0, 0x02, &LOOP));
+
+        return path;
+    }
+
+    fn new_emulator(rom :&str) -> GBEmulator {
+        let mut gbemu = common::new_emulator(rom);
+        gbemu.set_uncapped(true);
+        gbemu.set_rewind_budget(0);
+
+        // Enable the RAM and the clock
+        gbemu.get_bus().borrow_mut().write(0x0000, 0x0A);
+
+        return gbemu;
+    }
+
+    fn read_rtc(bus :&Rc<RefCell<Bus>>, reg :u8) -> u8 {
+        bus.borrow_mut().write(0x4000, reg);
+        return bus.borrow().read(0xA000);
+    }
+
+    fn write_rtc(bus :&Rc<RefCell<Bus>>, reg :u8, val :u8) {
+        bus.borrow_mut().write(0x4000, reg);
+        bus.borrow_mut().write(0xA000, val);
+    }
+
+    fn latch(bus :&Rc<RefCell<Bus>>) {
+        bus.borrow_mut().write(0x6000, 0x00);
+        bus.borrow_mut().write(0x6000, 0x01);
+    }
+
+    fn read_all(bus :&Rc<RefCell<Bus>>) -> [u8;5] {
+        return [0x08, 0x09, 0x0A, 0x0B, 0x0C].map(|reg| read_rtc(bus, reg));
+    }
+
+    fn run_one_second(gbemu :&mut GBEmulator) {
+        for _ in 0..60 { gbemu.run_frame(); }
+    }
+
+    #[test]
+    fn counts_with_carry_and_latches() {
+        let dir = TestDir::new("rtc_carry");
+        let mut gbemu = new_emulator(&build_rom(&dir));
+        let bus = gbemu.get_bus();
+
+        // Day 511, 23:59:59
+        for (reg, val) in [(0x08, 59), (0x09, 59), (0x0A, 23), (0x0B, 0xFF), (0x0C, 0x01)] {
+            write_rtc(&bus, reg, val);
+        }
+        assert_eq!(read_all(&bus), [59, 59, 23, 0xFF, 0x01]);
+
+        run_one_second(&mut gbemu);
+
+        // Still the latched time
+        assert_eq!(read_all(&bus), [59, 59, 23, 0xFF, 0x01]);
+
+        // Writing 0x01 alone doesn't latch
+        bus.borrow_mut().write(0x6000, 0x01);
+        assert_eq!(read_rtc(&bus, 0x08), 59);
+
+        latch(&bus);
+        assert_eq!(read_all(&bus), [0, 0, 0, 0, 0x80]);
+    }
+
+    #[test]
+    fn invalid_values_overflow_without_carry() {
+        let dir = TestDir::new("rtc_invalid");
+        let mut gbemu = new_emulator(&build_rom(&dir));
+        let bus = gbemu.get_bus();
+
+        write_rtc(&bus, 0x08, 0xFF);
+        write_rtc(&bus, 0x09, 0x05);
+        assert_eq!(read_rtc(&bus, 0x08), 0x3F);
+
+        run_one_second(&mut gbemu);
+        latch(&bus);
+
+        assert_eq!(read_rtc(&bus, 0x08), 0x00);
+        assert_eq!(read_rtc(&bus, 0x09), 0x05);
+    }
+
+    #[test]
+    fn halt_stops_the_clock() {
+        let dir = TestDir::new("rtc_halt");
+        let mut gbemu = new_emulator(&build_rom(&dir));
+        let bus = gbemu.get_bus();
+
+        write_rtc(&bus, 0x0C, 0x40);
+        write_rtc(&bus, 0x08, 10);
+
+        run_one_second(&mut gbemu);
+        latch(&bus);
+        assert_eq!(read_rtc(&bus, 0x08), 10);
+
+        write_rtc(&bus, 0x0C, 0x00);
+        run_one_second(&mut gbemu);
+        latch(&bus);
+        assert_eq!(read_rtc(&bus, 0x08), 11);
+    }
+
+    #[test]
+    fn save_state_keeps_the_clock() {
+        let dir = TestDir::new("rtc_savestate");
+        let mut gbemu = new_emulator(&build_rom(&dir));
+        let bus = gbemu.get_bus();
+
+        write_rtc(&bus, 0x09, 42);
+        let state = gbemu.save_state();
+
+        write_rtc(&bus, 0x09, 0);
+        gbemu.load_state(&state).unwrap();
+        latch(&bus);
+
+        assert_eq!(read_rtc(&bus, 0x09), 42);
+    }
+
+    fn unix_time() -> u64 {
+        return SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs();
+    }
+
+    #[test]
+    fn persisted_in_footer() {
+        let dir = TestDir::new("rtc_footer");
+        let rom = build_rom(&dir);
+        let save = dir.file("game.sav");
+
+        // Saved 1:01:01 ago at day 1, 10:00:00, with a 32-bit time like older emulators
+        let mut file = vec![0x5A; 0x2000];
+        for val in [0, 0, 10, 1, 0, 0, 0, 10, 1, 0] {
+            file.extend_from_slice(&(val as u32).to_le_bytes());
+        }
+        file.extend_from_slice(&((unix_time() - 3661) as u32).to_le_bytes());
+        std::fs::write(&save, &file).unwrap();
+
+        let gbemu = new_emulator(&rom);
+        let bus = gbemu.get_bus();
+
+        // The latched registers are restored as they were
+        assert_eq!(read_all(&bus), [0, 0, 10, 1, 0]);
+
+        latch(&bus);
+        let regs = read_all(&bus);
+        assert_eq!(regs[1..], [1, 11, 1, 0]);
+        assert!(regs[0] == 1 || regs[0] == 2);
+
+        // RAM is still there
+        bus.borrow_mut().write(0x4000, 0x00);
+        assert_eq!(bus.borrow().read(0xA000), 0x5A);
+
+        bus.borrow().save_ram();
+        let file = std::fs::read(&save).unwrap();
+
+        assert_eq!(file.len(), 0x2000 + 48);
+        let footer = &file[0x2000..];
+        assert_eq!(footer[0x08], 11);
+        assert_eq!(footer[0x1C], 11);
+        let timestamp = u64::from_le_bytes(footer[40..48].try_into().unwrap());
+        assert!(unix_time() - timestamp <= 1);
+    }
+}
+
+fn main() {}
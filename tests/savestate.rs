@@ -0,0 +1,109 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::gbemulator::GBEmulator;
+    use gb::savestate::StateError;
+    use crate::common::{self, ROM_ONLY};
+
+    /*
+     Keeps incrementing 0xC000:
+        0x0150: LD HL, 0xC000
+        0x0153: INC (HL)
+        0x0154: JR 0x0153
+     */
+    const PROGRAM :[u8;6] = [0x21, 0x00, 0xC0, 0x34, 0x18, 0xFD];
+
+    fn new_emulator(name :&str) -> GBEmulator {
+        return common::new_emulator(&common::build_rom(&format!("savestate_{}", name), ROM_ONLY, 0, 0, &PROGRAM));
+    }
+
+    #[test]
+    fn restore_is_deterministic() {
+        let mut gbemu = new_emulator("deterministic");
+        for _ in 0..3 { gbemu.run_frame(); }
+
+        let state = gbemu.save_state();
+        for _ in 0..5 { gbemu.run_frame(); }
+        let expected = gbemu.save_state();
+
+        gbemu.load_state(&state).unwrap();
+        assert_eq!(gbemu.save_state(), state);
+
+        for _ in 0..5 { gbemu.run_frame(); }
+        assert_eq!(gbemu.save_state(), expected);
+    }
+
+    #[test]
+    fn slots() {
+        let mut gbemu = new_emulator("slots");
+        gbemu.run_frame();
+
+        gbemu.save_state_slot(1).unwrap();
+        let state = gbemu.save_state();
+        gbemu.run_frame();
+
+        gbemu.load_state_slot(1).unwrap();
+        assert_eq!(gbemu.save_state(), state);
+
+        std::fs::remove_file(gbemu.state_slot_path(1)).unwrap();
+        assert!(matches!(gbemu.load_state_slot(1), Err(StateError::Io(_))));
+    }
+
+    #[test]
+    fn rejects_invalid_states() {
+        let mut gbemu = new_emulator("invalid");
+        gbemu.run_frame();
+        let state = gbemu.save_state();
+
+        assert!(matches!(gbemu.load_state(b"nope"), Err(StateError::BadMagic)));
+
+        let mut other_version = state.clone();
+        other_version[4] = 0xFF;
+        assert!(matches!(gbemu.load_state(&other_version), Err(StateError::Version(_))));
+
+        let mut other_rom = state.clone();
+        other_rom[6] ^= 0xFF;
+        assert!(matches!(gbemu.load_state(&other_rom), Err(StateError::RomMismatch)));
+
+        // A failed load leaves the machine as it was
+        assert!(matches!(gbemu.load_state(&state[..state.len()/2]), Err(StateError::Truncated)));
+        assert_eq!(gbemu.save_state(), state);
+    }
+
+    #[test]
+    fn rewind() {
+        let mut gbemu = new_emulator("rewind");
+        for _ in 0..2 { gbemu.run_frame(); }
+
+        let state = gbemu.save_state();
+        for _ in 0..4 { gbemu.run_frame(); }
+
+        gbemu.set_rewinding(true);
+        for _ in 0..4 { gbemu.run_frame(); }
+        assert_eq!(gbemu.save_state(), state);
+
+        // Nothing left before the first frame
+        gbemu.run_frame();
+        let first = gbemu.save_state();
+        gbemu.run_frame();
+        assert_eq!(gbemu.save_state(), first);
+        assert_eq!(gbemu.rewind_frames(), 0);
+    }
+
+    #[test]
+    fn rewind_budget() {
+        let mut gbemu = new_emulator("rewind_budget");
+        gbemu.run_frame();
+        gbemu.set_rewind_budget(gbemu.save_state().len() + 256);
+
+        for _ in 0..20 { gbemu.run_frame(); }
+        assert!(gbemu.rewind_frames() > 0 && gbemu.rewind_frames() < 19);
+
+        gbemu.set_rewind_budget(0);
+        gbemu.run_frame();
+        assert_eq!(gbemu.rewind_frames(), 0);
+    }
+}
+
+fn main() {}
@@ -0,0 +1,156 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::thread;
+    use gb::gbemulator::GBEmulator;
+    use gb::consts::*;
+    use gb::link::loopback::loopback_cable;
+    use gb::link::tcp::TcpLink;
+    use crate::common::{self, ROM_ONLY};
+
+    /*
+     Send 0x42 as master:
+        LD A, 0x42; LDH (SB), A; LD A, 0x81; LDH (SC), A
+        wait until SC bit 7 is cleared, then LD (0xC000), SB and loop forever
+     */
+    const MASTER :[u8;21] = [
+        0x3E, 0x42, 0xE0, 0x01, 0x3E, 0x81, 0xE0, 0x02,
+        0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA,
+        0xF0, 0x01, 0xEA, 0x00, 0xC0, 0x18, 0xFE
+    ];
+    // Same, but waiting with 0x99 for the external clock
+    const SLAVE :[u8;21] = [
+        0x3E, 0x99, 0xE0, 0x01, 0x3E, 0x80, 0xE0, 0x02,
+        0xF0, 0x02, 0xCB, 0x7F, 0x20, 0xFA,
+        0xF0, 0x01, 0xEA, 0x00, 0xC0, 0x18, 0xFE
+    ];
+
+    fn build_rom(name :&str, program :&[u8]) -> String {
+        return common::build_rom(&format!("serial_{}", name), ROM_ONLY, 0, 0, program);
+    }
+
+    fn new_emulator(name :&str, program :&[u8]) -> GBEmulator {
+        let mut gbemu = common::new_emulator(&build_rom(name, program));
+        gbemu.set_uncapped(true);
+        gbemu.set_rewind_budget(0);
+
+        return gbemu;
+    }
+
+    fn received(gbemu :&GBEmulator) -> u8 {
+        return gbemu.get_bus().borrow().read(0xC000);
+    }
+
+    #[test]
+    fn nothing_connected() {
+        let mut gbemu = new_emulator("nothing", &MASTER);
+        for _ in 0..2 { gbemu.run_frame(); }
+
+        let bus = gbemu.get_bus();
+        assert_eq!(received(&gbemu), 0xFF);
+        assert_eq!(bus.borrow().read(ADDR_SC), 0x7F);
+        assert_eq!(bus.borrow().read(ADDR_IF) & 0b01000, 0b01000);
+    }
+
+    #[test]
+    fn transfer_takes_8_bits_at_8192hz() {
+        let mut gbemu = new_emulator("timing", &MASTER);
+        let bus = gbemu.get_bus();
+
+        // Run until the transfer starts
+        while bus.borrow().read(ADDR_SC) != 0xFF {
+            bus.borrow_mut().tick();
+            gbemu.get_cpu_mut().tick();
+        }
+
+        let mut cycles = 0;
+        while bus.borrow().read(ADDR_SC) == 0xFF {
+            bus.borrow_mut().tick();
+            gbemu.get_cpu_mut().tick();
+            cycles += 1;
+        }
+
+        // The first bit may be shifted up to 512 cycles after the start
+        assert!(cycles > 7*512 && cycles <= 8*512, "{} cycles", cycles);
+    }
+
+    #[test]
+    fn loopback() {
+        let mut master = new_emulator("loopback_master", &MASTER);
+        let mut slave = new_emulator("loopback_slave", &SLAVE);
+
+        let (end0, end1) = loopback_cable();
+        master.set_link(Box::new(end0));
+        slave.set_link(Box::new(end1));
+
+        for _ in 0..3 {
+            slave.run_frame();
+            master.run_frame();
+        }
+
+        assert_eq!(received(&master), 0x99);
+        assert_eq!(received(&slave), 0x42);
+    }
+
+    #[test]
+    fn tcp() {
+        let master_rom = build_rom("tcp_master", &MASTER);
+        let slave_rom = build_rom("tcp_slave", &SLAVE);
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        // Each emulator runs in its own thread, so that both answer in time
+        let run = |rom :String, link :TcpLink, wait_slave :bool| {
+            let mut gbemu = common::new_emulator(&rom);
+            gbemu.set_link(Box::new(link));
+
+            if wait_slave {
+                std::thread::sleep(std::time::Duration::from_millis(100));
+            }
+
+            for _ in 0..30 { gbemu.run_frame(); }
+            return received(&gbemu);
+        };
+
+        let slave = thread::spawn(move || run(slave_rom, TcpLink::listen(addr).unwrap(), false));
+        thread::sleep(std::time::Duration::from_millis(100));
+        let master = thread::spawn(move || run(master_rom, TcpLink::connect(addr).unwrap(), true));
+
+        assert_eq!(master.join().unwrap(), 0x99);
+        assert_eq!(slave.join().unwrap(), 0x42);
+    }
+
+    #[test]
+    fn tcp_master_doesnt_wait_for_the_reply() {
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut gbemu = new_emulator("tcp_waiting", &MASTER);
+        gbemu.set_link(Box::new(TcpLink::connect(listener.local_addr().unwrap()).unwrap()));
+        let (mut peer, _) = listener.accept().unwrap();
+        let bus = gbemu.get_bus();
+
+        // The frames go on while the transfer waits for the other side
+        let start = std::time::Instant::now();
+        for _ in 0..2 { gbemu.run_frame(); }
+        assert!(start.elapsed() < std::time::Duration::from_millis(250));
+        assert_eq!(bus.borrow().read(ADDR_SC), 0xFF);
+        assert_eq!(bus.borrow().read(ADDR_IF) & 0b01000, 0);
+
+        let mut msg = [0;2];
+        std::io::Read::read_exact(&mut peer, &mut msg).unwrap();
+        assert_eq!(msg, [0x00, 0x42]);
+
+        // Done once the reply is there
+        std::io::Write::write_all(&mut peer, &[0x01, 0x5A]).unwrap();
+        for _ in 0..10 {
+            gbemu.run_frame();
+            if received(&gbemu) == 0x5A { break; }
+        }
+        assert_eq!(received(&gbemu), 0x5A);
+        assert_eq!(bus.borrow().read(ADDR_IF) & 0b01000, 0b01000);
+    }
+}
+
+fn main() {}
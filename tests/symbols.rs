@@ -0,0 +1,144 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::ComponentWithMemory;
+    use gb::debugger::disassemble;
+    use gb::debugger::breakpoints::{Breakpoint, Breakpoints, RunMode, Stop};
+    use gb::debugger::console::Console;
+    use gb::debugger::search::RamSearch;
+    use gb::debugger::symbols::{Symbol, Symbols, SymbolError};
+    use gb::gbemulator::GBEmulator;
+    use crate::common;
+
+    // Calls 4000 in bank 2 then in bank 3
+    const MAIN :[u8;18] = [
+        0x3E, 0x02,         // 0150 LD A, 2
+        0xEA, 0x00, 0x20,   // 0152 LD (2000), A
+        0xCD, 0x00, 0x40,   // 0155 CALL 4000
+        0x3E, 0x03,         // 0158 LD A, 3
+        0xEA, 0x00, 0x20,   // 015A LD (2000), A
+        0xCD, 0x00, 0x40,   // 015D CALL 4000
+        0x18, 0xFE,         // 0160 JR -2
+    ];
+
+    // At 4000 in every bank
+    const FUNCTION :[u8;4] = [
+        0xEA, 0xA0, 0xC0,   // 4000 LD (C0A0), A
+        0xC9,               // 4003 RET
+    ];
+
+    const SYM_FILE :&str = "\
+; File generated by rgblink
+00:0150 Main
+00:0160 Main.loop
+02:4000 FuncTwo
+03:4000 FuncThree ; The one to stop at
+00:c0a0 wCounter
+";
+
+    /* 128 KiB MBC5 ROM */
+    fn build_rom(name :&str) -> String {
+        let mut rom = common::rom(0x19, 0x02, 0x00, &MAIN);
+        for bank in rom.chunks_mut(0x4000).skip(1) {
+            bank[..FUNCTION.len()].copy_from_slice(&FUNCTION);
+        }
+
+        return common::write_rom(&format!("symbols_{}", name), rom);
+    }
+
+    fn new_emulator(name :&str) -> GBEmulator {
+        return common::new_emulator(&build_rom(name));
+    }
+
+    #[test]
+    fn parse() {
+        let symbols = Symbols::parse(SYM_FILE).unwrap();
+
+        assert_eq!(symbols.len(), 5);
+        assert_eq!(symbols.get("FuncThree"), Some(&Symbol { bank: 3, addr: 0x4000, name: "FuncThree".to_string() }));
+        assert_eq!(symbols.label(2, 0x4000), Some("FuncTwo"));
+        assert_eq!(symbols.label(1, 0x4000), None);
+        assert_eq!(symbols.resolve("wCounter"), Ok((Some(0), 0xC0A0)));
+        assert_eq!(symbols.resolve("$4000"), Ok((None, 0x4000)));
+        assert!(symbols.resolve("Nowhere").is_err());
+
+        // no$gmb writes them the same way, in uppercase
+        assert_eq!(Symbols::parse("0001:4ABC UPDATE_SPRITES\n").unwrap().label(1, 0x4ABC), Some("UPDATE_SPRITES"));
+
+        for bad in ["Main", "00:0150", "0150 Main", "zz:0150 Main"] {
+            assert!(matches!(Symbols::parse(&format!("; ok\n{}\n", bad)), Err(SymbolError::BadLine(2, _))), "{}", bad);
+        }
+
+        // Next to the ROM by default
+        let gbemu = new_emulator("path");
+        assert!(gbemu.symbols_path().ends_with("gb_symbols_path.sym"));
+        let path = std::env::temp_dir().join("gb_symbols_path.sym");
+        std::fs::write(&path, SYM_FILE).unwrap();
+        assert_eq!(Symbols::load(&path).unwrap().len(), 5);
+    }
+
+    #[test]
+    fn labelled_disassembly() {
+        let gbemu = new_emulator("disassembly");
+        let symbols = Symbols::parse(SYM_FILE).unwrap();
+        let bus = gbemu.get_bus();
+
+        let text = |addr :u16| disassemble(&bus.borrow(), &symbols, addr, 1)[0].to_string();
+
+        // Bank 1 is mapped at first, without labels
+        let instrs = disassemble(&bus.borrow(), &symbols, 0x0150, 3);
+        assert_eq!(instrs[0].get_label(), Some("Main"));
+        assert_eq!(instrs[1].get_label(), None);
+        assert!(instrs[2].to_string().ends_with("CALL 4000h"), "{}", instrs[2]);
+        assert!(text(0x0160).ends_with("JR Main.loop"));
+
+        bus.borrow_mut().write(0x2000, 3);
+        assert!(text(0x0155).ends_with("CALL FuncThree"));
+        assert_eq!(disassemble(&bus.borrow(), &symbols, 0x4000, 1)[0].get_label(), Some("FuncThree"));
+        assert!(text(0x4000).ends_with("LD (wCounter) A"));
+
+        bus.borrow_mut().write(0x2000, 2);
+        assert!(text(0x0155).ends_with("CALL FuncTwo"));
+    }
+
+    #[test]
+    fn label_breakpoints() {
+        let mut gbemu = new_emulator("breakpoints");
+        let symbols = Symbols::parse(SYM_FILE).unwrap();
+        let mut breakpoints = Breakpoints::new();
+        breakpoints.set_mode(RunMode::Continue);
+
+        let breakpoint = Breakpoint::parse_with_symbols("FuncThree if A == 3", &symbols).unwrap();
+        assert_eq!((breakpoint.bank, breakpoint.addr), (Some(3), 0x4000));
+        breakpoints.add(breakpoint);
+
+        // Added from the console, with a label as the address
+        let mut console = Console::new();
+        console.run(&mut gbemu, &mut breakpoints, &mut RamSearch::new(), &symbols, "x/2 wCounter");
+        assert!(console.output().back().unwrap().starts_with("C0A0  "));
+        console.run(&mut gbemu, &mut breakpoints, &mut RamSearch::new(), &symbols, "disas Main 1");
+        assert_eq!(console.output().iter().rev().nth(1).unwrap(), "Main:");
+
+        // Doesn't stop in bank 2
+        let mut stop = None;
+        for _ in 0..100_000 {
+            gbemu.get_bus().borrow_mut().tick();
+            gbemu.get_cpu_mut().tick();
+
+            if gbemu.get_cpu().is_new_instr() {
+                let bus = gbemu.get_bus();
+                stop = breakpoints.check(gbemu.get_cpu(), &mut bus.borrow_mut());
+                if stop.is_some() {
+                    break;
+                }
+            }
+        }
+
+        assert_eq!(stop, Some(Stop::Breakpoint(0)));
+        assert_eq!(gbemu.get_bus().borrow().rom_bank(0x4000), 3);
+        assert_eq!(gbemu.get_bus().borrow().read(0xC0A0), 2);
+    }
+}
+
+fn main() {}
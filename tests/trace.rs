@@ -0,0 +1,182 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use gb::consts::*;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, ROM_ONLY};
+    use gb::trace::{Tracer, TraceError};
+
+    const MAIN :[u8;6] = [
+        0x3E, 0x05,         // 0150 LD A, 5
+        0x47,               // 0152 LD B, A
+        0x04,               // 0153 INC B
+        0x18, 0xFD,         // 0154 JR -3
+    ];
+
+    // Waits for VBlank with HALT, the interrupts enabled or not
+    fn halt_main(is_ime :bool) -> [u8;9] {
+        return [
+            0x3E, 0x01,         // 0150 LD A, 1
+            0xE0, 0xFF,         // 0152 LDH (FF), A
+            if is_ime {0xFB} else {0xF3}, // 0154 EI or DI
+            0x76,               // 0155 HALT
+            0x04,               // 0156 INC B
+            0x18, 0xFC,         // 0157 JR -4
+        ];
+    }
+
+    const VBLANK_HANDLER :[u8;2] = [
+        0x0C,               // 0040 INC C
+        0xD9,               // 0041 RETI
+    ];
+
+    /* 32 KiB ROM without a mapper */
+    fn build_rom(name :&str, main :&[u8]) -> String {
+        let mut rom = common::rom(ROM_ONLY, 0x00, 0x00, main);
+        rom[0x40..0x42].copy_from_slice(&VBLANK_HANDLER);
+
+        return common::write_rom(&format!("trace_{}", name), rom);
+    }
+
+    fn new_emulator(name :&str, main :&[u8]) -> GBEmulator {
+        let mut gbemu = common::new_emulator(&build_rom(name, main));
+        gbemu.set_uncapped(true);
+        return gbemu;
+    }
+
+    /* Run for n T-cycles like the emulator, tracing to a file. Returns the lines. */
+    fn trace(gbemu :&mut GBEmulator, name :&str, n_cycles :u32) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("gb_trace_{}.log", name));
+        gbemu.set_tracer(Tracer::open(path.to_str(), None).unwrap());
+
+        for _ in 0..n_cycles {
+            gbemu.trace().unwrap();
+            gbemu.get_bus().borrow_mut().tick();
+            gbemu.get_cpu_mut().tick();
+        }
+        gbemu.take_tracer().unwrap().flush().unwrap();
+
+        return std::fs::read_to_string(path).unwrap().lines().map(String::from).collect();
+    }
+
+    fn pc(line :&str) -> &str {
+        return line.split("PC:").nth(1).unwrap().get(..4).unwrap();
+    }
+
+    #[test]
+    fn doctor_format() {
+        let mut gbemu = new_emulator("format", &MAIN);
+        let f = if gbemu.get_bus().borrow().read(0x014D) == 0 {"80"} else {"B0"};
+        let lines = trace(&mut gbemu, "format", 200);
+
+        assert_eq!(lines[0], format!("A:01 F:{} B:00 C:13 D:00 E:D8 H:01 L:4D SP:FFFE PC:0100 PCMEM:00,C3,50,01", f));
+        assert!(lines[2].ends_with("PC:0150 PCMEM:3E,05,47,04"), "{}", lines[2]);
+        assert!(lines[3].starts_with("A:05 ") && lines[4].contains(" B:05 ") && lines[6].contains(" B:06 "));
+
+        let pcs :Vec<&str> = lines.iter().take(8).map(|line| pc(line)).collect();
+        assert_eq!(pcs, ["0100", "0101", "0150", "0152", "0153", "0154", "0153", "0154"]);
+        // NOP 1, JP 4, LD 2 and LD 1 M-cycles, then INC and JR every 4 from M-cycle 8 to 49
+        assert_eq!(lines.len(), 4 + 2*11);
+    }
+
+    #[test]
+    fn halt_and_interrupts() {
+        // The dispatch has no line, and the HALT is followed by the handler
+        let mut gbemu = new_emulator("halt_ime", &halt_main(true));
+        let lines = trace(&mut gbemu, "halt_ime", 3*70224);
+        let pcs :Vec<&str> = lines.iter().map(|line| pc(line)).collect();
+
+        assert_eq!(pcs[..4], ["0100", "0101", "0150", "0152"]);
+        assert!(pcs.contains(&"0154"));
+        let n_halts = pcs.iter().filter(|pc| **pc == "0155").count();
+        assert!(n_halts >= 2, "{:?}", pcs);
+        let first_halt = pcs.iter().position(|pc| *pc == "0155").unwrap();
+        for window in pcs.windows(2).skip(first_halt) {
+            match window[0] {
+                "0155" => assert_eq!(window[1], "0040"),
+                "0041" => assert_eq!(window[1], "0156"),
+                _ => {}
+            }
+        }
+
+        // Without the interrupts the CPU goes on after HALT, the instruction being traced once
+        let mut gbemu = new_emulator("halt_di", &halt_main(false));
+        let lines = trace(&mut gbemu, "halt_di", 3*70224);
+        let pcs :Vec<&str> = lines.iter().map(|line| pc(line)).collect();
+
+        let first_halt = pcs.iter().position(|pc| *pc == "0155").unwrap();
+        assert!(!pcs[first_halt..].contains(&"0040"));
+        assert!(pcs.iter().filter(|pc| **pc == "0155").count() >= 2, "{:?}", pcs);
+        for window in pcs.windows(2).skip(first_halt) {
+            if window[0] == "0155" {
+                assert_eq!(window[1], "0156");
+            }
+        }
+    }
+
+    #[test]
+    fn compare() {
+        let mut gbemu = new_emulator("compare", &MAIN);
+        let mut reference = trace(&mut gbemu, "compare", 200);
+        reference[5] = reference[5].replace(" B:06 ", " B:07 ").replace(" F:10 ", " F:90 ");
+
+        let mut gbemu = new_emulator("compare", &MAIN);
+        let mut tracer = Tracer::new(None, Some(Box::new(Cursor::new(reference.join("\n")))));
+        let mut result = Ok(());
+        for _ in 0..200 {
+            result = tracer.tick(gbemu.get_cpu(), &gbemu.get_bus().borrow());
+            if result.is_err() {
+                break;
+            }
+            gbemu.get_bus().borrow_mut().tick();
+            gbemu.get_cpu_mut().tick();
+        }
+
+        let Err(TraceError::Diverged(divergence)) = result else { panic!("No divergence") };
+        assert_eq!(divergence.line, 6);
+        assert_eq!(divergence.context, reference[..5]);
+        assert_eq!(divergence.fields(), ["F", "B"]);
+        let report = divergence.to_string();
+        assert!(report.starts_with("The trace diverges from the reference at line 6:\n         1  A:01"), "{}", report);
+        assert!(report.ends_with("differs in F, B"));
+
+        // The log goes on without the reference
+        assert!(!tracer.is_comparing());
+        assert!(tracer.tick(gbemu.get_cpu(), &gbemu.get_bus().borrow()).is_ok());
+    }
+
+    #[test]
+    fn end_of_reference() {
+        // A frame of reference, then the emulator quits at the first line after it
+        let mut gbemu = new_emulator("reference", &MAIN);
+        let log = std::env::temp_dir().join("gb_trace_reference.log");
+        gbemu.set_tracer(Tracer::open(log.to_str(), None).unwrap());
+        gbemu.run_frame();
+        let n_lines = gbemu.tracer().unwrap().n_lines();
+        gbemu.take_tracer().unwrap().flush().unwrap();
+
+        let mut gbemu = new_emulator("reference", &MAIN);
+        gbemu.set_tracer(Tracer::open(None, log.to_str()).unwrap());
+        gbemu.run_frame();
+        assert!(!gbemu.is_quit());
+        assert!(gbemu.tracer().unwrap().is_comparing());
+
+        gbemu.run_frame();
+        assert!(gbemu.is_quit());
+        assert_eq!(gbemu.tracer().unwrap().n_lines(), n_lines + 1);
+        assert!(!gbemu.tracer().unwrap().is_comparing());
+
+        // The logs of Gameboy Doctor expect LY at 0x90
+        let bus = gbemu.get_bus();
+        bus.borrow_mut().write(ADDR_LY, 0);
+        bus.borrow_mut().set_ly_stub(Some(0x90));
+        assert_eq!(bus.borrow().read(ADDR_LY), 0x90);
+        bus.borrow_mut().set_ly_stub(None);
+        assert_eq!(bus.borrow().read(ADDR_LY), 0);
+    }
+}
+
+fn main() {}
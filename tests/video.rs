@@ -0,0 +1,170 @@
+mod common;
+
+#[cfg(test)]
+mod tests {
+    use gb::consts::*;
+    use gb::debugger::breakpoints::Breakpoints;
+    use gb::debugger::console::Console;
+    use gb::debugger::search::RamSearch;
+    use gb::debugger::symbols::Symbols;
+    use gb::debugger::video::{self, Object, VideoView};
+    use gb::frontend::SHADES;
+    use gb::gbemulator::GBEmulator;
+    use crate::common::{self, LOOP, ROM_ONLY};
+
+    const RED  :(u8, u8, u8) = (255, 0, 0);
+    const BLUE :(u8, u8, u8) = (0, 128, 255);
+
+    // Color ids 0, 1, 2, 3, 0, 1, 2, 3 on every line
+    const STRIPES :[u8;2] = [0b01010101, 0b00110011];
+
+    /* 32 KiB ROM without a mapper */
+    fn build_rom(name :&str) -> String {
+        return common::build_rom(&format!("video_{}", name), ROM_ONLY, 0x00, 0x00, &LOOP);
+    }
+
+    fn new_emulator(name :&str) -> GBEmulator {
+        return common::new_emulator(&build_rom(name));
+    }
+
+    fn write_tile(gbemu :&GBEmulator, addr :u16, line :[u8;2]) {
+        for i in 0..16 {
+            gbemu.get_bus().borrow_mut().poke_video(addr + i, line[i as usize % 2]);
+        }
+    }
+
+    #[test]
+    fn tiles() {
+        let gbemu = new_emulator("tiles");
+        write_tile(&gbemu, 0x8010, STRIPES);
+        write_tile(&gbemu, 0x8800, [0xFF, 0xFF]);
+        write_tile(&gbemu, 0x9000, [0xFF, 0x00]);
+        let bus = gbemu.get_bus();
+
+        let image = VideoView::Tiles8000.render(&bus.borrow());
+        assert_eq!((image.width, image.height), (128, 128));
+        for x in 0..8 {
+            assert_eq!(image.pixel(8 + x, 7), SHADES[x % 4], "{}", x);
+        }
+        assert_eq!(image.pixel(0, 0), SHADES[0]);
+        assert_eq!(image.pixel(0, 8*8), SHADES[3]); // Tile 128
+
+        // Index 0 at 9000, 128 at 8800
+        let image = VideoView::Tiles8800.render(&bus.borrow());
+        assert_eq!(image.pixel(0, 0), SHADES[1]);
+        assert_eq!(image.pixel(8, 0), SHADES[0]);
+        assert_eq!(image.pixel(0, 8*8), SHADES[3]);
+
+        assert_eq!(VideoView::parse("tiles 8800"), Ok(VideoView::Tiles8800));
+        assert_eq!(VideoView::parse("MAP 9C00"), Ok(VideoView::Tilemap9C00));
+        assert_eq!(VideoView::parse("oam"), Ok(VideoView::Objects));
+        for bad in ["tiles 9000", "map 9800 1", "sprites", ""] {
+            assert!(VideoView::parse(bad).is_err(), "{}", bad);
+        }
+    }
+
+    #[test]
+    fn tilemaps() {
+        let gbemu = new_emulator("tilemaps");
+        write_tile(&gbemu, 0x8010, STRIPES);
+        write_tile(&gbemu, 0x9010, [0xFF, 0xFF]);
+        let bus = gbemu.get_bus();
+
+        // Tile 1 at the second row and column of both maps, inverted palette
+        bus.borrow_mut().poke_video(0x9821, 1);
+        bus.borrow_mut().poke_video(0x9C21, 1);
+        bus.borrow_mut().write(ADDR_BGP, 0x1B);
+        bus.borrow_mut().write(ADDR_SCX, 250);
+        bus.borrow_mut().write(ADDR_SCY, 4);
+
+        let image = VideoView::Tilemap9800.render(&bus.borrow());
+        assert_eq!((image.width, image.height), (256, 256));
+        assert_eq!(image.pixel(8 + 1, 8 + 1), SHADES[2]);
+        assert_eq!(image.pixel(8 + 3, 8 + 1), SHADES[0]);
+
+        // The viewport wraps around
+        assert_eq!(image.pixel(250, 4), RED);
+        assert_eq!(image.pixel((250 + 159) % 256, 4 + 143), RED);
+        assert_eq!((image.pixel(20, 4), image.pixel(153, 100)), (RED, RED));
+        assert_eq!(image.pixel(100, 100), SHADES[3]);
+
+        // 8800 addressing, and the window in 9C00 from WX 87, WY 72
+        bus.borrow_mut().write(ADDR_LCDC, 0x91 & !0x10 | 0x20 | 0x40);
+        bus.borrow_mut().write(ADDR_WX, 87);
+        bus.borrow_mut().write(ADDR_WY, 72);
+
+        let image = VideoView::Tilemap9C00.render(&bus.borrow());
+        assert_eq!(image.pixel(8 + 1, 8 + 1), SHADES[0]);
+        assert_eq!((image.pixel(79, 10), image.pixel(10, 71)), (BLUE, BLUE));
+        assert_eq!(image.pixel(80, 10), SHADES[3]);
+        assert_eq!(image.pixel(250, 4), SHADES[3]);
+        assert_eq!(VideoView::Tilemap9800.render(&bus.borrow()).pixel(79, 10), SHADES[3]);
+    }
+
+    #[test]
+    fn objects() {
+        let gbemu = new_emulator("objects");
+        write_tile(&gbemu, 0x8020, STRIPES);
+        write_tile(&gbemu, 0x8030, [0xFF, 0xFF]);
+        let bus = gbemu.get_bus();
+
+        for (i, byte) in [16, 8, 2, 0x20, 0, 0, 3, 0xD0].iter().enumerate() {
+            bus.borrow_mut().poke_video(OAM_START + i as u16, *byte);
+        }
+        bus.borrow_mut().write(ADDR_OBP0, 0xE4);
+        bus.borrow_mut().write(ADDR_OBP1, 0x1B);
+
+        let objects = video::objects(&bus.borrow());
+        assert_eq!(objects.len(), 40);
+        assert_eq!(objects[0], Object { y: 16, x: 8, tile: 2, attrs: 0x20 });
+        assert!(objects[0].is_visible(8) && !objects[1].is_visible(16));
+
+        let list = video::object_list(&bus.borrow());
+        assert_eq!(list[0], " 0  X   8  Y  16  tile 02  OBP0 x-flip");
+        assert_eq!(list[1], " 1  X   0  Y   0  tile 03  OBP1 y-flip behind BG (hidden)");
+
+        // Flipped, with the transparent pixels and 8x8 objects leaving the bottom of the cell empty
+        let image = VideoView::Objects.render(&bus.borrow());
+        assert_eq!((image.width, image.height), (73, 86));
+        assert_eq!(image.pixel(1 + 7, 1), image.pixel(1 + 4, 12));
+        assert_eq!((image.pixel(1 + 6, 1), image.pixel(1 + 4, 1)), (SHADES[1], SHADES[3]));
+        assert_eq!(image.pixel(0, 0), (128, 128, 128));
+
+        // The tile pairs of 8x16 objects, the second one on top once flipped
+        bus.borrow_mut().write(ADDR_LCDC, 0x91 | 0x04);
+        let image = VideoView::Objects.render(&bus.borrow());
+        assert_eq!(image.pixel(10 + 1, 1), SHADES[0]);
+        assert_eq!(image.pixel(10 + 1, 1 + 8), image.pixel(10 + 5, 1 + 8));
+        assert_eq!(image.pixel(10 + 1, 1 + 8), SHADES[2]);
+    }
+
+    #[test]
+    fn export() {
+        let mut gbemu = new_emulator("export");
+        write_tile(&gbemu, 0x8000, STRIPES);
+
+        let path = std::env::temp_dir().join("gb_video_export.png");
+        let path_str = path.to_str().unwrap();
+        let mut console = Console::new();
+        console.run(&mut gbemu, &mut Breakpoints::new(), &mut RamSearch::new(), &Symbols::new(), &format!("export tiles {}", path_str));
+        assert_eq!(console.output().back().unwrap(), &format!("Saved the Tiles 8000 view to {}", path_str));
+
+        let decoder = png::Decoder::new(std::fs::File::open(&path).unwrap());
+        let mut reader = decoder.read_info().unwrap();
+        let mut pixels = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut pixels).unwrap();
+        assert_eq!((info.width, info.height, info.color_type), (128, 128, png::ColorType::Rgb));
+
+        let image = VideoView::Tiles8000.render(&gbemu.get_bus().borrow());
+        assert_eq!(&pixels[..info.buffer_size()], &image.pixels[..]);
+        assert_eq!(&pixels[3..6], &[SHADES[1].0, SHADES[1].1, SHADES[1].2]);
+
+        console.run(&mut gbemu, &mut Breakpoints::new(), &mut RamSearch::new(), &Symbols::new(), "export sprites x.png");
+        assert!(console.output().back().unwrap().starts_with("Error: unknown view sprites"));
+
+        console.run(&mut gbemu, &mut Breakpoints::new(), &mut RamSearch::new(), &Symbols::new(), "oam");
+        assert!(console.output().back().unwrap().starts_with("39  X   0"));
+    }
+}
+
+fn main() {}